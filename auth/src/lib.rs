@@ -1,42 +1,180 @@
 use aes_gcm::aead::generic_array::GenericArray;
 use aes_gcm::aead::Aead;
+use aes_gcm::aead::Payload;
 use aes_gcm::AeadInPlace;
 use aes_gcm::Aes256Gcm;
 use aes_gcm::NewAead;
 
 use displaydoc::Display;
-use once_cell::sync::OnceCell;
+use once_cell::sync::Lazy;
 use rand::Rng;
 use rand::RngCore;
+use serde::Deserialize;
 use serde::Serialize;
 use std::convert::TryInto;
+use std::sync::RwLock;
+use std::time::SystemTime;
 use thiserror::Error;
 
+pub mod totp;
+
 const NONCE_LEN: usize = 12;
 const TAG_LEN: usize = 16;
+const KID_LEN: usize = 4;
+/// Length of the encrypted payload, a big endian i64.
+const PAYLOAD_LEN: usize = 8;
+
+/// A secret key with which tokens are sealed. Every key has an id which is embedded in the tokens
+/// it seals, so that several keys can be valid at the same time while the key is being rotated.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct SigningKey {
+    pub id: u32,
+    pub key: [u8; 32],
+    /// Unix timestamp of when this key was superseded by a newer key. Tokens sealed with a retired
+    /// key are accepted until the grace period has passed.
+    #[serde(default)]
+    pub retired_at: Option<u64>,
+}
+
+/// The set of keys tokens are sealed and verified with.
+#[derive(Clone, Debug, Default)]
+struct KeyRing {
+    keys: Vec<SigningKey>,
+    /// How many seconds tokens of retired keys remain valid.
+    grace_period: u64,
+}
+
+impl KeyRing {
+    /// The key new tokens are sealed with, which is the newest key that hasnt been retired.
+    fn active(&self) -> Option<&SigningKey> {
+        self.keys
+            .iter()
+            .filter(|x| x.retired_at.is_none())
+            .max_by_key(|x| x.id)
+    }
+
+    /// Returns the key with the supplied id if tokens sealed with it are still valid at `now`.
+    fn verifying(&self, id: u32, now: u64) -> Result<&SigningKey, AuthError> {
+        let key = self
+            .keys
+            .iter()
+            .find(|x| x.id == id)
+            .ok_or(AuthError::UnknownKey)?;
+
+        match key.retired_at {
+            Some(ts) if now > ts.saturating_add(self.grace_period) => Err(AuthError::RetiredKey),
+            _ => Ok(key),
+        }
+    }
+}
 
-/// This is the secret key with which we sign the cookies.
-// TODO: Generate this at first run to ensure security
-static KEY: OnceCell<[u8; 32]> = OnceCell::new();
+/// These are the secret keys with which we sign the cookies.
+static KEYS: Lazy<RwLock<Option<KeyRing>>> = Lazy::new(|| RwLock::new(None));
+
+/// Key with which secrets stored in the database, ie TOTP secrets, are encrypted. Unlike the
+/// signing keys this key is never rotated, as that would make the stored secrets unreadable.
+static STORAGE_KEY: Lazy<RwLock<Option<[u8; 32]>>> = Lazy::new(|| RwLock::new(None));
 
 pub fn generate_key() -> [u8; 32] {
     rand::thread_rng().gen()
 }
 
+/// Sets a single key with id `0` as the only signing key.
 pub fn set_key(k: [u8; 32]) {
-    KEY.set(k).expect("Failed to set secret_key")
+    set_keys(
+        vec![SigningKey {
+            id: 0,
+            key: k,
+            retired_at: None,
+        }],
+        0,
+    )
+}
+
+/// Replaces the set of signing keys. New tokens are sealed with the newest key that hasnt been
+/// retired, tokens of retired keys are accepted for `grace_period` seconds after their key was
+/// retired.
+///
+/// # Panics
+/// Panics if none of the keys is active.
+pub fn set_keys(keys: Vec<SigningKey>, grace_period: u64) {
+    let ring = KeyRing { keys, grace_period };
+    assert!(ring.active().is_some(), "at least one key must be active");
+
+    *KEYS.write().unwrap() = Some(ring);
 }
 
 /// This function should only be called from tests
 pub fn set_key_fallible(k: [u8; 32]) {
-    let _ = KEY.set(k);
+    let mut lock = KEYS.write().unwrap();
+    if lock.is_none() {
+        *lock = Some(KeyRing {
+            keys: vec![SigningKey {
+                id: 0,
+                key: k,
+                retired_at: None,
+            }],
+            grace_period: 0,
+        });
+    }
 }
 
-fn get_key() -> &'static [u8; 32] {
-    KEY.get().expect("key must be initialized")
+/// Sets the key with which secrets stored in the database are encrypted, see [`encrypt_secret`].
+pub fn set_storage_key(k: [u8; 32]) {
+    *STORAGE_KEY.write().unwrap() = Some(k);
 }
 
-#[derive(Clone, Debug, Display, Error, Serialize)]
+/// This function should only be called from tests
+pub fn set_storage_key_fallible(k: [u8; 32]) {
+    STORAGE_KEY.write().unwrap().get_or_insert(k);
+}
+
+/// Retires the currently active key as of `now` and appends a freshly generated key which becomes
+/// the active key. Keys whose grace period has already run out are dropped. Returns the id of the
+/// new key.
+pub fn rotate_keys(keys: &mut Vec<SigningKey>, grace_period: u64, now: u64) -> u32 {
+    keys.retain(|x| match x.retired_at {
+        Some(ts) => now <= ts.saturating_add(grace_period),
+        None => true,
+    });
+
+    for key in keys.iter_mut() {
+        key.retired_at.get_or_insert(now);
+    }
+
+    let id = keys.iter().map(|x| x.id + 1).max().unwrap_or(0);
+    keys.push(SigningKey {
+        id,
+        key: generate_key(),
+        retired_at: None,
+    });
+
+    id
+}
+
+fn get_keys() -> KeyRing {
+    KEYS.read()
+        .unwrap()
+        .clone()
+        .expect("key must be initialized")
+}
+
+/// Function returns the current unix timestamp in seconds.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn get_storage_key() -> [u8; 32] {
+    STORAGE_KEY
+        .read()
+        .unwrap()
+        .expect("storage key must be initialized")
+}
+
+#[derive(Clone, Debug, Display, Error, Serialize, PartialEq)]
 pub enum AuthError {
     /// Token is not base64 encoded.
     BadBase64,
@@ -46,17 +184,83 @@ pub enum AuthError {
     DecryptError,
     /// Token plaintext does not contain a UserID.
     PlainTextNoti64,
+    /// Token was signed with a unknown key.
+    UnknownKey,
+    /// Token was signed with a key which has been retired.
+    RetiredKey,
 }
 
 /// Function encrypts a UserID with a nonce and returns it as a base64 string to be used as a cookie/token.
 pub fn user_cookie_generate(user: i64) -> String {
-    // Create a vec to hold the [nonce | cookie value].
+    seal(&get_keys(), user)
+}
+
+/// Function decrypts a UserID which was encrypted with `user_cookie_generate`
+pub fn user_cookie_decode(cookie: String) -> Result<i64, AuthError> {
+    open(&get_keys(), cookie, unix_now())
+}
+
+/// Function encrypts `secret` with the storage key so that it can be stored in the database, and
+/// returns it as a base64 string. `context` is authenticated along with the secret, ie the id of
+/// the user the secret belongs to, so that a encrypted secret cant be moved to another row.
+pub fn encrypt_secret(secret: &str, context: &[u8]) -> String {
+    let key = get_storage_key();
+
+    let mut nonce = [0; NONCE_LEN];
+    rand::thread_rng()
+        .try_fill_bytes(&mut nonce)
+        .expect("couldn't random fill nonce");
+
+    let aead = Aes256Gcm::new(GenericArray::from_slice(&key));
+    let cipher = aead
+        .encrypt(
+            GenericArray::from_slice(&nonce),
+            Payload {
+                msg: secret.as_bytes(),
+                aad: context,
+            },
+        )
+        .expect("encryption failure!");
+
+    // Base64 encode [nonce | encrypted value | tag].
+    base64::encode([&nonce[..], &cipher[..]].concat())
+}
+
+/// Function decrypts a secret which was encrypted with [`encrypt_secret`] using the same
+/// `context`.
+pub fn decrypt_secret(data: &str, context: &[u8]) -> Result<String, AuthError> {
+    let data = base64::decode(data).map_err(|_| AuthError::BadBase64)?;
+    if data.len() < NONCE_LEN + TAG_LEN {
+        return Err(AuthError::ShortData);
+    }
+
+    let (nonce, cipher) = data.split_at(NONCE_LEN);
+    let aead = Aes256Gcm::new(GenericArray::from_slice(&get_storage_key()));
+    let plaintext = aead
+        .decrypt(
+            GenericArray::from_slice(nonce),
+            Payload {
+                msg: cipher,
+                aad: context,
+            },
+        )
+        .map_err(|_| AuthError::DecryptError)?;
+
+    String::from_utf8(plaintext).map_err(|_| AuthError::DecryptError)
+}
+
+fn seal(ring: &KeyRing, user: i64) -> String {
+    let key = ring.active().expect("no active key");
+
+    // Create a vec to hold the [key id | nonce | cookie value].
     let cookie_val = &user.to_be_bytes();
-    let mut data = vec![0; NONCE_LEN + cookie_val.len() + TAG_LEN];
+    let mut data = vec![0; KID_LEN + NONCE_LEN + cookie_val.len() + TAG_LEN];
 
-    // Split data into three: nonce, input/output, tag. Copy input.
-    let (nonce, in_out) = data.split_at_mut(NONCE_LEN);
+    // Split data into four: key id, nonce, input/output, tag. Copy input.
+    let (kid, rest) = data.split_at_mut(KID_LEN);
+    let (nonce, in_out) = rest.split_at_mut(NONCE_LEN);
     let (in_out, tag) = in_out.split_at_mut(cookie_val.len());
+    kid.copy_from_slice(&key.id.to_be_bytes());
     in_out.copy_from_slice(cookie_val);
 
     // Fill nonce piece with random data.
@@ -65,30 +269,48 @@ pub fn user_cookie_generate(user: i64) -> String {
         .expect("couldn't random fill nonce");
     let nonce = GenericArray::clone_from_slice(nonce);
 
-    // Perform the actual sealing operation, using the cookie's name as
-    // associated data to prevent value swapping.
-    let aead = Aes256Gcm::new(GenericArray::from_slice(get_key()));
+    // Perform the actual sealing operation, using the key id as associated data so that it cant
+    // be swapped out.
+    let aead = Aes256Gcm::new(GenericArray::from_slice(&key.key));
     let aad_tag = aead
-        .encrypt_in_place_detached(&nonce, b"", in_out)
+        .encrypt_in_place_detached(&nonce, kid, in_out)
         .expect("encryption failure!");
 
     // Copy the tag into the tag piece.
     tag.copy_from_slice(&aad_tag);
 
-    // Base64 encode [nonce | encrypted value | tag].
+    // Base64 encode [key id | nonce | encrypted value | tag].
     base64::encode(&data)
 }
 
-/// Function decrypts a UserID which was encrypted with `user_cookie_generate`
-pub fn user_cookie_decode(cookie: String) -> Result<i64, AuthError> {
+fn open(ring: &KeyRing, cookie: String, now: u64) -> Result<i64, AuthError> {
     let data = base64::decode(cookie).map_err(|_| AuthError::BadBase64)?;
-    if data.len() <= NONCE_LEN {
+
+    // Tokens issued before key rotation existed dont carry a key id and were sealed with key `0`.
+    let (kid, rest) = if data.len() == NONCE_LEN + PAYLOAD_LEN + TAG_LEN {
+        (None, &data[..])
+    } else if data.len() > KID_LEN + NONCE_LEN {
+        let (kid, rest) = data.split_at(KID_LEN);
+        (Some(kid), rest)
+    } else {
         return Err(AuthError::ShortData);
-    }
-    let (nonce, cipher) = data.split_at(NONCE_LEN);
-    let aead = Aes256Gcm::new(GenericArray::from_slice(get_key()));
+    };
+
+    let id = kid
+        .map(|x| u32::from_be_bytes(x.try_into().unwrap()))
+        .unwrap_or(0);
+    let key = ring.verifying(id, now)?;
+
+    let (nonce, cipher) = rest.split_at(NONCE_LEN);
+    let aead = Aes256Gcm::new(GenericArray::from_slice(&key.key));
     let plaintext = aead
-        .decrypt(GenericArray::from_slice(nonce), cipher)
+        .decrypt(
+            GenericArray::from_slice(nonce),
+            Payload {
+                msg: cipher,
+                aad: kid.unwrap_or(b""),
+            },
+        )
         .map_err(|_| AuthError::DecryptError)?;
 
     Ok(i64::from_be_bytes(
@@ -97,3 +319,89 @@ pub fn user_cookie_decode(cookie: String) -> Result<i64, AuthError> {
             .map_err(|_| AuthError::PlainTextNoti64)?,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(keys: Vec<SigningKey>) -> KeyRing {
+        KeyRing {
+            keys,
+            grace_period: 100,
+        }
+    }
+
+    #[test]
+    fn test_rotation() {
+        let mut keys = vec![SigningKey {
+            id: 0,
+            key: generate_key(),
+            retired_at: None,
+        }];
+
+        let old = seal(&ring(keys.clone()), 42);
+        assert_eq!(open(&ring(keys.clone()), old.clone(), 1000), Ok(42));
+
+        assert_eq!(rotate_keys(&mut keys, 100, 1000), 1);
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].retired_at, Some(1000));
+
+        let new = seal(&ring(keys.clone()), 42);
+        assert_ne!(
+            base64::decode(&old).unwrap()[..KID_LEN],
+            base64::decode(&new).unwrap()[..KID_LEN]
+        );
+
+        // tokens of the retired key stay valid for the grace period.
+        assert_eq!(open(&ring(keys.clone()), old.clone(), 1100), Ok(42));
+        assert_eq!(
+            open(&ring(keys.clone()), old.clone(), 1101),
+            Err(AuthError::RetiredKey)
+        );
+        assert_eq!(open(&ring(keys.clone()), new.clone(), 5000), Ok(42));
+
+        // keys past their grace period are dropped on the next rotation.
+        assert_eq!(rotate_keys(&mut keys, 100, 5000), 2);
+        assert_eq!(keys.iter().map(|x| x.id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(open(&ring(keys), old, 5000), Err(AuthError::UnknownKey));
+    }
+
+    #[test]
+    fn test_secret_roundtrip() {
+        set_storage_key_fallible(generate_key());
+
+        let sealed = encrypt_secret("JBSWY3DPEHPK3PXP", b"1");
+        assert!(!sealed.contains("JBSWY3DPEHPK3PXP"));
+        assert_eq!(
+            decrypt_secret(&sealed, b"1"),
+            Ok("JBSWY3DPEHPK3PXP".to_string())
+        );
+
+        // secrets are bound to their context.
+        assert_eq!(decrypt_secret(&sealed, b"2"), Err(AuthError::DecryptError));
+        assert_eq!(decrypt_secret("c2hvcnQ=", b"1"), Err(AuthError::ShortData));
+    }
+
+    #[test]
+    fn test_legacy_token() {
+        let key = generate_key();
+        let keys = vec![SigningKey {
+            id: 0,
+            key,
+            retired_at: None,
+        }];
+
+        // tokens sealed before rotation support have no key id and no associated data.
+        let mut data = vec![0; NONCE_LEN + PAYLOAD_LEN + TAG_LEN];
+        let (nonce, in_out) = data.split_at_mut(NONCE_LEN);
+        let (in_out, tag) = in_out.split_at_mut(PAYLOAD_LEN);
+        in_out.copy_from_slice(&7i64.to_be_bytes());
+        let aead = Aes256Gcm::new(GenericArray::from_slice(&key));
+        let aad_tag = aead
+            .encrypt_in_place_detached(GenericArray::from_slice(nonce), b"", in_out)
+            .unwrap();
+        tag.copy_from_slice(&aad_tag);
+
+        assert_eq!(open(&ring(keys), base64::encode(&data), 0), Ok(7));
+    }
+}
@@ -0,0 +1,106 @@
+//! Time based one time passwords as described in [RFC 6238], used for two-factor authentication.
+//! Codes are 6 digits long, use HMAC-SHA1 and a 30 second step which is what every common
+//! authenticator app expects. Generating and checking codes is left to [`totp_rs`].
+//!
+//! [RFC 6238]: https://datatracker.ietf.org/doc/html/rfc6238
+use base32::Alphabet;
+use rand::RngCore;
+use totp_rs::Algorithm;
+use totp_rs::TOTP;
+
+/// Length of a step in seconds.
+pub const STEP: u64 = 30;
+/// Number of steps before and after the current one for which we still accept codes, this
+/// accounts for clock drift between the server and the client.
+pub const SKEW: u64 = 1;
+/// Number of digits of a code.
+pub const DIGITS: u32 = 6;
+
+const SECRET_LEN: usize = 20;
+const ALPHABET: Alphabet = Alphabet::RFC4648 { padding: false };
+
+/// Function generates a new random secret encoded as base32.
+pub fn generate_secret() -> String {
+    let mut secret = [0; SECRET_LEN];
+    rand::thread_rng().fill_bytes(&mut secret);
+    base32::encode(ALPHABET, &secret)
+}
+
+/// Function returns a `otpauth://` uri which authenticator apps can import, usually by scanning it
+/// as a QR code.
+pub fn provisioning_uri(secret: &str, account: &str, issuer: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={}&period={}",
+        DIGITS,
+        STEP,
+        issuer = url_encode(issuer),
+        account = url_encode(account),
+        secret = secret,
+    )
+}
+
+/// Function checks `code` against the base32 encoded `secret` at `time`, which is a unix timestamp
+/// in seconds. If the code is valid the counter of the step it belongs to is returned. A code
+/// stays valid for several steps, so callers have to remember the counter of the last code they
+/// accepted and reject codes at or below it, otherwise a code can be replayed.
+pub fn verify(secret: &str, code: &str, time: u64) -> Option<u64> {
+    let key = base32::decode(ALPHABET, &secret.trim_end_matches('=').to_ascii_uppercase())?;
+    let code = code.trim();
+
+    if code.len() != DIGITS as usize || !code.bytes().all(|x| x.is_ascii_digit()) {
+        return None;
+    }
+
+    let totp = TOTP::new(Algorithm::SHA1, DIGITS as usize, 0, STEP, key);
+    let counter = time / STEP;
+
+    // NOTE: we check the newest step first so that the highest matching counter is returned.
+    (counter.saturating_sub(SKEW)..=counter + SKEW)
+        .rev()
+        .find(|x| totp.check(code, x * STEP))
+}
+
+fn url_encode(data: &str) -> String {
+    data.bytes()
+        .map(|x| match x {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (x as char).to_string()
+            }
+            x => format!("%{:02X}", x),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // base32 of the RFC 6238 SHA1 test secret `12345678901234567890`.
+    const SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn test_generate_secret() {
+        let secret = generate_secret();
+        assert_eq!(base32::decode(ALPHABET, &secret).unwrap().len(), SECRET_LEN);
+        assert!(verify("NOT-BASE32", "287082", 59).is_none());
+    }
+
+    #[test]
+    fn test_rfc_vectors() {
+        // last 6 digits of the RFC 6238 test vectors.
+        for (time, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1234567890, "005924"),
+        ] {
+            assert_eq!(verify(SECRET, code, time), Some(time / STEP));
+        }
+    }
+
+    #[test]
+    fn test_skew() {
+        assert_eq!(verify(SECRET, "287082", 59 + STEP), Some(1));
+        assert_eq!(verify(SECRET, "287082", 59 + 2 * STEP), None);
+        assert_eq!(verify(SECRET, "28708", 59), None);
+    }
+}
@@ -0,0 +1,7 @@
+-- Media types used to be decoded leniently, thus older rows might contain media types with stray
+-- whitespace or uppercase characters. Normalize them so that they decode with the strict parser.
+UPDATE library SET media_type = LOWER(TRIM(media_type))
+WHERE media_type != LOWER(TRIM(media_type));
+
+UPDATE _tblmedia SET media_type = LOWER(TRIM(media_type))
+WHERE media_type != LOWER(TRIM(media_type));
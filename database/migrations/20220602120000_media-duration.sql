@@ -0,0 +1,20 @@
+-- Add a duration column to media so that runtimes dont have to be queried from mediafiles.
+ALTER TABLE _tblmedia ADD COLUMN duration INTEGER;
+
+-- Backfill durations from the longest mediafile of each media.
+UPDATE _tblmedia SET duration = (
+    SELECT MAX(mediafile.duration) FROM mediafile WHERE mediafile.media_id = _tblmedia.id
+);
+
+-- Recreate media view
+DROP VIEW media;
+
+CREATE VIEW media AS
+SELECT _tblmedia.*, pp.local_path as poster_path, bp.local_path as backdrop_path
+FROM _tblmedia
+LEFT OUTER JOIN assets pp ON _tblmedia.poster = pp.id
+LEFT OUTER JOIN assets bp ON _tblmedia.backdrop = bp.id;
+
+CREATE TRIGGER media_delete
+INSTEAD OF DELETE ON media
+BEGIN DELETE FROM _tblmedia WHERE _tblmedia.id = old.id; END;
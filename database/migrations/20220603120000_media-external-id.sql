@@ -0,0 +1,11 @@
+-- Add a external id column to media which is used to identify media with a metadata provider.
+ALTER TABLE _tblmedia ADD COLUMN external_id TEXT;
+
+-- Media with a external id can share a name with other media, thus we only want to enforce unique
+-- names for media without one.
+DROP INDEX media_idx;
+CREATE UNIQUE INDEX media_idx ON _tblmedia(library_id, name, media_type)
+WHERE NOT _tblmedia.media_type = "episode" AND _tblmedia.external_id IS NULL;
+
+CREATE UNIQUE INDEX media_external_id_idx ON _tblmedia(library_id, external_id, media_type)
+WHERE _tblmedia.external_id IS NOT NULL;
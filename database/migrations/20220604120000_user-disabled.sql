@@ -0,0 +1,2 @@
+-- Disabled accounts are kept around along with their progress, but they can no longer log in.
+ALTER TABLE users ADD COLUMN is_disabled BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,7 @@
+-- Track when an account has been created. Existing accounts get backfilled with the creation date
+-- of the invite they claimed which is the closest thing we have.
+ALTER TABLE users ADD COLUMN created INTEGER;
+
+UPDATE users SET created = (
+    SELECT invites.date_added FROM invites WHERE invites.id = users.claimed_invite
+);
@@ -0,0 +1,4 @@
+-- Optional TOTP based two-factor authentication. A secret is stored as soon as a user starts
+-- enrolling, but it is only enforced once the user has verified it with a valid code.
+ALTER TABLE users ADD COLUMN totp_secret TEXT;
+ALTER TABLE users ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,10 @@
+-- Single-use password reset tokens. These work like invites, except that they are tied to an
+-- existing user, expire, and are kept around after use so they can't be replayed.
+CREATE TABLE password_resets (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id INTEGER NOT NULL,
+    date_added INTEGER NOT NULL,
+    expires INTEGER NOT NULL,
+    consumed BOOLEAN NOT NULL DEFAULT 0,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
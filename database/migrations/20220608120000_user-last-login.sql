@@ -0,0 +1,3 @@
+-- Track when and from where an account last logged in successfully.
+ALTER TABLE users ADD COLUMN last_login INTEGER;
+ALTER TABLE users ADD COLUMN last_login_ip TEXT;
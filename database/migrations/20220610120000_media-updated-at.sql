@@ -0,0 +1,3 @@
+-- Track when the metadata of a media object has last been fetched. Existing rows are left as NULL
+-- which is treated as stale.
+ALTER TABLE _tblmedia ADD COLUMN updated_at INTEGER;
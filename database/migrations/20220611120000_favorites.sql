@@ -0,0 +1,11 @@
+-- Media a user has bookmarked. Entries are also removed manually when the media is deleted, see
+-- `Media::delete`.
+CREATE TABLE favorites (
+    user_id INTEGER NOT NULL,
+    media_id INTEGER NOT NULL,
+    date_added INTEGER NOT NULL,
+
+    PRIMARY KEY (user_id, media_id),
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY(media_id) REFERENCES _tblmedia(id) ON DELETE CASCADE
+);
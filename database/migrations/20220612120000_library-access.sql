@@ -0,0 +1,11 @@
+-- Grants users access to restricted libraries. A library without any entries here can be browsed
+-- by everyone, once a library has at least one entry only the listed users (and owners) can see
+-- it.
+CREATE TABLE library_access (
+    library_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+
+    PRIMARY KEY (library_id, user_id),
+    FOREIGN KEY(library_id) REFERENCES library(id) ON DELETE CASCADE,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,9 @@
+-- Movies and tv shows that have been deleted, so that clients syncing a library can drop them.
+-- Entries are removed manually when the library is deleted, see `Library::delete`.
+CREATE TABLE media_tombstones (
+    media_id INTEGER NOT NULL PRIMARY KEY,
+    library_id INTEGER NOT NULL,
+    deleted_at INTEGER NOT NULL
+);
+
+CREATE INDEX media_tombstones_library_idx ON media_tombstones (library_id, deleted_at);
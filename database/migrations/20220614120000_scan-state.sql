@@ -0,0 +1,10 @@
+-- Last known scanner state of every library, so that it survives restarts. Rows are created
+-- lazily when a scan starts and removed manually when the library is deleted, see
+-- `Library::delete`.
+CREATE TABLE scan_state (
+    library_id INTEGER NOT NULL PRIMARY KEY,
+    status TEXT NOT NULL DEFAULT 'idle',
+    started_at INTEGER,
+    finished_at INTEGER,
+    files_processed INTEGER NOT NULL DEFAULT 0
+);
@@ -0,0 +1,6 @@
+-- `added` used to be stored in whatever format the caller passed, usually
+-- `2022-06-13 12:34:56.123 UTC`. Rewrite every value sqlite can parse as `2022-06-13T12:34:56Z` so
+-- that they sort along with the values written by `InsertableMedia`.
+UPDATE _tblmedia
+SET added = strftime('%Y-%m-%dT%H:%M:%SZ', substr(added, 1, 19))
+WHERE strftime('%Y-%m-%dT%H:%M:%SZ', substr(added, 1, 19)) IS NOT NULL;
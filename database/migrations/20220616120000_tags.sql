@@ -0,0 +1,17 @@
+-- Free-form labels users can put on media, ie "4k" or "rewatch". Unlike genres these are never
+-- touched by the scanner. Names are stored lowercase, see `Tag::normalize`.
+CREATE TABLE tags (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE
+);
+
+-- Links are removed manually when the media is deleted, see `Media::delete`.
+CREATE TABLE media_tags (
+    tag_id INTEGER NOT NULL,
+    media_id INTEGER NOT NULL,
+    PRIMARY KEY (tag_id, media_id),
+    FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE,
+    FOREIGN KEY (media_id) REFERENCES _tblmedia(id) ON DELETE CASCADE
+);
+
+CREATE INDEX media_tags_media_idx ON media_tags (media_id);
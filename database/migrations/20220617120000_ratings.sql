@@ -0,0 +1,13 @@
+-- Scores from different sources, ie "tmdb" or "imdb", normalized to 0-100. `_tblmedia.rating`
+-- keeps the score of the primary source in its original 0-10 scale, see `Rating::sync_primary`.
+CREATE TABLE ratings (
+    media_id INTEGER NOT NULL,
+    source TEXT NOT NULL,
+    score INTEGER NOT NULL CHECK (score BETWEEN 0 AND 100),
+    PRIMARY KEY (media_id, source),
+    FOREIGN KEY (media_id) REFERENCES _tblmedia(id) ON DELETE CASCADE
+);
+
+-- Existing ratings all came from tmdb.
+INSERT INTO ratings (media_id, source, score)
+SELECT id, 'tmdb', MIN(MAX(rating * 10, 0), 100) FROM _tblmedia WHERE rating IS NOT NULL;
@@ -0,0 +1,14 @@
+-- Personal scores and reviews, one per user and media. Scores use the same 0-100 range as
+-- `ratings`.
+CREATE TABLE user_ratings (
+    user_id INTEGER NOT NULL,
+    media_id INTEGER NOT NULL,
+    score INTEGER NOT NULL CHECK (score BETWEEN 0 AND 100),
+    review TEXT,
+    updated_at INTEGER NOT NULL,
+    PRIMARY KEY (user_id, media_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (media_id) REFERENCES _tblmedia(id) ON DELETE CASCADE
+);
+
+CREATE INDEX user_ratings_media_idx ON user_ratings (media_id);
@@ -0,0 +1,14 @@
+-- The unique index on progress got lost when the table was rebuilt for user ids, so a user could
+-- end up with several rows for the same media. Keep the most recent one.
+DELETE FROM progress WHERE EXISTS (
+    SELECT 1 FROM progress newer
+    WHERE newer.user_id = progress.user_id
+    AND newer.media_id = progress.media_id
+    AND (newer.populated > progress.populated
+        OR (newer.populated = progress.populated AND newer.id > progress.id))
+);
+
+CREATE UNIQUE INDEX progress_user_media_idx ON progress (user_id, media_id);
+
+-- Duration of the media as reported by the player, see `Progress::upsert`.
+ALTER TABLE progress ADD COLUMN duration INTEGER;
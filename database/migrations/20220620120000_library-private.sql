@@ -0,0 +1,2 @@
+-- Private libraries are never listed without authentication, see `Library::get_all_public`.
+ALTER TABLE library ADD COLUMN is_private INTEGER NOT NULL DEFAULT 0;
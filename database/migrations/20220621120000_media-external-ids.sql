@@ -0,0 +1,22 @@
+-- Add the tmdb and imdb ids of media as separate columns so that they can be linked to and matched on.
+ALTER TABLE _tblmedia ADD COLUMN tmdb_id INTEGER;
+ALTER TABLE _tblmedia ADD COLUMN imdb_id TEXT;
+
+CREATE INDEX media_tmdb_id_idx ON _tblmedia(tmdb_id);
+
+-- Backfill tmdb ids from the external ids the scanners already stored.
+UPDATE _tblmedia SET tmdb_id = CAST(SUBSTR(external_id, 6) AS INTEGER)
+WHERE external_id LIKE 'tmdb:%';
+
+-- Recreate media view
+DROP VIEW media;
+
+CREATE VIEW media AS
+SELECT _tblmedia.*, pp.local_path as poster_path, bp.local_path as backdrop_path
+FROM _tblmedia
+LEFT OUTER JOIN assets pp ON _tblmedia.poster = pp.id
+LEFT OUTER JOIN assets bp ON _tblmedia.backdrop = bp.id;
+
+CREATE TRIGGER media_delete
+INSTEAD OF DELETE ON media
+BEGIN DELETE FROM _tblmedia WHERE _tblmedia.id = old.id; END;
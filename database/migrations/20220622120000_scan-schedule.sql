@@ -0,0 +1,9 @@
+-- Periodic rescans of libraries, see `Library::set_scan_schedule`. Rows are removed manually when
+-- the library is deleted, see `Library::delete`.
+CREATE TABLE scan_schedule (
+    library_id INTEGER NOT NULL PRIMARY KEY,
+    -- seconds between two scheduled scans.
+    interval INTEGER NOT NULL,
+    -- unix timestamp of the next scheduled scan.
+    next_run INTEGER NOT NULL
+);
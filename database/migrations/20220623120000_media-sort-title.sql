@@ -0,0 +1,18 @@
+-- Key media are sorted by when sorting by name, see `sort_title`. Existing rows are filled in on
+-- boot by `Media::fill_sort_titles` as the normalization cant be expressed in sql.
+ALTER TABLE _tblmedia ADD COLUMN sort_title TEXT;
+
+CREATE INDEX media_sort_title_idx ON _tblmedia(library_id, sort_title);
+
+-- Recreate media view
+DROP VIEW media;
+
+CREATE VIEW media AS
+SELECT _tblmedia.*, pp.local_path as poster_path, bp.local_path as backdrop_path
+FROM _tblmedia
+LEFT OUTER JOIN assets pp ON _tblmedia.poster = pp.id
+LEFT OUTER JOIN assets bp ON _tblmedia.backdrop = bp.id;
+
+CREATE TRIGGER media_delete
+INSTEAD OF DELETE ON media
+BEGIN DELETE FROM _tblmedia WHERE _tblmedia.id = old.id; END;
@@ -0,0 +1,12 @@
+-- Files uploaded by users, ie avatars, used to enforce the per user upload quota. Avatars uploaded
+-- before this migration have no entry and dont count towards the quota.
+CREATE TABLE uploads (
+    asset_id INTEGER NOT NULL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    -- size of the file in bytes.
+    size INTEGER NOT NULL,
+
+    FOREIGN KEY (asset_id) REFERENCES assets(id) ON DELETE CASCADE
+);
+
+CREATE INDEX uploads_user_idx ON uploads(user_id);
@@ -0,0 +1,2 @@
+-- Optional note owners can attach to a library, ie "4K remux collection".
+ALTER TABLE library ADD COLUMN description TEXT;
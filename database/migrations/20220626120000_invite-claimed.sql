@@ -0,0 +1,5 @@
+-- Invites are marked claimed in the same statement that checks they are unclaimed, see
+-- `Login::claim_invite`, so that two registrations cannot redeem the same invite.
+ALTER TABLE invites ADD COLUMN claimed INTEGER NOT NULL DEFAULT 0;
+
+UPDATE invites SET claimed = 1 WHERE id IN (SELECT claimed_invite FROM users);
@@ -0,0 +1,6 @@
+-- Episodes get their own thumbnail, the still tmdb provides for them, so that they dont have to
+-- borrow the backdrop slot of the underlying media.
+ALTER TABLE episode ADD COLUMN still INTEGER REFERENCES assets(id);
+
+-- Scanners used to store the still as the backdrop of the episode.
+UPDATE episode SET still = (SELECT backdrop FROM _tblmedia WHERE _tblmedia.id = episode.id);
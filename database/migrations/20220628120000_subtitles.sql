@@ -0,0 +1,18 @@
+-- Subtitle tracks found while scanning a mediafile, both the ones embedded in the file and
+-- sidecar `.srt`/`.vtt` files next to it, so that clients can list them without probing the file.
+CREATE TABLE subtitles (
+    id INTEGER NOT NULL PRIMARY KEY,
+    mediafile_id INTEGER NOT NULL,
+    -- name of the language, ie "English", if known.
+    language TEXT,
+    codec TEXT NOT NULL,
+    -- path of the sidecar file, NULL for embedded tracks.
+    external_path TEXT,
+    -- index of the stream inside the mediafile, NULL for sidecar files.
+    stream_index INTEGER,
+    forced INTEGER NOT NULL DEFAULT 0,
+
+    FOREIGN KEY (mediafile_id) REFERENCES mediafile(id) ON DELETE CASCADE
+);
+
+CREATE INDEX subtitles_mediafile_idx ON subtitles(mediafile_id);
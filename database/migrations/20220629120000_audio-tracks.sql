@@ -0,0 +1,17 @@
+-- Audio tracks found while scanning a mediafile, so that clients can offer a track selector
+-- without probing the file.
+CREATE TABLE audio_tracks (
+    id INTEGER NOT NULL PRIMARY KEY,
+    mediafile_id INTEGER NOT NULL,
+    -- index of the stream inside the mediafile.
+    stream_index INTEGER NOT NULL,
+    -- name of the language, ie "English", if known.
+    language TEXT,
+    codec TEXT NOT NULL,
+    channels INTEGER,
+    is_default INTEGER NOT NULL DEFAULT 0,
+
+    FOREIGN KEY (mediafile_id) REFERENCES mediafile(id) ON DELETE CASCADE
+);
+
+CREATE INDEX audio_tracks_mediafile_idx ON audio_tracks(mediafile_id);
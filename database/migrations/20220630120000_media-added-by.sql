@@ -0,0 +1,16 @@
+-- Who added a media, either the username of the user who matched it by hand or "scanner". Media
+-- added before this migration are left NULL as we cant tell anymore.
+ALTER TABLE _tblmedia ADD COLUMN added_by TEXT;
+
+-- Recreate media view
+DROP VIEW media;
+
+CREATE VIEW media AS
+SELECT _tblmedia.*, pp.local_path as poster_path, bp.local_path as backdrop_path
+FROM _tblmedia
+LEFT OUTER JOIN assets pp ON _tblmedia.poster = pp.id
+LEFT OUTER JOIN assets bp ON _tblmedia.backdrop = bp.id;
+
+CREATE TRIGGER media_delete
+INSTEAD OF DELETE ON media
+BEGIN DELETE FROM _tblmedia WHERE _tblmedia.id = old.id; END;
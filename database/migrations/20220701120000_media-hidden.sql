@@ -0,0 +1,15 @@
+-- Hidden media are kept out of listings, search and the dashboard but stay accessible by id.
+ALTER TABLE _tblmedia ADD COLUMN is_hidden BOOLEAN NOT NULL DEFAULT 0;
+
+-- Recreate media view
+DROP VIEW media;
+
+CREATE VIEW media AS
+SELECT _tblmedia.*, pp.local_path as poster_path, bp.local_path as backdrop_path
+FROM _tblmedia
+LEFT OUTER JOIN assets pp ON _tblmedia.poster = pp.id
+LEFT OUTER JOIN assets bp ON _tblmedia.backdrop = bp.id;
+
+CREATE TRIGGER media_delete
+INSTEAD OF DELETE ON media
+BEGIN DELETE FROM _tblmedia WHERE _tblmedia.id = old.id; END;
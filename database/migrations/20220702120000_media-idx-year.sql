@@ -0,0 +1,6 @@
+-- Tv shows without a external id are told apart by name and year, see
+-- `InsertableMedia::get_or_create_show`, so the year has to be part of the unique index. Media
+-- without a year are treated as having the same year.
+DROP INDEX media_idx;
+CREATE UNIQUE INDEX media_idx ON _tblmedia(library_id, name, media_type, IFNULL(year, 0))
+WHERE NOT _tblmedia.media_type = "episode" AND _tblmedia.external_id IS NULL;
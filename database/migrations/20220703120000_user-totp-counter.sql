@@ -0,0 +1,3 @@
+-- Counter of the last TOTP code a user has logged in with, codes at or below it are rejected so
+-- that a code cant be used twice.
+ALTER TABLE users ADD COLUMN totp_last_counter INTEGER;
@@ -0,0 +1,5 @@
+-- Libraries are restricted explicitly instead of by having grants, otherwise revoking the last
+-- grant of a library would open it up to everyone.
+ALTER TABLE library ADD COLUMN restricted INTEGER NOT NULL DEFAULT 0;
+
+UPDATE library SET restricted = 1 WHERE id IN (SELECT library_id FROM library_access);
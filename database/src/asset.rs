@@ -1,7 +1,40 @@
 use crate::user::UserID;
 use crate::DatabaseError;
+use serde::Serializer;
 use std::path::PathBuf;
 
+/// Function turns a stored asset path into the url under which clients can fetch it. Local assets
+/// are stored either as `images/<file>` or as a bare file name, both of which are served under
+/// `/images/<file>`. Absolute urls, ie posters still hosted by TMDB, are returned unchanged.
+/// Empty paths yield `None`.
+pub fn resolve_url(path: &str) -> Option<String> {
+    if path.is_empty() {
+        return None;
+    }
+
+    if path.starts_with("http://") || path.starts_with("https://") || path.starts_with("/images/") {
+        return Some(path.to_string());
+    }
+
+    let file = path.trim_start_matches('/');
+    let file = file.strip_prefix("images/").unwrap_or(file);
+
+    Some(format!("/images/{}", file))
+}
+
+/// Serializer which runs a asset path through [`resolve_url`], to be used with
+/// `#[serde(serialize_with = "database::asset::serialize_url")]`. The stored value is left
+/// untouched.
+pub fn serialize_url<S: Serializer>(
+    path: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match path.as_deref().and_then(resolve_url) {
+        Some(x) => serializer.serialize_some(&x),
+        None => serializer.serialize_none(),
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Asset {
     pub id: i64,
@@ -37,6 +70,21 @@ impl Asset {
         .await?)
     }
 
+    /// Get an asset by its id, but only if it is used as the avatar of a user.
+    pub async fn get_avatar(
+        conn: &mut crate::Transaction<'_>,
+        id: i64,
+    ) -> Result<Option<Self>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            Asset,
+            r#"SELECT * FROM assets
+                WHERE id = ? AND id IN (SELECT picture FROM users)"#,
+            id
+        )
+        .fetch_optional(&mut *conn)
+        .await?)
+    }
+
     pub async fn into_media_poster(
         &self,
         conn: &mut crate::Transaction<'_>,
@@ -82,6 +130,85 @@ impl Asset {
         .await?
         .remote_url)
     }
+
+    /// Method records that `asset_id` has been uploaded by a user so that it counts towards the
+    /// upload quota of that user.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `asset_id` - id of the uploaded asset.
+    /// * `uid` - id of the user who uploaded the file.
+    /// * `size` - size of the file in bytes.
+    pub async fn record_upload(
+        conn: &mut crate::Transaction<'_>,
+        asset_id: i64,
+        uid: UserID,
+        size: i64,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "INSERT OR REPLACE INTO uploads (asset_id, user_id, size) VALUES (?, ?, ?)",
+            asset_id,
+            uid,
+            size
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Method returns how many bytes a user has uploaded in total.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `uid` - id of the user.
+    pub async fn uploaded_bytes(
+        conn: &mut crate::Transaction<'_>,
+        uid: UserID,
+    ) -> Result<i64, DatabaseError> {
+        Ok(sqlx::query!(
+            r#"SELECT COALESCE(SUM(size), 0) as "total!: i64" FROM uploads WHERE user_id = ?"#,
+            uid
+        )
+        .fetch_one(&mut *conn)
+        .await?
+        .total)
+    }
+
+    /// Method removes an uploaded asset from the upload quota of its user. The asset itself is
+    /// left untouched, see [`Asset::delete`].
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `asset_id` - id of the uploaded asset.
+    pub async fn forget_upload(
+        conn: &mut crate::Transaction<'_>,
+        asset_id: i64,
+    ) -> Result<usize, DatabaseError> {
+        Ok(
+            sqlx::query!("DELETE FROM uploads WHERE asset_id = ?", asset_id)
+                .execute(&mut *conn)
+                .await?
+                .rows_affected() as usize,
+        )
+    }
+
+    /// Method removes an asset. The file on disk has to be removed by the caller.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `id` - id of the asset.
+    pub async fn delete(
+        conn: &mut crate::Transaction<'_>,
+        id: i64,
+    ) -> Result<usize, DatabaseError> {
+        Self::forget_upload(&mut *conn, id).await?;
+
+        Ok(sqlx::query!("DELETE FROM assets WHERE id = ?", id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected() as usize)
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -0,0 +1,78 @@
+use crate::DatabaseError;
+
+use serde::Serialize;
+
+/// Struct represents a audio track embedded in a mediafile.
+#[derive(Clone, Serialize, PartialEq, Debug)]
+pub struct AudioTrack {
+    pub id: i64,
+    pub mediafile_id: i64,
+    /// Index of the stream inside the mediafile.
+    pub stream_index: i64,
+    /// Name of the language, ie "English", if known.
+    pub language: Option<String>,
+    /// Codec as reported by ffprobe, ie `aac`.
+    pub codec: String,
+    pub channels: Option<i64>,
+    /// Whether the track is flagged as the default track of the mediafile.
+    pub is_default: bool,
+}
+
+impl AudioTrack {
+    /// Method returns the audio tracks of all mediafiles of a media object in stream order.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media object.
+    pub async fn get_for_media(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+    ) -> Result<Vec<Self>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            AudioTrack,
+            r#"SELECT audio_tracks.id, audio_tracks.mediafile_id, audio_tracks.stream_index,
+                audio_tracks.language, audio_tracks.codec, audio_tracks.channels,
+                audio_tracks.is_default as "is_default: bool"
+            FROM audio_tracks
+            INNER JOIN mediafile ON mediafile.id = audio_tracks.mediafile_id
+            WHERE mediafile.media_id = ?
+            ORDER BY audio_tracks.mediafile_id, audio_tracks.stream_index"#,
+            media_id
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+}
+
+/// Same as [`AudioTrack`] except its missing the id field.
+#[derive(Clone, Debug, Default)]
+pub struct InsertableAudioTrack {
+    pub mediafile_id: i64,
+    pub stream_index: i64,
+    pub language: Option<String>,
+    pub codec: String,
+    pub channels: Option<i64>,
+    pub is_default: bool,
+}
+
+impl InsertableAudioTrack {
+    /// Method inserts a new audio track into the database.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    pub async fn insert(&self, conn: &mut crate::Transaction<'_>) -> Result<i64, DatabaseError> {
+        Ok(sqlx::query!(
+            "INSERT INTO audio_tracks (mediafile_id, stream_index, language, codec, channels, is_default)
+            VALUES (?, ?, ?, ?, ?, ?)",
+            self.mediafile_id,
+            self.stream_index,
+            self.language,
+            self.codec,
+            self.channels,
+            self.is_default
+        )
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid())
+    }
+}
@@ -0,0 +1,75 @@
+use crate::utils::unix_now;
+use crate::DatabaseError;
+
+use serde::Serialize;
+
+/// Struct represents a single entry in the audit log. Entries can only ever be appended, there is
+/// deliberately no way to update or remove them.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AuditLog {
+    pub id: i64,
+    /// Unix timestamp of when the action happened.
+    pub ts: i64,
+    /// Username of whoever performed the action, `None` for unauthenticated requests.
+    pub actor: Option<String>,
+    /// Name of the action, ie `invite_created`.
+    pub action: String,
+    /// What the action has been performed on, ie a username or an invite token.
+    pub target: Option<String>,
+    /// Free-form additional information.
+    pub detail: Option<String>,
+}
+
+impl AuditLog {
+    /// Method appends a new entry to the audit log and returns its id.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `actor` - username of the user performing the action.
+    /// * `action` - name of the action.
+    /// * `target` - target of the action.
+    /// * `detail` - additional information.
+    pub async fn record(
+        conn: &mut crate::Transaction<'_>,
+        actor: Option<&str>,
+        action: &str,
+        target: Option<&str>,
+        detail: Option<&str>,
+    ) -> Result<i64, DatabaseError> {
+        let ts = unix_now();
+
+        Ok(sqlx::query!(
+            r#"INSERT INTO audit_log (ts, actor, action, target, detail)
+                VALUES ($1, $2, $3, $4, $5)"#,
+            ts,
+            actor,
+            action,
+            target,
+            detail
+        )
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid())
+    }
+
+    /// Method returns a page of audit log entries, newest first.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `limit` - max number of entries to return.
+    /// * `offset` - number of entries to skip.
+    pub async fn get_page(
+        conn: &mut crate::Transaction<'_>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Self>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            AuditLog,
+            "SELECT * FROM audit_log ORDER BY id DESC LIMIT ? OFFSET ?",
+            limit,
+            offset
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+}
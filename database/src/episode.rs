@@ -1,10 +1,12 @@
 use crate::media::InsertableMedia;
 use crate::media::Media;
 use crate::media::UpdateMedia;
+use crate::season::InsertableSeason;
 use crate::user::UserID;
 use crate::DatabaseError;
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
 /// Episode struct encapsulates a media entry representing a episode
 #[derive(Clone, Serialize, Debug)]
@@ -16,6 +18,9 @@ pub struct Episode {
     pub seasonid: i64,
     /// episode number
     pub episode: i64,
+    /// Thumbnail of this episode, unlike `poster_path` which episodes share with their show.
+    #[serde(serialize_with = "crate::asset::serialize_url")]
+    pub still_path: Option<String>,
 
     /// Regerence to a media object which represents this epsiode.
     /// We are essnetially aliasing and wrapping around Media transparently, behind the
@@ -26,11 +31,12 @@ pub struct Episode {
 
 /// This struct is purely used for querying episodes which later gets converted into a Episode
 /// struct
-#[derive(PartialEq, Debug, Copy, Clone, sqlx::FromRow)]
+#[derive(PartialEq, Debug, Clone, sqlx::FromRow)]
 pub struct EpisodeWrapper {
     pub id: i64,
     pub seasonid: i64,
     pub episode_: i64,
+    pub still_path: Option<String>,
 }
 
 impl Episode {
@@ -40,10 +46,12 @@ impl Episode {
     ) -> Result<Self, DatabaseError> {
         let wrapper = sqlx::query_as!(
             EpisodeWrapper,
-            r#"SELECT id as "id!", seasonid, episode_
+            r#"SELECT episode.id as "id!", episode.seasonid as "seasonid!", episode.episode_ as "episode_!",
+                assets.local_path as "still_path?"
             FROM episode
-            WHERE seasonid = ?
-            ORDER BY episode_ ASC"#,
+            LEFT JOIN assets ON assets.id = episode.still
+            WHERE episode.seasonid = ?
+            ORDER BY episode.episode_ ASC"#,
             season_id
         )
         .fetch_one(&mut *conn)
@@ -60,9 +68,11 @@ impl Episode {
     ) -> Result<Self, DatabaseError> {
         let wrapper = sqlx::query_as!(
             EpisodeWrapper,
-            r#"SELECT episode.id as "id!", seasonid, episode_
+            r#"SELECT episode.id as "id!", episode.seasonid as "seasonid!", episode.episode_ as "episode_!",
+                assets.local_path as "still_path?"
             FROM episode
             INNER JOIN season on season.id = episode.seasonid
+            LEFT JOIN assets ON assets.id = episode.still
             WHERE season.tvshowid = ?
             ORDER BY episode_ ASC, season.season_number ASC
             LIMIT 1"#,
@@ -89,9 +99,12 @@ impl Episode {
 
         let wrappers = sqlx::query_as!(
             EpisodeWrapper,
-            r#"SELECT episode.id as "id!", episode.episode_, episode.seasonid FROM episode
+            r#"SELECT episode.id as "id!", episode.episode_ as "episode_!", episode.seasonid as "seasonid!",
+                assets.local_path as "still_path?"
+                FROM episode
                 INNER JOIN season ON season.id = episode.seasonid
                 INNER JOIN tv_show ON tv_show.id = season.tvshowid
+                LEFT JOIN assets ON assets.id = episode.still
                 WHERE tv_show.id = ?
                 ORDER BY season.season_number, episode.episode_"#,
             tv_show_id
@@ -120,7 +133,11 @@ impl Episode {
     ) -> Result<Vec<Episode>, DatabaseError> {
         let wrappers = sqlx::query_as!(
             EpisodeWrapper,
-            r#"SELECT id as "id!", episode_, seasonid FROM episode WHERE seasonid = ?"#,
+            r#"SELECT episode.id as "id!", episode.episode_ as "episode_!", episode.seasonid as "seasonid!",
+                assets.local_path as "still_path?"
+            FROM episode
+            LEFT JOIN assets ON assets.id = episode.still
+            WHERE episode.seasonid = ?"#,
             season_id
         )
         .fetch_all(&mut *conn)
@@ -152,8 +169,11 @@ impl Episode {
     ) -> Result<Episode, DatabaseError> {
         let wrapper = sqlx::query_as!(
             EpisodeWrapper,
-            r#"SELECT episode.id as "id!", episode.episode_, episode.seasonid  FROM episode
+            r#"SELECT episode.id as "id!", episode.episode_ as "episode_!", episode.seasonid as "seasonid!",
+                assets.local_path as "still_path?"
+            FROM episode
             INNER JOIN season ON season.id = episode.seasonid
+            LEFT JOIN assets ON assets.id = episode.still
             WHERE season.tvshowid = ?
             AND season.season_number = ?
             AND episode.episode_ = ?"#,
@@ -175,7 +195,10 @@ impl Episode {
     ) -> Result<Episode, DatabaseError> {
         let wrapper = sqlx::query_as!(
             EpisodeWrapper,
-            r#"SELECT episode.*  FROM episode
+            r#"SELECT episode.id as "id!", episode.episode_ as "episode_!", episode.seasonid as "seasonid!",
+                assets.local_path as "still_path?"
+            FROM episode
+            LEFT JOIN assets ON assets.id = episode.still
             WHERE episode.id = ?"#,
             episode_id
         )
@@ -233,8 +256,11 @@ impl Episode {
 
         let record = sqlx::query_as!(
             EpisodeWrapper,
-            r#"SELECT episode.id as "id!", episode.seasonid, episode.episode_ FROM episode
+            r#"SELECT episode.id as "id!", episode.seasonid as "seasonid!", episode.episode_ as "episode_!",
+                assets.local_path as "still_path?"
+            FROM episode
             INNER JOIN season ON season.id = episode.seasonid
+            LEFT JOIN assets ON assets.id = episode.still
             WHERE season.tvshowid = (
                 SELECT _tblseason.tvshowid FROM _tblseason
                 WHERE _tblseason.id = ?
@@ -266,8 +292,11 @@ impl Episode {
 
         let record = sqlx::query_as!(
             EpisodeWrapper,
-            r#"SELECT episode.id as "id!", episode.seasonid, episode.episode_ FROM episode
+            r#"SELECT episode.id as "id!", episode.seasonid as "seasonid!", episode.episode_ as "episode_!",
+                assets.local_path as "still_path?"
+            FROM episode
             INNER JOIN season ON season.id = episode.seasonid
+            LEFT JOIN assets ON assets.id = episode.still
             WHERE season.tvshowid = (
                 SELECT _tblseason.tvshowid FROM _tblseason
                 WHERE _tblseason.id = ?
@@ -299,8 +328,10 @@ impl Episode {
         // FIXME: We're using the query_as function instead of macro because `LEFT OUTER JOIN`
         // crashes the proc macro.
         let result = sqlx::query_as::<_, EpisodeWrapper>(
-            "SELECT episode.* FROM episode
+            "SELECT episode.id, episode.seasonid, episode.episode_, assets.local_path as still_path
+            FROM episode
             INNER JOIN season ON season.id = episode.seasonid
+            LEFT JOIN assets ON assets.id = episode.still
             INNER JOIN progress ON progress.media_id = episode.id AND progress.user_id = ?
             WHERE season.tvshowid = ?
             ORDER BY progress.populated DESC
@@ -336,6 +367,112 @@ impl Episode {
         // cascade delete.
         Ok(Media::delete(conn, episode_id).await?)
     }
+
+    /// Method moves a episode to a different season and/or episode number. Seasons that dont
+    /// exist yet are created. See [`renumber_many`](Self::renumber_many) for the details.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `episode_id` - id of the episode to move.
+    /// * `season` - season number the episode should be part of.
+    /// * `episode` - new episode number.
+    pub async fn renumber(
+        conn: &mut crate::Transaction<'_>,
+        episode_id: i64,
+        season: i64,
+        episode: i64,
+    ) -> Result<(), DatabaseError> {
+        Self::renumber_many(conn, &[(episode_id, season, episode)]).await
+    }
+
+    /// Method moves a batch of episodes, each entry being `(episode_id, season, episode)`. The
+    /// batch is validated as a whole before anything is changed, so episodes within the same
+    /// batch may swap places. If two episodes of the same show would end up in the same slot,
+    /// [`DatabaseError::DuplicateEpisode`] is returned and nothing is changed, for this to be
+    /// atomic `conn` should be a write transaction. Each episode may only be part of the batch
+    /// once, otherwise [`DatabaseError::DuplicateRenumber`] is returned.
+    ///
+    /// Seasons left without episodes are kept.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `renumbers` - the episodes to move and their new season and episode numbers.
+    pub async fn renumber_many(
+        conn: &mut crate::Transaction<'_>,
+        renumbers: &[(i64, i64, i64)],
+    ) -> Result<(), DatabaseError> {
+        let mut moved = HashSet::with_capacity(renumbers.len());
+        for &(id, ..) in renumbers {
+            if !moved.insert(id) {
+                return Err(DatabaseError::DuplicateRenumber { id });
+            }
+        }
+
+        let mut slots = HashSet::new();
+        let mut shows = Vec::with_capacity(renumbers.len());
+
+        for &(id, season, episode) in renumbers {
+            let show = sqlx::query_scalar!(
+                r#"SELECT _tblseason.tvshowid as "tvshowid!" FROM episode
+                INNER JOIN _tblseason ON _tblseason.id = episode.seasonid
+                WHERE episode.id = ?"#,
+                id
+            )
+            .fetch_one(&mut *conn)
+            .await?;
+
+            if !slots.insert((show, season, episode)) {
+                return Err(DatabaseError::DuplicateEpisode { season, episode });
+            }
+
+            // episodes that are moved as part of this batch free up their slot.
+            let occupants = sqlx::query_scalar!(
+                r#"SELECT episode.id as "id!" FROM episode
+                INNER JOIN _tblseason ON _tblseason.id = episode.seasonid
+                WHERE _tblseason.tvshowid = ? AND _tblseason.season_number = ?
+                AND episode.episode_ = ?"#,
+                show,
+                season,
+                episode
+            )
+            .fetch_all(&mut *conn)
+            .await?;
+
+            if occupants.iter().any(|x| !moved.contains(x)) {
+                return Err(DatabaseError::DuplicateEpisode { season, episode });
+            }
+
+            shows.push(show);
+        }
+
+        // NOTE: (seasonid, episode_) is unique, park the moved episodes on a number that cant be
+        // taken first so that episodes can swap places.
+        for &(id, ..) in renumbers {
+            sqlx::query!("UPDATE episode SET episode_ = -id WHERE id = ?", id)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        for (&(id, season, episode), show) in renumbers.iter().zip(shows) {
+            let seasonid = InsertableSeason {
+                season_number: season,
+                ..Default::default()
+            }
+            .insert(&mut *conn, show)
+            .await?;
+
+            sqlx::query!(
+                "UPDATE episode SET seasonid = ?, episode_ = ? WHERE id = ?",
+                seasonid,
+                episode,
+                id
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -343,6 +480,8 @@ pub struct InsertableEpisode {
     pub media: InsertableMedia,
     pub seasonid: i64,
     pub episode: i64,
+    /// Id of the asset holding the still of this episode.
+    pub still: Option<i64>,
 }
 
 impl InsertableEpisode {
@@ -365,11 +504,12 @@ impl InsertableEpisode {
         // NOTE: use insert blind here just in case we have conflicts between episode names.
         let media_id = self.media.insert_blind(&mut *conn).await?;
         let result = sqlx::query!(
-            "INSERT INTO episode (id, episode_, seasonid)
-            VALUES ($1, $2, $3)",
+            "INSERT INTO episode (id, episode_, seasonid, still)
+            VALUES ($1, $2, $3, $4)",
             media_id,
             self.episode,
-            self.seasonid
+            self.seasonid,
+            self.still
         )
         .execute(&mut *conn)
         .await?
@@ -385,6 +525,7 @@ impl EpisodeWrapper {
             id: self.id,
             seasonid: self.seasonid,
             episode: self.episode_,
+            still_path: self.still_path,
             media,
         }
     }
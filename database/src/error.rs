@@ -7,10 +7,80 @@ use thiserror::Error;
 pub enum DatabaseError {
     /// Generic database error: {0:?}
     DatabaseError(sqlx::error::Error),
+    /// The requested row does not exist.
+    NotFound,
+    /// A unique constraint has been violated: {0:?}
+    UniqueViolation(sqlx::error::Error),
+    /// A foreign key constraint has been violated: {0:?}
+    ForeignKeyViolation(sqlx::error::Error),
+    /// The database is busy or locked by another connection: {0:?}
+    Busy(sqlx::error::Error),
+    /// A library with this name already exists or already indexes this location.
+    LibraryExists,
+    /// Library names cannot be blank.
+    InvalidLibraryName,
+    /// The media type of a library that holds media cannot be changed.
+    LibraryNotEmpty,
+    /// Invalid media type: {0:?}
+    InvalidMediaType(String),
+    /// A {media} cannot be added to a {library} library.
+    MediaTypeMismatch {
+        library: crate::library::MediaType,
+        media: crate::library::MediaType,
+    },
+    /// Unknown role: {0:?}
+    InvalidRole(String),
+    /// Invalid timestamp: {0:?}
+    InvalidTimestamp(String),
+    /// Invalid tag: {0:?}
+    InvalidTag(String),
+    /// Invalid rating {0}, ratings must be between 0 and 100.
+    InvalidRating(i64),
+    /// Invalid scan interval {0}, libraries can be rescanned at most every 5 minutes.
+    InvalidScanInterval(i64),
+    /// Season {season} already has a episode {episode}.
+    DuplicateEpisode { season: i64, episode: i64 },
+    /// Episode {id} is renumbered more than once.
+    DuplicateRenumber { id: i64 },
+    /// Cannot remove the last owner.
+    LastOwner,
+    /// The user lacks the permission required for this action.
+    PermissionDenied,
+    /// Password must be at least {min_len} characters long.
+    WeakPassword { min_len: usize },
+    /// Invalid password hashing iterations {rounds}, must be between {min} and {max}.
+    InvalidHashRounds { rounds: u32, min: u32, max: u32 },
+    /// A invite token is required.
+    InviteRequired,
+    /// The invite token doesnt exist or has already been claimed.
+    InviteInvalid,
+    /// The invite token has expired.
+    InviteExpired,
+    /// The invite token has already been claimed.
+    InviteAlreadyClaimed,
+    /// Failed to decrypt a stored secret: {0}
+    SecretUnreadable(AuthError),
+    /// Statement failed after {attempts} attempts: {source:?}
+    RetryExhausted {
+        attempts: usize,
+        source: sqlx::error::Error,
+    },
 }
 
 impl From<sqlx::error::Error> for DatabaseError {
     fn from(e: sqlx::error::Error) -> DatabaseError {
-        Self::DatabaseError(e)
+        if let sqlx::Error::RowNotFound = e {
+            return Self::NotFound;
+        }
+
+        // NOTE: sqlite returns extended error codes, see https://www.sqlite.org/rescode.html
+        match crate::utils::sqlite_code(&e) {
+            // SQLITE_CONSTRAINT_UNIQUE and SQLITE_CONSTRAINT_PRIMARYKEY
+            Some(2067 | 1555) => Self::UniqueViolation(e),
+            // SQLITE_CONSTRAINT_FOREIGNKEY
+            Some(787) => Self::ForeignKeyViolation(e),
+            Some(x) if matches!(x & 0xff, 5 | 6) => Self::Busy(e),
+            _ => Self::DatabaseError(e),
+        }
     }
 }
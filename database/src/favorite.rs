@@ -0,0 +1,96 @@
+use crate::media::Media;
+use crate::user::UserID;
+use crate::utils::unix_now;
+use crate::DatabaseError;
+
+/// Struct represents a media object a user has bookmarked.
+pub struct Favorite;
+
+impl Favorite {
+    /// Method marks a media object as a favorite of a user. Adding a media that is already a
+    /// favorite is a no-op.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `uid` - id of the user.
+    /// * `media_id` - id of the media to bookmark.
+    pub async fn add(
+        conn: &mut crate::Transaction<'_>,
+        uid: UserID,
+        media_id: i64,
+    ) -> Result<usize, DatabaseError> {
+        let timestamp = unix_now();
+
+        Ok(sqlx::query!(
+            "INSERT OR IGNORE INTO favorites (user_id, media_id, date_added) VALUES ($1, $2, $3)",
+            uid,
+            media_id,
+            timestamp
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
+    /// Method removes a media object from the favorites of a user.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `uid` - id of the user.
+    /// * `media_id` - id of the media to remove.
+    pub async fn remove(
+        conn: &mut crate::Transaction<'_>,
+        uid: UserID,
+        media_id: i64,
+    ) -> Result<usize, DatabaseError> {
+        Ok(sqlx::query!(
+            "DELETE FROM favorites WHERE user_id = ? AND media_id = ?",
+            uid,
+            media_id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
+    /// Method returns all the favorites of a user, most recently added first.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `uid` - id of the user.
+    pub async fn get_for_user(
+        conn: &mut crate::Transaction<'_>,
+        uid: UserID,
+    ) -> Result<Vec<Media>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            Media,
+            r#"SELECT media.id, media.library_id, media.name, media.description, media.rating,
+                media.year, media.added, media.poster_path, media.backdrop_path, media.duration, media.tmdb_id as "tmdb_id: i32", media.imdb_id, media.added_by,
+                media.media_type as "media_type: _"
+            FROM media
+            INNER JOIN favorites ON favorites.media_id = media.id
+            WHERE favorites.user_id = ?
+            ORDER BY favorites.date_added DESC, favorites.rowid DESC"#,
+            uid
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Method removes all favorite entries pointing at a media object.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media being deleted.
+    pub async fn delete_by_media_id(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+    ) -> Result<usize, DatabaseError> {
+        Ok(
+            sqlx::query!("DELETE FROM favorites WHERE media_id = ?", media_id)
+                .execute(&mut *conn)
+                .await?
+                .rows_affected() as usize,
+        )
+    }
+}
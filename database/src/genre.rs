@@ -79,6 +79,66 @@ impl Genre {
         .await?)
     }
 
+    /// Method links a genre to a media object, creating the genre first if it doesnt exist yet.
+    /// Linking is idempotent, thus calling this method multiple times with the same arguments,
+    /// for example when rescanning a library, will not create duplicate links.
+    ///
+    /// Returns whether a new link has been created.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media object we want to tag.
+    /// * `genre_name` - name of the genre, ie "Action"
+    pub async fn attach(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+        genre_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        let genre_id = InsertableGenre {
+            name: genre_name.to_string(),
+        }
+        .insert(&mut *conn)
+        .await?;
+
+        // NOTE: `ON CONFLICT DO NOTHING` is understood by both sqlite and postgres and behaves like
+        // `INSERT OR IGNORE` on sqlite.
+        let rows = sqlx::query!(
+            "INSERT INTO genre_media (genre_id, media_id) VALUES ($1, $2)
+            ON CONFLICT DO NOTHING",
+            genre_id,
+            media_id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        Ok(rows > 0)
+    }
+
+    /// Method replaces all genres of a media object with `genres`. Genres previously linked to
+    /// this media that are not in `genres` are unlinked. This should be called within a single
+    /// transaction so that the media is never left with a partial set of genres.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media object we want to tag.
+    /// * `genres` - the full set of genre names for this media.
+    pub async fn set_for_media(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+        genres: &[&str],
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!("DELETE FROM genre_media WHERE media_id = ?", media_id)
+            .execute(&mut *conn)
+            .await?;
+
+        for genre in genres {
+            Self::attach(&mut *conn, media_id, genre).await?;
+        }
+
+        Ok(())
+    }
+
     /// Method removes a genre from the genre table based on its id
     ///
     /// # Arguments
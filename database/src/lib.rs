@@ -11,9 +11,12 @@ use sqlx::ConnectOptions;
 use tracing::{info, instrument};
 
 pub mod asset;
+pub mod audio_track;
+pub mod audit;
 pub mod compact_mediafile;
 pub mod episode;
 pub mod error;
+pub mod favorite;
 pub mod genre;
 pub mod library;
 pub mod media;
@@ -21,9 +24,14 @@ pub mod mediafile;
 pub mod movie;
 pub mod progress;
 pub mod query_ext;
+pub mod rating;
 #[cfg(feature = "sqlite")]
 pub mod rw_pool;
 pub mod season;
+pub mod subtitle;
+pub mod tag;
+#[cfg(test)]
+pub mod test_support;
 #[cfg(test)]
 pub mod tests;
 pub mod tv;
@@ -34,7 +42,20 @@ pub use crate::error::DatabaseError;
 /// Ugly hack because of a shitty deadlock in `Pool`
 pub use crate::rw_pool::write_tx;
 pub use auth::generate_key;
+pub use auth::rotate_keys;
 pub use auth::set_key;
+pub use auth::set_keys;
+pub use auth::set_storage_key;
+pub use auth::totp;
+pub use auth::unix_now;
+pub use auth::SigningKey;
+
+/// Function encodes a list of ids as a json array. sqlx cant bind a list, so queries that filter
+/// on a list of ids bind this instead and expand it with `SELECT value FROM json_each(?)`.
+pub fn json_ids(ids: &[i64]) -> String {
+    let ids: Vec<String> = ids.iter().map(ToString::to_string).collect();
+    format!("[{}]", ids.join(","))
+}
 
 #[cfg(all(feature = "sqlite", feature = "postgres"))]
 compile_error!("Features sqlite and postgres are mutually exclusive");
@@ -104,7 +125,15 @@ pub fn try_get_conn() -> Option<&'static crate::DbConnection> {
     __GLOBAL.get()
 }
 
-#[cfg(all(feature = "sqlite", test))]
+/// Function returns whether the embedded migrations have been applied successfully.
+pub fn migrations_done() -> bool {
+    MIGRATIONS_FLAG.load(Ordering::SeqCst)
+}
+
+/// Function returns a connection to a fresh in-memory database with all migrations applied. This
+/// is only used by tests, other crates get it through the `test-utils` feature.
+#[cfg(all(feature = "sqlite", any(test, feature = "test-utils")))]
+#[doc(hidden)]
 pub async fn get_conn_memory() -> sqlx::Result<crate::DbConnection> {
     let pool = sqlx::Pool::connect(":memory:").await?;
     let connection: sqlx::pool::PoolConnection<sqlx::Sqlite> = pool.acquire().await?;
@@ -133,6 +162,7 @@ pub async fn get_conn_devel() -> sqlx::Result<crate::DbConnection> {
                     .await?;
 
             let rd_only = sqlx::pool::PoolOptions::new()
+                .max_connections(rw_pool::MAX_READERS)
                 .connect_with(sqlx::sqlite::SqliteConnectOptions::new()
                     .read_only(true)
                     .create_if_missing(true)
@@ -188,6 +218,7 @@ async fn internal_get_conn() -> sqlx::Result<DbConnection> {
                     .await?;
 
             let rd_only = sqlx::pool::PoolOptions::new()
+                .max_connections(rw_pool::MAX_READERS)
                 .connect_with(sqlx::sqlite::SqliteConnectOptions::from_str(ffpath("config/dim.db"))?
                     .read_only(true)
                     .synchronous(sqlx::sqlite::SqliteSynchronous::Normal)
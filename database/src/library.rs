@@ -1,13 +1,31 @@
+use crate::user::UserID;
+use crate::utils::unix_now;
 use crate::DatabaseError;
 use serde::Deserialize;
 use serde::Serialize;
+use sqlx::database::HasArguments;
+use sqlx::database::HasValueRef;
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::Database;
+use sqlx::Decode;
+use sqlx::Encode;
+use sqlx::Type;
+use std::convert::TryFrom;
 use std::fmt;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
 
 /// Enum represents a media type and can be used on a library or on a media.
 /// When returned in a http response, the fields are lowercase.
-#[derive(Copy, Serialize, Debug, Clone, Eq, PartialEq, Deserialize, Hash, sqlx::Type)]
+///
+/// In the database media types are stored as lowercase strings. Decoding a row with a media type
+/// that is not recognized by [`MediaType::from_str`] fails with
+/// [`DatabaseError::InvalidMediaType`] instead of silently yielding a bogus value.
+#[derive(Copy, Serialize, Debug, Clone, Eq, PartialEq, Deserialize, Hash)]
 #[serde(rename_all = "lowercase")]
-#[sqlx(rename_all = "lowercase")]
 pub enum MediaType {
     Movie,
     Tv,
@@ -34,6 +52,100 @@ impl Default for MediaType {
     }
 }
 
+impl FromStr for MediaType {
+    type Err = DatabaseError;
+
+    /// Parses a media type, ignoring case and surrounding whitespace. Any value other than
+    /// `movie`, `tv` or `episode` is rejected.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "movie" => Ok(Self::Movie),
+            "tv" => Ok(Self::Tv),
+            "episode" => Ok(Self::Episode),
+            _ => Err(DatabaseError::InvalidMediaType(s.to_string())),
+        }
+    }
+}
+
+impl TryFrom<&str> for MediaType {
+    type Error = DatabaseError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl<DB: Database> Type<DB> for MediaType
+where
+    str: Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <str as Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <str as Type<DB>>::compatible(ty)
+    }
+}
+
+impl<'r, DB: Database> Decode<'r, DB> for MediaType
+where
+    &'r str: Decode<'r, DB>,
+{
+    fn decode(value: <DB as HasValueRef<'r>>::ValueRef) -> Result<Self, BoxDynError> {
+        Ok(<&str as Decode<DB>>::decode(value)?.parse()?)
+    }
+}
+
+impl<'q, DB: Database> Encode<'q, DB> for MediaType
+where
+    String: Encode<'q, DB>,
+{
+    fn encode_by_ref(&self, buf: &mut <DB as HasArguments<'q>>::ArgumentBuffer) -> IsNull {
+        <String as Encode<DB>>::encode(self.to_string(), buf)
+    }
+}
+
+/// Enum represents the state a library scan is in. In the database the states are stored as
+/// lowercase strings.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum ScanStatus {
+    /// No scan is running, either because none ever ran or because the last one finished.
+    #[default]
+    Idle,
+    Scanning,
+    /// The last scan errored out or was interrupted by a restart.
+    Failed,
+}
+
+/// Struct represents the last known scanner state of a library.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ScanState {
+    pub library_id: i64,
+    pub status: ScanStatus,
+    /// Unix timestamp of when the last scan started.
+    pub started_at: Option<i64>,
+    /// Unix timestamp of when the last scan finished or failed.
+    pub finished_at: Option<i64>,
+    /// Number of files the last scan has processed.
+    pub files_processed: i64,
+}
+
+/// Shortest interval in seconds a library can be rescanned at by a [`ScanSchedule`].
+pub const MIN_SCAN_INTERVAL: i64 = 5 * 60;
+
+/// Struct represents how often a library is rescanned automatically.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ScanSchedule {
+    pub library_id: i64,
+    /// Seconds between two scheduled scans.
+    pub interval: i64,
+    /// Unix timestamp of the next scheduled scan.
+    pub next_run: i64,
+}
+
 /// Library struct which we can use to deserialize database queries into.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Library {
@@ -50,6 +162,15 @@ pub struct Library {
     /// moment only `movie` and `tv` are supported
     // TODO: support mixed content, music
     pub media_type: MediaType,
+
+    /// Whether this library must never be listed to unauthenticated clients. Unlike access
+    /// entries this doesnt restrict which users can see the library.
+    #[serde(default)]
+    pub is_private: bool,
+
+    /// Optional note the owner attached to this library, ie "4K remux collection".
+    #[serde(default)]
+    pub description: Option<String>,
 }
 
 impl Library {
@@ -59,7 +180,7 @@ impl Library {
     /// This method will not return the locations indexed for this library, if you need those you
     /// must query for them separately.
     pub async fn get_all(conn: &mut crate::Transaction<'_>) -> Vec<Self> {
-        sqlx::query!(r#"SELECT id, name, media_type as "media_type: MediaType" FROM library WHERE NOT hidden"#)
+        sqlx::query!(r#"SELECT id, name, media_type as "media_type: MediaType", is_private as "is_private: bool", description FROM library WHERE NOT hidden"#)
             .fetch_all(&mut *conn)
             .await
             .unwrap_or_default()
@@ -69,10 +190,227 @@ impl Library {
                 name: x.name,
                 media_type: x.media_type,
                 locations: vec![],
+                is_private: x.is_private,
+                description: x.description,
             })
             .collect()
     }
 
+    /// Method returns all libraries along with how many media each of them holds, in a single
+    /// query. Episodes and hidden media dont count towards the total, a library with no media is
+    /// returned with a count of 0.
+    ///
+    /// Like [`Library::get_all`] the locations of the libraries are not returned.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    pub async fn get_all_with_counts(
+        conn: &mut crate::Transaction<'_>,
+    ) -> Result<Vec<(Self, i64)>, DatabaseError> {
+        Ok(sqlx::query!(
+            r#"SELECT id, name, media_type as "media_type: MediaType",
+                is_private as "is_private: bool", description,
+                (SELECT COUNT(*) FROM _tblmedia
+                    WHERE _tblmedia.library_id = library.id
+                    AND NOT _tblmedia.media_type = 'episode'
+                    AND NOT _tblmedia.is_hidden) as "count!: i64"
+            FROM library
+            WHERE NOT hidden"#
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|x| {
+            let library = Self {
+                id: x.id,
+                name: x.name,
+                media_type: x.media_type,
+                locations: vec![],
+                is_private: x.is_private,
+                description: x.description,
+            };
+
+            (library, x.count)
+        })
+        .collect())
+    }
+
+    /// Method returns all libraries the user with the supplied id is allowed to browse.
+    /// Libraries that arent restricted are visible to everyone, see [`Library::set_restricted`].
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `uid` - id of the user.
+    pub async fn get_all_for_user(conn: &mut crate::Transaction<'_>, uid: UserID) -> Vec<Self> {
+        sqlx::query!(
+            r#"SELECT id, name, media_type as "media_type: MediaType", is_private as "is_private: bool",
+                description
+            FROM library
+            WHERE NOT hidden
+            AND (NOT restricted
+                OR id IN (SELECT library_id FROM library_access WHERE user_id = ?))"#,
+            uid
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|x| Self {
+            id: x.id,
+            name: x.name,
+            media_type: x.media_type,
+            locations: vec![],
+            is_private: x.is_private,
+            description: x.description,
+        })
+        .collect()
+    }
+
+    /// Method checks whether the user with the supplied id is allowed to browse a library.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `lib_id` - id of the library.
+    /// * `uid` - id of the user.
+    pub async fn can_access(
+        conn: &mut crate::Transaction<'_>,
+        lib_id: i64,
+        uid: UserID,
+    ) -> Result<bool, DatabaseError> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT NOT EXISTS(SELECT 1 FROM library WHERE id = ? AND restricted)
+                OR ? IN (SELECT library_id FROM library_access WHERE user_id = ?) as "allowed!: bool""#,
+            lib_id,
+            lib_id,
+            uid
+        )
+        .fetch_one(&mut *conn)
+        .await?)
+    }
+
+    /// Method returns the ids of all restricted libraries the user with the supplied id has not
+    /// been granted access to.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `uid` - id of the user.
+    pub async fn get_denied_ids(
+        conn: &mut crate::Transaction<'_>,
+        uid: UserID,
+    ) -> Result<Vec<i64>, DatabaseError> {
+        Ok(sqlx::query_scalar!(
+            "SELECT id FROM library
+            WHERE restricted
+            AND id NOT IN (SELECT library_id FROM library_access WHERE user_id = ?)",
+            uid
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Method grants a user access to a library. Granting access restricts the library, so it is
+    /// hidden from everyone that hasnt been granted access. Granting access twice is a no-op.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `lib_id` - id of the library.
+    /// * `uid` - id of the user.
+    pub async fn grant_access(
+        conn: &mut crate::Transaction<'_>,
+        lib_id: i64,
+        uid: UserID,
+    ) -> Result<usize, DatabaseError> {
+        Self::set_restricted(&mut *conn, lib_id, true).await?;
+
+        Ok(sqlx::query!(
+            "INSERT OR IGNORE INTO library_access (library_id, user_id) VALUES ($1, $2)",
+            lib_id,
+            uid
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
+    /// Method revokes the access of a user to a library. The library stays restricted even once
+    /// the last grant has been revoked, see [`Library::set_restricted`] to open it up again.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `lib_id` - id of the library.
+    /// * `uid` - id of the user.
+    pub async fn revoke_access(
+        conn: &mut crate::Transaction<'_>,
+        lib_id: i64,
+        uid: UserID,
+    ) -> Result<usize, DatabaseError> {
+        Ok(sqlx::query!(
+            "DELETE FROM library_access WHERE library_id = ? AND user_id = ?",
+            lib_id,
+            uid
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
+    /// Method marks a library as restricted, or opens it up to everyone again. Restricted libraries
+    /// can only be browsed by the users that have been granted access with
+    /// [`Library::grant_access`] and by owners.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `lib_id` - id of the library.
+    /// * `restricted` - whether the library should be restricted.
+    pub async fn set_restricted(
+        conn: &mut crate::Transaction<'_>,
+        lib_id: i64,
+        restricted: bool,
+    ) -> Result<usize, DatabaseError> {
+        Ok(sqlx::query!(
+            "UPDATE library SET restricted = ? WHERE id = ?",
+            restricted,
+            lib_id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
+    /// Method returns whether a library is restricted, see [`Library::set_restricted`].
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `lib_id` - id of the library.
+    pub async fn is_restricted(
+        conn: &mut crate::Transaction<'_>,
+        lib_id: i64,
+    ) -> Result<bool, DatabaseError> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT restricted as "restricted: bool" FROM library WHERE id = ?"#,
+            lib_id
+        )
+        .fetch_one(&mut *conn)
+        .await?)
+    }
+
+    /// Method returns the ids of all users that have been granted access to a library.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `lib_id` - id of the library.
+    pub async fn get_access(
+        conn: &mut crate::Transaction<'_>,
+        lib_id: i64,
+    ) -> Result<Vec<UserID>, DatabaseError> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT user_id as "user_id: UserID" FROM library_access WHERE library_id = ?"#,
+            lib_id
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
     pub async fn get_locations(
         conn: &mut crate::Transaction<'_>,
         id: i64,
@@ -97,8 +435,9 @@ impl Library {
         lib_id: i64,
     ) -> Result<Self, DatabaseError> {
         let library = sqlx::query!(
-            r#"SELECT id, name, media_type as "media_type: MediaType" FROM library
-            WHERE id = ?"#,
+            r#"SELECT id, name, media_type as "media_type: MediaType", is_private as "is_private: bool",
+                description
+            FROM library WHERE id = ?"#,
             lib_id
         )
         .fetch_one(&mut *conn)
@@ -117,6 +456,8 @@ impl Library {
             name: library.name,
             media_type: library.media_type,
             locations,
+            is_private: library.is_private,
+            description: library.description,
         })
     }
 
@@ -129,12 +470,469 @@ impl Library {
         conn: &mut crate::Transaction<'_>,
         id_to_del: i64,
     ) -> Result<usize, DatabaseError> {
+        sqlx::query!("DELETE FROM library_access WHERE library_id = ?", id_to_del)
+            .execute(&mut *conn)
+            .await?;
+
+        sqlx::query!(
+            "DELETE FROM media_tombstones WHERE library_id = ?",
+            id_to_del
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!("DELETE FROM scan_state WHERE library_id = ?", id_to_del)
+            .execute(&mut *conn)
+            .await?;
+
+        sqlx::query!("DELETE FROM scan_schedule WHERE library_id = ?", id_to_del)
+            .execute(&mut *conn)
+            .await?;
+
         Ok(sqlx::query!("DELETE FROM library WHERE id = ?", id_to_del)
             .execute(&mut *conn)
             .await?
             .rows_affected() as usize)
     }
 
+    /// Method returns whether the media type of a library can be changed, which is only the case
+    /// while the library holds no media and no files. Queries and joins rely on all media of a
+    /// library matching its type, so a populated library must keep its type.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - id of the library.
+    pub async fn can_change_type(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+    ) -> Result<bool, DatabaseError> {
+        Ok(sqlx::query!(
+            r#"SELECT NOT EXISTS (SELECT 1 FROM _tblmedia WHERE library_id = ?1)
+                AND NOT EXISTS (SELECT 1 FROM mediafile WHERE library_id = ?1) as "empty!: bool""#,
+            library_id
+        )
+        .fetch_one(&mut *conn)
+        .await?
+        .empty)
+    }
+
+    /// Method returns the scanner state of a library. Libraries that have never been scanned are
+    /// reported as [`ScanStatus::Idle`].
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - id of the library.
+    pub async fn scan_status(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+    ) -> Result<ScanState, DatabaseError> {
+        let state = sqlx::query_as!(
+            ScanState,
+            r#"SELECT library_id, status as "status: ScanStatus", started_at, finished_at,
+                files_processed
+            FROM scan_state WHERE library_id = ?"#,
+            library_id
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        Ok(state.unwrap_or(ScanState {
+            library_id,
+            status: ScanStatus::Idle,
+            started_at: None,
+            finished_at: None,
+            files_processed: 0,
+        }))
+    }
+
+    /// Method marks a library as being scanned and resets the counters of the previous scan.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - id of the library.
+    pub async fn scan_started(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+    ) -> Result<(), DatabaseError> {
+        let now = unix_now();
+        let status = ScanStatus::Scanning;
+
+        sqlx::query!(
+            "INSERT OR REPLACE INTO scan_state (library_id, status, started_at, finished_at, files_processed)
+            VALUES (?, ?, ?, NULL, 0)",
+            library_id,
+            status,
+            now
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Method records that the scan of a library is over.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - id of the library.
+    /// * `files_processed` - number of files the scan went through.
+    /// * `failed` - whether the scan errored out.
+    pub async fn scan_finished(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+        files_processed: i64,
+        failed: bool,
+    ) -> Result<(), DatabaseError> {
+        let now = unix_now();
+        let status = if failed {
+            ScanStatus::Failed
+        } else {
+            ScanStatus::Idle
+        };
+
+        sqlx::query!(
+            "UPDATE scan_state SET status = ?, finished_at = ?, files_processed = ?
+            WHERE library_id = ?",
+            status,
+            now,
+            files_processed,
+            library_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Method marks all scans that are still flagged as running as failed. This is meant to be
+    /// called on boot, before any scanner is started, as such scans have been interrupted.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    pub async fn reset_interrupted_scans(
+        conn: &mut crate::Transaction<'_>,
+    ) -> Result<usize, DatabaseError> {
+        let now = unix_now();
+        let scanning = ScanStatus::Scanning;
+        let failed = ScanStatus::Failed;
+
+        Ok(sqlx::query!(
+            "UPDATE scan_state SET status = ?, finished_at = ? WHERE status = ?",
+            failed,
+            now,
+            scanning
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
+    /// Method sets how often a library is rescanned, replacing any previous schedule. The first
+    /// scheduled scan runs `interval` seconds from now.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - id of the library.
+    /// * `interval` - seconds between two scans, at least [`MIN_SCAN_INTERVAL`].
+    pub async fn set_scan_schedule(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+        interval: i64,
+    ) -> Result<ScanSchedule, DatabaseError> {
+        if interval < MIN_SCAN_INTERVAL {
+            return Err(DatabaseError::InvalidScanInterval(interval));
+        }
+
+        let next_run = unix_now() + interval;
+
+        sqlx::query!(
+            "INSERT OR REPLACE INTO scan_schedule (library_id, interval, next_run) VALUES (?, ?, ?)",
+            library_id,
+            interval,
+            next_run
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(ScanSchedule {
+            library_id,
+            interval,
+            next_run,
+        })
+    }
+
+    /// Method returns the scan schedule of a library, or `None` if it isnt rescanned
+    /// automatically.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - id of the library.
+    pub async fn get_scan_schedule(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+    ) -> Result<Option<ScanSchedule>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            ScanSchedule,
+            "SELECT library_id, interval, next_run FROM scan_schedule WHERE library_id = ?",
+            library_id
+        )
+        .fetch_optional(&mut *conn)
+        .await?)
+    }
+
+    /// Method removes the scan schedule of a library.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - id of the library.
+    pub async fn clear_scan_schedule(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+    ) -> Result<usize, DatabaseError> {
+        Ok(
+            sqlx::query!("DELETE FROM scan_schedule WHERE library_id = ?", library_id)
+                .execute(&mut *conn)
+                .await?
+                .rows_affected() as usize,
+        )
+    }
+
+    /// Method returns the schedules whose next scan is due at `now` and moves their next run one
+    /// interval past `now`, so that every due schedule is only returned once. Runs that were
+    /// missed while the server was down are collapsed into a single scan.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `now` - current unix timestamp.
+    pub async fn take_due_scans(
+        conn: &mut crate::Transaction<'_>,
+        now: i64,
+    ) -> Result<Vec<ScanSchedule>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            ScanSchedule,
+            r#"UPDATE scan_schedule SET next_run = ?1 + interval
+            WHERE next_run <= ?1
+            RETURNING library_id as "library_id!", interval as "interval!", next_run as "next_run!""#,
+            now
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Method removes all content belonging to a library in dependency order, including rows in
+    /// other tables that reference media of this library. The library itself and its indexed
+    /// paths are left untouched, use [`Library::delete`] for that.
+    ///
+    /// This method should be called from within a single write transaction so that the
+    /// library is never left half-purged.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `lib_id` - id of the library we want to purge.
+    pub async fn purge(
+        conn: &mut crate::Transaction<'_>,
+        lib_id: i64,
+    ) -> Result<PurgeSummary, DatabaseError> {
+        let progress = sqlx::query!(
+            "DELETE FROM progress
+            WHERE media_id IN (SELECT id FROM _tblmedia WHERE library_id = ?)",
+            lib_id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize;
+
+        let mediafiles = sqlx::query!("DELETE FROM mediafile WHERE library_id = ?", lib_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected() as usize;
+
+        let episodes = sqlx::query!(
+            "DELETE FROM episode
+            WHERE id IN (SELECT id FROM _tblmedia WHERE library_id = ?)",
+            lib_id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize;
+
+        let seasons = sqlx::query!(
+            "DELETE FROM _tblseason
+            WHERE tvshowid IN (SELECT id FROM _tblmedia WHERE library_id = ?)",
+            lib_id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize;
+
+        let movies = sqlx::query!(
+            "DELETE FROM movie
+            WHERE id IN (SELECT id FROM _tblmedia WHERE library_id = ?)",
+            lib_id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize;
+
+        let tv_shows = sqlx::query!(
+            "DELETE FROM tv_show
+            WHERE id IN (SELECT id FROM _tblmedia WHERE library_id = ?)",
+            lib_id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize;
+
+        sqlx::query!(
+            "DELETE FROM genre_media
+            WHERE media_id IN (SELECT id FROM _tblmedia WHERE library_id = ?)",
+            lib_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM favorites
+            WHERE media_id IN (SELECT id FROM _tblmedia WHERE library_id = ?)",
+            lib_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM media_tags
+            WHERE media_id IN (SELECT id FROM _tblmedia WHERE library_id = ?)",
+            lib_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM ratings
+            WHERE media_id IN (SELECT id FROM _tblmedia WHERE library_id = ?)",
+            lib_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM user_ratings
+            WHERE media_id IN (SELECT id FROM _tblmedia WHERE library_id = ?)",
+            lib_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        let media = sqlx::query!("DELETE FROM _tblmedia WHERE library_id = ?", lib_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected() as usize;
+
+        Ok(PurgeSummary {
+            mediafiles,
+            progress,
+            movies,
+            tv_shows,
+            seasons,
+            episodes,
+            media,
+        })
+    }
+
+    /// Method removes all media of a library along with their files, progress and everything
+    /// else [`Library::purge`] removes, but keeps the library and its indexed paths so that it can
+    /// be scanned again from scratch. Unlike a purge ahead of a delete this records tombstones for
+    /// the removed movies and tv shows, so that syncing clients drop them too, and resets the
+    /// scanner state of the library.
+    ///
+    /// This method should be called from within a single write transaction so that the
+    /// library is never left half-emptied.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `lib_id` - id of the library we want to empty.
+    pub async fn empty(
+        conn: &mut crate::Transaction<'_>,
+        lib_id: i64,
+    ) -> Result<PurgeSummary, DatabaseError> {
+        let deleted_at = unix_now();
+        sqlx::query!(
+            r#"INSERT OR REPLACE INTO media_tombstones (media_id, library_id, deleted_at)
+            SELECT id, library_id, ? FROM _tblmedia
+            WHERE library_id = ? AND NOT media_type = "episode""#,
+            deleted_at,
+            lib_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        let summary = Self::purge(&mut *conn, lib_id).await?;
+
+        sqlx::query!("DELETE FROM scan_state WHERE library_id = ?", lib_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(summary)
+    }
+
+    /// Method renames the library with the id `id`. The name is trimmed before being stored.
+    /// Blank names are rejected with [`DatabaseError::InvalidLibraryName`] and names already used
+    /// by another library with [`DatabaseError::LibraryExists`]. Hidden libraries that are still
+    /// being deleted keep their name until they are gone.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `id` - id of the library we want to rename.
+    /// * `name` - the new name.
+    pub async fn rename(
+        conn: &mut crate::Transaction<'_>,
+        id: i64,
+        name: &str,
+    ) -> Result<usize, DatabaseError> {
+        let name = check_name(&mut *conn, name, Some(id)).await?;
+
+        let rows = sqlx::query!(
+            "UPDATE library SET name = ? WHERE id = ? AND NOT hidden",
+            name,
+            id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize;
+
+        if rows == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+
+        Ok(rows)
+    }
+
+    /// Method returns how many media, episodes and files a library holds, in a single query.
+    /// Like [`Library::get_all_with_counts`], `media` only counts movies and tv shows.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `id` - id of the library.
+    pub async fn get_stats(
+        conn: &mut crate::Transaction<'_>,
+        id: i64,
+    ) -> Result<LibraryStats, DatabaseError> {
+        Ok(sqlx::query_as!(
+            LibraryStats,
+            r#"SELECT
+                (SELECT COUNT(*) FROM _tblmedia
+                    WHERE library_id = ?1 AND NOT media_type = 'episode') as "media!: i64",
+                (SELECT COUNT(*) FROM _tblmedia
+                    WHERE library_id = ?1 AND media_type = 'episode') as "episodes!: i64",
+                (SELECT COUNT(*) FROM mediafile WHERE library_id = ?1) as "mediafiles!: i64",
+                (SELECT COUNT(*) FROM mediafile
+                    WHERE library_id = ?1 AND media_id IS NULL) as "unmatched!: i64""#,
+            id
+        )
+        .fetch_one(&mut *conn)
+        .await?)
+    }
+
     pub async fn mark_hidden(
         conn: &mut crate::Transaction<'_>,
         id: i64,
@@ -148,30 +946,151 @@ impl Library {
     }
 }
 
+/// Struct holds the counts returned by [`Library::get_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct LibraryStats {
+    /// Number of movies and tv shows.
+    pub media: i64,
+    pub episodes: i64,
+    pub mediafiles: i64,
+    /// Number of mediafiles that havent been matched to a media.
+    pub unmatched: i64,
+}
+
+/// Struct summarizes how many rows of each type were removed by [`Library::purge`] or
+/// [`Library::empty`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct PurgeSummary {
+    pub mediafiles: usize,
+    pub progress: usize,
+    pub movies: usize,
+    pub tv_shows: usize,
+    pub seasons: usize,
+    pub episodes: usize,
+    pub media: usize,
+}
+
+/// Struct used to update a library. Every field is optional, fields that are `None` are left
+/// untouched. Locations are managed separately.
+#[derive(Clone, Default, Deserialize, Debug)]
+pub struct UpdateLibrary {
+    pub name: Option<String>,
+    pub media_type: Option<MediaType>,
+    pub is_private: Option<bool>,
+    /// An empty description removes the current one.
+    pub description: Option<String>,
+}
+
+impl UpdateLibrary {
+    /// Method updates the library with the id `id`. Changing the media type of a library which
+    /// already holds media is rejected with [`DatabaseError::LibraryNotEmpty`], see
+    /// [`Library::can_change_type`], and libraries can never hold episodes directly so
+    /// [`MediaType::Episode`] is rejected with [`DatabaseError::InvalidMediaType`]. Names are
+    /// validated like in [`Library::rename`].
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `id` - id of the library we want to update.
+    pub async fn update(
+        &self,
+        conn: &mut crate::Transaction<'_>,
+        id: i64,
+    ) -> Result<usize, DatabaseError> {
+        if self.media_type == Some(MediaType::Episode) {
+            return Err(DatabaseError::InvalidMediaType(
+                MediaType::Episode.to_string(),
+            ));
+        }
+
+        let library = Library::get_one(&mut *conn, id).await?;
+
+        if let Some(media_type) = self.media_type {
+            if media_type != library.media_type && !Library::can_change_type(&mut *conn, id).await?
+            {
+                return Err(DatabaseError::LibraryNotEmpty);
+            }
+        }
+
+        if let Some(ref name) = self.name {
+            Library::rename(&mut *conn, id, name).await?;
+        }
+
+        crate::opt_update!(conn,
+            "UPDATE library SET media_type = ? WHERE id = ?" => (self.media_type, id),
+            "UPDATE library SET is_private = ? WHERE id = ?" => (self.is_private, id)
+        );
+
+        if let Some(ref description) = self.description {
+            let description = clean_description(description);
+            sqlx::query!(
+                "UPDATE library SET description = ? WHERE id = ?",
+                description,
+                id
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(1)
+    }
+}
+
 /// InsertableLibrary struct, same as [`Library`](Library) but without the id field.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct InsertableLibrary {
     pub name: String,
     pub locations: Vec<String>,
     pub media_type: MediaType,
+    #[serde(default)]
+    pub description: Option<String>,
 }
 
 impl InsertableLibrary {
     /// Method inserts a InsertableLibrary object into the database (makes a new library).
     ///
+    /// Locations are normalized with [`normalize_location`] before being stored. If any of the
+    /// locations is already indexed by another library, or the name is already used by another
+    /// library, [`DatabaseError::LibraryExists`] is returned and nothing is inserted. Names are
+    /// trimmed and checked the same way as in [`Library::rename`].
+    ///
     /// # Arguments
     /// * `conn` - mutable reference to a sqlx transaction.
     pub async fn insert(&self, conn: &mut crate::Transaction<'_>) -> Result<i64, DatabaseError> {
+        let name = check_name(&mut *conn, &self.name, None).await?;
+
+        let mut locations: Vec<String> = Vec::with_capacity(self.locations.len());
+        for location in self.locations.iter().map(|x| normalize_location(x)) {
+            if !locations.contains(&location) {
+                locations.push(location);
+            }
+        }
+
+        // NOTE: Older rows might have been stored without normalization, thus we have to normalize
+        // them too before comparing.
+        let indexed = sqlx::query_scalar!("SELECT location FROM indexed_paths")
+            .fetch_all(&mut *conn)
+            .await?;
+
+        if indexed
+            .iter()
+            .any(|x| locations.contains(&normalize_location(x)))
+        {
+            return Err(DatabaseError::LibraryExists);
+        }
+
+        let description = self.description.as_deref().and_then(clean_description);
+
         let lib_id = sqlx::query!(
-            r#"INSERT INTO library (name, media_type) VALUES ($1, $2)"#,
-            self.name,
-            self.media_type
+            r#"INSERT INTO library (name, media_type, description) VALUES ($1, $2, $3)"#,
+            name,
+            self.media_type,
+            description
         )
         .execute(&mut *conn)
         .await?
         .last_insert_rowid();
 
-        for location in &self.locations {
+        for location in &locations {
             sqlx::query!(
                 r#"INSERT into indexed_paths(location, library_id)
                 VALUES ($1, $2)"#,
@@ -185,3 +1104,65 @@ impl InsertableLibrary {
         Ok(lib_id)
     }
 }
+
+/// Function trims a library name and makes sure it is not blank and not used by any other library,
+/// hidden ones included. `except` is the id of the library being renamed, if any.
+async fn check_name<'a>(
+    conn: &mut crate::Transaction<'_>,
+    name: &'a str,
+    except: Option<i64>,
+) -> Result<&'a str, DatabaseError> {
+    let name = name.trim();
+
+    if name.is_empty() {
+        return Err(DatabaseError::InvalidLibraryName);
+    }
+
+    let taken = sqlx::query_scalar!(
+        r#"SELECT EXISTS(
+            SELECT 1 FROM library WHERE name = ? AND id IS NOT ?
+        ) as "taken: bool""#,
+        name,
+        except
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if taken {
+        return Err(DatabaseError::LibraryExists);
+    }
+
+    Ok(name)
+}
+
+/// Function trims a library description, blank descriptions are stored as `NULL`.
+fn clean_description(description: &str) -> Option<String> {
+    Some(description.trim())
+        .filter(|x| !x.is_empty())
+        .map(ToString::to_string)
+}
+
+/// Function lexically normalizes a library location so that different spellings of the same
+/// directory compare equal. For example `/media/movies/`, `/media/./movies` and
+/// `/media/tv/../movies` all normalize to `/media/movies`.
+///
+/// Symlinks are not resolved as the location might not exist on disk yet.
+pub fn normalize_location(location: &str) -> String {
+    let mut normalized = PathBuf::new();
+
+    for component in Path::new(location).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            x => normalized.push(x.as_os_str()),
+        }
+    }
+
+    normalized.to_string_lossy().into_owned()
+}
@@ -1,8 +1,20 @@
 use crate::library::MediaType;
+use crate::user::UserID;
+use crate::utils::unix_now;
 use crate::DatabaseError;
 
+use std::collections::HashMap;
+
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use chrono::SecondsFormat;
+use chrono::TimeZone;
+use chrono::Utc;
 use serde::Deserialize;
 use serde::Serialize;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
 
 /// Marker trait used to mark media types that inherit from Media.
 /// Used internally by InsertableTVShow.
@@ -29,14 +41,117 @@ pub struct Media {
     /// the db.
     pub added: Option<String>,
     /// Path to the media poster.
+    #[serde(serialize_with = "crate::asset::serialize_url")]
     pub poster_path: Option<String>,
     /// Path to the backdrop for this media object.
+    #[serde(serialize_with = "crate::asset::serialize_url")]
     pub backdrop_path: Option<String>,
+    /// Runtime of this media object in seconds. Always none for tv shows.
+    pub duration: Option<i64>,
+    /// Id of this media on TMDB, used to link to it and to match it during scans.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tmdb_id: Option<i32>,
+    /// Id of this media on IMDb, ie `tt0133093`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imdb_id: Option<String>,
+    /// Who added this media, either the username of the user who matched it by hand or
+    /// [`Media::ADDED_BY_SCANNER`]. Unknown for media added before this was recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub added_by: Option<String>,
     /// Media type encoded as a string. Either movie/tv/episode or none.
     #[serde(flatten)]
     pub media_type: MediaType,
 }
 
+/// Struct represents a single result of [`Media::search_global`](Media::search_global).
+#[derive(Clone, Serialize, Debug, PartialEq)]
+pub struct SearchResult {
+    pub id: i64,
+    pub library_id: i64,
+    pub name: String,
+    pub media_type: MediaType,
+    /// Poster of the media, for episodes this falls back to the poster of the tv show.
+    #[serde(serialize_with = "crate::asset::serialize_url")]
+    pub poster_path: Option<String>,
+    /// Name of the tv show if this result is a episode.
+    pub show_name: Option<String>,
+}
+
+/// Struct represents a media object whose metadata is due for a refresh, as returned by
+/// [`Media::get_stale`](Media::get_stale).
+#[derive(Clone, Debug, PartialEq)]
+pub struct StaleMedia {
+    pub id: i64,
+    /// Id of this media with the metadata provider, ie `tmdb:603`.
+    pub external_id: String,
+    pub media_type: MediaType,
+}
+
+/// Struct represents a single episode of a tv show as returned by
+/// [`Media::get_episodes`](Media::get_episodes).
+#[derive(Clone, Serialize, Debug, PartialEq)]
+pub struct EpisodeRecord {
+    /// id of the episode media object.
+    pub id: i64,
+    /// season number of the season this episode belongs to.
+    pub season: i64,
+    /// episode number.
+    pub episode: i64,
+    /// name of the episode.
+    pub name: String,
+    /// Path to the still for this episode.
+    #[serde(serialize_with = "crate::asset::serialize_url")]
+    pub still_path: Option<String>,
+}
+
+/// Granularity used when grouping media by the date they were added, see
+/// [`Media::get_added_grouped`](Media::get_added_grouped).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddedGranularity {
+    Day,
+    #[default]
+    Month,
+}
+
+impl AddedGranularity {
+    fn format(&self) -> &'static str {
+        match self {
+            Self::Day => "%Y-%m-%d",
+            Self::Month => "%Y-%m",
+        }
+    }
+}
+
+/// Struct represents the media added within a single day or month, as returned by
+/// [`Media::get_added_grouped`](Media::get_added_grouped).
+#[derive(Clone, Serialize, Debug, PartialEq)]
+pub struct AddedBucket {
+    /// The day (`2022-06-13`) or month (`2022-06`) of this bucket.
+    pub date: String,
+    /// Number of movies and tv shows added in this bucket.
+    pub count: i64,
+}
+
+/// Position in a library listing ordered by `(added, id)`, as returned by
+/// [`Media::get_page_after`](Media::get_page_after). Clients pass both fields back unchanged to
+/// fetch the next page, ie `?after_added=2022-06-13T12:34:56Z&after_id=42`. Media without an
+/// `added` timestamp sort first with an empty `added`.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PageCursor {
+    pub added: String,
+    pub id: i64,
+}
+
+/// Struct represents a page of media together with the cursor of the next page, see
+/// [`Media::get_page_after`](Media::get_page_after).
+#[derive(Clone, Serialize, Debug)]
+pub struct MediaPage {
+    pub media: Vec<Media>,
+    /// Cursor to request the next page with, `None` on the last page.
+    pub next: Option<PageCursor>,
+}
+
 impl PartialEq for Media {
     fn eq(&self, other: &Media) -> bool {
         self.id == other.id
@@ -44,6 +159,9 @@ impl PartialEq for Media {
 }
 
 impl Media {
+    /// Value of [`Media::added_by`] for media added by a scan.
+    pub const ADDED_BY_SCANNER: &'static str = "scanner";
+
     /// Method returns all Media objects associated with a Library. Its exactly the same as
     /// [`Library::get`](Library::get) except it takes in a Library object instead of a id.
     /// [`Library::get`](Library::get) is a intermediary to this function, as it calls this
@@ -55,16 +173,271 @@ impl Media {
     pub async fn get_all(
         conn: &mut crate::Transaction<'_>,
         library_id: i64,
+    ) -> Result<Vec<Self>, DatabaseError> {
+        Self::get_all_with_hidden(conn, library_id, false).await
+    }
+
+    /// Method returns all Media objects associated with a Library, same as [`Media::get_all`]
+    /// except hidden media are included when `include_hidden` is set.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - a [`Library`](Library) id.
+    /// * `include_hidden` - whether to include media hidden with [`Media::set_hidden`].
+    pub async fn get_all_with_hidden(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+        include_hidden: bool,
     ) -> Result<Vec<Self>, DatabaseError> {
         Ok(sqlx::query_as!(
                 Media,
-                r#"SELECT id, library_id, name, description, rating, year, added, poster_path, backdrop_path, media_type as "media_type: _" FROM media WHERE library_id = ? AND NOT media_type = "episode""#,
-                library_id
+                r#"SELECT id, library_id, name, description, rating, year, added, poster_path, backdrop_path, duration, tmdb_id as "tmdb_id: i32", imdb_id, added_by, media_type as "media_type: _" FROM media WHERE library_id = ? AND NOT media_type = "episode" AND (? OR NOT is_hidden)"#,
+                library_id,
+                include_hidden
             )
             .fetch_all(&mut *conn)
             .await?)
     }
 
+    /// Method returns a page of the movies and tv shows of a library ordered by `(added, id)`,
+    /// starting after `after`. Unlike offset pagination this is stable while media is being
+    /// added, and the id breaks ties between media added at the same second so nothing is skipped
+    /// or returned twice.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - a [`Library`](Library) id.
+    /// * `after` - cursor of the previous page, `None` for the first page.
+    /// * `limit` - max number of medias to return, at least one.
+    pub async fn get_page_after(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+        after: Option<&PageCursor>,
+        limit: i64,
+    ) -> Result<MediaPage, DatabaseError> {
+        let limit = limit.max(1);
+        let after_added = after.map(|x| x.added.as_str());
+        let after_id = after.map(|x| x.id);
+        // fetch one extra row to find out whether there is a next page.
+        let fetch = limit + 1;
+
+        let mut media = sqlx::query_as!(
+            Media,
+            r#"SELECT id, library_id, name, description as "description?", rating, year,
+                added as "added?", poster_path as "poster_path?", backdrop_path as "backdrop_path?",
+                duration, tmdb_id as "tmdb_id: i32", imdb_id, added_by, media_type as "media_type: _"
+            FROM media
+            WHERE library_id = ?1 AND NOT media_type = "episode" AND NOT is_hidden
+            AND (?2 IS NULL
+                OR COALESCE(added, '') > ?2
+                OR (COALESCE(added, '') = ?2 AND id > ?3))
+            ORDER BY COALESCE(added, ''), id
+            LIMIT ?4"#,
+            library_id,
+            after_added,
+            after_id,
+            fetch
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let next = if media.len() as i64 > limit {
+            media.truncate(limit as usize);
+            media.last().map(|x| PageCursor {
+                added: x.added.clone().unwrap_or_default(),
+                id: x.id,
+            })
+        } else {
+            None
+        };
+
+        Ok(MediaPage { media, next })
+    }
+
+    /// Method returns the movies and tv shows of a library that have been tagged with `tag`,
+    /// sorted by name. Tags are matched case-insensitively.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - a [`Library`](Library) id.
+    /// * `tag` - name of the tag.
+    pub async fn get_by_tag(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+        tag: &str,
+    ) -> Result<Vec<Self>, DatabaseError> {
+        let tag = crate::tag::Tag::normalize(tag)?;
+
+        Ok(sqlx::query_as!(
+            Media,
+            r#"SELECT id, library_id, name, description as "description?", rating, year,
+                added as "added?", poster_path as "poster_path?", backdrop_path as "backdrop_path?",
+                duration, tmdb_id as "tmdb_id: i32", imdb_id, added_by, media_type as "media_type: _"
+            FROM media
+            WHERE library_id = ? AND NOT media_type = "episode" AND NOT is_hidden
+            AND id IN (
+                SELECT media_tags.media_id FROM media_tags
+                INNER JOIN tags ON tags.id = media_tags.tag_id
+                WHERE tags.name = ?
+            )
+            ORDER BY sort_title"#,
+            library_id,
+            tag
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Method returns the movies and tv shows of a library the user hasnt finished watching yet,
+    /// most recently added first. A movie counts as watched once the user's progress is past 90%
+    /// of its duration, a tv show counts as unwatched as long as any of its episodes is.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - a [`Library`](Library) id.
+    /// * `uid` - id of the user whose progress we check against.
+    /// * `limit` - max number of medias to return.
+    pub async fn get_unwatched(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+        uid: UserID,
+        limit: i64,
+    ) -> Result<Vec<Self>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            Media,
+            r#"SELECT media.id, media.library_id, media.name, media.description as "description?", media.rating,
+                media.year, media.added as "added?", media.poster_path as "poster_path?",
+                media.backdrop_path as "backdrop_path?", media.duration, media.tmdb_id as "tmdb_id: i32", media.imdb_id, media.added_by,
+                media.media_type as "media_type: _"
+            FROM media
+            WHERE media.library_id = ? AND NOT media.media_type = "episode" AND NOT media.is_hidden
+            AND (
+                (media.media_type = "movie" AND media.id NOT IN (
+                    SELECT progress.media_id FROM progress
+                    INNER JOIN mediafile ON mediafile.media_id = progress.media_id
+                    WHERE progress.user_id = ?
+                    GROUP BY progress.media_id
+                    HAVING progress.delta * 10 > MAX(mediafile.duration) * 9
+                ))
+                OR (media.media_type = "tv" AND media.id IN (
+                    SELECT _tblseason.tvshowid FROM episode
+                    INNER JOIN _tblseason ON _tblseason.id = episode.seasonid
+                    WHERE episode.id NOT IN (
+                        SELECT progress.media_id FROM progress
+                        INNER JOIN mediafile ON mediafile.media_id = progress.media_id
+                        WHERE progress.user_id = ?
+                        GROUP BY progress.media_id
+                        HAVING progress.delta * 10 > MAX(mediafile.duration) * 9
+                    )
+                ))
+            )
+            ORDER BY media.added DESC
+            LIMIT ?"#,
+            library_id,
+            uid,
+            uid,
+            limit
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Method returns the number of movies and tv shows added to a library per day or month,
+    /// newest first. Buckets without any media are left out, as are hidden media and media whose
+    /// `added` date cant be parsed.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - a [`Library`](Library) id.
+    /// * `granularity` - whether to group by day or month.
+    pub async fn get_added_grouped(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+        granularity: AddedGranularity,
+    ) -> Result<Vec<AddedBucket>, DatabaseError> {
+        let format = granularity.format();
+
+        // NOTE: `added` is stored as `2022-06-13T12:34:56Z` (see `normalize_added`), older rows may
+        // carry fractional seconds or a timezone sqlite doesnt understand so we only look at the
+        // first 19 characters.
+        Ok(sqlx::query_as!(
+            AddedBucket,
+            r#"SELECT strftime(?, substr(added, 1, 19)) as "date!: String", COUNT(*) as "count!: i64"
+            FROM _tblmedia
+            WHERE library_id = ? AND NOT media_type = "episode" AND NOT is_hidden
+            AND strftime(?, substr(added, 1, 19)) IS NOT NULL
+            GROUP BY 1
+            ORDER BY 1 DESC"#,
+            format,
+            library_id,
+            format
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Method returns the movies and tv shows of a library whose metadata has changed at or after
+    /// `since`, this includes newly added and unhidden media. Hidden media are left out, see
+    /// [`Media::get_deleted_since`]. Media that predate change tracking are only returned when
+    /// `since` is `0`.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - a [`Library`](Library) id.
+    /// * `since` - unix timestamp of the last sync.
+    pub async fn get_changed_since(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+        since: i64,
+    ) -> Result<Vec<Self>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            Media,
+            r#"SELECT media.id, media.library_id, media.name, media.description as "description?",
+                media.rating, media.year, media.added as "added?",
+                media.poster_path as "poster_path?", media.backdrop_path as "backdrop_path?",
+                media.duration, media.tmdb_id as "tmdb_id: i32", media.imdb_id, media.added_by, media.media_type as "media_type: _"
+            FROM media
+            INNER JOIN _tblmedia ON _tblmedia.id = media.id
+            WHERE media.library_id = ? AND NOT media.media_type = "episode"
+            AND NOT _tblmedia.is_hidden
+            AND COALESCE(_tblmedia.updated_at, 0) >= ?
+            ORDER BY media.id"#,
+            library_id,
+            since
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Method returns the ids of movies and tv shows that have been deleted from a library at or
+    /// after `since`. Ids that have since been reused by a new media are left out. Media hidden at
+    /// or after `since` are included as well, as far as clients are concerned they are gone.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - a [`Library`](Library) id.
+    /// * `since` - unix timestamp of the last sync.
+    pub async fn get_deleted_since(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+        since: i64,
+    ) -> Result<Vec<i64>, DatabaseError> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT media_id as "media_id!" FROM media_tombstones
+            WHERE library_id = ?1 AND deleted_at >= ?2
+            AND media_id NOT IN (SELECT id FROM _tblmedia)
+            UNION
+            SELECT id FROM _tblmedia
+            WHERE library_id = ?1 AND NOT media_type = "episode" AND is_hidden
+            AND COALESCE(updated_at, 0) >= ?2
+            ORDER BY 1"#,
+            library_id,
+            since
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
     /// Method returns a media object based on its id
     ///
     /// # Arguments
@@ -73,13 +446,40 @@ impl Media {
     pub async fn get(conn: &mut crate::Transaction<'_>, id: i64) -> Result<Self, DatabaseError> {
         Ok(sqlx::query_as!(
                 Media,
-                r#"SELECT id, library_id, name, description, rating, year, added, poster_path, backdrop_path, media_type as "media_type: _" FROM media WHERE id = ?"#,
+                r#"SELECT id, library_id, name, description, rating, year, added, poster_path, backdrop_path, duration, tmdb_id as "tmdb_id: i32", imdb_id, added_by, media_type as "media_type: _" FROM media WHERE id = ?"#,
                 id
             )
             .fetch_one(&mut *conn)
             .await?)
     }
 
+    /// Method returns the media with the supplied ids in a single query, in the same order as
+    /// `ids`. Ids that dont exist are left out, and ids passed more than once are only returned
+    /// at their first position.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `ids` - ids of the media, in the order they should be returned in.
+    pub async fn get_many(
+        conn: &mut crate::Transaction<'_>,
+        ids: &[i64],
+    ) -> Result<Vec<Self>, DatabaseError> {
+        let json_ids = crate::json_ids(ids);
+
+        let mut media = sqlx::query_as!(
+                Media,
+                r#"SELECT id, library_id, name, description, rating, year, added, poster_path, backdrop_path, duration, tmdb_id as "tmdb_id: i32", imdb_id, added_by, media_type as "media_type: _" FROM media WHERE id IN (SELECT value FROM json_each(?))"#,
+                json_ids
+            )
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|x| (x.id, x))
+            .collect::<HashMap<_, _>>();
+
+        Ok(ids.iter().filter_map(|id| media.remove(id)).collect())
+    }
+
     /// Method to get a entry in a library based on name and library
     ///
     /// # Arguments
@@ -93,21 +493,67 @@ impl Media {
     ) -> Result<Self, DatabaseError> {
         Ok(sqlx::query_as!(
                 Media,
-                r#"SELECT id, library_id, name, description, rating, year, added, poster_path, backdrop_path, media_type as "media_type: _" FROM media WHERE library_id = ? AND name = ? AND NOT media_type = "episode""#,
+                r#"SELECT id, library_id, name, description, rating, year, added, poster_path, backdrop_path, duration, tmdb_id as "tmdb_id: i32", imdb_id, added_by, media_type as "media_type: _" FROM media WHERE library_id = ? AND name = ? AND NOT media_type = "episode""#,
+                library_id,
+                name,
+            )
+            .fetch_one(&mut *conn)
+            .await?)
+    }
+
+    /// Method to get a entry in a library based on name and year, which unlike
+    /// [`get_by_name_and_lib`](Self::get_by_name_and_lib) tells apart remakes sharing a title.
+    /// A `None` year only matches media without a year.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - a library id.
+    /// * `name` - name we would like to filter by.
+    /// * `year` - release year we would like to filter by.
+    pub async fn get_by_name_year_and_lib(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+        name: &str,
+        year: Option<i64>,
+    ) -> Result<Self, DatabaseError> {
+        Ok(sqlx::query_as!(
+                Media,
+                r#"SELECT id, library_id, name, description as "description?", rating, year, added as "added?", poster_path as "poster_path?", backdrop_path as "backdrop_path?", duration, tmdb_id as "tmdb_id: i32", imdb_id, added_by, media_type as "media_type: _" FROM media WHERE library_id = ? AND name = ? AND year IS ? AND NOT media_type = "episode" ORDER BY id LIMIT 1"#,
                 library_id,
                 name,
+                year,
             )
             .fetch_one(&mut *conn)
             .await?)
     }
 
+    /// Method returns the movie or tv show with the given tmdb id, used by the scanners to
+    /// deduplicate media that was matched before. If several media share the id, the oldest one
+    /// is returned.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `tmdb_id` - id of the media on TMDB.
+    pub async fn get_by_tmdb_id(
+        conn: &mut crate::Transaction<'_>,
+        tmdb_id: i32,
+    ) -> Result<Option<Self>, DatabaseError> {
+        Ok(sqlx::query_as!(
+                Media,
+                r#"SELECT id, library_id, name, description, rating, year, added, poster_path, backdrop_path, duration, tmdb_id as "tmdb_id: i32", imdb_id, added_by, media_type as "media_type: _" FROM media WHERE tmdb_id = ? AND NOT media_type = "episode" ORDER BY id LIMIT 1"#,
+                tmdb_id,
+            )
+            .fetch_optional(&mut *conn)
+            .await?)
+    }
+
     pub async fn get_of_mediafile(
         conn: &mut crate::Transaction<'_>,
         mediafile_id: i64,
     ) -> Result<Self, DatabaseError> {
         Ok(sqlx::query_as!(
                 Media,
-                r#"SELECT media.id, media.library_id, name, description, rating, year, added, poster_path, backdrop_path, media_type as "media_type: _"
+                r#"SELECT media.id, media.library_id, name, description, rating, year, added, poster_path, backdrop_path, media.duration, media.tmdb_id as "tmdb_id: i32", media.imdb_id, media.added_by, media_type as "media_type: _"
                 FROM media
                 INNER JOIN mediafile ON mediafile.media_id = media.id
                 WHERE mediafile.id = ?"#,
@@ -115,56 +561,215 @@ impl Media {
             ).fetch_one(&mut *conn).await?)
     }
 
-    /// Method returns the top rated medias
+    /// Method returns all episodes belonging to a tv show ordered by season number and then
+    /// episode number. Each record carries just enough information to render a season grid. If
+    /// the show has no episodes, an empty vec is returned.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `tv_show_id` - id of the tv show.
+    pub async fn get_episodes(
+        conn: &mut crate::Transaction<'_>,
+        tv_show_id: i64,
+    ) -> Result<Vec<EpisodeRecord>, DatabaseError> {
+        // NOTE: we join on `_tblseason` instead of the `season` view because the view drops
+        // seasons without a poster.
+        Ok(sqlx::query_as!(
+            EpisodeRecord,
+            r#"SELECT episode.id as "id!", _tblseason.season_number as season,
+                episode.episode_ as episode, _tblmedia.name,
+                assets.local_path as "still_path?"
+            FROM episode
+            INNER JOIN _tblseason ON _tblseason.id = episode.seasonid
+            INNER JOIN _tblmedia ON _tblmedia.id = episode.id
+            LEFT JOIN assets ON assets.id = _tblmedia.backdrop
+            WHERE _tblseason.tvshowid = ?
+            ORDER BY _tblseason.season_number, episode.episode_"#,
+            tv_show_id
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Method returns the total runtime in seconds of all the media in a library. Medias without
+    /// a known duration are ignored.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - id of the library.
+    pub async fn get_total_runtime(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+    ) -> Result<i64, DatabaseError> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT COALESCE(SUM(duration), 0) as "total!: i64" FROM _tblmedia
+            WHERE library_id = ?"#,
+            library_id
+        )
+        .fetch_one(&mut *conn)
+        .await?)
+    }
+
+    /// Method returns all medias in a library which have no mediafiles backing them, ie because
+    /// the files have been removed from disk. Movies and episodes are orphaned if no mediafile
+    /// points at them, while tv shows are orphaned if none of their episodes have a mediafile.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - id of the library.
+    pub async fn get_orphaned(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+    ) -> Result<Vec<Self>, DatabaseError> {
+        Ok(sqlx::query_as!(
+                Media,
+                r#"SELECT media.id as "id!", media.library_id as "library_id!", name, description, rating, year, added, poster_path as "poster_path?", backdrop_path as "backdrop_path?", media.duration, media.tmdb_id as "tmdb_id: i32", media.imdb_id, media.added_by, media_type as "media_type: _"
+                FROM media
+                LEFT JOIN mediafile ON mediafile.media_id = media.id
+                WHERE media.library_id = ? AND mediafile.id IS NULL
+                AND media.id NOT IN (
+                    SELECT _tblseason.tvshowid FROM _tblseason
+                    INNER JOIN episode ON episode.seasonid = _tblseason.id
+                    INNER JOIN mediafile ON mediafile.media_id = episode.id
+                )
+                ORDER BY media.sort_title"#,
+                library_id
+            )
+            .fetch_all(&mut *conn)
+            .await?)
+    }
+
+    /// Method returns the movies and tv shows in a library whose metadata hasnt been fetched
+    /// since `cutoff`. Media without a external id are skipped as there is nothing to refresh
+    /// them from. If `cutoff` is `None` all media with a external id are returned.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - id of the library.
+    /// * `cutoff` - unix timestamp before which media count as stale.
+    pub async fn get_stale(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+        cutoff: Option<i64>,
+    ) -> Result<Vec<StaleMedia>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            StaleMedia,
+            r#"SELECT id, external_id as "external_id!", media_type as "media_type: _"
+            FROM _tblmedia
+            WHERE library_id = $1 AND external_id IS NOT NULL
+            AND NOT media_type = "episode"
+            AND ($2 IS NULL OR updated_at IS NULL OR updated_at < $2)
+            ORDER BY id"#,
+            library_id,
+            cutoff
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Method returns the top rated medias, leaving out media of the libraries in `denied`.
     pub async fn get_top_rated(
         conn: &mut crate::Transaction<'_>,
         limit: i64,
+        denied: &[i64],
     ) -> Result<Vec<i64>, DatabaseError> {
+        let denied = crate::json_ids(denied);
+
         Ok(sqlx::query_scalar!(
             r#"SELECT _tblmedia.id
                 FROM _tblmedia
                 JOIN library ON library.id = _tblmedia.library_id
-                WHERE NOT _tblmedia.media_type = "episode" AND NOT library.hidden
+                WHERE NOT _tblmedia.media_type = "episode" AND NOT _tblmedia.is_hidden
+                AND NOT library.hidden
+                AND _tblmedia.library_id NOT IN (SELECT value FROM json_each(?))
                 ORDER BY rating DESC
                 LIMIT ?"#,
+            denied,
             limit
         )
         .fetch_all(&mut *conn)
         .await?)
     }
 
-    /// Method returns the recently added medias
+    /// Method returns the recently added medias, leaving out media of the libraries in `denied`.
     pub async fn get_recently_added(
         conn: &mut crate::Transaction<'_>,
         limit: i64,
+        denied: &[i64],
     ) -> Result<Vec<i64>, DatabaseError> {
+        let denied = crate::json_ids(denied);
+
         Ok(sqlx::query_scalar!(
             r#"SELECT _tblmedia.id
                 FROM _tblmedia
                 JOIN library ON library.id = _tblmedia.library_id
-                WHERE NOT _tblmedia.media_type = "episode" AND NOT library.hidden
+                WHERE NOT _tblmedia.media_type = "episode" AND NOT _tblmedia.is_hidden
+                AND NOT library.hidden
+                AND _tblmedia.library_id NOT IN (SELECT value FROM json_each(?))
                 ORDER BY added DESC
                 LIMIT ?"#,
+            denied,
             limit
         )
         .fetch_all(&mut *conn)
         .await?)
     }
 
+    /// Method returns up to `count` random movies and tv shows of a library. The shuffling is done
+    /// by the database, so only the picked rows are ever loaded.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - a [`Library`](Library) id.
+    /// * `count` - how many media to pick at most.
+    /// * `media_type` - only pick media of this type, if set.
+    pub async fn get_random(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+        count: i64,
+        media_type: Option<MediaType>,
+    ) -> Result<Vec<Self>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            Media,
+            r#"SELECT id, library_id, name, description as "description?", rating, year,
+                added as "added?", poster_path as "poster_path?", backdrop_path as "backdrop_path?",
+                duration, tmdb_id as "tmdb_id: i32", imdb_id, added_by, media_type as "media_type: _"
+            FROM media
+            WHERE library_id = ? AND NOT media_type = "episode" AND NOT is_hidden
+            AND (? IS NULL OR media_type = ?)
+            ORDER BY RANDOM()
+            LIMIT ?"#,
+            library_id,
+            media_type,
+            media_type,
+            count
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Method returns up to `limit` random movies and tv shows of all visible libraries, leaving
+    /// out media of the libraries in `denied`.
     pub async fn get_random_with(
         conn: &mut crate::Transaction<'_>,
         limit: i64,
+        denied: &[i64],
     ) -> Result<Vec<Self>, DatabaseError> {
+        let denied = crate::json_ids(denied);
+
         Ok(sqlx::query_as!(
                 Media,
-                r#"SELECT media.id, media.library_id, media.name, description, rating, year, added, poster_path as "poster_path?", backdrop_path as "backdrop_path?", media.media_type as "media_type: _"
+                r#"SELECT media.id, media.library_id, media.name, media.description, rating, year, added, poster_path as "poster_path?", backdrop_path as "backdrop_path?", media.duration, media.tmdb_id as "tmdb_id: i32", media.imdb_id, media.added_by, media.media_type as "media_type: _"
                 FROM media
                 JOIN library ON media.library_id = library.id
-                WHERE NOT media.media_type = "episode" AND NOT library.hidden
+                WHERE NOT media.media_type = "episode" AND NOT media.is_hidden
+                AND NOT library.hidden
+                AND media.library_id NOT IN (SELECT value FROM json_each(?))
                 GROUP BY media.id
                 ORDER BY RANDOM()
                 LIMIT ?
                 "#,
+                denied,
                 limit
         ).fetch_all(&mut *conn).await?)
     }
@@ -177,10 +782,11 @@ impl Media {
         let query = format!("%{}%", query);
         Ok(sqlx::query_as!(
                 Media,
-                r#"SELECT media.id, media.library_id, media.name, description, rating, year, added, poster_path, backdrop_path, media.media_type as "media_type: _"
+                r#"SELECT media.id, media.library_id, media.name, media.description, rating, year, added, poster_path, backdrop_path, media.duration, media.tmdb_id as "tmdb_id: i32", media.imdb_id, media.added_by, media.media_type as "media_type: _"
                 FROM media
                 JOIN library ON library.id = media.library_id
-                WHERE NOT media.media_type = "episode" AND NOT library.hidden
+                WHERE NOT media.media_type = "episode" AND NOT media.is_hidden
+                AND NOT library.hidden
                 AND UPPER(media.name) LIKE ?
                 LIMIT ?
                 "#,
@@ -189,17 +795,67 @@ impl Media {
         ).fetch_all(&mut *conn).await?)
     }
 
+    /// Method searches the names of all media across all visible libraries, including episodes.
+    /// Like the regular search, the words of `query` have to appear in the name in order but not
+    /// necessarily next to each other, and matching is case-insensitive. Results are tagged
+    /// with their media type and episodes carry the name of their tv show.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `query` - search query.
+    /// * `limit` - max number of results to return.
+    /// * `denied` - ids of libraries whose media are left out.
+    pub async fn search_global(
+        conn: &mut crate::Transaction<'_>,
+        query: &str,
+        limit: i64,
+        denied: &[i64],
+    ) -> Result<Vec<SearchResult>, DatabaseError> {
+        let query = query
+            .split_whitespace()
+            .map(|x| format!("%{}%", x))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let denied = crate::json_ids(denied);
+
+        Ok(sqlx::query_as!(
+            SearchResult,
+            r#"SELECT media.id as "id!", media.library_id as "library_id!", media.name as "name!",
+                    media.media_type as "media_type!: _",
+                    COALESCE(media.poster_path, show.poster_path) as "poster_path?: String",
+                    show.name as "show_name?"
+                FROM media
+                JOIN library ON library.id = media.library_id
+                LEFT JOIN episode ON episode.id = media.id
+                LEFT JOIN _tblseason ON _tblseason.id = episode.seasonid
+                LEFT JOIN media show ON show.id = _tblseason.tvshowid
+                WHERE NOT library.hidden AND NOT media.is_hidden
+                AND NOT COALESCE(show.is_hidden, 0)
+                AND UPPER(media.name) LIKE UPPER(?)
+                AND media.library_id NOT IN (SELECT value FROM json_each(?))
+                ORDER BY media.sort_title
+                LIMIT ?
+                "#,
+            query,
+            denied,
+            limit
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
     pub async fn get_of_genre(
         conn: &mut crate::Transaction<'_>,
         genre_id: i64,
     ) -> Result<Vec<Self>, DatabaseError> {
         Ok(sqlx::query_as!(
                 Media,
-                r#"SELECT media.id, media.library_id, media.name, description, rating, year, added, poster_path, backdrop_path, media.media_type as "media_type: _"
+                r#"SELECT media.id, media.library_id, media.name, media.description, rating, year, added, poster_path, backdrop_path, media.duration, media.tmdb_id as "tmdb_id: i32", media.imdb_id, media.added_by, media.media_type as "media_type: _"
                 FROM media
                 INNER JOIN genre_media ON genre_media.media_id = media.id
                 JOIN library ON library.id = media.library_id
-                WHERE NOT media.media_type = "episode" AND NOT library.hidden
+                WHERE NOT media.media_type = "episode" AND NOT media.is_hidden
+                AND NOT library.hidden
                 AND genre_media.genre_id = ?
                 "#,
                 genre_id,
@@ -212,16 +868,45 @@ impl Media {
     ) -> Result<Vec<Self>, DatabaseError> {
         Ok(sqlx::query_as!(
                 Media,
-                r#"SELECT media.id, media.library_id, media.name, description, rating, year, added, poster_path, backdrop_path, media.media_type as "media_type: _"
+                r#"SELECT media.id, media.library_id, media.name, media.description, rating, year, added, poster_path, backdrop_path, media.duration, media.tmdb_id as "tmdb_id: i32", media.imdb_id, media.added_by, media.media_type as "media_type: _"
                 FROM media
                 JOIN library ON library.id = media.library_id
-                WHERE NOT media.media_type = "episode" AND NOT library.hidden
+                WHERE NOT media.media_type = "episode" AND NOT media.is_hidden
+                AND NOT library.hidden
                 AND year = ?
                 "#,
                 year,
         ).fetch_all(&mut *conn).await?)
     }
 
+    /// Method fills in the [`sort_title`] of media that dont have one yet, which are media that
+    /// were added before sort titles were introduced. This is meant to be called on boot.
+    ///
+    /// Returns the number of media updated.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    pub async fn fill_sort_titles(
+        conn: &mut crate::Transaction<'_>,
+    ) -> Result<usize, DatabaseError> {
+        let rows = sqlx::query!("SELECT id, name FROM _tblmedia WHERE sort_title IS NULL")
+            .fetch_all(&mut *conn)
+            .await?;
+
+        for row in rows.iter() {
+            let sort_title = sort_title(&row.name);
+            sqlx::query!(
+                "UPDATE _tblmedia SET sort_title = ? WHERE id = ?",
+                sort_title,
+                row.id
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(rows.len())
+    }
+
     pub async fn get_first_duration(&self, conn: &mut crate::Transaction<'_>) -> i64 {
         sqlx::query!(
             r#"
@@ -238,6 +923,51 @@ impl Media {
         .unwrap_or(0)
     }
 
+    /// Method hides or unhides a media. Hidden media are left out of library listings, search and
+    /// the dashboard, but are otherwise left intact and can still be fetched by id. Hiding or
+    /// unhiding a media counts as a change, so sync clients pick it up.
+    ///
+    /// Returns the number of rows updated.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `id` - id of the media.
+    /// * `hidden` - whether the media should be hidden.
+    pub async fn set_hidden(
+        conn: &mut crate::Transaction<'_>,
+        id: i64,
+        hidden: bool,
+    ) -> Result<usize, DatabaseError> {
+        let updated_at = unix_now();
+
+        Ok(sqlx::query!(
+            "UPDATE _tblmedia SET is_hidden = ?, updated_at = ? WHERE id = ?",
+            hidden,
+            updated_at,
+            id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
+    /// Method returns whether a media has been hidden with [`Media::set_hidden`].
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `id` - id of the media.
+    pub async fn is_hidden(
+        conn: &mut crate::Transaction<'_>,
+        id: i64,
+    ) -> Result<bool, DatabaseError> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT is_hidden as "is_hidden: bool" FROM _tblmedia WHERE id = ?"#,
+            id
+        )
+        .fetch_one(&mut *conn)
+        .await?)
+    }
+
     pub async fn media_mediatype(
         conn: &mut crate::Transaction<'_>,
         id: i64,
@@ -250,6 +980,25 @@ impl Media {
         .await?)
     }
 
+    /// Method checks whether a media object can be played back, ie whether it is a movie or an
+    /// episode. Tv shows and seasons are only containers and return `false`, as do ids that dont
+    /// exist.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `id` - id of the media to check.
+    pub async fn is_streamable(
+        conn: &mut crate::Transaction<'_>,
+        id: i64,
+    ) -> Result<bool, DatabaseError> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT (? IN (SELECT id FROM movie UNION ALL SELECT id FROM episode)) as "streamable!: bool""#,
+            id
+        )
+        .fetch_one(&mut *conn)
+        .await?)
+    }
+
     pub async fn decouple_mediafiles(
         conn: &mut crate::Transaction<'_>,
         id: i64,
@@ -271,18 +1020,102 @@ impl Media {
         conn: &mut crate::Transaction<'_>,
         id: i64,
     ) -> Result<usize, DatabaseError> {
+        crate::favorite::Favorite::delete_by_media_id(&mut *conn, id).await?;
+        crate::tag::Tag::delete_by_media_id(&mut *conn, id).await?;
+        crate::rating::Rating::delete_by_media_id(&mut *conn, id).await?;
+        crate::rating::UserRating::delete_by_media_id(&mut *conn, id).await?;
+
+        // episodes are synced as part of their tv show, so they dont need a tombstone.
+        let deleted_at = unix_now();
+        sqlx::query!(
+            r#"INSERT OR REPLACE INTO media_tombstones (media_id, library_id, deleted_at)
+            SELECT id, library_id, ? FROM _tblmedia WHERE id = ? AND NOT media_type = "episode""#,
+            deleted_at,
+            id
+        )
+        .execute(&mut *conn)
+        .await?;
+
         Ok(sqlx::query!("DELETE FROM _tblmedia WHERE id = ?", id)
             .execute(&mut *conn)
             .await?
             .rows_affected() as usize)
     }
 
+    /// Method deletes a media object like [`Media::delete`] and also deletes the asset rows of its
+    /// poster, backdrop and still. Assets that are still used by another media, season or user are
+    /// left alone. The files themselves are not touched, instead the `local_path` of every deleted
+    /// asset is returned so that the caller can remove them once the transaction has committed.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `id` - id of the media to delete.
+    pub async fn delete_with_assets(
+        conn: &mut crate::Transaction<'_>,
+        id: i64,
+    ) -> Result<Vec<String>, DatabaseError> {
+        let orphans = sqlx::query!(
+            r#"SELECT assets.id as "id!", assets.local_path FROM assets
+            INNER JOIN _tblmedia ON _tblmedia.poster = assets.id OR _tblmedia.backdrop = assets.id
+                OR assets.id = (SELECT still FROM episode WHERE episode.id = _tblmedia.id)
+            WHERE _tblmedia.id = ?1
+            AND assets.id NOT IN (
+                SELECT poster FROM _tblmedia WHERE poster IS NOT NULL AND id != ?1
+                UNION SELECT backdrop FROM _tblmedia WHERE backdrop IS NOT NULL AND id != ?1
+                UNION SELECT still FROM episode WHERE still IS NOT NULL AND id != ?1
+                UNION SELECT poster FROM _tblseason WHERE poster IS NOT NULL
+                UNION SELECT picture FROM users WHERE picture IS NOT NULL
+            )"#,
+            id
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Self::delete(&mut *conn, id).await?;
+
+        for asset in orphans.iter() {
+            sqlx::query!("DELETE FROM assets WHERE id = ?", asset.id)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        Ok(orphans.into_iter().map(|x| x.local_path).collect())
+    }
+
     /// This function exists because for some reason `CASCADE DELETE` doesnt work with a sqlite
     /// backend. Thus we must manually delete entries when deleting a library.
     pub async fn delete_by_lib_id(
         conn: &mut crate::Transaction<'_>,
         library_id: i64,
     ) -> Result<usize, DatabaseError> {
+        sqlx::query!(
+            "DELETE FROM favorites WHERE media_id IN (SELECT id FROM _tblmedia WHERE library_id = ?)",
+            library_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM media_tags WHERE media_id IN (SELECT id FROM _tblmedia WHERE library_id = ?)",
+            library_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM ratings WHERE media_id IN (SELECT id FROM _tblmedia WHERE library_id = ?)",
+            library_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM user_ratings WHERE media_id IN (SELECT id FROM _tblmedia WHERE library_id = ?)",
+            library_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
         Ok(
             sqlx::query!("DELETE FROM _tblmedia WHERE library_id = ?", library_id)
                 .execute(&mut *conn)
@@ -311,16 +1144,47 @@ pub struct InsertableMedia {
     pub added: String,
     pub poster: Option<i64>,
     pub backdrop: Option<i64>,
+    pub duration: Option<i64>,
+    /// Identifier of this media with a metadata provider, ie `tmdb:603`.
+    pub external_id: Option<String>,
+    pub tmdb_id: Option<i32>,
+    pub imdb_id: Option<String>,
+    /// See [`Media::added_by`].
+    pub added_by: Option<String>,
     pub media_type: MediaType,
 }
 
 impl InsertableMedia {
-    /// Method used to insert a new media object.
+    /// Method checks that `self` may be inserted into its library. Movie libraries only hold
+    /// movies, tv libraries hold tv shows and their episodes.
+    async fn check_library_media_type(
+        &self,
+        conn: &mut crate::Transaction<'_>,
+    ) -> Result<(), DatabaseError> {
+        let library_type = sqlx::query_scalar!(
+            r#"SELECT media_type as "media_type: MediaType" FROM library WHERE id = ?"#,
+            self.library_id
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        match (library_type, self.media_type) {
+            (None, _)
+            | (Some(MediaType::Movie), MediaType::Movie)
+            | (Some(MediaType::Tv), MediaType::Tv | MediaType::Episode) => Ok(()),
+            (Some(library), media) => Err(DatabaseError::MediaTypeMismatch { library, media }),
+        }
+    }
+
+    /// Method used to insert a new media object. The insert is retried with
+    /// [`retry_while!`](crate::retry_while) while the database is busy.
     ///
     /// # Arguments
     /// * `conn` - mutable reference to a sqlx transaction.
     #[tracing::instrument(skip(self, conn), fields(self.name = %self.name, self.library_id = %self.library_id))]
     pub async fn insert(&self, conn: &mut crate::Transaction<'_>) -> Result<i64, DatabaseError> {
+        self.check_library_media_type(&mut *conn).await?;
+
         if let Some(record) = sqlx::query!(r#"SELECT id FROM _tblmedia where name = ?"#, self.name)
             .fetch_optional(&mut *conn)
             .await?
@@ -328,23 +1192,39 @@ impl InsertableMedia {
             return Ok(record.id);
         }
 
-        let id = sqlx::query!(
-            r#"INSERT INTO _tblmedia (library_id, name, description, rating, year, added, poster, backdrop, media_type)
-            VALUES ($1, $2, $3, $4, $5, $6,$7, $8, $9)
-            ON CONFLICT DO UPDATE
-            SET name = $2
-            RETURNING _tblmedia.id as "id!: i64"
-            "#,
-            self.library_id,
-            self.name,
-            self.description,
-            self.rating,
-            self.year,
-            self.added,
-            self.poster,
-            self.backdrop,
-            self.media_type
-        ).fetch_one(&mut *conn).await?.id;
+        let added = normalize_added(&self.added)?;
+        let updated_at = unix_now();
+        let sort_title = sort_title(&self.name);
+        let id = crate::retry_while!(
+            crate::utils::is_busy,
+            sqlx::query!(
+                r#"INSERT INTO _tblmedia (library_id, name, description, rating, year, added, poster, backdrop, duration, external_id, tmdb_id, imdb_id, media_type, updated_at, sort_title, added_by)
+                VALUES ($1, $2, $3, $4, $5, $6,$7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+                ON CONFLICT DO UPDATE
+                SET name = $2
+                RETURNING _tblmedia.id as "id!: i64"
+                "#,
+                self.library_id,
+                self.name,
+                self.description,
+                self.rating,
+                self.year,
+                added,
+                self.poster,
+                self.backdrop,
+                self.duration,
+                self.external_id,
+                self.tmdb_id,
+                self.imdb_id,
+                self.media_type,
+                updated_at,
+                sort_title,
+                self.added_by
+            )
+            .fetch_one(&mut *conn)
+            .await
+        )?
+        .id;
 
         Ok(id)
     }
@@ -359,6 +1239,8 @@ impl InsertableMedia {
         conn: &mut crate::Transaction<'_>,
         id: i64,
     ) -> Result<i64, DatabaseError> {
+        self.check_library_media_type(&mut *conn).await?;
+
         if let Some(record) = sqlx::query!(r#"SELECT id FROM _tblmedia where name = ?"#, self.name)
             .fetch_optional(&mut *conn)
             .await?
@@ -366,9 +1248,12 @@ impl InsertableMedia {
             return Ok(record.id);
         }
 
+        let added = normalize_added(&self.added)?;
+        let updated_at = unix_now();
+        let sort_title = sort_title(&self.name);
         sqlx::query!(
-            r#"INSERT INTO _tblmedia (id, library_id, name, description, rating, year, added, poster, backdrop, media_type)
-            VALUES ($1, $2, $3, $4, $5, $6,$7, $8, $9, $10)
+            r#"INSERT INTO _tblmedia (id, library_id, name, description, rating, year, added, poster, backdrop, duration, external_id, tmdb_id, imdb_id, media_type, updated_at, sort_title, added_by)
+            VALUES ($1, $2, $3, $4, $5, $6,$7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
             "#,
             id,
             self.library_id,
@@ -376,35 +1261,182 @@ impl InsertableMedia {
             self.description,
             self.rating,
             self.year,
-            self.added,
+            added,
             self.poster,
             self.backdrop,
-            self.media_type
+            self.duration,
+            self.external_id,
+            self.tmdb_id,
+            self.imdb_id,
+            self.media_type,
+            updated_at,
+            sort_title,
+            self.added_by
         ).execute(&mut *conn).await?;
 
         Ok(id)
     }
 
+    /// Method inserts `self` if no media with the same [`external_id`](Self::external_id) exists
+    /// in the library, otherwise the metadata of the existing media is updated. Returns the id of
+    /// the media and whether it has been newly created.
+    ///
+    /// If `self` has no external id, this deduplicates against other media without an external id
+    /// in the same library with the same name, media type and year, which is what the unique index
+    /// on media is keyed on. Media without a year are treated as having the same year.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    pub async fn upsert_by_external_id(
+        &self,
+        conn: &mut crate::Transaction<'_>,
+    ) -> Result<(i64, bool), DatabaseError> {
+        let external_id = match self.external_id.as_ref() {
+            Some(x) => x,
+            None => {
+                let existing = sqlx::query_scalar!(
+                    r#"SELECT id as "id!" FROM _tblmedia
+                    WHERE name = ? AND library_id = ? AND media_type = ?
+                    AND IFNULL(year, 0) = IFNULL(?, 0) AND external_id IS NULL"#,
+                    self.name,
+                    self.library_id,
+                    self.media_type,
+                    self.year
+                )
+                .fetch_optional(&mut *conn)
+                .await?;
+
+                return match existing {
+                    Some(id) => Ok((id, false)),
+                    None => Ok((self.insert_blind(&mut *conn).await?, true)),
+                };
+            }
+        };
+
+        let existing = sqlx::query_scalar!(
+            r#"SELECT id as "id!" FROM _tblmedia
+            WHERE library_id = ? AND external_id = ? AND media_type = ?"#,
+            self.library_id,
+            external_id,
+            self.media_type
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if let Some(id) = existing {
+            let updated_at = unix_now();
+            let sort_title = sort_title(&self.name);
+            sqlx::query!(
+                "UPDATE _tblmedia
+                SET name = $1, description = $2, rating = $3, year = $4, poster = $5,
+                    backdrop = $6, duration = $7, tmdb_id = COALESCE($8, tmdb_id),
+                    imdb_id = COALESCE($9, imdb_id), updated_at = $10, sort_title = $11
+                WHERE id = $12",
+                self.name,
+                self.description,
+                self.rating,
+                self.year,
+                self.poster,
+                self.backdrop,
+                self.duration,
+                self.tmdb_id,
+                self.imdb_id,
+                updated_at,
+                sort_title,
+                id
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            return Ok((id, false));
+        }
+
+        Ok((self.insert_blind(&mut *conn).await?, true))
+    }
+
+    /// Method returns the id of the tv show described by `self`, inserting it if it doesnt exist
+    /// yet. Shows are matched within the library by their external id, or by name and year if
+    /// `self` has no external id. Unlike [`insert`](Self::insert), two different shows with the
+    /// same name are kept apart.
+    ///
+    /// `conn` should be a write transaction, these are exclusive so no other connection can
+    /// insert the same show in between the lookup and the insert.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    pub async fn get_or_create_show(
+        &self,
+        conn: &mut crate::Transaction<'_>,
+    ) -> Result<i64, DatabaseError> {
+        let existing = match self.external_id.as_ref() {
+            Some(external_id) => {
+                sqlx::query_scalar!(
+                    r#"SELECT id as "id!" FROM _tblmedia
+                    WHERE library_id = ? AND external_id = ? AND media_type = "tv""#,
+                    self.library_id,
+                    external_id
+                )
+                .fetch_optional(&mut *conn)
+                .await?
+            }
+            None => {
+                sqlx::query_scalar!(
+                    r#"SELECT id as "id!" FROM _tblmedia
+                    WHERE library_id = ? AND name = ? AND year IS ? AND media_type = "tv""#,
+                    self.library_id,
+                    self.name,
+                    self.year
+                )
+                .fetch_optional(&mut *conn)
+                .await?
+            }
+        };
+
+        match existing {
+            Some(id) => Ok(id),
+            None => self.insert_blind(&mut *conn).await,
+        }
+    }
+
     /// Method blindly inserts `self` into the database without checking whether a similar entry exists.
     /// This is especially useful for tv shows as they usually have similar metadata with key differences
-    /// which are not indexed in the database.
+    /// which are not indexed in the database. Like [`insert`](Self::insert) the insert is retried while
+    /// the database is busy.
     pub async fn insert_blind(
         &self,
         conn: &mut crate::Transaction<'_>,
     ) -> Result<i64, DatabaseError> {
-        Ok(sqlx::query!(
-            r#"INSERT INTO _tblmedia (library_id, name, description, rating, year, added, poster, backdrop, media_type)
-            VALUES ($1, $2, $3, $4, $5, $6,$7, $8, $9)"#,
-            self.library_id,
-            self.name,
-            self.description,
-            self.rating,
-            self.year,
-            self.added,
-            self.poster,
-            self.backdrop,
-            self.media_type
-        ).execute(&mut *conn).await?.last_insert_rowid())
+        self.check_library_media_type(&mut *conn).await?;
+
+        let added = normalize_added(&self.added)?;
+        let updated_at = unix_now();
+        let sort_title = sort_title(&self.name);
+        Ok(crate::retry_while!(
+            crate::utils::is_busy,
+            sqlx::query!(
+                r#"INSERT INTO _tblmedia (library_id, name, description, rating, year, added, poster, backdrop, duration, external_id, tmdb_id, imdb_id, media_type, updated_at, sort_title, added_by)
+                VALUES ($1, $2, $3, $4, $5, $6,$7, $8, $9, $10, $11, $12, $13, $14, $15, $16)"#,
+                self.library_id,
+                self.name,
+                self.description,
+                self.rating,
+                self.year,
+                added,
+                self.poster,
+                self.backdrop,
+                self.duration,
+                self.external_id,
+                self.tmdb_id,
+                self.imdb_id,
+                self.media_type,
+                updated_at,
+                sort_title,
+                self.added_by
+            )
+            .execute(&mut *conn)
+            .await
+        )?
+        .last_insert_rowid())
     }
 }
 
@@ -420,12 +1452,16 @@ pub struct UpdateMedia {
     pub added: Option<String>,
     pub poster: Option<i64>,
     pub backdrop: Option<i64>,
+    pub duration: Option<i64>,
+    pub tmdb_id: Option<i32>,
+    pub imdb_id: Option<String>,
     pub media_type: Option<MediaType>,
 }
 
 impl UpdateMedia {
     /// Method used to update the fields of a media object that is in the database using the id of
-    /// this object as a discriminator.
+    /// this object as a discriminator. If any field is set the media is stamped as updated, so that
+    /// the change shows up in [`Media::get_changed_since`].
     ///
     /// # Arguments
     /// * `conn` - mutable reference to a sqlx transaction.
@@ -435,17 +1471,100 @@ impl UpdateMedia {
         conn: &mut crate::Transaction<'_>,
         id: i64,
     ) -> Result<usize, DatabaseError> {
+        let added = self.added.as_deref().map(normalize_added).transpose()?;
+        let sort_title = self.name.as_deref().map(sort_title);
+
         crate::opt_update!(conn,
             "UPDATE _tblmedia SET name = ? WHERE id = ?" => (self.name, id),
+            "UPDATE _tblmedia SET sort_title = ? WHERE id = ?" => (sort_title, id),
             "UPDATE _tblmedia SET description = ? WHERE id = ?" => (self.description, id),
             "UPDATE _tblmedia SET rating = ? WHERE id = ?" => (self.rating, id),
             "UPDATE _tblmedia SET year = ? WHERE id = ?" => (self.year, id),
-            "UPDATE _tblmedia SET added = ? WHERE id = ?" => (self.added, id),
+            "UPDATE _tblmedia SET added = ? WHERE id = ?" => (added, id),
             "UPDATE _tblmedia SET poster = ? WHERE id = ?" => (self.poster, id),
             "UPDATE _tblmedia SET backdrop = ? WHERE id = ?" => (self.backdrop, id),
+            "UPDATE _tblmedia SET duration = ? WHERE id = ?" => (self.duration, id),
+            "UPDATE _tblmedia SET tmdb_id = ? WHERE id = ?" => (self.tmdb_id, id),
+            "UPDATE _tblmedia SET imdb_id = ? WHERE id = ?" => (self.imdb_id, id),
             "UPDATE _tblmedia SET media_type = ? WHERE id = ?" => (self.media_type, id)
         );
 
+        let changed = self.name.is_some()
+            || self.description.is_some()
+            || self.rating.is_some()
+            || self.year.is_some()
+            || self.added.is_some()
+            || self.poster.is_some()
+            || self.backdrop.is_some()
+            || self.duration.is_some()
+            || self.tmdb_id.is_some()
+            || self.imdb_id.is_some()
+            || self.media_type.is_some();
+
+        if changed {
+            let updated_at = unix_now();
+            sqlx::query!(
+                "UPDATE _tblmedia SET updated_at = ? WHERE id = ?",
+                updated_at,
+                id
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
         Ok(1)
     }
 }
+
+/// Function normalizes the `added` timestamp of a media object to a UTC RFC 3339 timestamp without
+/// fractional seconds, ie `2022-06-13T12:34:56Z`, so that timestamps sort lexicographically. An
+/// empty string is stamped with the current time.
+///
+/// Besides RFC 3339 this accepts the format chrono's `DateTime<Utc>` is displayed in
+/// (`2022-06-13 12:34:56.123 UTC`) and plain dates. Anything else is rejected with
+/// [`DatabaseError::InvalidTimestamp`].
+pub fn normalize_added(added: &str) -> Result<String, DatabaseError> {
+    let added = added.trim();
+
+    let timestamp = if added.is_empty() {
+        Utc::now()
+    } else if let Ok(x) = DateTime::parse_from_rfc3339(added) {
+        x.with_timezone(&Utc)
+    } else if let Ok(x) =
+        NaiveDateTime::parse_from_str(added.trim_end_matches(" UTC"), "%Y-%m-%d %H:%M:%S%.f")
+    {
+        Utc.from_utc_datetime(&x)
+    } else if let Some(x) = NaiveDate::parse_from_str(added, "%Y-%m-%d")
+        .ok()
+        .and_then(|x| x.and_hms_opt(0, 0, 0))
+    {
+        Utc.from_utc_datetime(&x)
+    } else {
+        return Err(DatabaseError::InvalidTimestamp(added.to_string()));
+    };
+
+    Ok(timestamp.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// Leading articles that are ignored when sorting by name.
+const SORT_ARTICLES: [&str; 3] = ["the ", "a ", "an "];
+
+/// Function returns the key a media named `name` is sorted by. The key is lowercase, has
+/// diacritics stripped and a leading `The`, `A` or `An` removed, so that "The Matrix" sorts under
+/// M and "Ámelie" next to "Amelie".
+pub fn sort_title(name: &str) -> String {
+    let key = name
+        .trim()
+        .nfd()
+        .filter(|x| !is_combining_mark(*x))
+        .collect::<String>()
+        .to_lowercase();
+
+    SORT_ARTICLES
+        .iter()
+        .find_map(|article| key.strip_prefix(article))
+        .map(str::trim_start)
+        .filter(|rest| !rest.is_empty())
+        .map(ToString::to_string)
+        .unwrap_or(key)
+}
@@ -97,6 +97,26 @@ impl MediaFile {
         .await?)
     }
 
+    /// Method returns all mediafiles in a library which the scanner failed to match to a media,
+    /// ordered by their path.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `library_id` - id of the library.
+    pub async fn get_unmatched(
+        conn: &mut crate::Transaction<'_>,
+        library_id: i64,
+    ) -> Result<Vec<Self>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            MediaFile,
+            "SELECT * FROM mediafile WHERE library_id = ? AND media_id IS NULL
+            ORDER BY target_file",
+            library_id
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
     /// Method returns all mediafiles associated with a Media object.
     ///
     /// # Arguments
@@ -117,6 +137,30 @@ impl MediaFile {
         .await?)
     }
 
+    /// Method returns the stream properties ffprobe reported for the files of a media object when
+    /// they were scanned, so that playback can choose between direct play and transcoding without
+    /// probing the files again. Corrupt files are skipped.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media object.
+    pub async fn get_stream_info(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+    ) -> Result<Vec<StreamInfo>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            StreamInfo,
+            r#"SELECT id as "mediafile_id!", container, codec as video_codec, profile,
+                original_resolution as resolution, audio as audio_codec, channels, duration
+            FROM mediafile
+            WHERE media_id = ? AND NOT COALESCE(corrupt, 0)
+            ORDER BY id"#,
+            media_id
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
     pub async fn get_of_show(
         conn: &mut crate::Transaction<'_>,
         id: i64,
@@ -243,6 +287,23 @@ impl MediaFile {
     }
 }
 
+/// Stream properties of a mediafile as reported by ffprobe at scan time, see
+/// [`MediaFile::get_stream_info`].
+#[derive(Serialize, PartialEq, Debug, Clone)]
+pub struct StreamInfo {
+    pub mediafile_id: i64,
+    pub container: Option<String>,
+    pub video_codec: Option<String>,
+    /// Profile of the video stream, ie `High`.
+    pub profile: Option<String>,
+    /// Resolution of the video stream, ie `1920x1080`.
+    pub resolution: Option<String>,
+    pub audio_codec: Option<String>,
+    pub channels: Option<i64>,
+    /// Duration in seconds.
+    pub duration: Option<i64>,
+}
+
 /// Same as [`MediaFile`](MediaFile) except its missing the id field.
 #[derive(Clone, Serialize, Debug, Default)]
 pub struct InsertableMediaFile {
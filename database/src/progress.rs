@@ -1,10 +1,11 @@
 use crate::library::MediaType;
 use crate::media::Media;
 use crate::user::UserID;
+use crate::utils::unix_now;
 use crate::DatabaseError as DieselError;
 
 use serde::Serialize;
-use std::time::SystemTime;
+use std::collections::HashMap;
 
 #[derive(Debug, Serialize)]
 pub struct Progress {
@@ -13,6 +14,45 @@ pub struct Progress {
     pub media_id: i64,
     pub user_id: UserID,
     pub populated: i64,
+    /// Duration of the media in seconds as last reported by the player.
+    pub duration: Option<i64>,
+}
+
+/// Struct represents a single entry in the watch history of a user, see
+/// [`Progress::get_history`].
+#[derive(Clone, Debug, Serialize)]
+pub struct HistoryEntry {
+    #[serde(flatten)]
+    pub media: Media,
+    /// Playback position in seconds.
+    pub position: i64,
+    /// Unix timestamp of the last time the user watched this media.
+    pub watched_at: i64,
+    /// Whether the user got past 90% of the media.
+    pub completed: bool,
+}
+
+/// Struct represents a single entry in the activity feed of the server, see
+/// [`Progress::get_recent_activity`].
+#[derive(Clone, Debug, Serialize)]
+pub struct ActivityEntry {
+    /// Name of the user who watched the media.
+    pub username: String,
+    #[serde(flatten)]
+    pub media: Media,
+    /// Unix timestamp of the last time the user watched this media.
+    pub watched_at: i64,
+}
+
+/// Struct holds how much a user has watched, see [`Progress::get_user_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct UserStats {
+    /// Number of media the user started but hasnt finished yet.
+    pub in_progress: i64,
+    /// Number of media the user got past 90% of.
+    pub completed: i64,
+    /// Total time spent watching in seconds.
+    pub watch_seconds: i64,
 }
 
 impl Progress {
@@ -22,10 +62,7 @@ impl Progress {
         uid: UserID,
         mid: i64,
     ) -> Result<usize, DieselError> {
-        let timestamp = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+        let timestamp = unix_now();
 
         Ok(sqlx::query!(
             "INSERT OR REPLACE INTO progress (delta, media_id, user_id, populated)
@@ -40,6 +77,129 @@ impl Progress {
         .rows_affected() as usize)
     }
 
+    /// Method records the playback position of a user along with the duration of the media and
+    /// bumps the last-watched timestamp, in a single statement. This is called by the player
+    /// heartbeat every few seconds, so if the database is busy the statement is retried with
+    /// [`retry_while!`](crate::retry_while) instead of failing the heartbeat. A `duration` of
+    /// `None` keeps the previously reported duration.
+    ///
+    /// Returns the stored row.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `uid` - id of the user.
+    /// * `mid` - id of the media being played.
+    /// * `position` - playback position in seconds.
+    /// * `duration` - duration of the media in seconds.
+    pub async fn upsert(
+        conn: &mut crate::Transaction<'_>,
+        uid: UserID,
+        mid: i64,
+        position: i64,
+        duration: Option<i64>,
+    ) -> Result<Self, DieselError> {
+        let timestamp = unix_now();
+        let position = match duration {
+            Some(duration) => position.clamp(0, duration.max(0)),
+            None => position.max(0),
+        };
+
+        crate::retry_while!(
+            crate::utils::is_busy,
+            sqlx::query_as!(
+                Progress,
+                r#"INSERT INTO progress (delta, media_id, user_id, populated, duration)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (user_id, media_id) DO UPDATE SET
+                    delta = excluded.delta,
+                    populated = excluded.populated,
+                    duration = COALESCE(excluded.duration, progress.duration)
+                RETURNING id as "id!", user_id as "user_id!: UserID", delta as "delta!",
+                    media_id as "media_id!", populated as "populated!", duration"#,
+                position,
+                mid,
+                uid,
+                timestamp,
+                duration
+            )
+            .fetch_one(&mut *conn)
+            .await
+        )
+    }
+
+    /// Method marks every episode of a tv show as watched by setting the progress of the user to
+    /// the duration of each episode. Episodes the user has no progress for yet get a new progress
+    /// entry, existing entries are overwritten. Episodes without a known duration are still marked
+    /// watched with a position of 0. All episodes are upserted in a single statement.
+    ///
+    /// Returns the number of episodes marked watched.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `uid` - id of the user.
+    /// * `tv_show_id` - id of the tv show.
+    pub async fn set_show_watched(
+        conn: &mut crate::Transaction<'_>,
+        uid: UserID,
+        tv_show_id: i64,
+    ) -> Result<usize, DieselError> {
+        let timestamp = unix_now();
+
+        Ok(sqlx::query!(
+            r#"INSERT INTO progress (delta, media_id, user_id, populated, duration)
+            SELECT duration, id, ?1, ?2, duration FROM (
+                SELECT _tblmedia.id, COALESCE(_tblmedia.duration, MAX(mediafile.duration), 0) as duration
+                FROM _tblmedia
+                INNER JOIN episode ON episode.id = _tblmedia.id
+                INNER JOIN season ON season.id = episode.seasonid
+                LEFT OUTER JOIN mediafile ON mediafile.media_id = _tblmedia.id
+                WHERE season.tvshowid = ?3
+                GROUP BY _tblmedia.id
+            )
+            -- sqlite needs a WHERE clause to tell the upsert apart from a join constraint.
+            WHERE true
+            ON CONFLICT (user_id, media_id) DO UPDATE SET
+                delta = excluded.delta,
+                populated = excluded.populated,
+                duration = excluded.duration"#,
+            uid,
+            timestamp,
+            tv_show_id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
+    /// Method clears the progress of a user for every episode of a tv show.
+    ///
+    /// Returns the number of progress entries removed.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `uid` - id of the user.
+    /// * `tv_show_id` - id of the tv show.
+    pub async fn clear_show(
+        conn: &mut crate::Transaction<'_>,
+        uid: UserID,
+        tv_show_id: i64,
+    ) -> Result<usize, DieselError> {
+        Ok(sqlx::query!(
+            "DELETE FROM progress
+            WHERE user_id = ?
+            AND media_id IN (
+                SELECT episode.id FROM episode
+                INNER JOIN season ON season.id = episode.seasonid
+                WHERE season.tvshowid = ?
+            )",
+            uid,
+            tv_show_id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
     pub async fn get_for_media_user(
         conn: &mut crate::Transaction<'_>,
         uid: UserID,
@@ -47,7 +207,7 @@ impl Progress {
     ) -> Result<Self, DieselError> {
         Ok(sqlx::query_as!(
             Progress,
-            r#"SELECT id, user_id as "user_id: UserID", delta, media_id, populated FROM progress
+            r#"SELECT id, user_id as "user_id: UserID", delta, media_id, populated, duration FROM progress
             WHERE user_id = ?
             AND media_id = ?"#,
             uid,
@@ -61,9 +221,165 @@ impl Progress {
             user_id: uid,
             delta: Default::default(),
             populated: Default::default(),
+            duration: None,
         }))
     }
 
+    /// Method returns the progress of a user for many media objects in a single query. Media the
+    /// user hasnt started watching yet are absent from the returned map.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `uid` - id of the user.
+    /// * `ids` - ids of the media we want the progress of.
+    pub async fn get_for_media_ids(
+        conn: &mut crate::Transaction<'_>,
+        uid: UserID,
+        ids: &[i64],
+    ) -> Result<HashMap<i64, Self>, DieselError> {
+        let ids = crate::json_ids(ids);
+
+        Ok(sqlx::query_as!(
+            Progress,
+            r#"SELECT id, user_id as "user_id: UserID", delta, media_id, populated, duration FROM progress
+            WHERE user_id = ?
+            AND media_id IN (SELECT value FROM json_each(?))"#,
+            uid,
+            ids
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|x| (x.media_id, x))
+        .collect())
+    }
+
+    /// Method returns everything a user has watched, most recently watched first. Unlike
+    /// [`Progress::get_continue_watching`] this includes media the user has finished.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `uid` - id of the user.
+    /// * `limit` - max number of entries to return.
+    /// * `offset` - number of entries to skip.
+    /// * `denied` - ids of libraries whose media should be left out.
+    pub async fn get_history(
+        conn: &mut crate::Transaction<'_>,
+        uid: UserID,
+        limit: i64,
+        offset: i64,
+        denied: &[i64],
+    ) -> Result<Vec<HistoryEntry>, DieselError> {
+        let denied = crate::json_ids(denied);
+
+        let rows = sqlx::query!(
+            r#"SELECT media.id, media.library_id, media.name, media.description as "description?",
+                media.rating, media.year, media.added as "added?",
+                media.poster_path as "poster_path?", media.backdrop_path as "backdrop_path?",
+                media.duration, media.tmdb_id as "tmdb_id: i32", media.imdb_id, media.added_by,
+                media.media_type as "media_type: MediaType",
+                progress.delta, progress.populated,
+                COALESCE(progress.duration, media.duration,
+                    (SELECT MAX(mediafile.duration) FROM mediafile WHERE mediafile.media_id = media.id)
+                ) as "known_duration?: i64"
+            FROM progress
+            INNER JOIN media ON media.id = progress.media_id
+            WHERE progress.user_id = ?
+            AND media.library_id NOT IN (SELECT value FROM json_each(?))
+            ORDER BY progress.populated DESC, progress.id DESC
+            LIMIT ? OFFSET ?"#,
+            uid,
+            denied,
+            limit,
+            offset
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|x| HistoryEntry {
+                completed: matches!(x.known_duration, Some(duration) if x.delta * 10 > duration * 9),
+                position: x.delta,
+                watched_at: x.populated,
+                media: Media {
+                    id: x.id,
+                    library_id: x.library_id,
+                    name: x.name,
+                    description: x.description,
+                    rating: x.rating,
+                    year: x.year,
+                    added: x.added,
+                    poster_path: x.poster_path,
+                    backdrop_path: x.backdrop_path,
+                    duration: x.duration,
+                    tmdb_id: x.tmdb_id,
+                    imdb_id: x.imdb_id,
+                    added_by: x.added_by,
+                    media_type: x.media_type,
+                },
+            })
+            .collect())
+    }
+
+    /// Method returns what all users have watched recently, most recently watched first. Users
+    /// who turned on `hide_activity` in their preferences are left out.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `limit` - max number of entries to return.
+    pub async fn get_recent_activity(
+        conn: &mut crate::Transaction<'_>,
+        limit: i64,
+    ) -> Result<Vec<ActivityEntry>, DieselError> {
+        // NOTE: prefs are stored as a json blob, which the json functions of sqlite refuse unless
+        // it is cast to text first.
+        let rows = sqlx::query!(
+            r#"SELECT media.id, media.library_id, media.name, media.description as "description?",
+                media.rating, media.year, media.added as "added?",
+                media.poster_path as "poster_path?", media.backdrop_path as "backdrop_path?",
+                media.duration, media.tmdb_id as "tmdb_id: i32", media.imdb_id, media.added_by,
+                media.media_type as "media_type: MediaType",
+                users.username, progress.populated
+            FROM progress
+            INNER JOIN media ON media.id = progress.media_id
+            INNER JOIN users ON users.id = progress.user_id
+            WHERE NOT COALESCE(
+                CASE WHEN json_valid(CAST(users.prefs AS TEXT))
+                THEN json_extract(CAST(users.prefs AS TEXT), '$.hide_activity') END,
+                0)
+            ORDER BY progress.populated DESC, progress.id DESC
+            LIMIT ?"#,
+            limit
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|x| ActivityEntry {
+                username: x.username,
+                watched_at: x.populated,
+                media: Media {
+                    id: x.id,
+                    library_id: x.library_id,
+                    name: x.name,
+                    description: x.description,
+                    rating: x.rating,
+                    year: x.year,
+                    added: x.added,
+                    poster_path: x.poster_path,
+                    backdrop_path: x.backdrop_path,
+                    duration: x.duration,
+                    tmdb_id: x.tmdb_id,
+                    imdb_id: x.imdb_id,
+                    added_by: x.added_by,
+                    media_type: x.media_type,
+                },
+            })
+            .collect())
+    }
+
     pub async fn get_total_time_spent_watching(
         conn: &mut crate::Transaction<'_>,
         uid: UserID,
@@ -79,6 +395,47 @@ impl Progress {
         .unwrap_or_default())
     }
 
+    /// Method returns how many media a user is watching and has finished, along with the total
+    /// time spent watching, in a single query. Media count as finished by the same rule as
+    /// [`Progress::get_history`].
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `uid` - id of the user.
+    pub async fn get_user_stats(
+        conn: &mut crate::Transaction<'_>,
+        uid: UserID,
+    ) -> Result<UserStats, DieselError> {
+        let row = sqlx::query!(
+            r#"SELECT
+                COALESCE(SUM(CASE
+                    WHEN delta * 10 > known_duration * 9 THEN 0
+                    WHEN delta > 0 THEN 1
+                    ELSE 0 END), 0) as "in_progress!: i64",
+                COALESCE(SUM(CASE WHEN delta * 10 > known_duration * 9 THEN 1 ELSE 0 END), 0)
+                    as "completed!: i64",
+                COALESCE(SUM(delta), 0) as "watch_seconds!: i64"
+            FROM (
+                SELECT progress.delta, COALESCE(progress.duration, _tblmedia.duration,
+                    (SELECT MAX(mediafile.duration) FROM mediafile
+                        WHERE mediafile.media_id = progress.media_id)
+                ) as known_duration
+                FROM progress
+                INNER JOIN _tblmedia ON _tblmedia.id = progress.media_id
+                WHERE progress.user_id = ?
+            )"#,
+            uid
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok(UserStats {
+            in_progress: row.in_progress,
+            completed: row.completed,
+            watch_seconds: row.watch_seconds,
+        })
+    }
+
     pub async fn get_total_for_media(
         conn: &mut crate::Transaction<'_>,
         media: &Media,
@@ -147,11 +504,16 @@ impl Progress {
         .total)
     }
 
+    /// Method returns the tv shows the user is in the middle of watching, most recently watched
+    /// first. Shows of the libraries in `denied` are left out.
     pub async fn get_continue_watching(
         conn: &mut crate::Transaction<'_>,
         uid: UserID,
         count: i64,
+        denied: &[i64],
     ) -> Result<Vec<i64>, DieselError> {
+        let denied = crate::json_ids(denied);
+
         Ok(sqlx::query_scalar(
             r#"SELECT _tblmedia.id  FROM _tblmedia
 
@@ -163,12 +525,14 @@ impl Progress {
             WHERE NOT progress.populated = 0
             AND progress.user_id = ?
             AND NOT library.hidden
+            AND _tblmedia.library_id NOT IN (SELECT value FROM json_each(?))
 
             GROUP BY _tblmedia.id
             ORDER BY progress.populated DESC
             LIMIT ?"#,
         )
         .bind(uid)
+        .bind(denied)
         .bind(count)
         .fetch_all(&mut *conn)
         .await?)
@@ -0,0 +1,226 @@
+use crate::user::UserID;
+use crate::utils::unix_now;
+use crate::DatabaseError;
+
+use serde::Serialize;
+
+/// Struct represents the score a media object got from one source, ie tmdb or imdb.
+#[derive(Clone, Serialize, PartialEq, Debug)]
+pub struct Rating {
+    /// Name of the source, always lowercase.
+    pub source: String,
+    /// Score normalized to the range 0 to 100.
+    pub score: i64,
+}
+
+impl Rating {
+    /// Source of the ratings the scanners fetch.
+    pub const TMDB: &'static str = "tmdb";
+
+    /// Method sets the score a media object got from `source`, replacing any previous score from
+    /// the same source.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media object.
+    /// * `source` - name of the source, ie "imdb".
+    /// * `score` - score normalized to the range 0 to 100.
+    pub async fn set(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+        source: &str,
+        score: i64,
+    ) -> Result<(), DatabaseError> {
+        if !(0..=100).contains(&score) {
+            return Err(DatabaseError::InvalidRating(score));
+        }
+
+        let source = source.trim().to_lowercase();
+
+        sqlx::query!(
+            "INSERT OR REPLACE INTO ratings (media_id, source, score) VALUES (?, ?, ?)",
+            media_id,
+            source,
+            score
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Method returns the scores of a media object from all sources, sorted by source.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media object.
+    pub async fn get_all(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+    ) -> Result<Vec<Self>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            Rating,
+            "SELECT source, score FROM ratings WHERE media_id = ? ORDER BY source",
+            media_id
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Method copies the score from `source` into `Media.rating`, which is kept in the 0 to 10
+    /// scale tmdb uses for backwards compatibility. If the media has no score from `source` its
+    /// rating is left untouched.
+    ///
+    /// Returns whether the rating has been updated.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media object.
+    /// * `source` - name of the primary source.
+    pub async fn sync_primary(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+        source: &str,
+    ) -> Result<bool, DatabaseError> {
+        let source = source.trim().to_lowercase();
+
+        let rows = sqlx::query!(
+            "UPDATE _tblmedia
+            SET rating = (SELECT score / 10 FROM ratings WHERE media_id = ?1 AND source = ?2)
+            WHERE id = ?1 AND EXISTS (SELECT 1 FROM ratings WHERE media_id = ?1 AND source = ?2)",
+            media_id,
+            source
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        Ok(rows > 0)
+    }
+
+    /// Method removes all scores of a media object.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media being deleted.
+    pub async fn delete_by_media_id(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+    ) -> Result<usize, DatabaseError> {
+        Ok(
+            sqlx::query!("DELETE FROM ratings WHERE media_id = ?", media_id)
+                .execute(&mut *conn)
+                .await?
+                .rows_affected() as usize,
+        )
+    }
+}
+
+/// Struct represents the personal score and review a user gave a media object.
+#[derive(Clone, Serialize, PartialEq, Debug)]
+pub struct UserRating {
+    pub media_id: i64,
+    pub user_id: UserID,
+    /// Score in the range 0 to 100.
+    pub score: i64,
+    pub review: Option<String>,
+    /// Unix timestamp of the last time the rating was changed.
+    pub updated_at: i64,
+}
+
+impl UserRating {
+    /// Method sets the score and review a user gave a media object. Rating a media again replaces
+    /// the previous rating.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `uid` - id of the user.
+    /// * `media_id` - id of the media object.
+    /// * `score` - score in the range 0 to 100.
+    /// * `review` - optional text review.
+    pub async fn set(
+        conn: &mut crate::Transaction<'_>,
+        uid: UserID,
+        media_id: i64,
+        score: i64,
+        review: Option<String>,
+    ) -> Result<(), DatabaseError> {
+        if !(0..=100).contains(&score) {
+            return Err(DatabaseError::InvalidRating(score));
+        }
+
+        let updated_at = unix_now();
+
+        sqlx::query!(
+            "INSERT OR REPLACE INTO user_ratings (user_id, media_id, score, review, updated_at)
+            VALUES (?, ?, ?, ?, ?)",
+            uid,
+            media_id,
+            score,
+            review,
+            updated_at
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Method returns the rating a user gave a media object.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `uid` - id of the user.
+    /// * `media_id` - id of the media object.
+    pub async fn get(
+        conn: &mut crate::Transaction<'_>,
+        uid: UserID,
+        media_id: i64,
+    ) -> Result<Self, DatabaseError> {
+        Ok(sqlx::query_as!(
+            UserRating,
+            r#"SELECT media_id, user_id as "user_id: UserID", score, review, updated_at
+            FROM user_ratings WHERE user_id = ? AND media_id = ?"#,
+            uid,
+            media_id
+        )
+        .fetch_one(&mut *conn)
+        .await?)
+    }
+
+    /// Method returns the average score all users gave a media object, or `None` if nobody rated
+    /// it yet.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media object.
+    pub async fn average_for_media(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+    ) -> Result<Option<f64>, DatabaseError> {
+        Ok(sqlx::query!(
+            r#"SELECT AVG(score) as "average?: f64" FROM user_ratings WHERE media_id = ?"#,
+            media_id
+        )
+        .fetch_one(&mut *conn)
+        .await?
+        .average)
+    }
+
+    /// Method removes all user ratings of a media object.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media being deleted.
+    pub async fn delete_by_media_id(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+    ) -> Result<usize, DatabaseError> {
+        Ok(
+            sqlx::query!("DELETE FROM user_ratings WHERE media_id = ?", media_id)
+                .execute(&mut *conn)
+                .await?
+                .rows_affected() as usize,
+        )
+    }
+}
@@ -2,6 +2,8 @@ use sqlx::Pool;
 use sqlx::Sqlite;
 use sqlx::SqliteConnection;
 
+use serde::Serialize;
+
 use tracing::debug_span;
 use tracing::Instrument;
 
@@ -9,6 +11,9 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 use tokio::sync::OwnedMutexGuard;
 
+/// Maximum number of connections opened by the reader pool.
+pub const MAX_READERS: u32 = 10;
+
 #[derive(Debug, Clone)]
 pub struct SqlitePool {
     pub writer: Arc<Mutex<SqliteConnection>>,
@@ -34,17 +39,55 @@ impl SqlitePool {
     pub fn read_ref(&self) -> &Pool<Sqlite> {
         &self.reader
     }
+
+    /// Method returns a snapshot of the current state of the reader pool and whether the writer
+    /// connection is currently held by someone.
+    pub fn pool_status(&self) -> PoolStatus {
+        let size = self.reader.size();
+        let idle = self.reader.num_idle() as u32;
+
+        PoolStatus {
+            idle,
+            active: size.saturating_sub(idle),
+            max_connections: MAX_READERS,
+            writer_busy: self.writer.try_lock().is_err(),
+        }
+    }
+}
+
+/// Struct describes the state of the connection pool at a point in time.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PoolStatus {
+    /// Number of idle reader connections.
+    pub idle: u32,
+    /// Number of reader connections currently in use.
+    pub active: u32,
+    /// Maximum number of reader connections the pool will open.
+    pub max_connections: u32,
+    /// Whether the single writer connection is currently locked.
+    pub writer_busy: bool,
 }
 
+/// Function opens a exclusive write transaction on the writer connection. If the database is busy,
+/// for example under heavy scan contention, acquiring the transaction is retried a bounded amount
+/// of times before giving up with [`DatabaseError::RetryExhausted`](crate::DatabaseError::RetryExhausted).
+///
+/// This is the only place where the isolation of writes is decided. Sqlite transactions are
+/// always serializable, and as the transaction is exclusive and all writes go through the single
+/// writer connection, methods such as [`InsertableMedia::insert`](crate::media::InsertableMedia::insert)
+/// dont need to pick an isolation level themselves.
 pub async fn write_tx(
     lock: &mut OwnedMutexGuard<SqliteConnection>,
-) -> Result<crate::Transaction<'_>, sqlx::Error> {
+) -> Result<crate::Transaction<'_>, crate::DatabaseError> {
     use sqlx::Connection;
 
     let mut tx = lock.begin().instrument(debug_span!("TxBegin")).await?;
 
     sqlx::query("END").execute(&mut tx).await?;
-    sqlx::query("BEGIN EXCLUSIVE").execute(&mut tx).await?;
+    crate::retry_while!(
+        crate::utils::is_busy,
+        sqlx::query("BEGIN EXCLUSIVE").execute(&mut tx).await
+    )?;
 
     Ok(tx)
 }
@@ -13,7 +13,8 @@ pub struct Season {
     pub tvshowid: i64,
     /// String holding the date when the season was added to the database.
     pub added: Option<String>,
-    /// Id of the asset pointing to the poster.
+    /// Path to the poster of this season.
+    #[serde(serialize_with = "crate::asset::serialize_url")]
     pub poster: Option<String>,
 }
 
@@ -0,0 +1,80 @@
+use crate::DatabaseError;
+
+use serde::Serialize;
+
+/// Struct represents a subtitle track of a mediafile, either embedded in the file or a sidecar
+/// file next to it.
+#[derive(Clone, Serialize, PartialEq, Debug)]
+pub struct Subtitle {
+    pub id: i64,
+    pub mediafile_id: i64,
+    /// Name of the language, ie "English", if known.
+    pub language: Option<String>,
+    /// Codec as reported by ffprobe, ie `subrip` or `webvtt`.
+    pub codec: String,
+    /// Path of the sidecar file, `None` for tracks embedded in the mediafile.
+    pub external_path: Option<String>,
+    /// Index of the stream inside the mediafile, `None` for sidecar files.
+    pub stream_index: Option<i64>,
+    pub forced: bool,
+}
+
+impl Subtitle {
+    /// Method returns the subtitle tracks of all mediafiles of a media object. Embedded tracks
+    /// come first in stream order, followed by sidecar files.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media object.
+    pub async fn get_for_media(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+    ) -> Result<Vec<Self>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            Subtitle,
+            r#"SELECT subtitles.id, subtitles.mediafile_id, subtitles.language, subtitles.codec,
+                subtitles.external_path, subtitles.stream_index, subtitles.forced as "forced: bool"
+            FROM subtitles
+            INNER JOIN mediafile ON mediafile.id = subtitles.mediafile_id
+            WHERE mediafile.media_id = ?
+            ORDER BY subtitles.mediafile_id, subtitles.stream_index IS NULL,
+                subtitles.stream_index, subtitles.external_path"#,
+            media_id
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+}
+
+/// Same as [`Subtitle`] except its missing the id field.
+#[derive(Clone, Debug, Default)]
+pub struct InsertableSubtitle {
+    pub mediafile_id: i64,
+    pub language: Option<String>,
+    pub codec: String,
+    pub external_path: Option<String>,
+    pub stream_index: Option<i64>,
+    pub forced: bool,
+}
+
+impl InsertableSubtitle {
+    /// Method inserts a new subtitle track into the database.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    pub async fn insert(&self, conn: &mut crate::Transaction<'_>) -> Result<i64, DatabaseError> {
+        Ok(sqlx::query!(
+            "INSERT INTO subtitles (mediafile_id, language, codec, external_path, stream_index, forced)
+            VALUES (?, ?, ?, ?, ?, ?)",
+            self.mediafile_id,
+            self.language,
+            self.codec,
+            self.external_path,
+            self.stream_index,
+            self.forced
+        )
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid())
+    }
+}
@@ -0,0 +1,141 @@
+use crate::DatabaseError;
+
+use serde::Serialize;
+
+/// Struct represents a free-form label users can put on media, ie "4k" or "director's cut".
+#[derive(Clone, Serialize, PartialEq, Debug)]
+pub struct Tag {
+    pub id: i64,
+    /// Tag name, always lowercase.
+    pub name: String,
+}
+
+impl Tag {
+    /// Method normalizes a tag name by trimming it and turning it lowercase, so that "4K" and
+    /// " 4k" end up as the same tag. Empty names are rejected.
+    pub fn normalize(name: &str) -> Result<String, DatabaseError> {
+        let name = name.trim().to_lowercase();
+
+        if name.is_empty() {
+            return Err(DatabaseError::InvalidTag(name));
+        }
+
+        Ok(name)
+    }
+
+    /// Method returns all tags of a media object, sorted by name.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media object.
+    pub async fn get_by_media(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+    ) -> Result<Vec<Self>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            Tag,
+            r#"SELECT tags.id as "id!", tags.name FROM tags
+            INNER JOIN media_tags ON media_tags.tag_id = tags.id
+            WHERE media_tags.media_id = ?
+            ORDER BY tags.name"#,
+            media_id
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Method tags a media object, creating the tag first if it doesnt exist yet. Adding a tag a
+    /// media already has is a no-op.
+    ///
+    /// Returns whether a new link has been created.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media object we want to tag.
+    /// * `name` - name of the tag.
+    pub async fn add(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+        name: &str,
+    ) -> Result<bool, DatabaseError> {
+        let name = Self::normalize(name)?;
+
+        sqlx::query!("INSERT OR IGNORE INTO tags (name) VALUES (?)", name)
+            .execute(&mut *conn)
+            .await?;
+
+        let rows = sqlx::query!(
+            "INSERT OR IGNORE INTO media_tags (tag_id, media_id)
+            SELECT id, ? FROM tags WHERE name = ?",
+            media_id,
+            name
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        Ok(rows > 0)
+    }
+
+    /// Method removes a tag from a media object. The tag itself is kept around.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media object.
+    /// * `name` - name of the tag.
+    pub async fn remove(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+        name: &str,
+    ) -> Result<usize, DatabaseError> {
+        let name = Self::normalize(name)?;
+
+        Ok(sqlx::query!(
+            "DELETE FROM media_tags
+            WHERE media_id = ? AND tag_id IN (SELECT id FROM tags WHERE name = ?)",
+            media_id,
+            name
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
+    /// Method replaces all tags of a media object with `tags`. This should be called within a
+    /// single transaction so that the media is never left with a partial set of tags.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media object we want to tag.
+    /// * `tags` - the full set of tag names for this media.
+    pub async fn set_for_media(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+        tags: &[&str],
+    ) -> Result<(), DatabaseError> {
+        Self::delete_by_media_id(&mut *conn, media_id).await?;
+
+        for tag in tags {
+            Self::add(&mut *conn, media_id, tag).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Method removes all tag links of a media object.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `media_id` - id of the media being deleted.
+    pub async fn delete_by_media_id(
+        conn: &mut crate::Transaction<'_>,
+        media_id: i64,
+    ) -> Result<usize, DatabaseError> {
+        Ok(
+            sqlx::query!("DELETE FROM media_tags WHERE media_id = ?", media_id)
+                .execute(&mut *conn)
+                .await?
+                .rows_affected() as usize,
+        )
+    }
+}
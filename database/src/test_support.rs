@@ -0,0 +1,52 @@
+//! Fixtures shared by the tests of this crate.
+//!
+//! Every test runs against its own in-memory database inside a transaction that is never
+//! committed, so fixtures dont need to be cleaned up afterwards.
+use crate::library::InsertableLibrary;
+use crate::library::MediaType;
+use crate::media::InsertableMedia;
+use crate::movie::InsertableMovie;
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+/// Function creates a movie library holding `count` movies and returns the id of the library
+/// along with the ids of the movies in insertion order. Names and locations are derived from a
+/// counter, so repeated calls never collide with each other.
+pub async fn seed_library_with_media(
+    conn: &mut crate::Transaction<'_>,
+    count: usize,
+) -> (i64, Vec<i64>) {
+    static SEED: AtomicU64 = AtomicU64::new(0);
+    let seed = SEED.fetch_add(1, Ordering::SeqCst);
+
+    let library_id = InsertableLibrary {
+        name: format!("fixture{}", seed),
+        locations: vec![format!("/dev/null/fixture{}", seed)],
+        media_type: MediaType::Movie,
+        description: None,
+    }
+    .insert(&mut *conn)
+    .await
+    .unwrap();
+
+    let mut media = Vec::with_capacity(count);
+    for i in 0..count {
+        let id = InsertableMedia {
+            library_id,
+            name: format!("Fixture{}-{}", seed, i),
+            year: Some(2000 + i as i64),
+            added: "2020-01-01".into(),
+            media_type: MediaType::Movie,
+            ..Default::default()
+        }
+        .insert(&mut *conn)
+        .await
+        .unwrap();
+
+        InsertableMovie::insert(&mut *conn, id).await.unwrap();
+        media.push(id);
+    }
+
+    (library_id, media)
+}
@@ -0,0 +1,42 @@
+use crate::asset::resolve_url;
+use crate::media::Media;
+
+#[test]
+fn test_resolve_url() {
+    assert_eq!(resolve_url(""), None);
+    assert_eq!(
+        resolve_url("images/poster.jpg").as_deref(),
+        Some("/images/poster.jpg")
+    );
+    assert_eq!(
+        resolve_url("/images/poster.jpg").as_deref(),
+        Some("/images/poster.jpg")
+    );
+    assert_eq!(
+        resolve_url("avatar.png").as_deref(),
+        Some("/images/avatar.png")
+    );
+    assert_eq!(
+        resolve_url("/poster.jpg").as_deref(),
+        Some("/images/poster.jpg")
+    );
+    assert_eq!(
+        resolve_url("https://image.tmdb.org/t/p/original/poster.jpg").as_deref(),
+        Some("https://image.tmdb.org/t/p/original/poster.jpg")
+    );
+}
+
+#[test]
+fn test_media_serializes_resolved_urls() {
+    let media = Media {
+        poster_path: Some("images/poster.jpg".into()),
+        backdrop_path: Some("".into()),
+        ..Default::default()
+    };
+
+    let json = serde_json::to_value(&media).unwrap();
+    assert_eq!(json["poster_path"], "/images/poster.jpg");
+    assert!(json["backdrop_path"].is_null());
+    // the stored value is left untouched.
+    assert_eq!(media.poster_path.as_deref(), Some("images/poster.jpg"));
+}
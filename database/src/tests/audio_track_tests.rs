@@ -0,0 +1,63 @@
+use crate::audio_track::AudioTrack;
+use crate::audio_track::InsertableAudioTrack;
+use crate::get_conn_memory;
+use crate::mediafile;
+use crate::write_tx;
+
+use super::library_tests::create_test_library;
+use super::media_tests::insert_media;
+use super::mediafile_tests::insert_mediafile_with_mediaid;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_for_media() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _library = create_test_library(&mut tx).await;
+    let media = insert_media(&mut tx).await;
+    let mediafile = insert_mediafile_with_mediaid(&mut tx, media).await;
+
+    assert!(AudioTrack::get_for_media(&mut tx, media)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let tracks = [
+        (2, "German", "ac3", 6, false),
+        (1, "English", "aac", 2, true),
+    ];
+
+    for (index, language, codec, channels, is_default) in tracks.iter() {
+        InsertableAudioTrack {
+            mediafile_id: mediafile,
+            stream_index: *index,
+            language: Some(language.to_string()),
+            codec: codec.to_string(),
+            channels: Some(*channels),
+            is_default: *is_default,
+        }
+        .insert(&mut tx)
+        .await
+        .unwrap();
+    }
+
+    let result = AudioTrack::get_for_media(&mut tx, media).await.unwrap();
+    assert_eq!(
+        result
+            .iter()
+            .map(|x| x.language.as_deref())
+            .collect::<Vec<_>>(),
+        vec![Some("English"), Some("German")]
+    );
+    assert!(result[0].is_default);
+    assert!(!result[1].is_default);
+    assert_eq!(result[1].channels, Some(6));
+
+    // tracks go away along with their mediafile.
+    mediafile::MediaFile::delete(&mut tx, mediafile)
+        .await
+        .unwrap();
+    assert!(AudioTrack::get_for_media(&mut tx, media)
+        .await
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,44 @@
+use crate::audit::AuditLog;
+use crate::get_conn_memory;
+use crate::write_tx;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_record_and_get_page() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+
+    for i in 0..3 {
+        let target = format!("test{}", i);
+        AuditLog::record(&mut tx, Some("admin"), "role_changed", Some(&target), None)
+            .await
+            .unwrap();
+    }
+
+    let result = AuditLog::get_page(&mut tx, 2, 0).await.unwrap();
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].target.as_deref(), Some("test2"));
+    assert_eq!(result[0].actor.as_deref(), Some("admin"));
+
+    let result = AuditLog::get_page(&mut tx, 2, 2).await.unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].target.as_deref(), Some("test0"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_append_only() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+
+    AuditLog::record(&mut tx, None, "login_failed", Some("test"), None)
+        .await
+        .unwrap();
+
+    assert!(sqlx::query("DELETE FROM audit_log")
+        .execute(&mut tx)
+        .await
+        .is_err());
+    assert!(sqlx::query("UPDATE audit_log SET action = 'other'")
+        .execute(&mut tx)
+        .await
+        .is_err());
+}
@@ -1,9 +1,11 @@
+use crate::asset::InsertableAsset;
 use crate::episode;
 use crate::get_conn_memory;
 use crate::media;
 use crate::season;
 use crate::tv;
 use crate::write_tx;
+use crate::DatabaseError;
 
 use super::library_tests::create_test_library;
 use super::media_tests::insert_media;
@@ -35,6 +37,7 @@ async fn test_insert_get_and_delete() {
         },
         seasonid: season,
         episode: 2,
+        still: None,
     }
     .insert(&mut tx)
     .await
@@ -80,6 +83,7 @@ async fn test_get_all_of_season() {
             },
             seasonid: season,
             episode: i,
+            still: None,
         }
         .insert(&mut tx)
         .await
@@ -120,6 +124,7 @@ async fn test_get_first_of_season() {
             },
             seasonid: season,
             episode: i,
+            still: None,
         }
         .insert(&mut tx)
         .await
@@ -140,6 +145,7 @@ async fn test_get_first_of_season() {
             },
             seasonid: season,
             episode: i,
+            still: None,
         }
         .insert(&mut tx)
         .await
@@ -182,6 +188,7 @@ async fn test_get_all_of_tv() {
                 },
                 seasonid: season,
                 episode: i,
+                still: None,
             }
             .insert(&mut tx)
             .await
@@ -219,6 +226,7 @@ async fn test_update() {
         },
         seasonid: season,
         episode: 2,
+        still: None,
     }
     .insert(&mut tx)
     .await
@@ -263,6 +271,7 @@ async fn test_get_next_episode() {
             },
             seasonid: season,
             episode: i,
+            still: None,
         }
         .insert(&mut tx)
         .await
@@ -275,3 +284,137 @@ async fn test_get_next_episode() {
     let second_ep = first_ep.get_next_episode(&mut tx).await.unwrap();
     assert_eq!(second_ep.episode, 2);
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_renumber() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _lib = create_test_library(&mut tx).await;
+    let tv = insert_media(&mut tx).await;
+    tv::TVShow::insert(&mut tx, tv).await.unwrap();
+
+    let season = season::InsertableSeason {
+        season_number: 1,
+        ..Default::default()
+    }
+    .insert(&mut tx, tv)
+    .await
+    .unwrap();
+
+    let mut episodes = vec![];
+    for episode in 1..=2 {
+        let id = episode::InsertableEpisode {
+            media: media::InsertableMedia {
+                library_id: _lib,
+                name: format!("TestEpisode{}", episode),
+                ..Default::default()
+            },
+            seasonid: season,
+            episode,
+            still: None,
+        }
+        .insert(&mut tx)
+        .await
+        .unwrap();
+        episodes.push(id);
+    }
+
+    let result = episode::Episode::renumber(&mut tx, episodes[0], 1, 2).await;
+    assert!(matches!(
+        result,
+        Err(DatabaseError::DuplicateEpisode {
+            season: 1,
+            episode: 2
+        })
+    ));
+
+    let result =
+        episode::Episode::renumber_many(&mut tx, &[(episodes[0], 1, 3), (episodes[1], 1, 3)]).await;
+    assert!(matches!(
+        result,
+        Err(DatabaseError::DuplicateEpisode { .. })
+    ));
+
+    // a episode can only be moved once per batch.
+    let result =
+        episode::Episode::renumber_many(&mut tx, &[(episodes[0], 1, 3), (episodes[0], 1, 4)]).await;
+    assert!(matches!(
+        result,
+        Err(DatabaseError::DuplicateRenumber { id }) if id == episodes[0]
+    ));
+
+    // episodes within the same batch can swap places.
+    episode::Episode::renumber_many(&mut tx, &[(episodes[0], 1, 2), (episodes[1], 1, 1)])
+        .await
+        .unwrap();
+    let result = episode::Episode::get(&mut tx, tv, 1, 1).await.unwrap();
+    assert_eq!(result.id, episodes[1]);
+    let result = episode::Episode::get(&mut tx, tv, 1, 2).await.unwrap();
+    assert_eq!(result.id, episodes[0]);
+
+    // moving a episode into a season that doesnt exist yet creates it.
+    episode::Episode::renumber(&mut tx, episodes[0], 2, 1)
+        .await
+        .unwrap();
+    let result = episode::Episode::get(&mut tx, tv, 2, 1).await.unwrap();
+    assert_eq!(result.id, episodes[0]);
+    assert!(episode::Episode::get(&mut tx, tv, 1, 2).await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_episode_still() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _lib = create_test_library(&mut tx).await;
+    let tv = insert_media(&mut tx).await;
+    tv::TVShow::insert(&mut tx, tv).await.unwrap();
+
+    let season = season::InsertableSeason {
+        season_number: 1,
+        ..Default::default()
+    }
+    .insert(&mut tx, tv)
+    .await
+    .unwrap();
+
+    let still = InsertableAsset {
+        local_path: "still.jpg".into(),
+        file_ext: "jpg".into(),
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    for (i, still) in [(1, Some(still.id)), (2, None)].iter() {
+        episode::InsertableEpisode {
+            media: media::InsertableMedia {
+                library_id: _lib,
+                name: format!("TestEpisode{}", i),
+                ..Default::default()
+            },
+            seasonid: season,
+            episode: *i,
+            still: *still,
+        }
+        .insert(&mut tx)
+        .await
+        .unwrap();
+    }
+
+    let result = episode::Episode::get(&mut tx, tv, 1, 1).await.unwrap();
+    assert_eq!(result.still_path.as_deref(), Some("still.jpg"));
+
+    let result = episode::Episode::get_all_of_season(&mut tx, season)
+        .await
+        .unwrap();
+    let mut stills = result
+        .iter()
+        .map(|x| (x.episode, x.still_path.clone()))
+        .collect::<Vec<_>>();
+    stills.sort();
+    assert_eq!(stills, vec![(1, Some("still.jpg".into())), (2, None)]);
+
+    // stills belong to the episode, not to the media it wraps.
+    assert!(result.iter().all(|x| x.media.backdrop_path.is_none()));
+}
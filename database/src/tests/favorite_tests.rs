@@ -0,0 +1,57 @@
+use crate::favorite::Favorite;
+use crate::get_conn_memory;
+use crate::media;
+use crate::write_tx;
+
+use super::library_tests::create_test_library;
+use super::media_tests::insert_media;
+use super::user_tests::insert_user;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_add_remove_favorite() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _library = create_test_library(&mut tx).await;
+    let user = insert_user(&mut tx).await;
+    let media = insert_media(&mut tx).await;
+
+    assert!(Favorite::get_for_user(&mut tx, user.id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    assert_eq!(Favorite::add(&mut tx, user.id, media).await.unwrap(), 1);
+    // adding a favorite twice is a no-op.
+    assert_eq!(Favorite::add(&mut tx, user.id, media).await.unwrap(), 0);
+
+    let result = Favorite::get_for_user(&mut tx, user.id).await.unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].id, media);
+
+    assert_eq!(Favorite::remove(&mut tx, user.id, media).await.unwrap(), 1);
+    assert_eq!(Favorite::remove(&mut tx, user.id, media).await.unwrap(), 0);
+    assert!(Favorite::get_for_user(&mut tx, user.id)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_favorite_removed_with_media() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _library = create_test_library(&mut tx).await;
+    let user = insert_user(&mut tx).await;
+    let media = insert_media(&mut tx).await;
+
+    Favorite::add(&mut tx, user.id, media).await.unwrap();
+    media::Media::delete(&mut tx, media).await.unwrap();
+
+    assert!(Favorite::get_for_user(&mut tx, user.id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // the entry itself must be gone, not just hidden by the join.
+    assert_eq!(Favorite::remove(&mut tx, user.id, media).await.unwrap(), 0);
+}
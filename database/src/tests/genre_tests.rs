@@ -40,9 +40,14 @@ async fn test_get_by_media() {
         description: None,
         rating: Some(10),
         year: Some(2020),
-        added: "Test".into(),
+        added: "2020-01-01".into(),
         poster: None,
         backdrop: None,
+        duration: None,
+        external_id: None,
+        tmdb_id: None,
+        imdb_id: None,
+        added_by: None,
         media_type: library::MediaType::Movie,
     };
 
@@ -85,3 +90,47 @@ async fn test_delete() {
     let result = genre::Genre::get_by_id(&mut tx, id).await;
     assert!(result.is_err());
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_attach_and_set_for_media() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library_id = create_test_library(&mut tx).await;
+
+    let media_id = media::InsertableMedia {
+        library_id,
+        name: "TestMedia".into(),
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    assert!(genre::Genre::attach(&mut tx, media_id, "Action")
+        .await
+        .unwrap());
+    assert!(!genre::Genre::attach(&mut tx, media_id, "Action")
+        .await
+        .unwrap());
+    assert_eq!(
+        genre::Genre::get_by_media(&mut tx, media_id)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    genre::Genre::set_for_media(&mut tx, media_id, &["Comedy", "Drama"])
+        .await
+        .unwrap();
+
+    let mut genres = genre::Genre::get_by_media(&mut tx, media_id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| x.name)
+        .collect::<Vec<_>>();
+    genres.sort();
+
+    assert_eq!(genres, vec!["Comedy".to_string(), "Drama".to_string()]);
+}
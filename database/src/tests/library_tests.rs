@@ -1,7 +1,15 @@
 use crate::get_conn_memory;
 use crate::library;
+use crate::media;
+use crate::mediafile;
+use crate::progress;
+use crate::test_support::seed_library_with_media;
 use crate::write_tx;
 
+use super::media_tests::insert_many;
+use super::user_tests;
+use super::user_tests::insert_user;
+
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 
@@ -11,12 +19,25 @@ pub async fn create_test_library(conn: &mut crate::Transaction<'_>) -> i64 {
         name: format!("test{}", _LIB.load(Ordering::Relaxed)),
         locations: vec![format!("/dev/null{}", _LIB.load(Ordering::Relaxed))],
         media_type: library::MediaType::Movie,
+        description: None,
     };
 
     _LIB.fetch_add(1, Ordering::SeqCst);
     lib.insert(&mut *conn).await.unwrap()
 }
 
+pub async fn create_test_tv_library(conn: &mut crate::Transaction<'_>) -> i64 {
+    library::InsertableLibrary {
+        name: "test_tv".into(),
+        locations: vec!["/dev/null_tv".into()],
+        media_type: library::MediaType::Tv,
+        description: None,
+    }
+    .insert(&mut *conn)
+    .await
+    .unwrap()
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_insert() {
     let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
@@ -49,6 +70,86 @@ async fn test_get_all() {
     assert_eq!(result.len(), 10);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_all_with_counts() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let empty = create_test_library(&mut tx).await;
+    let (movies, ids) = seed_library_with_media(&mut tx, 3).await;
+    // hidden media dont count towards the total either.
+    media::Media::set_hidden(&mut tx, ids[0], true)
+        .await
+        .unwrap();
+
+    let shows = create_test_tv_library(&mut tx).await;
+    super::media_tests::insert_tv(&mut tx, shows).await;
+    // episodes dont count towards the total.
+    media::InsertableMedia {
+        library_id: shows,
+        name: "TestEpisode".into(),
+        media_type: library::MediaType::Episode,
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    let result = library::Library::get_all_with_counts(&mut tx)
+        .await
+        .unwrap();
+    let counts = result
+        .into_iter()
+        .map(|(library, count)| (library.id, count))
+        .collect::<Vec<_>>();
+
+    assert_eq!(counts.len(), 3);
+    assert!(counts.contains(&(empty, 0)));
+    assert!(counts.contains(&(movies, 2)));
+    assert!(counts.contains(&(shows, 1)));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_stats() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let id = create_test_library(&mut tx).await;
+    let (other, _) = seed_library_with_media(&mut tx, 2).await;
+
+    assert_eq!(
+        library::Library::get_stats(&mut tx, id).await.unwrap(),
+        library::LibraryStats::default()
+    );
+
+    let media = super::media_tests::insert_media(&mut tx).await;
+    super::mediafile_tests::insert_mediafile_with_mediaid(&mut tx, media).await;
+    mediafile::InsertableMediaFile {
+        library_id: id,
+        target_file: "/dev/null/unmatched".into(),
+        raw_name: "Unmatched".into(),
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    assert_eq!(
+        library::Library::get_stats(&mut tx, id).await.unwrap(),
+        library::LibraryStats {
+            media: 1,
+            episodes: 0,
+            mediafiles: 2,
+            unmatched: 1,
+        }
+    );
+    assert_eq!(
+        library::Library::get_stats(&mut tx, other)
+            .await
+            .unwrap()
+            .media,
+        2
+    );
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_delete() {
     let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
@@ -60,3 +161,604 @@ async fn test_delete() {
     let rows = library::Library::delete(&mut tx, id).await.unwrap();
     assert_eq!(rows, 1);
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_library_access() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let open = create_test_library(&mut tx).await;
+    let restricted = create_test_library(&mut tx).await;
+
+    user_tests::insert_many(&mut tx, 2).await;
+    let granted = crate::user::User::get(&mut tx, "test0").await.unwrap();
+    let other = crate::user::User::get(&mut tx, "test1").await.unwrap();
+
+    // libraries that arent restricted are open to everyone.
+    assert!(library::Library::can_access(&mut tx, restricted, other.id)
+        .await
+        .unwrap());
+    assert_eq!(
+        library::Library::get_all_for_user(&mut tx, other.id)
+            .await
+            .len(),
+        2
+    );
+
+    let rows = library::Library::grant_access(&mut tx, restricted, granted.id)
+        .await
+        .unwrap();
+    assert_eq!(rows, 1);
+    // granting twice is a no-op.
+    let rows = library::Library::grant_access(&mut tx, restricted, granted.id)
+        .await
+        .unwrap();
+    assert_eq!(rows, 0);
+
+    assert!(
+        library::Library::can_access(&mut tx, restricted, granted.id)
+            .await
+            .unwrap()
+    );
+    assert!(!library::Library::can_access(&mut tx, restricted, other.id)
+        .await
+        .unwrap());
+    assert!(library::Library::can_access(&mut tx, open, other.id)
+        .await
+        .unwrap());
+
+    let visible = library::Library::get_all_for_user(&mut tx, other.id).await;
+    assert_eq!(visible.len(), 1);
+    assert_eq!(visible[0].id, open);
+    assert_eq!(
+        library::Library::get_all_for_user(&mut tx, granted.id)
+            .await
+            .len(),
+        2
+    );
+
+    assert_eq!(
+        library::Library::get_denied_ids(&mut tx, other.id)
+            .await
+            .unwrap(),
+        vec![restricted]
+    );
+    assert!(library::Library::get_denied_ids(&mut tx, granted.id)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        library::Library::get_access(&mut tx, restricted)
+            .await
+            .unwrap(),
+        vec![granted.id]
+    );
+
+    // revoking the last grant keeps the library restricted.
+    let rows = library::Library::revoke_access(&mut tx, restricted, granted.id)
+        .await
+        .unwrap();
+    assert_eq!(rows, 1);
+    assert!(library::Library::is_restricted(&mut tx, restricted)
+        .await
+        .unwrap());
+    assert!(
+        !library::Library::can_access(&mut tx, restricted, granted.id)
+            .await
+            .unwrap()
+    );
+    assert!(!library::Library::can_access(&mut tx, restricted, other.id)
+        .await
+        .unwrap());
+    assert_eq!(
+        library::Library::get_denied_ids(&mut tx, granted.id)
+            .await
+            .unwrap(),
+        vec![restricted]
+    );
+
+    // only lifting the restriction opens it up again.
+    library::Library::set_restricted(&mut tx, restricted, false)
+        .await
+        .unwrap();
+    assert!(library::Library::can_access(&mut tx, restricted, other.id)
+        .await
+        .unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_insert_duplicate_location() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+
+    let lib = library::InsertableLibrary {
+        name: "movies".into(),
+        locations: vec!["/media/movies".into()],
+        media_type: library::MediaType::Movie,
+        description: None,
+    };
+    lib.insert(&mut tx).await.unwrap();
+
+    for location in ["/media/movies/", "/media/tv/../movies", "/media/./movies"] {
+        let lib = library::InsertableLibrary {
+            name: format!("movies {}", location),
+            locations: vec![location.into()],
+            media_type: library::MediaType::Movie,
+            description: None,
+        };
+
+        assert!(matches!(
+            lib.insert(&mut tx).await,
+            Err(crate::DatabaseError::LibraryExists)
+        ));
+    }
+
+    assert_eq!(library::Library::get_all(&mut tx).await.len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_purge() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let id = create_test_library(&mut tx).await;
+    let user = insert_user(&mut tx).await;
+
+    insert_many(&mut tx, 3).await;
+    for media in media::Media::get_all(&mut tx, id).await.unwrap() {
+        mediafile::InsertableMediaFile {
+            library_id: id,
+            target_file: format!("/dev/null/{}", media.id),
+            raw_name: "Test".into(),
+            media_id: Some(media.id),
+            ..Default::default()
+        }
+        .insert(&mut tx)
+        .await
+        .unwrap();
+        progress::Progress::set(&mut tx, 100, user.id, media.id)
+            .await
+            .unwrap();
+    }
+
+    let summary = library::Library::purge(&mut tx, id).await.unwrap();
+    assert_eq!(summary.media, 3);
+    assert_eq!(summary.mediafiles, 3);
+    assert_eq!(summary.progress, 3);
+
+    assert!(media::Media::get_all(&mut tx, id).await.unwrap().is_empty());
+    assert!(mediafile::MediaFile::get_by_lib(&mut tx, id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // the library itself must survive a purge.
+    library::Library::get_one(&mut tx, id).await.unwrap();
+}
+
+#[test]
+fn test_media_type_from_str() {
+    use library::MediaType;
+    use std::convert::TryFrom;
+
+    assert_eq!("movie".parse::<MediaType>().unwrap(), MediaType::Movie);
+    assert_eq!(" TV ".parse::<MediaType>().unwrap(), MediaType::Tv);
+    assert_eq!(MediaType::try_from("episode").unwrap(), MediaType::Episode);
+    assert!(matches!(
+        "movies".parse::<MediaType>(),
+        Err(crate::DatabaseError::InvalidMediaType(x)) if x == "movies"
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_decode_invalid_media_type() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let id = create_test_library(&mut tx).await;
+
+    sqlx::query("UPDATE library SET media_type = 'movies' WHERE id = ?")
+        .bind(id)
+        .execute(&mut tx)
+        .await
+        .unwrap();
+
+    assert!(library::Library::get_one(&mut tx, id).await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_scan_status() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library = create_test_library(&mut tx).await;
+
+    let state = library::Library::scan_status(&mut tx, library)
+        .await
+        .unwrap();
+    assert_eq!(state.status, library::ScanStatus::Idle);
+    assert_eq!(state.started_at, None);
+
+    library::Library::scan_started(&mut tx, library)
+        .await
+        .unwrap();
+    let state = library::Library::scan_status(&mut tx, library)
+        .await
+        .unwrap();
+    assert_eq!(state.status, library::ScanStatus::Scanning);
+    assert!(state.started_at.is_some());
+    assert_eq!(state.finished_at, None);
+
+    library::Library::scan_finished(&mut tx, library, 12, false)
+        .await
+        .unwrap();
+    let state = library::Library::scan_status(&mut tx, library)
+        .await
+        .unwrap();
+    assert_eq!(state.status, library::ScanStatus::Idle);
+    assert!(state.finished_at.is_some());
+    assert_eq!(state.files_processed, 12);
+
+    // a scan that is still running on boot has been interrupted.
+    library::Library::scan_started(&mut tx, library)
+        .await
+        .unwrap();
+    assert_eq!(
+        library::Library::reset_interrupted_scans(&mut tx)
+            .await
+            .unwrap(),
+        1
+    );
+    let state = library::Library::scan_status(&mut tx, library)
+        .await
+        .unwrap();
+    assert_eq!(state.status, library::ScanStatus::Failed);
+    assert_eq!(
+        library::Library::reset_interrupted_scans(&mut tx)
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_scan_schedule() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library = create_test_library(&mut tx).await;
+
+    assert_eq!(
+        library::Library::get_scan_schedule(&mut tx, library)
+            .await
+            .unwrap(),
+        None
+    );
+
+    let result = library::Library::set_scan_schedule(&mut tx, library, 10).await;
+    assert!(matches!(
+        result,
+        Err(crate::DatabaseError::InvalidScanInterval(10))
+    ));
+
+    let schedule = library::Library::set_scan_schedule(&mut tx, library, 3600)
+        .await
+        .unwrap();
+    assert_eq!(
+        library::Library::get_scan_schedule(&mut tx, library)
+            .await
+            .unwrap(),
+        Some(schedule.clone())
+    );
+
+    // nothing is due before the next run.
+    assert!(
+        library::Library::take_due_scans(&mut tx, schedule.next_run - 1)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    // runs that were missed are collapsed into one.
+    let now = schedule.next_run + 3 * 3600;
+    let due = library::Library::take_due_scans(&mut tx, now)
+        .await
+        .unwrap();
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].library_id, library);
+    assert_eq!(due[0].next_run, now + 3600);
+    assert!(library::Library::take_due_scans(&mut tx, now)
+        .await
+        .unwrap()
+        .is_empty());
+
+    assert_eq!(
+        library::Library::clear_scan_schedule(&mut tx, library)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        library::Library::get_scan_schedule(&mut tx, library)
+            .await
+            .unwrap(),
+        None
+    );
+
+    // deleting the library removes its schedule.
+    library::Library::set_scan_schedule(&mut tx, library, 3600)
+        .await
+        .unwrap();
+    library::Library::delete(&mut tx, library).await.unwrap();
+    assert_eq!(
+        library::Library::get_scan_schedule(&mut tx, library)
+            .await
+            .unwrap(),
+        None
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_change_type() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library = create_test_library(&mut tx).await;
+
+    assert!(library::Library::can_change_type(&mut tx, library)
+        .await
+        .unwrap());
+
+    // libraries never hold episodes directly, even while they are empty.
+    let result = library::UpdateLibrary {
+        media_type: Some(library::MediaType::Episode),
+        ..Default::default()
+    }
+    .update(&mut tx, library)
+    .await;
+    assert!(matches!(
+        result,
+        Err(crate::DatabaseError::InvalidMediaType(_))
+    ));
+    assert_eq!(
+        library::Library::get_one(&mut tx, library)
+            .await
+            .unwrap()
+            .media_type,
+        library::MediaType::Movie
+    );
+
+    library::UpdateLibrary {
+        name: Some("Shows".into()),
+        media_type: Some(library::MediaType::Tv),
+        ..Default::default()
+    }
+    .update(&mut tx, library)
+    .await
+    .unwrap();
+
+    let result = library::Library::get_one(&mut tx, library).await.unwrap();
+    assert_eq!(result.name, "Shows");
+    assert_eq!(result.media_type, library::MediaType::Tv);
+
+    super::media_tests::insert_tv(&mut tx, library).await;
+    assert!(!library::Library::can_change_type(&mut tx, library)
+        .await
+        .unwrap());
+
+    let result = library::UpdateLibrary {
+        media_type: Some(library::MediaType::Movie),
+        ..Default::default()
+    }
+    .update(&mut tx, library)
+    .await;
+    assert!(matches!(result, Err(crate::DatabaseError::LibraryNotEmpty)));
+
+    // renaming and keeping the type is still fine.
+    library::UpdateLibrary {
+        name: Some("TV".into()),
+        media_type: Some(library::MediaType::Tv),
+        ..Default::default()
+    }
+    .update(&mut tx, library)
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_library_description() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let plain = create_test_library(&mut tx).await;
+
+    let annotated = library::InsertableLibrary {
+        name: "Remuxes".into(),
+        locations: vec!["/dev/null_remux".into()],
+        media_type: library::MediaType::Movie,
+        description: Some(" 4K remux collection ".into()),
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    let result = library::Library::get_one(&mut tx, plain).await.unwrap();
+    assert_eq!(result.description, None);
+    let result = library::Library::get_one(&mut tx, annotated).await.unwrap();
+    assert_eq!(result.description.as_deref(), Some("4K remux collection"));
+
+    library::UpdateLibrary {
+        description: Some("UHD only".into()),
+        ..Default::default()
+    }
+    .update(&mut tx, plain)
+    .await
+    .unwrap();
+
+    let result = library::Library::get_all(&mut tx).await;
+    let result = result.iter().find(|x| x.id == plain).unwrap();
+    assert_eq!(result.description.as_deref(), Some("UHD only"));
+
+    // leaving the description out keeps it, an empty one removes it.
+    library::UpdateLibrary {
+        name: Some("Renamed".into()),
+        ..Default::default()
+    }
+    .update(&mut tx, annotated)
+    .await
+    .unwrap();
+    let result = library::Library::get_one(&mut tx, annotated).await.unwrap();
+    assert_eq!(result.description.as_deref(), Some("4K remux collection"));
+
+    library::UpdateLibrary {
+        description: Some("  ".into()),
+        ..Default::default()
+    }
+    .update(&mut tx, annotated)
+    .await
+    .unwrap();
+    let result = library::Library::get_one(&mut tx, annotated).await.unwrap();
+    assert_eq!(result.description, None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_is_private() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let public = create_test_library(&mut tx).await;
+    let private = create_test_library(&mut tx).await;
+
+    library::UpdateLibrary {
+        is_private: Some(true),
+        ..Default::default()
+    }
+    .update(&mut tx, private)
+    .await
+    .unwrap();
+
+    let result = library::Library::get_all(&mut tx).await;
+    assert_eq!(result.len(), 2);
+    assert!(result.iter().any(|x| x.id == private && x.is_private));
+    assert!(result.iter().any(|x| x.id == public && !x.is_private));
+    assert!(
+        library::Library::get_one(&mut tx, private)
+            .await
+            .unwrap()
+            .is_private
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_empty() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let (id, media) = seed_library_with_media(&mut tx, 2).await;
+    library::Library::scan_started(&mut tx, id).await.unwrap();
+
+    let summary = library::Library::empty(&mut tx, id).await.unwrap();
+    assert_eq!(summary.media, 2);
+    assert_eq!(summary.movies, 2);
+
+    assert!(media::Media::get_all(&mut tx, id).await.unwrap().is_empty());
+    // syncing clients have to learn about the removed media.
+    let mut deleted = media::Media::get_deleted_since(&mut tx, id, 0)
+        .await
+        .unwrap();
+    deleted.sort_unstable();
+    assert_eq!(deleted, media);
+
+    let library = library::Library::get_one(&mut tx, id).await.unwrap();
+    assert_eq!(library.locations.len(), 1);
+    assert_eq!(
+        library::Library::scan_status(&mut tx, id)
+            .await
+            .unwrap()
+            .status,
+        library::ScanStatus::Idle
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rename() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let id = create_test_library(&mut tx).await;
+    let other = create_test_library(&mut tx).await;
+    let other_name = library::Library::get_one(&mut tx, other)
+        .await
+        .unwrap()
+        .name;
+
+    library::Library::rename(&mut tx, id, "  Movies ")
+        .await
+        .unwrap();
+    assert_eq!(
+        library::Library::get_one(&mut tx, id).await.unwrap().name,
+        "Movies"
+    );
+
+    // renaming a library to its current name is fine.
+    library::Library::rename(&mut tx, id, "Movies")
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        library::Library::rename(&mut tx, id, "   ").await,
+        Err(crate::DatabaseError::InvalidLibraryName)
+    ));
+    assert!(matches!(
+        library::Library::rename(&mut tx, id, &other_name).await,
+        Err(crate::DatabaseError::LibraryExists)
+    ));
+    assert!(matches!(
+        library::Library::rename(&mut tx, 9999, "Shows").await,
+        Err(crate::DatabaseError::NotFound)
+    ));
+
+    // hidden libraries still hold on to their name.
+    library::Library::mark_hidden(&mut tx, other).await.unwrap();
+    assert!(matches!(
+        library::Library::rename(&mut tx, id, &other_name).await,
+        Err(crate::DatabaseError::LibraryExists)
+    ));
+
+    assert_eq!(
+        library::Library::get_one(&mut tx, id).await.unwrap().name,
+        "Movies"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_insert_name() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+
+    let id = library::InsertableLibrary {
+        name: "  Movies ".into(),
+        locations: vec!["/media/movies".into()],
+        media_type: library::MediaType::Movie,
+        description: None,
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+    assert_eq!(
+        library::Library::get_one(&mut tx, id).await.unwrap().name,
+        "Movies"
+    );
+
+    for name in ["Movies", " Movies", "   "] {
+        let result = library::InsertableLibrary {
+            name: name.into(),
+            locations: vec!["/media/other".into()],
+            media_type: library::MediaType::Movie,
+            description: None,
+        }
+        .insert(&mut tx)
+        .await;
+
+        if name.trim().is_empty() {
+            assert!(matches!(
+                result,
+                Err(crate::DatabaseError::InvalidLibraryName)
+            ));
+        } else {
+            assert!(matches!(result, Err(crate::DatabaseError::LibraryExists)));
+        }
+    }
+
+    assert_eq!(library::Library::get_all(&mut tx).await.len(), 1);
+}
@@ -1,11 +1,20 @@
+use crate::episode;
 use crate::get_conn_memory;
 use crate::library;
 use crate::media;
 use crate::mediafile;
+use crate::movie::InsertableMovie;
+use crate::progress::Progress;
+use crate::season;
+use crate::test_support::seed_library_with_media;
+use crate::tv;
 use crate::write_tx;
+use crate::DatabaseError;
 
 use super::library_tests::create_test_library;
+use super::library_tests::create_test_tv_library;
 use super::mediafile_tests::insert_mediafile_with_mediaid;
+use super::user_tests::insert_user;
 
 pub async fn insert_media(conn: &mut crate::Transaction<'_>) -> i64 {
     let media = media::InsertableMedia {
@@ -14,15 +23,35 @@ pub async fn insert_media(conn: &mut crate::Transaction<'_>) -> i64 {
         description: None,
         rating: Some(10),
         year: Some(2020),
-        added: "Test".into(),
+        added: "2020-01-01".into(),
         poster: None,
         backdrop: None,
+        duration: None,
+        external_id: None,
+        tmdb_id: None,
+        imdb_id: None,
+        added_by: None,
         media_type: library::MediaType::Movie,
     };
 
     media.insert(&mut *conn).await.unwrap()
 }
 
+pub async fn insert_tv(conn: &mut crate::Transaction<'_>, library_id: i64) -> i64 {
+    let tv = media::InsertableMedia {
+        library_id,
+        name: "TestMedia".into(),
+        media_type: library::MediaType::Tv,
+        ..Default::default()
+    }
+    .insert(&mut *conn)
+    .await
+    .unwrap();
+
+    tv::TVShow::insert(&mut *conn, tv).await.unwrap();
+    tv
+}
+
 pub async fn insert_many(conn: &mut crate::Transaction<'_>, n: usize) {
     for i in 0..n {
         let media = media::InsertableMedia {
@@ -31,9 +60,14 @@ pub async fn insert_many(conn: &mut crate::Transaction<'_>, n: usize) {
             description: None,
             rating: Some(10),
             year: Some(2020),
-            added: "Test".into(),
+            added: "2020-01-01".into(),
             poster: None,
             backdrop: None,
+            duration: None,
+            external_id: None,
+            tmdb_id: None,
+            imdb_id: None,
+            added_by: None,
             media_type: library::MediaType::Movie,
         };
 
@@ -84,6 +118,57 @@ async fn test_get_by_name_and_lib() {
     assert_eq!(result.library_id, 1);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_by_name_year_and_lib() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library_id = create_test_library(&mut tx).await;
+
+    // only media without a external id must have unique names.
+    let mut ids = vec![];
+    for (year, external_id) in [
+        (Some(1974), Some("tmdb:30497")),
+        (Some(2003), Some("tmdb:9373")),
+        (None, None),
+    ] {
+        let id = media::InsertableMedia {
+            library_id,
+            name: "The Texas Chainsaw Massacre".into(),
+            year,
+            external_id: external_id.map(Into::into),
+            media_type: library::MediaType::Movie,
+            ..Default::default()
+        }
+        .insert_blind(&mut tx)
+        .await
+        .unwrap();
+
+        ids.push(id);
+    }
+
+    for (year, id) in [(Some(1974), ids[0]), (Some(2003), ids[1]), (None, ids[2])] {
+        let result = media::Media::get_by_name_year_and_lib(
+            &mut tx,
+            library_id,
+            "The Texas Chainsaw Massacre",
+            year,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.id, id);
+        assert_eq!(result.year, year);
+    }
+
+    let result = media::Media::get_by_name_year_and_lib(
+        &mut tx,
+        library_id,
+        "The Texas Chainsaw Massacre",
+        Some(2022),
+    )
+    .await;
+    assert!(matches!(result, Err(DatabaseError::NotFound)));
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_get_of_mediafile() {
     let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
@@ -138,7 +223,7 @@ async fn test_delete_by_lib() {
 async fn test_blind_insert() {
     let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
     let mut tx = write_tx(&mut conn).await.unwrap();
-    let library_id = create_test_library(&mut tx).await;
+    let library_id = create_test_tv_library(&mut tx).await;
 
     let media = media::InsertableMedia {
         library_id: 1,
@@ -146,9 +231,14 @@ async fn test_blind_insert() {
         description: None,
         rating: Some(10),
         year: Some(2020),
-        added: "Test".into(),
+        added: "2020-01-01".into(),
         poster: None,
         backdrop: None,
+        duration: None,
+        external_id: None,
+        tmdb_id: None,
+        imdb_id: None,
+        added_by: None,
         media_type: library::MediaType::Episode,
     };
 
@@ -171,9 +261,14 @@ async fn test_update() {
         description: None,
         rating: Some(10),
         year: Some(2020),
-        added: "Test".into(),
+        added: "2020-01-01".into(),
         poster: None,
         backdrop: None,
+        duration: None,
+        external_id: None,
+        tmdb_id: None,
+        imdb_id: None,
+        added_by: None,
         media_type: library::MediaType::Movie,
     };
 
@@ -191,3 +286,1178 @@ async fn test_update() {
     assert_eq!(result.name, "TestMedia2".to_string());
     assert_eq!(result.rating, Some(5));
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_episodes() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library_id = create_test_tv_library(&mut tx).await;
+    let tv = insert_tv(&mut tx, library_id).await;
+
+    assert!(media::Media::get_episodes(&mut tx, tv)
+        .await
+        .unwrap()
+        .is_empty());
+
+    for (season_number, episode) in [(2, 1), (1, 2), (1, 1)] {
+        let seasonid = season::InsertableSeason {
+            season_number,
+            ..Default::default()
+        }
+        .insert(&mut tx, tv)
+        .await
+        .unwrap();
+
+        episode::InsertableEpisode {
+            media: media::InsertableMedia {
+                library_id,
+                name: format!("S{}E{}", season_number, episode),
+                media_type: library::MediaType::Episode,
+                ..Default::default()
+            },
+            seasonid,
+            episode,
+            still: None,
+        }
+        .insert(&mut tx)
+        .await
+        .unwrap();
+    }
+
+    let result = media::Media::get_episodes(&mut tx, tv)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| (x.season, x.episode, x.name))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        result,
+        vec![
+            (1, 1, "S1E1".to_string()),
+            (1, 2, "S1E2".to_string()),
+            (2, 1, "S2E1".to_string()),
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_total_runtime() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library_id = create_test_library(&mut tx).await;
+
+    assert_eq!(
+        media::Media::get_total_runtime(&mut tx, library_id)
+            .await
+            .unwrap(),
+        0
+    );
+
+    for (i, duration) in [Some(3600), Some(1800), None].iter().enumerate() {
+        media::InsertableMedia {
+            library_id,
+            name: format!("TestMedia{}", i),
+            duration: *duration,
+            ..Default::default()
+        }
+        .insert(&mut tx)
+        .await
+        .unwrap();
+    }
+
+    let media = media::Media::get_by_name_and_lib(&mut tx, library_id, "TestMedia0")
+        .await
+        .unwrap();
+    assert_eq!(media.duration, Some(3600));
+
+    assert_eq!(
+        media::Media::get_total_runtime(&mut tx, library_id)
+            .await
+            .unwrap(),
+        5400
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_upsert_by_external_id() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _library_id = create_test_library(&mut tx).await;
+
+    let mut media = media::InsertableMedia {
+        library_id: 1,
+        name: "TestMedia".into(),
+        description: None,
+        rating: Some(10),
+        year: Some(2020),
+        added: "2020-01-01".into(),
+        poster: None,
+        backdrop: None,
+        duration: None,
+        external_id: Some("tmdb:1".into()),
+        tmdb_id: None,
+        imdb_id: None,
+        added_by: None,
+        media_type: library::MediaType::Movie,
+    };
+
+    let (id, created) = media.upsert_by_external_id(&mut tx).await.unwrap();
+    assert!(created);
+
+    media.name = "TestMedia Renamed".into();
+    media.duration = Some(120);
+    let (same_id, created) = media.upsert_by_external_id(&mut tx).await.unwrap();
+    assert!(!created);
+    assert_eq!(id, same_id);
+
+    let result = media::Media::get(&mut tx, id).await.unwrap();
+    assert_eq!(result.name, "TestMedia Renamed");
+    assert_eq!(result.duration, Some(120));
+
+    // Same title but a different external id is a different piece of media.
+    media.name = "TestMedia".into();
+    media.external_id = Some("tmdb:2".into());
+    let (other_id, created) = media.upsert_by_external_id(&mut tx).await.unwrap();
+    assert!(created);
+    assert_ne!(id, other_id);
+
+    // Without an external id we fall back to deduplicating by name.
+    media.external_id = None;
+    media.name = "Unmatched".into();
+    let (first, created) = media.upsert_by_external_id(&mut tx).await.unwrap();
+    assert!(created);
+    let (second, created) = media.upsert_by_external_id(&mut tx).await.unwrap();
+    assert!(!created);
+    assert_eq!(first, second);
+
+    // remakes sharing a title are told apart by their year.
+    media.year = Some(1990);
+    let (remake, created) = media.upsert_by_external_id(&mut tx).await.unwrap();
+    assert!(created);
+    assert_ne!(first, remake);
+
+    // matched media with the same name and year dont count either.
+    media.year = Some(2020);
+    media.name = "TestMedia".into();
+    let (unmatched, created) = media.upsert_by_external_id(&mut tx).await.unwrap();
+    assert!(created);
+    assert_ne!(unmatched, other_id);
+    media.name = "Unmatched".into();
+
+    // a media with the same name in another library doesnt count.
+    media.library_id = create_test_library(&mut tx).await;
+    let (third, created) = media.upsert_by_external_id(&mut tx).await.unwrap();
+    assert!(created);
+    assert_ne!(first, third);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_by_tmdb_id() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _library_id = create_test_library(&mut tx).await;
+
+    assert!(media::Media::get_by_tmdb_id(&mut tx, 603)
+        .await
+        .unwrap()
+        .is_none());
+
+    let id = media::InsertableMedia {
+        library_id: 1,
+        name: "The Matrix".into(),
+        added: "2020-01-01".into(),
+        external_id: Some("tmdb:603".into()),
+        tmdb_id: Some(603),
+        imdb_id: Some("tt0133093".into()),
+        media_type: library::MediaType::Movie,
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    let result = media::Media::get_by_tmdb_id(&mut tx, 603)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.id, id);
+    assert_eq!(result.imdb_id.as_deref(), Some("tt0133093"));
+
+    let json = serde_json::to_value(&result).unwrap();
+    assert_eq!(json["tmdb_id"], 603);
+    assert_eq!(json["imdb_id"], "tt0133093");
+
+    // ids that are missing are left out of the json.
+    let other = insert_media(&mut tx).await;
+    let other = media::Media::get(&mut tx, other).await.unwrap();
+    let json = serde_json::to_value(&other).unwrap();
+    assert!(json.get("tmdb_id").is_none());
+
+    let update = media::UpdateMedia {
+        tmdb_id: Some(604),
+        ..Default::default()
+    };
+    update.update(&mut tx, id).await.unwrap();
+    assert!(media::Media::get_by_tmdb_id(&mut tx, 603)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_orphaned() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library_id = create_test_library(&mut tx).await;
+
+    let media_id = insert_media(&mut tx).await;
+    insert_many(&mut tx, 2).await;
+    let _ = insert_mediafile_with_mediaid(&mut tx, media_id).await;
+
+    let result = media::Media::get_orphaned(&mut tx, library_id)
+        .await
+        .unwrap();
+    let names = result.into_iter().map(|x| x.name).collect::<Vec<_>>();
+
+    assert_eq!(names, vec!["TestMedia0", "TestMedia1"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_stale() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library_id = create_test_library(&mut tx).await;
+
+    // media without a external id cant be refreshed.
+    insert_media(&mut tx).await;
+
+    let media = media::InsertableMedia {
+        library_id,
+        name: "TestMedia1".into(),
+        description: None,
+        rating: Some(10),
+        year: Some(2020),
+        added: "2020-01-01".into(),
+        poster: None,
+        backdrop: None,
+        duration: None,
+        external_id: Some("tmdb:1".into()),
+        tmdb_id: None,
+        imdb_id: None,
+        added_by: None,
+        media_type: library::MediaType::Movie,
+    };
+    let (id, _) = media.upsert_by_external_id(&mut tx).await.unwrap();
+
+    let result = media::Media::get_stale(&mut tx, library_id, None)
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].id, id);
+    assert_eq!(result[0].external_id, "tmdb:1");
+
+    // the media has just been inserted so it isnt stale yet.
+    let result = media::Media::get_stale(&mut tx, library_id, Some(0))
+        .await
+        .unwrap();
+    assert!(result.is_empty());
+
+    let result = media::Media::get_stale(&mut tx, library_id, Some(i64::MAX))
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_search_global() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library_id = create_test_tv_library(&mut tx).await;
+
+    let tv = insert_tv(&mut tx, library_id).await;
+
+    let season = season::InsertableSeason {
+        season_number: 1,
+        ..Default::default()
+    }
+    .insert(&mut tx, tv)
+    .await
+    .unwrap();
+
+    let episode = episode::InsertableEpisode {
+        media: media::InsertableMedia {
+            library_id,
+            name: "Test Pilot".into(),
+            media_type: library::MediaType::Episode,
+            ..Default::default()
+        },
+        seasonid: season,
+        episode: 1,
+        still: None,
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    let result = media::Media::search_global(&mut tx, "test", 10, &[])
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 2);
+
+    let result = media::Media::search_global(&mut tx, "TEST pil", 10, &[])
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].id, episode);
+    assert_eq!(result[0].media_type, library::MediaType::Episode);
+    assert_eq!(result[0].show_name.as_deref(), Some("TestMedia"));
+
+    // media of denied libraries dont count towards the limit.
+    let movies = create_test_library(&mut tx).await;
+    let movie = media::InsertableMedia {
+        library_id: movies,
+        name: "Test Zulu".into(),
+        media_type: library::MediaType::Movie,
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    let result = media::Media::search_global(&mut tx, "test", 1, &[library_id])
+        .await
+        .unwrap();
+    assert_eq!(
+        result.into_iter().map(|x| x.id).collect::<Vec<_>>(),
+        vec![movie]
+    );
+}
+
+#[test]
+fn test_sort_title() {
+    assert_eq!(media::sort_title("The Matrix"), "matrix");
+    assert_eq!(media::sort_title("A Quiet Place"), "quiet place");
+    assert_eq!(media::sort_title("An Education"), "education");
+    assert_eq!(media::sort_title("Ámelie"), "amelie");
+    // articles are only stripped when they are a separate word followed by a title.
+    assert_eq!(media::sort_title("Theodore"), "theodore");
+    assert_eq!(media::sort_title("The"), "the");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sort_by_sort_title() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library_id = create_test_library(&mut tx).await;
+
+    let mut ids = vec![];
+    for name in ["Zodiac", "The Matrix", "Ámelie", "Amelie 2"] {
+        let id = media::InsertableMedia {
+            library_id,
+            name: name.into(),
+            media_type: library::MediaType::Movie,
+            ..Default::default()
+        }
+        .insert(&mut tx)
+        .await
+        .unwrap();
+
+        ids.push(id);
+    }
+
+    let names = |x: Vec<media::SearchResult>| x.into_iter().map(|x| x.name).collect::<Vec<_>>();
+
+    let result = media::Media::search_global(&mut tx, "i", 10, &[])
+        .await
+        .unwrap();
+    assert_eq!(
+        names(result),
+        ["Ámelie", "Amelie 2", "The Matrix", "Zodiac"]
+    );
+
+    // renaming a media recomputes its sort title.
+    media::UpdateMedia {
+        name: Some("A Beautiful Mind".into()),
+        ..Default::default()
+    }
+    .update(&mut tx, ids[0])
+    .await
+    .unwrap();
+
+    // media added before sort titles existed are filled in.
+    sqlx::query!(
+        "UPDATE _tblmedia SET sort_title = NULL WHERE id = ?",
+        ids[1]
+    )
+    .execute(&mut tx)
+    .await
+    .unwrap();
+    assert_eq!(media::Media::fill_sort_titles(&mut tx).await.unwrap(), 1);
+    assert_eq!(media::Media::fill_sort_titles(&mut tx).await.unwrap(), 0);
+
+    let result = media::Media::search_global(&mut tx, "i", 10, &[])
+        .await
+        .unwrap();
+    assert_eq!(
+        names(result),
+        ["Ámelie", "Amelie 2", "A Beautiful Mind", "The Matrix"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_unwatched() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let movies = create_test_library(&mut tx).await;
+    let shows = create_test_tv_library(&mut tx).await;
+    let user = insert_user(&mut tx).await;
+
+    let insert = |library_id, name: &str, added: &str, media_type| media::InsertableMedia {
+        library_id,
+        name: name.into(),
+        added: added.into(),
+        media_type,
+        ..Default::default()
+    };
+
+    let watched = insert(movies, "Watched", "2020-01-01", library::MediaType::Movie)
+        .insert(&mut tx)
+        .await
+        .unwrap();
+    let started = insert(movies, "Started", "2020-01-02", library::MediaType::Movie)
+        .insert(&mut tx)
+        .await
+        .unwrap();
+    let unseen = insert(movies, "Unseen", "2020-01-03", library::MediaType::Movie)
+        .insert(&mut tx)
+        .await
+        .unwrap();
+    let tv = insert(shows, "Show", "2020-01-03", library::MediaType::Tv)
+        .insert(&mut tx)
+        .await
+        .unwrap();
+    tv::TVShow::insert(&mut tx, tv).await.unwrap();
+
+    let season = season::InsertableSeason {
+        season_number: 1,
+        ..Default::default()
+    }
+    .insert(&mut tx, tv)
+    .await
+    .unwrap();
+
+    let mut episodes = vec![];
+    for episode in 1..=2 {
+        let id = episode::InsertableEpisode {
+            media: insert(shows, "Episode", "2020-01-03", library::MediaType::Episode),
+            seasonid: season,
+            episode,
+            still: None,
+        }
+        .insert(&mut tx)
+        .await
+        .unwrap();
+        episodes.push(id);
+    }
+
+    for id in [watched, started, episodes[0], episodes[1]] {
+        mediafile::InsertableMediaFile {
+            library_id: movies,
+            target_file: format!("/dev/null/{}", id),
+            raw_name: "Test".into(),
+            media_id: Some(id),
+            duration: Some(100),
+            ..Default::default()
+        }
+        .insert(&mut tx)
+        .await
+        .unwrap();
+    }
+
+    Progress::set(&mut tx, 95, user.id, watched).await.unwrap();
+    Progress::set(&mut tx, 30, user.id, started).await.unwrap();
+    Progress::set(&mut tx, 95, user.id, episodes[0])
+        .await
+        .unwrap();
+
+    let ids = |result: Vec<media::Media>| result.iter().map(|x| x.id).collect::<Vec<_>>();
+
+    let result = media::Media::get_unwatched(&mut tx, movies, user.id, 10)
+        .await
+        .unwrap();
+    assert_eq!(ids(result), vec![unseen, started]);
+
+    let result = media::Media::get_unwatched(&mut tx, shows, user.id, 10)
+        .await
+        .unwrap();
+    assert_eq!(ids(result), vec![tv]);
+
+    // once every episode is done the show no longer counts as unwatched.
+    Progress::set(&mut tx, 95, user.id, episodes[1])
+        .await
+        .unwrap();
+    let result = media::Media::get_unwatched(&mut tx, shows, user.id, 10)
+        .await
+        .unwrap();
+    assert!(result.is_empty());
+
+    let result = media::Media::get_unwatched(&mut tx, movies, user.id, 1)
+        .await
+        .unwrap();
+    assert_eq!(ids(result), vec![unseen]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_is_streamable() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _ = create_test_library(&mut tx).await;
+    let library_id = create_test_tv_library(&mut tx).await;
+
+    let movie = insert_media(&mut tx).await;
+    InsertableMovie::insert(&mut tx, movie).await.unwrap();
+
+    let tv = media::InsertableMedia {
+        library_id,
+        name: "TestShow".into(),
+        media_type: library::MediaType::Tv,
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+    tv::TVShow::insert(&mut tx, tv).await.unwrap();
+
+    let season = season::InsertableSeason {
+        season_number: 1,
+        ..Default::default()
+    }
+    .insert(&mut tx, tv)
+    .await
+    .unwrap();
+
+    let episode = episode::InsertableEpisode {
+        media: media::InsertableMedia {
+            library_id,
+            name: "Test Pilot".into(),
+            media_type: library::MediaType::Episode,
+            ..Default::default()
+        },
+        seasonid: season,
+        episode: 1,
+        still: None,
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    assert!(media::Media::is_streamable(&mut tx, movie).await.unwrap());
+    assert!(media::Media::is_streamable(&mut tx, episode).await.unwrap());
+    assert!(!media::Media::is_streamable(&mut tx, tv).await.unwrap());
+    assert!(!media::Media::is_streamable(&mut tx, 1234).await.unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_added_grouped() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library_id = create_test_library(&mut tx).await;
+
+    let added = [
+        "2022-05-30 10:00:00.000000000 UTC",
+        "2022-06-01 10:00:00.000000000 UTC",
+        "2022-06-01 18:00:00.000000000 UTC",
+        "2022-06-13 12:00:00.000000000 UTC",
+    ];
+
+    for (i, added) in added.iter().enumerate() {
+        media::InsertableMedia {
+            library_id,
+            name: format!("TestMedia{}", i),
+            added: added.to_string(),
+            media_type: library::MediaType::Movie,
+            ..Default::default()
+        }
+        .insert(&mut tx)
+        .await
+        .unwrap();
+    }
+
+    // hidden media are left out.
+    let hidden = media::InsertableMedia {
+        library_id,
+        name: "Hidden".into(),
+        added: added[0].to_string(),
+        media_type: library::MediaType::Movie,
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+    media::Media::set_hidden(&mut tx, hidden, true)
+        .await
+        .unwrap();
+
+    let bucket = |date: &str, count| media::AddedBucket {
+        date: date.into(),
+        count,
+    };
+
+    let result = media::Media::get_added_grouped(&mut tx, library_id, Default::default())
+        .await
+        .unwrap();
+    assert_eq!(result, vec![bucket("2022-06", 3), bucket("2022-05", 1)]);
+
+    let result = media::Media::get_added_grouped(&mut tx, library_id, media::AddedGranularity::Day)
+        .await
+        .unwrap();
+    assert_eq!(
+        result,
+        vec![
+            bucket("2022-06-13", 1),
+            bucket("2022-06-01", 2),
+            bucket("2022-05-30", 1)
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_media_type_mismatch() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let movies = create_test_library(&mut tx).await;
+    let shows = create_test_tv_library(&mut tx).await;
+
+    let media = |library_id, media_type| media::InsertableMedia {
+        library_id,
+        name: "TestMedia".into(),
+        media_type,
+        ..Default::default()
+    };
+
+    let result = media(shows, library::MediaType::Movie)
+        .insert(&mut tx)
+        .await;
+    assert!(matches!(
+        result,
+        Err(DatabaseError::MediaTypeMismatch {
+            library: library::MediaType::Tv,
+            media: library::MediaType::Movie,
+        })
+    ));
+
+    let result = media(movies, library::MediaType::Episode)
+        .insert_blind(&mut tx)
+        .await;
+    assert!(matches!(
+        result,
+        Err(DatabaseError::MediaTypeMismatch { .. })
+    ));
+
+    assert!(media(shows, library::MediaType::Episode)
+        .insert_blind(&mut tx)
+        .await
+        .is_ok());
+    assert!(media(movies, library::MediaType::Movie)
+        .insert_with_id(&mut tx, 1234)
+        .await
+        .is_ok());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_changed_since() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library_id = create_test_library(&mut tx).await;
+
+    insert_many(&mut tx, 2).await;
+    let media = media::Media::get_all(&mut tx, library_id).await.unwrap();
+    let (kept, deleted) = (media[0].id, media[1].id);
+
+    let result = media::Media::get_changed_since(&mut tx, library_id, 0)
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 2);
+
+    // a sync from the far future sees nothing new.
+    let result = media::Media::get_changed_since(&mut tx, library_id, i64::MAX)
+        .await
+        .unwrap();
+    assert!(result.is_empty());
+
+    // edits made through the api count as a change as well.
+    sqlx::query("UPDATE _tblmedia SET updated_at = 0")
+        .execute(&mut tx)
+        .await
+        .unwrap();
+
+    media::UpdateMedia::default()
+        .update(&mut tx, kept)
+        .await
+        .unwrap();
+    let result = media::Media::get_changed_since(&mut tx, library_id, 1)
+        .await
+        .unwrap();
+    assert!(result.is_empty());
+
+    media::UpdateMedia {
+        rating: Some(8),
+        ..Default::default()
+    }
+    .update(&mut tx, kept)
+    .await
+    .unwrap();
+    let result = media::Media::get_changed_since(&mut tx, library_id, 1)
+        .await
+        .unwrap();
+    assert_eq!(
+        result.into_iter().map(|x| x.id).collect::<Vec<_>>(),
+        vec![kept]
+    );
+
+    // hidden media drop out of the changed media and show up as deleted until unhidden.
+    media::Media::set_hidden(&mut tx, kept, true).await.unwrap();
+    let result = media::Media::get_changed_since(&mut tx, library_id, 0)
+        .await
+        .unwrap();
+    assert_eq!(
+        result.into_iter().map(|x| x.id).collect::<Vec<_>>(),
+        vec![deleted]
+    );
+    let result = media::Media::get_deleted_since(&mut tx, library_id, 1)
+        .await
+        .unwrap();
+    assert_eq!(result, vec![kept]);
+
+    sqlx::query("UPDATE _tblmedia SET updated_at = 0")
+        .execute(&mut tx)
+        .await
+        .unwrap();
+    media::Media::set_hidden(&mut tx, kept, false)
+        .await
+        .unwrap();
+    let result = media::Media::get_changed_since(&mut tx, library_id, 1)
+        .await
+        .unwrap();
+    assert_eq!(
+        result.into_iter().map(|x| x.id).collect::<Vec<_>>(),
+        vec![kept]
+    );
+
+    media::Media::delete(&mut tx, deleted).await.unwrap();
+
+    let result = media::Media::get_changed_since(&mut tx, library_id, 0)
+        .await
+        .unwrap();
+    assert_eq!(
+        result.into_iter().map(|x| x.id).collect::<Vec<_>>(),
+        vec![kept]
+    );
+
+    let result = media::Media::get_deleted_since(&mut tx, library_id, 0)
+        .await
+        .unwrap();
+    assert_eq!(result, vec![deleted]);
+
+    let result = media::Media::get_deleted_since(&mut tx, library_id, i64::MAX)
+        .await
+        .unwrap();
+    assert!(result.is_empty());
+
+    // tombstones go away with their library.
+    library::Library::delete(&mut tx, library_id).await.unwrap();
+    let result = media::Media::get_deleted_since(&mut tx, library_id, 0)
+        .await
+        .unwrap();
+    assert!(result.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_or_create_show() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library_id = create_test_tv_library(&mut tx).await;
+
+    let show = |external_id: Option<&str>, year| media::InsertableMedia {
+        library_id,
+        name: "The Office".into(),
+        year,
+        external_id: external_id.map(Into::into),
+        media_type: library::MediaType::Tv,
+        ..Default::default()
+    };
+
+    let us = show(Some("tmdb:2316"), Some(2005))
+        .get_or_create_show(&mut tx)
+        .await
+        .unwrap();
+    let uk = show(Some("tmdb:2996"), Some(2001))
+        .get_or_create_show(&mut tx)
+        .await
+        .unwrap();
+    assert_ne!(us, uk);
+
+    let result = show(Some("tmdb:2316"), Some(2005))
+        .get_or_create_show(&mut tx)
+        .await
+        .unwrap();
+    assert_eq!(result, us);
+
+    // without a external id we fall back to name and year.
+    let result = show(None, Some(2005))
+        .get_or_create_show(&mut tx)
+        .await
+        .unwrap();
+    assert_eq!(result, us);
+
+    let unknown = show(None, None).get_or_create_show(&mut tx).await.unwrap();
+    assert!(![us, uk].contains(&unknown));
+    let result = show(None, None).get_or_create_show(&mut tx).await.unwrap();
+    assert_eq!(result, unknown);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_or_create_show_same_name() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library_id = create_test_tv_library(&mut tx).await;
+
+    let show = |year| media::InsertableMedia {
+        library_id,
+        name: "Battlestar Galactica".into(),
+        year: Some(year),
+        media_type: library::MediaType::Tv,
+        ..Default::default()
+    };
+
+    let original = show(1978).get_or_create_show(&mut tx).await.unwrap();
+    let remake = show(2004).get_or_create_show(&mut tx).await.unwrap();
+    assert_ne!(original, remake);
+
+    assert_eq!(
+        show(1978).get_or_create_show(&mut tx).await.unwrap(),
+        original
+    );
+    assert_eq!(
+        show(2004).get_or_create_show(&mut tx).await.unwrap(),
+        remake
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_delete_with_assets() {
+    use crate::asset::InsertableAsset;
+
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library = create_test_library(&mut tx).await;
+
+    let poster = InsertableAsset {
+        local_path: "images/poster.jpg".into(),
+        file_ext: "jpg".into(),
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    let shared = InsertableAsset {
+        local_path: "images/shared.jpg".into(),
+        file_ext: "jpg".into(),
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    let media = media::InsertableMedia {
+        library_id: library,
+        name: "First".into(),
+        poster: Some(poster.id),
+        backdrop: Some(shared.id),
+        media_type: library::MediaType::Movie,
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    let _other = media::InsertableMedia {
+        library_id: library,
+        name: "Second".into(),
+        backdrop: Some(shared.id),
+        media_type: library::MediaType::Movie,
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    let orphans = media::Media::delete_with_assets(&mut tx, media)
+        .await
+        .unwrap();
+    assert_eq!(orphans, vec!["images/poster.jpg".to_string()]);
+    assert!(media::Media::get(&mut tx, media).await.is_err());
+
+    assert!(crate::asset::Asset::get_by_id(&mut tx, poster.id)
+        .await
+        .is_err());
+
+    // the backdrop is still used by another media so it must stay.
+    assert!(crate::asset::Asset::get_by_id(&mut tx, shared.id)
+        .await
+        .is_ok());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_added_normalized() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library_id = create_test_library(&mut tx).await;
+
+    assert_eq!(
+        media::normalize_added("2022-06-13 12:34:56.123456789 UTC").unwrap(),
+        "2022-06-13T12:34:56Z"
+    );
+    assert_eq!(
+        media::normalize_added("2022-06-13T14:34:56+02:00").unwrap(),
+        "2022-06-13T12:34:56Z"
+    );
+    assert_eq!(
+        media::normalize_added("2022-06-13").unwrap(),
+        "2022-06-13T00:00:00Z"
+    );
+
+    let insert = |name: &str, added: &str| media::InsertableMedia {
+        library_id,
+        name: name.into(),
+        added: added.into(),
+        media_type: library::MediaType::Movie,
+        ..Default::default()
+    };
+
+    // an empty `added` is stamped with the current time.
+    let id = insert("Stamped", "").insert(&mut tx).await.unwrap();
+    let added = media::Media::get(&mut tx, id).await.unwrap().added.unwrap();
+    assert_eq!(media::normalize_added(&added).unwrap(), added);
+
+    let result = insert("Malformed", "not a date").insert(&mut tx).await;
+    assert!(matches!(result, Err(DatabaseError::InvalidTimestamp(_))));
+
+    let result = media::UpdateMedia {
+        added: Some("yesterday".into()),
+        ..Default::default()
+    }
+    .update(&mut tx, id)
+    .await;
+    assert!(matches!(result, Err(DatabaseError::InvalidTimestamp(_))));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_random() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let (movies, _) = seed_library_with_media(&mut tx, 5).await;
+    let shows = create_test_tv_library(&mut tx).await;
+    let tv = insert_tv(&mut tx, shows).await;
+
+    let result = media::Media::get_random(&mut tx, movies, 3, None)
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 3);
+    assert!(result.iter().all(|x| x.library_id == movies));
+
+    let mut ids = result.iter().map(|x| x.id).collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids.dedup();
+    assert_eq!(ids.len(), 3);
+
+    let result = media::Media::get_random(&mut tx, movies, 10, None)
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 5);
+
+    let result = media::Media::get_random(&mut tx, movies, 10, Some(library::MediaType::Tv))
+        .await
+        .unwrap();
+    assert!(result.is_empty());
+
+    let result = media::Media::get_random(&mut tx, shows, 10, Some(library::MediaType::Tv))
+        .await
+        .unwrap();
+    assert_eq!(result.iter().map(|x| x.id).collect::<Vec<_>>(), vec![tv]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_page_after() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    // all fixtures share the same `added`, so the order hinges on the id tie-breaker.
+    let (library, media) = seed_library_with_media(&mut tx, 5).await;
+
+    let first = media::Media::get_page_after(&mut tx, library, None, 2)
+        .await
+        .unwrap();
+    assert_eq!(
+        first.media.iter().map(|x| x.id).collect::<Vec<_>>(),
+        media[..2]
+    );
+    let cursor = first.next.unwrap();
+    assert_eq!(cursor.id, media[1]);
+
+    // media added in between must not shift the following pages.
+    media::InsertableMedia {
+        library_id: library,
+        name: "Latecomer".into(),
+        added: "2019-01-01".into(),
+        media_type: library::MediaType::Movie,
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    let second = media::Media::get_page_after(&mut tx, library, Some(&cursor), 2)
+        .await
+        .unwrap();
+    assert_eq!(
+        second.media.iter().map(|x| x.id).collect::<Vec<_>>(),
+        media[2..4]
+    );
+
+    let last = media::Media::get_page_after(&mut tx, library, second.next.as_ref(), 2)
+        .await
+        .unwrap();
+    assert_eq!(
+        last.media.iter().map(|x| x.id).collect::<Vec<_>>(),
+        media[4..]
+    );
+    assert!(last.next.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_added_by() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _library = create_test_library(&mut tx).await;
+
+    let media = media::InsertableMedia {
+        library_id: 1,
+        name: "The Matrix".into(),
+        added: "2020-01-01".into(),
+        external_id: Some("tmdb:603".into()),
+        added_by: Some(media::Media::ADDED_BY_SCANNER.into()),
+        media_type: library::MediaType::Movie,
+        ..Default::default()
+    };
+    let (id, _) = media.upsert_by_external_id(&mut tx).await.unwrap();
+
+    let result = media::Media::get(&mut tx, id).await.unwrap();
+    assert_eq!(result.added_by.as_deref(), Some("scanner"));
+    assert_eq!(
+        serde_json::to_value(&result).unwrap()["added_by"],
+        "scanner"
+    );
+
+    // refreshing the metadata doesnt change who added the media.
+    media::InsertableMedia {
+        added_by: Some("admin".into()),
+        ..media
+    }
+    .upsert_by_external_id(&mut tx)
+    .await
+    .unwrap();
+
+    let result = media::Media::get(&mut tx, id).await.unwrap();
+    assert_eq!(result.added_by.as_deref(), Some("scanner"));
+
+    // media without attribution leave the field out of the json.
+    let other = insert_media(&mut tx).await;
+    let other = media::Media::get(&mut tx, other).await.unwrap();
+    assert!(serde_json::to_value(&other)
+        .unwrap()
+        .get("added_by")
+        .is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_hidden() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let (library_id, ids) = seed_library_with_media(&mut tx, 3).await;
+    let hidden = ids[1];
+
+    assert!(!media::Media::is_hidden(&mut tx, hidden).await.unwrap());
+    assert_eq!(
+        media::Media::set_hidden(&mut tx, hidden, true)
+            .await
+            .unwrap(),
+        1
+    );
+    assert!(media::Media::is_hidden(&mut tx, hidden).await.unwrap());
+
+    let listed = media::Media::get_all(&mut tx, library_id).await.unwrap();
+    assert_eq!(listed.len(), 2);
+    assert!(listed.iter().all(|x| x.id != hidden));
+
+    let random = media::Media::get_random(&mut tx, library_id, 10, None)
+        .await
+        .unwrap();
+    assert!(random.iter().all(|x| x.id != hidden));
+
+    let name = media::Media::get(&mut tx, hidden).await.unwrap().name;
+    assert!(media::Media::search_global(&mut tx, &name, 10, &[])
+        .await
+        .unwrap()
+        .is_empty());
+
+    // hidden media are kept intact and can still be fetched by id.
+    assert_eq!(media::Media::get(&mut tx, hidden).await.unwrap().id, hidden);
+    assert_eq!(
+        media::Media::get_all_with_hidden(&mut tx, library_id, true)
+            .await
+            .unwrap()
+            .len(),
+        3
+    );
+
+    media::Media::set_hidden(&mut tx, hidden, false)
+        .await
+        .unwrap();
+    assert_eq!(
+        media::Media::get_all(&mut tx, library_id)
+            .await
+            .unwrap()
+            .len(),
+        3
+    );
+
+    assert_eq!(
+        media::Media::set_hidden(&mut tx, 9999, true).await.unwrap(),
+        0
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_many() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let (_, ids) = seed_library_with_media(&mut tx, 3).await;
+
+    assert!(media::Media::get_many(&mut tx, &[])
+        .await
+        .unwrap()
+        .is_empty());
+
+    // results follow the order of the ids, missing and repeated ids are skipped.
+    let requested = [ids[2], 9999, ids[0], ids[2], ids[1]];
+    let result = media::Media::get_many(&mut tx, &requested)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|x| x.id)
+        .collect::<Vec<_>>();
+
+    assert_eq!(result, vec![ids[2], ids[0], ids[1]]);
+}
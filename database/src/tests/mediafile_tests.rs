@@ -4,6 +4,7 @@ use crate::mediafile;
 use crate::write_tx;
 
 use super::library_tests::create_test_library;
+use super::media_tests::insert_media;
 
 pub async fn insert_mediafile(conn: &mut crate::Transaction<'_>) -> i64 {
     let mfile = mediafile::InsertableMediaFile {
@@ -172,3 +173,77 @@ async fn test_get_of_media() {
     assert_eq!(result[0].media_id, Some(media_id));
     assert_eq!(result[0].id, mfile);
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_unmatched() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let id = create_test_library(&mut tx).await;
+    let media_id = super::media_tests::insert_media(&mut tx).await;
+
+    insert_many_mediafile(&mut tx, 2).await;
+    let _ = insert_mediafile_with_mediaid(&mut tx, media_id).await;
+
+    let result = mediafile::MediaFile::get_unmatched(&mut tx, id)
+        .await
+        .unwrap();
+
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].target_file, "/dev/null/0");
+    assert_eq!(result[1].target_file, "/dev/null/1");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_stream_info() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _library = create_test_library(&mut tx).await;
+    let media = insert_media(&mut tx).await;
+
+    let mfile = mediafile::InsertableMediaFile {
+        library_id: 1,
+        media_id: Some(media),
+        target_file: "/dev/null/probed".into(),
+        raw_name: "Test".into(),
+        codec: Some("h264".into()),
+        container: Some("matroska".into()),
+        audio: Some("aac".into()),
+        original_resolution: Some("1920x1080".into()),
+        channels: Some(2),
+        duration: Some(600),
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    // corrupt files cant be played back, so they are left out.
+    mediafile::InsertableMediaFile {
+        library_id: 1,
+        media_id: Some(media),
+        target_file: "/dev/null/corrupt".into(),
+        raw_name: "Test".into(),
+        corrupt: Some(true),
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    let result = mediafile::MediaFile::get_stream_info(&mut tx, media)
+        .await
+        .unwrap();
+    assert_eq!(
+        result,
+        vec![mediafile::StreamInfo {
+            mediafile_id: mfile,
+            container: Some("matroska".into()),
+            video_codec: Some("h264".into()),
+            profile: None,
+            resolution: Some("1920x1080".into()),
+            audio_codec: Some("aac".into()),
+            channels: Some(2),
+            duration: Some(600),
+        }]
+    );
+}
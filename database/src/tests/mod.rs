@@ -1,10 +1,17 @@
+pub mod asset_tests;
+pub mod audio_track_tests;
+pub mod audit_tests;
 pub mod episode_tests;
+pub mod favorite_tests;
 pub mod genre_tests;
 pub mod library_tests;
 pub mod media_tests;
 pub mod mediafile_tests;
 pub mod movie_tests;
 pub mod progress_tests;
+pub mod rating_tests;
 pub mod season_tests;
+pub mod subtitle_tests;
+pub mod tag_tests;
 pub mod tv_tests;
 pub mod user_tests;
@@ -1,17 +1,17 @@
 use crate::episode;
 use crate::get_conn_memory;
+use crate::library::MediaType;
 use crate::media;
 use crate::progress;
 use crate::season;
 use crate::tv;
+use crate::utils::unix_now;
 use crate::write_tx;
 
 use super::library_tests::create_test_library;
 use super::media_tests::insert_media;
 use super::user_tests::insert_user;
 
-use std::time::SystemTime;
-
 #[tokio::test(flavor = "multi_thread")]
 async fn test_set_and_get_for_media_user() {
     let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
@@ -26,10 +26,7 @@ async fn test_set_and_get_for_media_user() {
     assert_eq!(result.delta, 0);
     assert_eq!(result.populated, 0);
 
-    let ts = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64;
+    let ts = unix_now();
 
     let rows = progress::Progress::set(&mut tx, 100, user.id, media)
         .await
@@ -102,6 +99,7 @@ async fn test_get_total_for_tv() {
             },
             seasonid: season,
             episode: i,
+            still: None,
         }
         .insert(&mut tx)
         .await
@@ -153,6 +151,7 @@ async fn test_get_continue_watching() {
         },
         seasonid: season1,
         episode: 1,
+        still: None,
     }
     .insert(&mut tx)
     .await
@@ -166,6 +165,7 @@ async fn test_get_continue_watching() {
         },
         seasonid: season2,
         episode: 1,
+        still: None,
     }
     .insert(&mut tx)
     .await
@@ -175,7 +175,7 @@ async fn test_get_continue_watching() {
         .await
         .unwrap();
 
-    let result = progress::Progress::get_continue_watching(&mut tx, user.id, 2)
+    let result = progress::Progress::get_continue_watching(&mut tx, user.id, 2, &[])
         .await
         .unwrap();
     assert_eq!(result.len(), 1);
@@ -185,9 +185,305 @@ async fn test_get_continue_watching() {
         .await
         .unwrap();
 
-    let result = progress::Progress::get_continue_watching(&mut tx, user.id, 2)
+    let result = progress::Progress::get_continue_watching(&mut tx, user.id, 2, &[])
         .await
         .unwrap();
     assert_eq!(result.len(), 2);
     assert_eq!(result[0], 2);
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_for_media_ids() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _library = create_test_library(&mut tx).await;
+    let user = insert_user(&mut tx).await;
+
+    let mut media = Vec::new();
+    for name in ["First", "Second", "Third"] {
+        let id = media::InsertableMedia {
+            library_id: 1,
+            name: name.into(),
+            media_type: MediaType::Movie,
+            ..Default::default()
+        }
+        .insert(&mut tx)
+        .await
+        .unwrap();
+        media.push(id);
+    }
+
+    let result = progress::Progress::get_for_media_ids(&mut tx, user.id, &media)
+        .await
+        .unwrap();
+    assert!(result.is_empty());
+
+    progress::Progress::set(&mut tx, 100, user.id, media[0])
+        .await
+        .unwrap();
+    progress::Progress::set(&mut tx, 200, user.id, media[2])
+        .await
+        .unwrap();
+
+    let result = progress::Progress::get_for_media_ids(&mut tx, user.id, &media)
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[&media[0]].delta, 100);
+    assert_eq!(result[&media[2]].delta, 200);
+    assert!(!result.contains_key(&media[1]));
+
+    let result = progress::Progress::get_for_media_ids(&mut tx, user.id, &[])
+        .await
+        .unwrap();
+    assert!(result.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_upsert() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _library = create_test_library(&mut tx).await;
+    let user = insert_user(&mut tx).await;
+    let media = insert_media(&mut tx).await;
+
+    let first = progress::Progress::upsert(&mut tx, user.id, media, 30, Some(600))
+        .await
+        .unwrap();
+    assert_eq!(first.delta, 30);
+    assert_eq!(first.duration, Some(600));
+
+    // a heartbeat without a duration keeps the known duration and updates the same row.
+    let second = progress::Progress::upsert(&mut tx, user.id, media, 60, None)
+        .await
+        .unwrap();
+    assert_eq!(second.id, first.id);
+    assert_eq!(second.delta, 60);
+    assert_eq!(second.duration, Some(600));
+    assert!(second.populated >= first.populated);
+
+    // positions past the end are clamped to the duration.
+    let last = progress::Progress::upsert(&mut tx, user.id, media, 700, Some(600))
+        .await
+        .unwrap();
+    assert_eq!(last.delta, 600);
+
+    let result = progress::Progress::get_for_media_user(&mut tx, user.id, media)
+        .await
+        .unwrap();
+    assert_eq!(result.delta, 600);
+    assert_eq!(result.duration, Some(600));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_show_watched() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let library = create_test_library(&mut tx).await;
+    let user = insert_user(&mut tx).await;
+
+    let tv = insert_media(&mut tx).await;
+    tv::TVShow::insert(&mut tx, tv).await.unwrap();
+
+    let season = season::InsertableSeason {
+        season_number: 1,
+        ..Default::default()
+    }
+    .insert(&mut tx, tv)
+    .await
+    .unwrap();
+
+    let mut episodes = vec![];
+    for i in 1..=3 {
+        let episode = episode::InsertableEpisode {
+            media: media::InsertableMedia {
+                library_id: library,
+                name: format!("TestEpisode{}", i),
+                duration: (i != 3).then(|| 100 * i),
+                ..Default::default()
+            },
+            seasonid: season,
+            episode: i,
+            still: None,
+        }
+        .insert(&mut tx)
+        .await
+        .unwrap();
+
+        episodes.push(episode);
+    }
+
+    // a episode that was partly watched is completed as well, the others get a new entry.
+    progress::Progress::set(&mut tx, 10, user.id, episodes[0])
+        .await
+        .unwrap();
+    assert_eq!(
+        progress::Progress::get_for_media_ids(&mut tx, user.id, &episodes)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    let rows = progress::Progress::set_show_watched(&mut tx, user.id, tv)
+        .await
+        .unwrap();
+    assert_eq!(rows, 3);
+
+    let result = progress::Progress::get_for_media_ids(&mut tx, user.id, &episodes)
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 3);
+    assert_eq!(result[&episodes[0]].delta, 100);
+    assert_eq!(result[&episodes[1]].delta, 200);
+    assert_eq!(result[&episodes[2]].delta, 0);
+
+    // the show itself has no progress entry.
+    let result = progress::Progress::get_for_media_user(&mut tx, user.id, tv)
+        .await
+        .unwrap();
+    assert_eq!(result.id, 0);
+
+    let rows = progress::Progress::clear_show(&mut tx, user.id, tv)
+        .await
+        .unwrap();
+    assert_eq!(rows, 3);
+    assert!(
+        progress::Progress::get_for_media_ids(&mut tx, user.id, &episodes)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_history() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let user = insert_user(&mut tx).await;
+    let (_, media) = crate::test_support::seed_library_with_media(&mut tx, 3).await;
+
+    assert!(
+        progress::Progress::get_history(&mut tx, user.id, 10, 0, &[])
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    progress::Progress::upsert(&mut tx, user.id, media[0], 50, Some(100))
+        .await
+        .unwrap();
+    progress::Progress::upsert(&mut tx, user.id, media[1], 95, Some(100))
+        .await
+        .unwrap();
+
+    // finished media are part of the history too, most recently watched first.
+    let result = progress::Progress::get_history(&mut tx, user.id, 10, 0, &[])
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].media.id, media[1]);
+    assert_eq!(result[0].position, 95);
+    assert!(result[0].completed);
+    assert_eq!(result[1].media.id, media[0]);
+    assert!(!result[1].completed);
+
+    let result = progress::Progress::get_history(&mut tx, user.id, 1, 1, &[])
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].media.id, media[0]);
+
+    // media of denied libraries are left out before the limit is applied.
+    let library_id = result[0].media.library_id;
+    assert!(
+        progress::Progress::get_history(&mut tx, user.id, 1, 0, &[library_id])
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_user_stats() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let user = insert_user(&mut tx).await;
+    let (_, media) = crate::test_support::seed_library_with_media(&mut tx, 4).await;
+
+    assert_eq!(
+        progress::Progress::get_user_stats(&mut tx, user.id)
+            .await
+            .unwrap(),
+        progress::UserStats::default()
+    );
+
+    progress::Progress::upsert(&mut tx, user.id, media[0], 50, Some(100))
+        .await
+        .unwrap();
+    progress::Progress::upsert(&mut tx, user.id, media[1], 95, Some(100))
+        .await
+        .unwrap();
+    // without a known duration media cant be finished.
+    progress::Progress::set(&mut tx, 30, user.id, media[2])
+        .await
+        .unwrap();
+
+    let stats = progress::Progress::get_user_stats(&mut tx, user.id)
+        .await
+        .unwrap();
+    assert_eq!(stats.in_progress, 2);
+    assert_eq!(stats.completed, 1);
+    assert_eq!(stats.watch_seconds, 175);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_recent_activity() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let user = insert_user(&mut tx).await;
+    super::user_tests::insert_many(&mut tx, 1).await;
+    let other = crate::user::User::get(&mut tx, "test0").await.unwrap();
+    let (_, media) = crate::test_support::seed_library_with_media(&mut tx, 2).await;
+
+    assert!(progress::Progress::get_recent_activity(&mut tx, 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    progress::Progress::set(&mut tx, 10, user.id, media[0])
+        .await
+        .unwrap();
+    progress::Progress::set(&mut tx, 20, other.id, media[1])
+        .await
+        .unwrap();
+
+    let result = progress::Progress::get_recent_activity(&mut tx, 10)
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].username, "test0");
+    assert_eq!(result[0].media.id, media[1]);
+
+    let result = progress::Progress::get_recent_activity(&mut tx, 1)
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 1);
+
+    // users can opt out of showing up in the feed.
+    let mut prefs = serde_json::to_value(crate::user::UserSettings::default()).unwrap();
+    prefs["hide_activity"] = true.into();
+    let rows = crate::user::UpdateableUser {
+        prefs: Some(serde_json::from_value(prefs).unwrap()),
+    }
+    .update(&mut tx, other.id)
+    .await
+    .unwrap();
+    assert_eq!(rows, 1);
+
+    let result = progress::Progress::get_recent_activity(&mut tx, 10)
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].username, "test");
+}
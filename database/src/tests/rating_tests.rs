@@ -0,0 +1,98 @@
+use crate::get_conn_memory;
+use crate::media::Media;
+use crate::rating::Rating;
+use crate::rating::UserRating;
+use crate::write_tx;
+use crate::DatabaseError;
+
+use super::library_tests::create_test_library;
+use super::media_tests::insert_media;
+use super::user_tests::insert_user;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_get_ratings() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _library = create_test_library(&mut tx).await;
+    let media = insert_media(&mut tx).await;
+
+    assert!(Rating::get_all(&mut tx, media).await.unwrap().is_empty());
+
+    Rating::set(&mut tx, media, "TMDB", 74).await.unwrap();
+    Rating::set(&mut tx, media, "imdb", 80).await.unwrap();
+    // setting a score again replaces the old one.
+    Rating::set(&mut tx, media, "tmdb", 75).await.unwrap();
+
+    let ratings = Rating::get_all(&mut tx, media).await.unwrap();
+    let ratings = ratings
+        .iter()
+        .map(|x| (x.source.as_str(), x.score))
+        .collect::<Vec<_>>();
+    assert_eq!(ratings, vec![("imdb", 80), ("tmdb", 75)]);
+
+    assert!(matches!(
+        Rating::set(&mut tx, media, "imdb", 101).await,
+        Err(DatabaseError::InvalidRating(101))
+    ));
+
+    Media::delete(&mut tx, media).await.unwrap();
+    assert!(Rating::get_all(&mut tx, media).await.unwrap().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sync_primary() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _library = create_test_library(&mut tx).await;
+    let media = insert_media(&mut tx).await;
+
+    // without a score from the source the rating stays as is.
+    assert!(!Rating::sync_primary(&mut tx, media, "imdb").await.unwrap());
+    assert_eq!(Media::get(&mut tx, media).await.unwrap().rating, Some(10));
+
+    Rating::set(&mut tx, media, "imdb", 68).await.unwrap();
+    assert!(Rating::sync_primary(&mut tx, media, "IMDB").await.unwrap());
+    assert_eq!(Media::get(&mut tx, media).await.unwrap().rating, Some(6));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_user_rating() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _library = create_test_library(&mut tx).await;
+    let user = insert_user(&mut tx).await;
+    let media = insert_media(&mut tx).await;
+
+    assert!(matches!(
+        UserRating::get(&mut tx, user.id, media).await,
+        Err(DatabaseError::NotFound)
+    ));
+    assert_eq!(
+        UserRating::average_for_media(&mut tx, media).await.unwrap(),
+        None
+    );
+
+    UserRating::set(&mut tx, user.id, media, 60, None)
+        .await
+        .unwrap();
+    // rating again replaces the old rating.
+    UserRating::set(&mut tx, user.id, media, 90, Some("Great".into()))
+        .await
+        .unwrap();
+
+    let rating = UserRating::get(&mut tx, user.id, media).await.unwrap();
+    assert_eq!(rating.score, 90);
+    assert_eq!(rating.review.as_deref(), Some("Great"));
+    assert_eq!(
+        UserRating::average_for_media(&mut tx, media).await.unwrap(),
+        Some(90.0)
+    );
+
+    assert!(matches!(
+        UserRating::set(&mut tx, user.id, media, -1, None).await,
+        Err(DatabaseError::InvalidRating(-1))
+    ));
+
+    Media::delete(&mut tx, media).await.unwrap();
+    assert!(UserRating::get(&mut tx, user.id, media).await.is_err());
+}
@@ -0,0 +1,63 @@
+use crate::get_conn_memory;
+use crate::mediafile;
+use crate::subtitle::InsertableSubtitle;
+use crate::subtitle::Subtitle;
+use crate::write_tx;
+
+use super::library_tests::create_test_library;
+use super::media_tests::insert_media;
+use super::mediafile_tests::insert_mediafile_with_mediaid;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_for_media() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _library = create_test_library(&mut tx).await;
+    let media = insert_media(&mut tx).await;
+    let mediafile = insert_mediafile_with_mediaid(&mut tx, media).await;
+
+    assert!(Subtitle::get_for_media(&mut tx, media)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let sidecar = InsertableSubtitle {
+        mediafile_id: mediafile,
+        language: Some("English".into()),
+        codec: "subrip".into(),
+        external_path: Some("/movies/Test.en.srt".into()),
+        ..Default::default()
+    };
+    sidecar.insert(&mut tx).await.unwrap();
+
+    for (index, forced) in [(3, false), (2, true)].iter() {
+        InsertableSubtitle {
+            mediafile_id: mediafile,
+            codec: "ass".into(),
+            stream_index: Some(*index),
+            forced: *forced,
+            ..Default::default()
+        }
+        .insert(&mut tx)
+        .await
+        .unwrap();
+    }
+
+    let result = Subtitle::get_for_media(&mut tx, media).await.unwrap();
+    assert_eq!(
+        result.iter().map(|x| x.stream_index).collect::<Vec<_>>(),
+        vec![Some(2), Some(3), None]
+    );
+    assert!(result[0].forced);
+    assert_eq!(result[2].external_path, sidecar.external_path);
+    assert_eq!(result[2].language.as_deref(), Some("English"));
+
+    // tracks go away along with their mediafile.
+    mediafile::MediaFile::delete(&mut tx, mediafile)
+        .await
+        .unwrap();
+    assert!(Subtitle::get_for_media(&mut tx, media)
+        .await
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,69 @@
+use crate::get_conn_memory;
+use crate::media;
+use crate::tag::Tag;
+use crate::test_support::seed_library_with_media;
+use crate::write_tx;
+use crate::DatabaseError;
+
+use super::library_tests::create_test_library;
+use super::media_tests::insert_media;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_add_remove_tag() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let _library = create_test_library(&mut tx).await;
+    let media = insert_media(&mut tx).await;
+
+    assert!(Tag::add(&mut tx, media, "4K").await.unwrap());
+    // tags are case-normalized, so this is the same tag.
+    assert!(!Tag::add(&mut tx, media, " 4k ").await.unwrap());
+    assert!(Tag::add(&mut tx, media, "Rewatch").await.unwrap());
+
+    let tags = Tag::get_by_media(&mut tx, media).await.unwrap();
+    let names = tags.iter().map(|x| x.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["4k", "rewatch"]);
+
+    assert_eq!(Tag::remove(&mut tx, media, "REWATCH").await.unwrap(), 1);
+    assert_eq!(Tag::remove(&mut tx, media, "rewatch").await.unwrap(), 0);
+    assert_eq!(Tag::get_by_media(&mut tx, media).await.unwrap().len(), 1);
+
+    assert!(matches!(
+        Tag::add(&mut tx, media, "  ").await,
+        Err(DatabaseError::InvalidTag(_))
+    ));
+
+    Tag::set_for_media(&mut tx, media, &["director's cut", "hdr"])
+        .await
+        .unwrap();
+    let tags = Tag::get_by_media(&mut tx, media).await.unwrap();
+    let names = tags.iter().map(|x| x.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["director's cut", "hdr"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_by_tag() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let (library, media) = seed_library_with_media(&mut tx, 2).await;
+    let (_, other) = seed_library_with_media(&mut tx, 1).await;
+    let (first, second, elsewhere) = (media[0], media[1], other[0]);
+
+    Tag::add(&mut tx, second, "4k").await.unwrap();
+    Tag::add(&mut tx, first, "4k").await.unwrap();
+    Tag::add(&mut tx, elsewhere, "4k").await.unwrap();
+
+    let result = media::Media::get_by_tag(&mut tx, library, "4K")
+        .await
+        .unwrap();
+    let ids = result.iter().map(|x| x.id).collect::<Vec<_>>();
+    assert_eq!(ids, vec![first, second]);
+
+    // deleting media has to drop its tag links.
+    media::Media::delete(&mut tx, first).await.unwrap();
+    let result = media::Media::get_by_tag(&mut tx, library, "4k")
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(Tag::get_by_media(&mut tx, first).await.unwrap(), vec![]);
+}
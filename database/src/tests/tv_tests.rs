@@ -14,9 +14,14 @@ pub async fn insert_tv(conn: &mut crate::Transaction<'_>) -> i64 {
         description: None,
         rating: Some(10),
         year: Some(2020),
-        added: "Test".into(),
+        added: "2020-01-01".into(),
         poster: None,
         backdrop: None,
+        duration: None,
+        external_id: None,
+        tmdb_id: None,
+        imdb_id: None,
+        added_by: None,
         media_type: library::MediaType::Movie,
     };
 
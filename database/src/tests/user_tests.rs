@@ -1,12 +1,17 @@
 use auth::generate_key;
 use auth::set_key_fallible;
+use auth::set_storage_key_fallible;
 
+use crate::asset::Asset;
+use crate::asset::InsertableAsset;
 use crate::get_conn_memory;
 use crate::user;
 use crate::user::Login;
+use crate::user::Permission;
 use crate::user::Roles;
 use crate::user::User;
 use crate::write_tx;
+use crate::DatabaseError;
 
 pub async fn insert_user(conn: &mut crate::Transaction<'_>) -> User {
     let invite = Login::new_invite(&mut *conn).await.unwrap();
@@ -66,6 +71,43 @@ async fn test_get_all() {
     assert_eq!(result.len(), 10);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_page() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+
+    insert_many(&mut tx, 10).await;
+
+    let result = user::User::get_page(&mut tx, 4, 0).await.unwrap();
+    assert_eq!(result.len(), 4);
+    assert_eq!(result[0].username, "test0");
+    assert!(result[0].created.is_some());
+
+    let result = user::User::get_page(&mut tx, 4, 8).await.unwrap();
+    let names = result.into_iter().map(|x| x.username).collect::<Vec<_>>();
+    assert_eq!(names, vec!["test8", "test9"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_record_login() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let user = insert_user(&mut tx).await;
+
+    let result = user::User::get_page(&mut tx, 1, 0).await.unwrap();
+    assert_eq!(result[0].last_login, None);
+
+    let rows = user
+        .record_login(&mut tx, 123, Some("10.0.0.1"))
+        .await
+        .unwrap();
+    assert_eq!(rows, 1);
+
+    let result = user::User::get_page(&mut tx, 1, 0).await.unwrap();
+    assert_eq!(result[0].last_login, Some(123));
+    assert_eq!(result[0].last_login_ip.as_deref(), Some("10.0.0.1"));
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_delete() {
     let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
@@ -83,6 +125,241 @@ async fn test_delete() {
     assert!(result.is_err());
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_disabled() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let user = insert_user(&mut tx).await;
+    assert!(!user.is_disabled);
+
+    let rows = user::User::set_disabled(&mut tx, &user.username, true)
+        .await
+        .unwrap();
+    assert_eq!(rows, 1);
+
+    let result = user::User::get_by_id(&mut tx, user.id).await.unwrap();
+    assert!(result.is_disabled);
+
+    // disabled accounts are still listed.
+    let result = user::User::get_all(&mut tx).await.unwrap();
+    assert_eq!(result.len(), 1);
+
+    user::User::set_disabled(&mut tx, &user.username, false)
+        .await
+        .unwrap();
+    let result = user::User::get_by_id(&mut tx, user.id).await.unwrap();
+    assert!(!result.is_disabled);
+
+    let rows = user::User::set_disabled(&mut tx, "nonexistent", true)
+        .await
+        .unwrap();
+    assert_eq!(rows, 0);
+
+    // the last enabled owner cannot be disabled, not even by themselves.
+    user::User::set_roles(&mut tx, &user.username, Roles(vec!["owner".into()]))
+        .await
+        .unwrap();
+    let result = user::User::set_disabled(&mut tx, &user.username, true).await;
+    assert!(matches!(result, Err(crate::DatabaseError::LastOwner)));
+
+    insert_many(&mut tx, 1).await;
+    user::User::set_roles(&mut tx, "test0", Roles(vec!["owner".into()]))
+        .await
+        .unwrap();
+    let rows = user::User::set_disabled(&mut tx, &user.username, true)
+        .await
+        .unwrap();
+    assert_eq!(rows, 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_roles() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let user = insert_user(&mut tx).await;
+
+    let result = user::User::set_roles(&mut tx, &user.username, Roles(vec!["admin".into()])).await;
+    assert!(matches!(result, Err(crate::DatabaseError::InvalidRole(x)) if x == "admin"));
+
+    user::User::set_roles(&mut tx, &user.username, Roles(vec!["owner".into()]))
+        .await
+        .unwrap();
+    let result = user::User::get_by_id(&mut tx, user.id).await.unwrap();
+    assert!(result.has_role("owner"));
+
+    // the only owner cannot demote themselves.
+    let result = user::User::set_roles(&mut tx, &user.username, Roles(vec!["user".into()])).await;
+    assert!(matches!(result, Err(crate::DatabaseError::LastOwner)));
+
+    insert_many(&mut tx, 1).await;
+    user::User::set_roles(&mut tx, "test0", Roles(vec!["owner".into()]))
+        .await
+        .unwrap();
+
+    let rows = user::User::set_roles(&mut tx, &user.username, Roles(vec!["user".into()]))
+        .await
+        .unwrap();
+    assert_eq!(rows, 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_permissions() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let user = insert_user(&mut tx).await;
+    assert!(!user.can(user::Permission::ManageInvites));
+
+    user::User::set_roles(&mut tx, &user.username, Roles(vec!["owner".into()]))
+        .await
+        .unwrap();
+    let user = user::User::get_by_id(&mut tx, user.id).await.unwrap();
+    assert!(user.can(user::Permission::ManageInvites));
+    assert!(user.can(user::Permission::ManageUsers));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_totp_enroll() {
+    set_storage_key_fallible(generate_key());
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let user = insert_user(&mut tx).await;
+
+    assert_eq!(user.get_totp_secret(&mut tx).await.unwrap(), None);
+
+    let rows = user
+        .set_pending_totp_secret(&mut tx, "SECRET")
+        .await
+        .unwrap();
+    assert_eq!(rows, 1);
+
+    // pending secrets are not enforced.
+    assert_eq!(user.get_totp_secret(&mut tx).await.unwrap(), None);
+    assert_eq!(
+        user.get_pending_totp_secret(&mut tx).await.unwrap(),
+        Some("SECRET".into())
+    );
+
+    // secrets are never stored in plaintext.
+    let stored = sqlx::query_scalar::<_, String>("SELECT totp_secret FROM users WHERE id = ?")
+        .bind(user.id)
+        .fetch_one(&mut tx)
+        .await
+        .unwrap();
+    assert!(!stored.contains("SECRET"));
+
+    user.enable_totp(&mut tx, 10).await.unwrap();
+    assert_eq!(
+        user.get_totp_secret(&mut tx).await.unwrap(),
+        Some("SECRET".into())
+    );
+
+    // the code used to enable two-factor authentication and older codes cant be used again.
+    assert!(!user.use_totp_counter(&mut tx, 9).await.unwrap());
+    assert!(!user.use_totp_counter(&mut tx, 10).await.unwrap());
+    assert!(user.use_totp_counter(&mut tx, 11).await.unwrap());
+    assert!(!user.use_totp_counter(&mut tx, 11).await.unwrap());
+
+    // the secret cant be swapped out once enabled.
+    let rows = user
+        .set_pending_totp_secret(&mut tx, "OTHER")
+        .await
+        .unwrap();
+    assert_eq!(rows, 0);
+    assert_eq!(user.get_pending_totp_secret(&mut tx).await.unwrap(), None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_reset_token() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let user = insert_user(&mut tx).await;
+
+    let token = user.new_reset_token(&mut tx).await.unwrap();
+
+    let result = User::consume_reset_token(&mut tx, &token).await.unwrap();
+    assert_eq!(result.map(|u| u.id), Some(user.id));
+
+    // tokens are single-use.
+    let result = User::consume_reset_token(&mut tx, &token).await.unwrap();
+    assert!(result.is_none());
+
+    let result = User::consume_reset_token(&mut tx, "garbage").await.unwrap();
+    assert!(result.is_none());
+
+    assert!(user::check_password("short").is_err());
+    assert!(user::check_password("longenough").is_ok());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_avatar() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let user = insert_user(&mut tx).await;
+
+    let asset = InsertableAsset {
+        local_path: "avatar.png".into(),
+        file_ext: "png".into(),
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+
+    // assets which aren't used as an avatar are not returned.
+    assert!(Asset::get_avatar(&mut tx, asset.id)
+        .await
+        .unwrap()
+        .is_none());
+
+    User::set_picture(&mut tx, user.id, asset.id).await.unwrap();
+
+    let result = Asset::get_avatar(&mut tx, asset.id).await.unwrap().unwrap();
+    assert_eq!(result.local_path, "avatar.png");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_uploaded_bytes() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let user = insert_user(&mut tx).await;
+
+    assert_eq!(Asset::uploaded_bytes(&mut tx, user.id).await.unwrap(), 0);
+
+    let mut assets = Vec::new();
+    for (file, size) in [("first.png", 100), ("second.png", 50)] {
+        let asset = InsertableAsset {
+            local_path: file.into(),
+            file_ext: "png".into(),
+            ..Default::default()
+        }
+        .insert(&mut tx)
+        .await
+        .unwrap();
+
+        Asset::record_upload(&mut tx, asset.id, user.id, size)
+            .await
+            .unwrap();
+        assets.push(asset);
+    }
+
+    assert_eq!(Asset::uploaded_bytes(&mut tx, user.id).await.unwrap(), 150);
+
+    User::set_picture(&mut tx, user.id, assets[1].id)
+        .await
+        .unwrap();
+    assert_eq!(Asset::delete(&mut tx, assets[0].id).await.unwrap(), 1);
+    assert_eq!(Asset::uploaded_bytes(&mut tx, user.id).await.unwrap(), 50);
+    assert!(Asset::get_by_id(&mut tx, assets[0].id).await.is_err());
+
+    Asset::forget_upload(&mut tx, assets[1].id).await.unwrap();
+    assert_eq!(Asset::uploaded_bytes(&mut tx, user.id).await.unwrap(), 0);
+    // forgetting an upload keeps the asset around.
+    assert!(Asset::get_avatar(&mut tx, assets[1].id)
+        .await
+        .unwrap()
+        .is_some());
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_invites() {
     let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
@@ -135,6 +412,115 @@ async fn test_invites() {
     assert_eq!(result, 0);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_check_invite() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+
+    let result = user::Login::default().check_invite(&mut tx, None).await;
+    assert!(matches!(result, Err(DatabaseError::InviteRequired)));
+
+    let result = user::Login {
+        invite_token: Some("TESTTESTTEST".into()),
+        ..Default::default()
+    }
+    .check_invite(&mut tx, None)
+    .await;
+    assert!(matches!(result, Err(DatabaseError::InviteInvalid)));
+
+    let invite = user::Login::new_invite(&mut tx).await.unwrap();
+    let login = user::Login {
+        invite_token: Some(invite.clone()),
+        ..Default::default()
+    };
+    login.check_invite(&mut tx, None).await.unwrap();
+    login.check_invite(&mut tx, Some(60)).await.unwrap();
+
+    sqlx::query!(
+        "UPDATE invites SET date_added = date_added - 120 WHERE id = ?",
+        invite
+    )
+    .execute(&mut tx)
+    .await
+    .unwrap();
+    let result = login.check_invite(&mut tx, Some(60)).await;
+    assert!(matches!(result, Err(DatabaseError::InviteExpired)));
+
+    // claimed invites cant be redeemed again.
+    user::Login::claim_invite(&mut tx, &invite).await.unwrap();
+    let result = login.check_invite(&mut tx, None).await;
+    assert!(matches!(result, Err(DatabaseError::InviteAlreadyClaimed)));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_claim_invite() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+
+    let result = user::Login::claim_invite(&mut tx, "TESTTESTTEST").await;
+    assert!(matches!(result, Err(DatabaseError::InviteInvalid)));
+
+    let invite = user::Login::new_invite(&mut tx).await.unwrap();
+    user::Login::claim_invite(&mut tx, &invite).await.unwrap();
+
+    // the second registration redeeming the same invite loses.
+    let result = user::Login::claim_invite(&mut tx, &invite).await;
+    assert!(matches!(result, Err(DatabaseError::InviteAlreadyClaimed)));
+}
+
+#[test]
+fn test_password_hashing() {
+    let hashed = user::hash_password("password");
+    assert!(!user::needs_rehash(&hashed));
+    assert!(user::verify(
+        "test".into(),
+        hashed.clone(),
+        "password".into()
+    ));
+    assert!(!user::verify("test".into(), hashed, "wrong".into()));
+
+    // legacy hashes are salted with the username and still have to verify.
+    let legacy = user::hash("test".into(), "password".into());
+    assert!(user::needs_rehash(&legacy));
+    assert!(user::verify(
+        "test".into(),
+        legacy.clone(),
+        "password".into()
+    ));
+    assert!(!user::verify("other".into(), legacy, "password".into()));
+}
+
+#[test]
+fn test_hash_rounds() {
+    // hashes carry their parameters, so they verify no matter what new passwords are hashed with.
+    let hashed = user::hash_password_with("password", 3);
+    assert!(hashed.starts_with("$argon2id$v=19$m=19456,t=3,p=1$"));
+    assert!(user::verify(
+        "test".into(),
+        hashed.clone(),
+        "password".into()
+    ));
+    assert!(!user::verify("test".into(), hashed, "wrong".into()));
+
+    // pbkdf2 hashes still verify, but get upgraded.
+    let pbkdf2 =
+        "$pbkdf2-sha256$i=10000$MDEyMzQ1Njc4OWFiY2RlZg==$0YdT6cDcX5qiDagy0ie+FrM2RN8V8MC0buKrDrj+aAU=";
+    assert!(user::needs_rehash(pbkdf2));
+    assert!(user::verify(
+        "test".into(),
+        pbkdf2.into(),
+        "password".into()
+    ));
+    assert!(!user::verify("test".into(), pbkdf2.into(), "wrong".into()));
+
+    for rounds in [0, 11, 100_000].iter() {
+        assert!(matches!(
+            user::set_hash_rounds(*rounds),
+            Err(DatabaseError::InvalidHashRounds { .. })
+        ));
+    }
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_cookie_encoding() {
     let _ = set_key_fallible(generate_key());
@@ -159,3 +545,142 @@ async fn test_invalid_cookie() {
     let res = Login::verify_cookie(String::from("bXl1c2VyaWQ="));
     assert!(res.is_err());
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_error_kinds() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+
+    let result = user::User::get(&mut tx, "test").await;
+    assert!(matches!(result, Err(DatabaseError::NotFound)));
+
+    let _ = insert_user(&mut tx).await;
+    let invite = Login::new_invite(&mut tx).await.unwrap();
+    let result = user::InsertableUser {
+        username: "test".into(),
+        password: "test".into(),
+        roles: Roles(vec!["User".into()]),
+        prefs: Default::default(),
+        claimed_invite: invite,
+    }
+    .insert(&mut tx)
+    .await;
+    assert!(matches!(result, Err(DatabaseError::UniqueViolation(_))));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_any_exist() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+
+    assert!(!user::User::any_exist(&mut tx).await.unwrap());
+    let _ = insert_user(&mut tx).await;
+    assert!(user::User::any_exist(&mut tx).await.unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_require_permission() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+
+    let mut users = Vec::new();
+    for (username, role) in [("owner", "owner"), ("user", "user")] {
+        let invite = Login::new_invite(&mut tx).await.unwrap();
+        let user = user::InsertableUser {
+            username: username.into(),
+            password: "test".into(),
+            roles: Roles(vec![role.into()]),
+            prefs: Default::default(),
+            claimed_invite: invite,
+        };
+
+        users.push(user.insert(&mut tx).await.unwrap());
+    }
+
+    let (owner, user) = (&users[0], &users[1]);
+
+    assert!(owner.require(Permission::ManageInvites).is_ok());
+    assert!(owner
+        .require_any(&[Permission::ManageInvites, Permission::ManageUsers])
+        .is_ok());
+
+    assert!(matches!(
+        user.require(Permission::ManageInvites),
+        Err(DatabaseError::PermissionDenied)
+    ));
+    assert!(user
+        .require_any(&[Permission::ManageInvites, Permission::ManageUsers])
+        .is_err());
+    assert!(owner.require_any(&[]).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_delete_account() {
+    let mut conn = get_conn_memory().await.unwrap().writer().lock_owned().await;
+    let mut tx = write_tx(&mut conn).await.unwrap();
+    let (_, media) = crate::test_support::seed_library_with_media(&mut tx, 2).await;
+
+    let mut users = Vec::new();
+    for (username, role) in [("owner", "owner"), ("user", "user")] {
+        let invite = Login::new_invite(&mut tx).await.unwrap();
+        let user = user::InsertableUser {
+            username: username.into(),
+            password: "test".into(),
+            roles: Roles(vec![role.into()]),
+            prefs: Default::default(),
+            claimed_invite: invite,
+        };
+
+        users.push(user.insert(&mut tx).await.unwrap());
+    }
+
+    let user = &users[1];
+    for id in &media {
+        crate::progress::Progress::set(&mut tx, 10, user.id, *id)
+            .await
+            .unwrap();
+    }
+    crate::favorite::Favorite::add(&mut tx, user.id, media[0])
+        .await
+        .unwrap();
+
+    let avatar = InsertableAsset {
+        local_path: "deleted_avatar.png".into(),
+        file_ext: "png".into(),
+        ..Default::default()
+    }
+    .insert(&mut tx)
+    .await
+    .unwrap();
+    Asset::record_upload(&mut tx, avatar.id, user.id, 10)
+        .await
+        .unwrap();
+    User::set_picture(&mut tx, user.id, avatar.id)
+        .await
+        .unwrap();
+
+    let result = User::delete_account(&mut tx, "user").await.unwrap();
+    assert_eq!(
+        result,
+        user::AccountPurgeSummary {
+            progress: 2,
+            favorites: 1,
+            assets: 1,
+            ..Default::default()
+        }
+    );
+
+    assert!(User::get(&mut tx, "user").await.is_err());
+    assert!(Asset::get_by_id(&mut tx, avatar.id).await.is_err());
+    assert!(matches!(
+        User::delete_account(&mut tx, "user").await,
+        Err(DatabaseError::NotFound)
+    ));
+
+    // the last owner cant be deleted.
+    assert!(matches!(
+        User::delete_account(&mut tx, "owner").await,
+        Err(DatabaseError::LastOwner)
+    ));
+    assert!(User::get(&mut tx, "owner").await.is_ok());
+}
@@ -23,7 +23,7 @@ impl TVShow {
             r#"SELECT 
                 media.id, media.library_id, media.name, media.description,
                 media.rating, media.year, media.added, media.poster_path, 
-                media.backdrop_path, media.media_type as "media_type: _" 
+                media.backdrop_path, media.duration, media.tmdb_id as "tmdb_id: i32", media.imdb_id, media.added_by, media.media_type as "media_type: _" 
                 FROM media INNER JOIN tv_show ON media.id = tv_show.id"#
         )
         .fetch_all(&mut *conn)
@@ -39,7 +39,7 @@ impl TVShow {
             r#"SELECT 
                 media.id, media.library_id, media.name, media.description,
                 media.rating, media.year, media.added, media.poster_path, 
-                media.backdrop_path, media.media_type as "media_type: _"
+                media.backdrop_path, media.duration, media.tmdb_id as "tmdb_id: i32", media.imdb_id, media.added_by, media.media_type as "media_type: _"
                 FROM media 
                 INNER JOIN tv_show ON tv_show.id = media.id
                 WHERE tv_show.id = ?"#,
@@ -1,16 +1,30 @@
+use crate::utils::unix_now;
 use crate::DatabaseError;
 use std::collections::HashMap;
 use std::num::NonZeroU32;
-use std::time::SystemTime;
+use std::ops::RangeInclusive;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering;
 
+use argon2::password_hash::PasswordHash;
+use argon2::password_hash::PasswordHasher;
+use argon2::password_hash::PasswordVerifier;
+use argon2::password_hash::SaltString;
+use argon2::Algorithm;
+use argon2::Argon2;
+use argon2::Params;
+use argon2::Version;
 use auth::user_cookie_decode;
 use auth::user_cookie_generate;
 use auth::AuthError;
+use once_cell::sync::Lazy;
 use serde::Deserialize;
 use serde::Serialize;
 
 use ring::digest;
 use ring::pbkdf2;
+use ring::rand::SecureRandom;
+use ring::rand::SystemRandom;
 use sqlx::Decode;
 use sqlx::Encode;
 
@@ -18,6 +32,29 @@ static PBKDF2_ALG: pbkdf2::Algorithm = pbkdf2::PBKDF2_HMAC_SHA256;
 const CREDENTIAL_LEN: usize = digest::SHA256_OUTPUT_LEN;
 const HASH_ROUNDS: NonZeroU32 = unsafe { NonZeroU32::new_unchecked(1_000) };
 
+/// Prefix of password hashes produced by [`hash_password`].
+const HASH_PREFIX: &str = "$argon2id$";
+/// Prefix of pbkdf2 password hashes, which are still verified but upgraded on the next login.
+const PBKDF2_PREFIX: &str = "$pbkdf2-sha256$";
+/// Memory in KiB argon2 uses per hash.
+const HASH_MEMORY: u32 = 19 * 1024;
+/// Iterations used for new password hashes unless configured otherwise with
+/// [`set_hash_rounds`]. Tests use fewer to keep them fast.
+#[cfg(not(test))]
+pub const DEFAULT_HASH_ROUNDS: u32 = 2;
+#[cfg(test)]
+pub const DEFAULT_HASH_ROUNDS: u32 = 1;
+/// Range of iterations accepted by [`set_hash_rounds`]. Passwords are hashed on the async runtime
+/// while handling requests, so the upper bound keeps a single hash well below a second.
+pub const HASH_ROUNDS_RANGE: RangeInclusive<u32> = 1..=10;
+static HASH_ROUNDS_V2: AtomicU32 = AtomicU32::new(DEFAULT_HASH_ROUNDS);
+const SALT_LEN: usize = 16;
+
+/// Minimum length of a password accepted by [`check_password`].
+pub const MIN_PASSWORD_LEN: usize = 8;
+/// How long a password reset token stays valid after it has been minted, in seconds.
+pub const RESET_TOKEN_TTL: i64 = 24 * 60 * 60;
+
 pub type Credential = [u8; CREDENTIAL_LEN];
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
@@ -76,6 +113,9 @@ pub struct UserSettings {
     show_hovercards: bool,
     /// Whether to auto play next video
     enable_autoplay: bool,
+    /// Whether what the user watches is left out of the activity feed owners can see.
+    #[serde(default)]
+    hide_activity: bool,
 }
 
 impl<DB: sqlx::Database> sqlx::Type<DB> for UserSettings
@@ -126,6 +166,7 @@ impl Default for UserSettings {
             show_hovercards: true,
             default_video_quality: DefaultVideoQuality::DirectPlay,
             enable_autoplay: true,
+            hide_activity: false,
         }
     }
 }
@@ -137,6 +178,55 @@ pub enum Role {
     User,
 }
 
+impl Role {
+    pub const ALL: [Role; 2] = [Role::Owner, Role::User];
+
+    /// Returns the name under which this role is stored for a user.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Owner => "owner",
+            Self::User => "user",
+        }
+    }
+
+    /// Returns the role stored under `name` if its a known role.
+    pub fn from_name(name: &str) -> Option<Self> {
+        IntoIterator::into_iter(Self::ALL).find(|x| x.as_str() == name)
+    }
+
+    /// Returns whether this role grants `permission`.
+    pub fn can(&self, permission: Permission) -> bool {
+        match self {
+            Self::Owner => true,
+            Self::User => match permission {
+                Permission::ManageInvites
+                | Permission::ManageUsers
+                | Permission::ManageLibraries
+                | Permission::ManageSettings
+                | Permission::ViewHostInfo
+                | Permission::AccessAllLibraries => false,
+            },
+        }
+    }
+}
+
+/// Privileged actions which can be granted to a user through their roles.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Permission {
+    /// Create, list and delete invite tokens.
+    ManageInvites,
+    /// List accounts and change their roles or disabled state.
+    ManageUsers,
+    /// Inspect and manage libraries beyond browsing them, ie scan issues.
+    ManageLibraries,
+    /// Change the global settings of the server.
+    ManageSettings,
+    /// Inspect the state of the host and the database.
+    ViewHostInfo,
+    /// Browse every library, including restricted libraries the user hasnt been granted.
+    AccessAllLibraries,
+}
+
 #[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize, sqlx::Type)]
 #[sqlx(transparent)]
 pub struct UserID(pub(crate) i64);
@@ -145,6 +235,20 @@ pub struct UserID(pub(crate) i64);
 #[serde(transparent)]
 pub struct Roles(pub Vec<String>);
 
+impl Roles {
+    /// Makes sure that all roles are known, returning the first unknown role otherwise.
+    pub fn validate(&self) -> Result<(), DatabaseError> {
+        match self
+            .0
+            .iter()
+            .find(|x| !Role::ALL.iter().any(|role| role.as_str() == x.as_str()))
+        {
+            Some(x) => Err(DatabaseError::InvalidRole(x.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
 impl<DB: sqlx::Database> sqlx::Type<DB> for Roles
 where
     String: sqlx::Type<DB>,
@@ -186,9 +290,49 @@ pub struct User {
     pub roles: Roles,
     pub prefs: UserSettings,
     pub picture: Option<i64>,
+    /// Disabled accounts show up for administration but can no longer authenticate.
+    pub is_disabled: bool,
 }
 
 impl User {
+    /// Method returns a page of accounts ordered by when they have been created. The summaries
+    /// only contain the information needed to manage accounts and never any credentials.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `limit` - max number of accounts to return.
+    /// * `offset` - number of accounts to skip.
+    pub async fn get_page(
+        conn: &mut crate::Transaction<'_>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<UserSummary>, DatabaseError> {
+        Ok(sqlx::query_as!(
+            UserSummary,
+            r#"SELECT id as "id: UserID", username, roles as "roles: Roles", is_disabled as "is_disabled: bool", created, last_login, last_login_ip
+            FROM users
+            ORDER BY id ASC
+            LIMIT ? OFFSET ?"#,
+            limit,
+            offset
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+
+    /// Method returns whether at least one account exists. A server without any accounts still
+    /// needs its owner to be registered.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    pub async fn any_exist(conn: &mut crate::Transaction<'_>) -> Result<bool, DatabaseError> {
+        Ok(
+            sqlx::query_scalar!(r#"SELECT EXISTS(SELECT 1 FROM users) as "exists!: bool""#)
+                .fetch_one(&mut *conn)
+                .await?,
+        )
+    }
+
     /// Method gets all entries from the table users.
     ///
     /// # Arguments
@@ -197,7 +341,7 @@ impl User {
     pub async fn get_all(conn: &mut crate::Transaction<'_>) -> Result<Vec<Self>, DatabaseError> {
         Ok(
             sqlx::query!(
-                r#"SELECT id as "id: UserID", username, roles as "roles: Roles", prefs as "prefs: UserSettings", picture FROM users"#
+                r#"SELECT id as "id: UserID", username, roles as "roles: Roles", prefs as "prefs: UserSettings", picture, is_disabled as "is_disabled: bool" FROM users"#
             )
             .fetch_all(&mut *conn)
             .await?
@@ -208,6 +352,7 @@ impl User {
                 roles: user.roles,
                 prefs: user.prefs,
                 picture: user.picture,
+                is_disabled: user.is_disabled,
             })
             .collect(),
         )
@@ -218,7 +363,7 @@ impl User {
         uid: UserID,
     ) -> Result<Self, DatabaseError> {
         Ok(sqlx::query!(
-            r#"SELECT id as "id: UserID", username, roles as "roles: Roles", prefs as "prefs: UserSettings", picture from users
+            r#"SELECT id as "id: UserID", username, roles as "roles: Roles", prefs as "prefs: UserSettings", picture, is_disabled as "is_disabled: bool" from users
                 WHERE id = ?"#,
             uid
         )
@@ -230,6 +375,7 @@ impl User {
             roles: u.roles,
             prefs: u.prefs,
             picture: u.picture,
+            is_disabled: u.is_disabled,
         })?)
     }
 
@@ -238,7 +384,7 @@ impl User {
         username: &str,
     ) -> Result<Self, DatabaseError> {
         Ok(sqlx::query!(
-            r#"SELECT id as "id: UserID", username, roles as "roles: Roles", prefs as "prefs: UserSettings", picture from users
+            r#"SELECT id as "id: UserID", username, roles as "roles: Roles", prefs as "prefs: UserSettings", picture, is_disabled as "is_disabled: bool" from users
                 WHERE username = ?"#,
             username
         )
@@ -250,28 +396,40 @@ impl User {
             roles: u.roles,
             prefs: u.prefs,
             picture: u.picture,
+            is_disabled: u.is_disabled,
         })?)
     }
 
     /// Method gets one entry from the table users based on the username supplied and password.
+    /// If the username doesnt exist, the password is still checked against a dummy hash so that
+    /// this takes roughly the same time either way.
     ///
     /// # Arguments
     /// * `&` - postgres &ection
     /// * `uname` - username we wish to target and delete
-    /// * `pw_hash` - hash of the password for the user we are trying to access
+    /// * `pw` - password of the user we are trying to access
     pub async fn authenticate(
         conn: &mut crate::Transaction<'_>,
         uname: String,
         pw: String,
     ) -> Result<Self, DatabaseError> {
-        let hash = hash(uname.clone(), pw);
-        let user = sqlx::query!(
-            r#"SELECT id as "id: UserID", username, roles as "roles: Roles", prefs as "prefs: UserSettings", picture FROM users WHERE username = ? AND password = ?"#,
+        let user = match sqlx::query!(
+            r#"SELECT id as "id: UserID", username, password, roles as "roles: Roles", prefs as "prefs: UserSettings", picture, is_disabled as "is_disabled: bool" FROM users WHERE username = ?"#,
             uname,
-            hash,
         )
-        .fetch_one(&mut *conn)
-        .await?;
+        .fetch_optional(&mut *conn)
+        .await?
+        {
+            Some(user) => user,
+            None => {
+                verify_dummy(pw);
+                return Err(sqlx::Error::RowNotFound.into());
+            }
+        };
+
+        if !verify(user.username.clone(), user.password, pw) {
+            return Err(sqlx::Error::RowNotFound.into());
+        }
 
         Ok(Self {
             id: user.id,
@@ -279,6 +437,7 @@ impl User {
             roles: user.roles,
             prefs: user.prefs,
             picture: user.picture,
+            is_disabled: user.is_disabled,
         })
     }
 
@@ -314,6 +473,90 @@ impl User {
             .rows_affected() as usize)
     }
 
+    /// Method deletes the account with the supplied username along with its progress, favorites,
+    /// ratings, library access entries, password resets and avatar. Deleting the last enabled
+    /// owner is refused with [`DatabaseError::LastOwner`].
+    ///
+    /// The avatar file on disk has to be removed by the caller. This method should be called from
+    /// within a single write transaction so that the account is never left half-deleted.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `username` - username of the account we want to delete.
+    pub async fn delete_account(
+        conn: &mut crate::Transaction<'_>,
+        username: &str,
+    ) -> Result<AccountPurgeSummary, DatabaseError> {
+        let user = Self::get(&mut *conn, username).await?;
+
+        if user.has_role(Role::Owner.as_str()) && Self::is_last_owner(&mut *conn, username).await? {
+            return Err(DatabaseError::LastOwner);
+        }
+
+        let progress = sqlx::query!("DELETE FROM progress WHERE user_id = ?", user.id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected() as usize;
+
+        let favorites = sqlx::query!("DELETE FROM favorites WHERE user_id = ?", user.id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected() as usize;
+
+        let ratings = sqlx::query!("DELETE FROM user_ratings WHERE user_id = ?", user.id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected() as usize;
+
+        let library_access = sqlx::query!("DELETE FROM library_access WHERE user_id = ?", user.id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected() as usize;
+
+        sqlx::query!("DELETE FROM password_resets WHERE user_id = ?", user.id)
+            .execute(&mut *conn)
+            .await?;
+
+        // assets are referenced by the account, so they can only go once the account is gone.
+        let assets = sqlx::query_scalar!(
+            r#"SELECT asset_id as "asset_id!" FROM uploads WHERE user_id = ?1
+            UNION SELECT picture as "picture!" FROM users WHERE id = ?1 AND picture IS NOT NULL"#,
+            user.id
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Self::delete(&mut *conn, user.id).await?;
+
+        let mut deleted_assets = 0;
+        for asset in assets {
+            deleted_assets += crate::asset::Asset::delete(&mut *conn, asset).await?;
+        }
+
+        Ok(AccountPurgeSummary {
+            progress,
+            favorites,
+            ratings,
+            library_access,
+            assets: deleted_assets,
+        })
+    }
+
+    /// Returns whether the account with the supplied username is the only enabled owner left.
+    async fn is_last_owner(
+        conn: &mut crate::Transaction<'_>,
+        username: &str,
+    ) -> Result<bool, DatabaseError> {
+        let owner = Role::Owner.as_str();
+        let owners = Self::get_all(&mut *conn)
+            .await?
+            .into_iter()
+            .filter(|x| x.has_role(owner) && !x.is_disabled)
+            .collect::<Vec<_>>();
+
+        Ok(owners.len() == 1 && owners[0].username == username)
+    }
+
     /// Method resets the password for a user to a new password.
     ///
     /// # Arguments
@@ -324,7 +567,7 @@ impl User {
         conn: &mut crate::Transaction<'_>,
         password: String,
     ) -> Result<usize, DatabaseError> {
-        let hash = hash(self.username.clone(), password);
+        let hash = hash_password(&password);
 
         Ok(sqlx::query!(
             "UPDATE users SET password = $1 WHERE username = ?2",
@@ -366,6 +609,273 @@ impl User {
         .rows_affected() as usize)
     }
 
+    /// Method returns the TOTP secret of this user if two-factor authentication has been enabled.
+    /// Secrets are stored encrypted with the storage key, see [`auth::encrypt_secret`].
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    pub async fn get_totp_secret(
+        &self,
+        conn: &mut crate::Transaction<'_>,
+    ) -> Result<Option<String>, DatabaseError> {
+        sqlx::query_scalar!(
+            "SELECT totp_secret FROM users WHERE id = ? AND totp_enabled",
+            self.id
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .flatten()
+        .map(|x| self.decrypt_totp_secret(&x))
+        .transpose()
+    }
+
+    /// Method returns the TOTP secret of this user that has been stored by
+    /// [`set_pending_totp_secret`](Self::set_pending_totp_secret) but not yet enabled.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    pub async fn get_pending_totp_secret(
+        &self,
+        conn: &mut crate::Transaction<'_>,
+    ) -> Result<Option<String>, DatabaseError> {
+        sqlx::query_scalar!(
+            "SELECT totp_secret FROM users WHERE id = ? AND NOT totp_enabled",
+            self.id
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .flatten()
+        .map(|x| self.decrypt_totp_secret(&x))
+        .transpose()
+    }
+
+    /// Method stores a new TOTP secret for this user. The secret is not enforced until
+    /// [`enable_totp`](Self::enable_totp) is called. Enrolling again while two-factor
+    /// authentication is enabled is refused, and 0 is returned.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `secret` - base32 encoded TOTP secret.
+    pub async fn set_pending_totp_secret(
+        &self,
+        conn: &mut crate::Transaction<'_>,
+        secret: &str,
+    ) -> Result<usize, DatabaseError> {
+        let secret = auth::encrypt_secret(secret, &self.id.0.to_be_bytes());
+
+        Ok(sqlx::query!(
+            "UPDATE users SET totp_secret = $1 WHERE id = ?2 AND NOT totp_enabled",
+            secret,
+            self.id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
+    /// Method enables two-factor authentication with the pending TOTP secret. `counter` is the
+    /// counter of the code the secret has been confirmed with, it counts as used.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `counter` - counter of the code returned by [`auth::totp::verify`].
+    pub async fn enable_totp(
+        &self,
+        conn: &mut crate::Transaction<'_>,
+        counter: u64,
+    ) -> Result<usize, DatabaseError> {
+        let counter = counter as i64;
+
+        Ok(sqlx::query!(
+            "UPDATE users SET totp_enabled = 1, totp_last_counter = ?
+            WHERE id = ? AND totp_secret IS NOT NULL",
+            counter,
+            self.id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
+    /// Method marks the TOTP code with the counter `counter` as used. Returns `false` if a code
+    /// with the same or a later counter has already been used, in which case the code must be
+    /// rejected as it is being replayed.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `counter` - counter of the code returned by [`auth::totp::verify`].
+    pub async fn use_totp_counter(
+        &self,
+        conn: &mut crate::Transaction<'_>,
+        counter: u64,
+    ) -> Result<bool, DatabaseError> {
+        let counter = counter as i64;
+
+        Ok(sqlx::query!(
+            "UPDATE users SET totp_last_counter = $1
+            WHERE id = ?2 AND (totp_last_counter IS NULL OR totp_last_counter < $1)",
+            counter,
+            self.id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected()
+            > 0)
+    }
+
+    fn decrypt_totp_secret(&self, secret: &str) -> Result<String, DatabaseError> {
+        auth::decrypt_secret(secret, &self.id.0.to_be_bytes())
+            .map_err(DatabaseError::SecretUnreadable)
+    }
+
+    /// Method records a successful login of this user at `ts`, optionally with the address the
+    /// request came from.
+    pub async fn record_login(
+        &self,
+        conn: &mut crate::Transaction<'_>,
+        ts: i64,
+        ip: Option<&str>,
+    ) -> Result<usize, DatabaseError> {
+        Ok(sqlx::query!(
+            "UPDATE users SET last_login = ?, last_login_ip = ? WHERE id = ?",
+            ts,
+            ip,
+            self.id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
+    /// Mint a single-use password reset token for this user. The token expires after
+    /// [`RESET_TOKEN_TTL`] seconds.
+    pub async fn new_reset_token(
+        &self,
+        conn: &mut crate::Transaction<'_>,
+    ) -> Result<String, DatabaseError> {
+        let ts = unix_now();
+        let expires = ts + RESET_TOKEN_TTL;
+        let token = uuid::Uuid::new_v4().to_hyphenated().to_string();
+
+        sqlx::query!(
+            "INSERT INTO password_resets (id, user_id, date_added, expires) VALUES ($1, $2, $3, $4)",
+            token,
+            self.id,
+            ts,
+            expires
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Mark a password reset token as consumed and return the user it belongs to. Returns `None`
+    /// if the token doesn't exist, has expired or has already been used.
+    pub async fn consume_reset_token(
+        conn: &mut crate::Transaction<'_>,
+        token: &str,
+    ) -> Result<Option<Self>, DatabaseError> {
+        let now = unix_now();
+
+        let user_id = sqlx::query!(
+            r#"UPDATE password_resets SET consumed = 1
+                WHERE id = ? AND consumed = 0 AND expires > ?
+                RETURNING user_id as "user_id: UserID""#,
+            token,
+            now
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .map(|x| x.user_id);
+
+        match user_id {
+            Some(id) => Ok(Some(Self::get_by_id(conn, id).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Method disables or re-enables the account with the supplied username and returns the
+    /// number of rows affected. Disabling the last enabled owner is refused as it would lock
+    /// everyone out of administration.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `username` - username of the account.
+    /// * `disabled` - whether the account should be disabled.
+    pub async fn set_disabled(
+        conn: &mut crate::Transaction<'_>,
+        username: &str,
+        disabled: bool,
+    ) -> Result<usize, DatabaseError> {
+        if disabled && Self::is_last_owner(&mut *conn, username).await? {
+            return Err(DatabaseError::LastOwner);
+        }
+
+        Ok(sqlx::query!(
+            "UPDATE users SET is_disabled = $1 WHERE users.username = ?2",
+            disabled,
+            username
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
+    /// Method replaces the roles of the account with the supplied username and returns the number
+    /// of rows affected. Removing the `owner` role from the last enabled owner is refused as it
+    /// would lock everyone out of administration.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `username` - username of the account.
+    /// * `roles` - new roles of the account.
+    pub async fn set_roles(
+        conn: &mut crate::Transaction<'_>,
+        username: &str,
+        roles: Roles,
+    ) -> Result<usize, DatabaseError> {
+        roles.validate()?;
+
+        let owner = Role::Owner.as_str();
+        if !roles.0.iter().any(|x| x == owner) && Self::is_last_owner(&mut *conn, username).await? {
+            return Err(DatabaseError::LastOwner);
+        }
+
+        Ok(sqlx::query!(
+            "UPDATE users SET roles = $1 WHERE users.username = ?2",
+            roles,
+            username
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as usize)
+    }
+
+    /// Returns whether any of the roles of this user grants `permission`.
+    pub fn can(&self, permission: Permission) -> bool {
+        self.roles
+            .0
+            .iter()
+            .filter_map(|x| Role::from_name(x))
+            .any(|x| x.can(permission))
+    }
+
+    /// Returns [`DatabaseError::PermissionDenied`] unless any of the roles of this user grants
+    /// `permission`. Handlers should prefer this over checking [`User::can`] by hand.
+    pub fn require(&self, permission: Permission) -> Result<(), DatabaseError> {
+        self.require_any(&[permission])
+    }
+
+    /// Same as [`User::require`], except that any one of `permissions` is enough.
+    pub fn require_any(&self, permissions: &[Permission]) -> Result<(), DatabaseError> {
+        if permissions.iter().any(|x| self.can(*x)) {
+            return Ok(());
+        }
+
+        Err(DatabaseError::PermissionDenied)
+    }
+
     pub fn has_role(&self, role: &str) -> bool {
         self.roles.0.contains(&role.to_string())
     }
@@ -375,6 +885,32 @@ impl User {
     }
 }
 
+/// Struct summarizes how many rows belonging to an account were removed by
+/// [`User::delete_account`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct AccountPurgeSummary {
+    pub progress: usize,
+    pub favorites: usize,
+    pub ratings: usize,
+    pub library_access: usize,
+    pub assets: usize,
+}
+
+/// Public information about an account, used to list accounts for administration.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct UserSummary {
+    pub id: UserID,
+    pub username: String,
+    pub roles: Roles,
+    pub is_disabled: bool,
+    /// Unix timestamp of when the account has been created.
+    pub created: Option<i64>,
+    /// Unix timestamp of the last successful login.
+    pub last_login: Option<i64>,
+    /// Address the last successful login came from, if it was known.
+    pub last_login_ip: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct InsertableUser {
     pub username: String,
@@ -400,16 +936,18 @@ impl InsertableUser {
             claimed_invite,
         } = self;
 
-        let password = hash(username.clone(), password);
+        let password = hash_password(&password);
+        let created = unix_now();
 
         let user = sqlx::query_as!(
             User,
-            r#"INSERT INTO users (username, password, prefs, claimed_invite, roles) VALUES ($1, $2, $3, $4, $5) returning id as "id: UserID",username,roles as "roles: Roles",prefs as "prefs: UserSettings",picture"#,
+            r#"INSERT INTO users (username, password, prefs, claimed_invite, roles, created) VALUES ($1, $2, $3, $4, $5, $6) returning id as "id: UserID",username,roles as "roles: Roles",prefs as "prefs: UserSettings",picture,is_disabled as "is_disabled: bool""#,
             username,
             password,
             prefs,
             claimed_invite,
-            roles
+            roles,
+            created
         ).fetch_one(&mut *conn)
         .await?;
         Ok(user)
@@ -429,7 +967,7 @@ impl UpdateableUser {
     ) -> Result<usize, DatabaseError> {
         if let Some(prefs) = &self.prefs {
             return Ok(sqlx::query!(
-                "UPDATE users SET prefs = $1 WHERE users.id = ?",
+                "UPDATE users SET prefs = $1 WHERE users.id = $2",
                 prefs,
                 user
             )
@@ -447,6 +985,9 @@ pub struct Login {
     pub username: String,
     pub password: String,
     pub invite_token: Option<String>,
+    /// TOTP code, required to log in if the user has enabled two-factor authentication.
+    #[serde(default)]
+    pub totp_code: Option<String>,
 }
 
 impl Login {
@@ -462,9 +1003,7 @@ impl Login {
 
         Ok(sqlx::query!(
             "SELECT id FROM invites
-                          WHERE id NOT IN (
-                              SELECT claimed_invite FROM users
-                          )
+                          WHERE NOT claimed
                           AND id = ?",
             tok
         )
@@ -473,6 +1012,79 @@ impl Login {
         .is_some())
     }
 
+    /// Method checks that the invite token of this login can be claimed, returning the reason if
+    /// it cant be. Invites older than `ttl` seconds are expired, with no `ttl` invites never
+    /// expire.
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `ttl` - how many seconds invites stay valid for.
+    pub async fn check_invite(
+        &self,
+        conn: &mut crate::Transaction<'_>,
+        ttl: Option<i64>,
+    ) -> Result<(), DatabaseError> {
+        let tok = self
+            .invite_token
+            .as_ref()
+            .ok_or(DatabaseError::InviteRequired)?;
+
+        // NOTE: invites stay claimed after the account that redeemed them has been deleted, so we
+        // have to go by the `claimed` column rather than by `users.claimed_invite`.
+        let invite = sqlx::query!(
+            r#"SELECT date_added, claimed as "claimed: bool" FROM invites WHERE id = ?"#,
+            tok
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(DatabaseError::InviteInvalid)?;
+
+        match ttl {
+            _ if invite.claimed => Err(DatabaseError::InviteAlreadyClaimed),
+            Some(ttl) if invite.date_added + ttl < unix_now() => Err(DatabaseError::InviteExpired),
+            _ => Ok(()),
+        }
+    }
+
+    /// Method marks the invite `token` as claimed. Checking that the invite is unclaimed and
+    /// claiming it happens in a single statement, so of two registrations redeeming the same
+    /// invite concurrently only one succeeds, the other one gets
+    /// [`DatabaseError::InviteAlreadyClaimed`].
+    ///
+    /// # Arguments
+    /// * `conn` - mutable reference to a sqlx transaction.
+    /// * `token` - the invite token being redeemed.
+    pub async fn claim_invite(
+        conn: &mut crate::Transaction<'_>,
+        token: &str,
+    ) -> Result<(), DatabaseError> {
+        let rows = sqlx::query!(
+            "UPDATE invites SET claimed = 1
+            WHERE id = ?1
+            AND NOT claimed
+            AND id NOT IN (SELECT claimed_invite FROM users)",
+            token
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        if rows > 0 {
+            return Ok(());
+        }
+
+        let exists = sqlx::query!("SELECT id FROM invites WHERE id = ?", token)
+            .fetch_optional(&mut *conn)
+            .await?
+            .is_some();
+
+        if exists {
+            Err(DatabaseError::InviteAlreadyClaimed)
+        } else {
+            Err(DatabaseError::InviteInvalid)
+        }
+    }
+
     pub async fn invalidate_token(
         &self,
         conn: &mut crate::Transaction<'_>,
@@ -488,10 +1100,7 @@ impl Login {
     }
 
     pub async fn new_invite(conn: &mut crate::Transaction<'_>) -> Result<String, DatabaseError> {
-        let ts = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+        let ts = unix_now();
         let token = uuid::Uuid::new_v4().to_hyphenated().to_string();
         let _ = sqlx::query!(
             "INSERT INTO invites (id, date_added) VALUES ($1, $2)",
@@ -540,6 +1149,8 @@ impl Login {
     }
 }
 
+/// Hashes `s` with the legacy scheme, which uses the username as salt and very few rounds. New
+/// passwords should be hashed with [`hash_password`] instead.
 pub fn hash(salt: String, s: String) -> String {
     let mut to_store: Credential = [0u8; CREDENTIAL_LEN];
     pbkdf2::derive(
@@ -552,8 +1163,80 @@ pub fn hash(salt: String, s: String) -> String {
     base64::encode(&to_store)
 }
 
+/// Sets the number of argon2 iterations new passwords are hashed with. Hashes store their own
+/// parameters, so existing passwords keep verifying after this changes.
+///
+/// # Errors
+/// Returns [`DatabaseError::InvalidHashRounds`] if `rounds` is outside of [`HASH_ROUNDS_RANGE`],
+/// the current iterations are kept in that case.
+pub fn set_hash_rounds(rounds: u32) -> Result<(), DatabaseError> {
+    if !HASH_ROUNDS_RANGE.contains(&rounds) {
+        return Err(DatabaseError::InvalidHashRounds {
+            rounds,
+            min: *HASH_ROUNDS_RANGE.start(),
+            max: *HASH_ROUNDS_RANGE.end(),
+        });
+    }
+
+    HASH_ROUNDS_V2.store(rounds, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Hashes `password` with argon2id, a random salt and the iterations set with
+/// [`set_hash_rounds`]. See [`hash_password_with`].
+pub fn hash_password(password: &str) -> String {
+    hash_password_with(password, HASH_ROUNDS_V2.load(Ordering::Relaxed))
+}
+
+/// Hashes `password` with argon2id, a random salt and `rounds` iterations. The result is a PHC
+/// string, ie `$argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>`, which carries the parameters it
+/// was hashed with so that [`verify`] can still check it after they change.
+///
+/// # Panics
+/// Panics if `rounds` is zero.
+pub fn hash_password_with(password: &str, rounds: u32) -> String {
+    let mut salt = [0u8; SALT_LEN];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .expect("Failed to generate salt.");
+    let salt = SaltString::b64_encode(&salt).expect("Failed to encode salt.");
+
+    let params = Params::new(HASH_MEMORY, rounds, 1, None).expect("Invalid argon2 parameters.");
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.as_bytes(), &salt)
+        .expect("Failed to hash password.")
+        .to_string()
+}
+
+/// Returns whether `password` has been hashed with an outdated scheme and should be re-hashed
+/// with [`hash_password`].
+pub fn needs_rehash(password: &str) -> bool {
+    !password.starts_with(HASH_PREFIX)
+}
+
+/// Verifies `attempted_password` against the stored hash `password`. Argon2 and pbkdf2 hashes
+/// carry their own salt, while legacy hashes are salted with `salt`, which should be the
+/// username.
 pub fn verify(salt: String, password: String, attempted_password: String) -> bool {
-    let real_pwd = base64::decode(&password).unwrap();
+    if password.starts_with(HASH_PREFIX) {
+        return PasswordHash::new(&password)
+            .map(|x| {
+                Argon2::default()
+                    .verify_password(attempted_password.as_bytes(), &x)
+                    .is_ok()
+            })
+            .unwrap_or(false);
+    }
+
+    if let Some(params) = password.strip_prefix(PBKDF2_PREFIX) {
+        return verify_pbkdf2(params, &attempted_password).unwrap_or(false);
+    }
+
+    let real_pwd = match base64::decode(&password) {
+        Ok(x) => x,
+        Err(_) => return false,
+    };
 
     pbkdf2::verify(
         PBKDF2_ALG,
@@ -564,3 +1247,41 @@ pub fn verify(salt: String, password: String, attempted_password: String) -> boo
     )
     .is_ok()
 }
+
+fn verify_pbkdf2(params: &str, attempted_password: &str) -> Option<bool> {
+    let mut parts = params.split('$');
+    let rounds = parts.next()?.strip_prefix("i=")?.parse::<u32>().ok()?;
+    let salt = base64::decode(parts.next()?).ok()?;
+    let real_pwd = base64::decode(parts.next()?).ok()?;
+
+    Some(
+        pbkdf2::verify(
+            PBKDF2_ALG,
+            NonZeroU32::new(rounds)?,
+            &salt,
+            attempted_password.as_bytes(),
+            &real_pwd,
+        )
+        .is_ok(),
+    )
+}
+
+/// Runs a password verification against a fixed hash and discards the result. This should be
+/// called when an account doesnt exist, so that rejecting the login takes as long as it would for
+/// an existing account and response times dont reveal which usernames are taken.
+pub fn verify_dummy(attempted_password: String) {
+    static DUMMY_HASH: Lazy<String> = Lazy::new(|| hash_password(""));
+
+    let _ = verify("dim".into(), DUMMY_HASH.clone(), attempted_password);
+}
+
+/// Check a new password against the password policy.
+pub fn check_password(password: &str) -> Result<(), DatabaseError> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(DatabaseError::WeakPassword {
+            min_len: MIN_PASSWORD_LEN,
+        });
+    }
+
+    Ok(())
+}
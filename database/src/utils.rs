@@ -17,6 +17,71 @@ macro_rules! opt_update {
     }
 }
 
+/// Default number of attempts used by [`retry_while!`](crate::retry_while).
+pub const DEFAULT_RETRIES: usize = 16;
+
+/// Macro evaluates `$expr` and retries it for as long as it fails with an error for which `$pred`
+/// returns true, for example when the database is busy. The number of attempts is capped at `$max`
+/// (or [`DEFAULT_RETRIES`] if omitted), in between attempts we back off exponentially. Once all
+/// attempts are exhausted, [`DatabaseError::RetryExhausted`](crate::DatabaseError::RetryExhausted)
+/// is returned with the last error.
+#[macro_export]
+macro_rules! retry_while {
+    ($pred:expr, $expr:expr) => {
+        $crate::retry_while!($pred, $crate::utils::DEFAULT_RETRIES, $expr)
+    };
+    ($pred:expr, $max:expr, $expr:expr) => {{
+        let max: usize = $max;
+        let mut attempts = 0usize;
+
+        loop {
+            match $expr {
+                Ok(x) => break Ok(x),
+                Err(e) if $pred(&e) => {
+                    attempts += 1;
+
+                    if attempts >= max {
+                        break Err($crate::DatabaseError::RetryExhausted {
+                            attempts,
+                            source: e,
+                        });
+                    }
+
+                    if attempts > 3 {
+                        ::tracing::warn!(attempts, max, reason = ?e, "Retrying statement.");
+                    }
+
+                    let backoff = 10u64 << attempts.min(8);
+                    ::tokio::time::sleep(::std::time::Duration::from_millis(backoff)).await;
+                }
+                Err(e) => break Err($crate::DatabaseError::from(e)),
+            }
+        }
+    }};
+}
+
+/// Function returns whether `e` was caused by the database being busy or locked by another
+/// connection. Such errors are usually transient and the statement can be retried.
+pub fn is_busy(e: &sqlx::Error) -> bool {
+    sqlite_code(e)
+        // NOTE: sqlite returns extended error codes, the lower byte is the primary code.
+        .map(|x| matches!(x & 0xff, 5 | 6))
+        .unwrap_or(false)
+}
+
+/// Function returns the (extended) sqlite result code of `e`, if `e` was returned by the database.
+pub(crate) fn sqlite_code(e: &sqlx::Error) -> Option<i32> {
+    match e {
+        sqlx::Error::Database(e) => e.code().and_then(|x| x.parse::<i32>().ok()),
+        _ => None,
+    }
+}
+
+/// Function returns the current unix timestamp in seconds, see [`auth::unix_now`].
+pub(crate) fn unix_now() -> i64 {
+    auth::unix_now() as i64
+}
+
 #[cfg(not(debug_assertions))]
 pub fn ffpath(bin: impl AsRef<str>) -> &'static str {
     let mut path = std::env::current_exe().expect("Failed to grab path to the `dim` binary.");
@@ -7,9 +7,12 @@ use crate::websocket;
 
 use once_cell::sync::OnceCell;
 
+use std::path::Path;
+use std::path::PathBuf;
+
 use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::sync::mpsc::UnboundedSender;
-use tracing::{info, instrument};
+use tracing::{error, info, instrument, warn};
 
 use warp::http::status::StatusCode;
 use warp::Filter;
@@ -23,8 +26,23 @@ pub type EventTx = UnboundedSender<String>;
 /// Path to where metadata is stored and should be fetched to.
 pub static METADATA_PATH: OnceCell<String> = OnceCell::new();
 
+/// Path to where files uploaded by users, ie avatars, are stored. This is the same as
+/// [`METADATA_PATH`] unless `uploads_dir` is configured.
+pub static UPLOADS_PATH: OnceCell<String> = OnceCell::new();
+
+/// Returns where the uploaded file `file` is stored on disk. Files uploaded before `uploads_dir`
+/// has been configured are still in the metadata directory, so that is checked as well.
+pub fn uploaded_file_path(file: &str) -> Option<PathBuf> {
+    [UPLOADS_PATH.get(), METADATA_PATH.get()]
+        .iter()
+        .flatten()
+        .map(|root| Path::new(root).join(file))
+        .find(|path| path.exists())
+}
+
 /// Function dumps a list of all libraries in the database and starts a scanner for each which
-/// monitors for new files using fsnotify. It also scans all orphans on boot.
+/// monitors for new files using fsnotify. It also scans all orphans on boot and starts the
+/// scheduler for periodic rescans.
 ///
 /// # Arguments
 /// * `log` - Logger to which to log shit
@@ -33,6 +51,11 @@ pub static METADATA_PATH: OnceCell<String> = OnceCell::new();
 #[instrument(skip_all)]
 pub async fn run_scanners(tx: EventTx) {
     if let Ok(conn) = database::get_conn_logged().await {
+        reset_interrupted_scans(&conn).await;
+        fill_sort_titles(&conn).await;
+
+        tokio::spawn(scanners::scheduler::run_scheduler(conn.clone(), tx.clone()));
+
         if let Ok(mut db_tx) = conn.read().begin().await {
             let mut libs = database::library::Library::get_all(&mut db_tx).await;
 
@@ -61,6 +84,45 @@ pub async fn run_scanners(tx: EventTx) {
     }
 }
 
+/// Function marks scans that were still running when the server last stopped as failed.
+async fn reset_interrupted_scans(conn: &DbConnection) {
+    let mut lock = conn.writer().lock_owned().await;
+    let result = async {
+        let mut tx = database::write_tx(&mut lock).await?;
+        let rows = database::library::Library::reset_interrupted_scans(&mut tx).await?;
+        tx.commit().await?;
+        Ok::<_, database::DatabaseError>(rows)
+    }
+    .await;
+
+    match result {
+        Ok(0) => {}
+        Ok(rows) => warn!(
+            libraries = rows,
+            "Marked interrupted library scans as failed."
+        ),
+        Err(e) => error!(reason = ?e, "Failed to reset interrupted library scans."),
+    }
+}
+
+/// Function fills in the sort titles of media that were added before they were introduced.
+async fn fill_sort_titles(conn: &DbConnection) {
+    let mut lock = conn.writer().lock_owned().await;
+    let result = async {
+        let mut tx = database::write_tx(&mut lock).await?;
+        let rows = database::media::Media::fill_sort_titles(&mut tx).await?;
+        tx.commit().await?;
+        Ok::<_, database::DatabaseError>(rows)
+    }
+    .await;
+
+    match result {
+        Ok(0) => {}
+        Ok(rows) => info!(media = rows, "Filled in missing sort titles."),
+        Err(e) => error!(reason = ?e, "Failed to fill in missing sort titles."),
+    }
+}
+
 #[instrument(skip(stream_manager, event_tx, rt, event_rx))]
 pub async fn warp_core(
     event_tx: EventTx,
@@ -81,9 +143,25 @@ pub async fn warp_core(
         /* NOTE: v1 REST API routes start HERE */
         /* /api/v1/auth routes*/
         auth::filters::login(conn.clone()),
+        auth::filters::logout(),
+        auth::filters::verify_token(conn.clone()),
         user::filters::whoami(conn.clone()),
         host::filters::admin_exists(conn.clone()),
+        host::filters::health(conn.clone()),
+        host::filters::info(),
+        host::filters::live(),
+        host::filters::ready(conn.clone()),
+        host::filters::reload_config(conn.clone()),
         auth::filters::register(conn.clone()),
+        auth::filters::get_all_users(conn.clone()),
+        auth::filters::set_roles(conn.clone()),
+        auth::filters::delete_user(conn.clone()),
+        auth::filters::get_audit_log(conn.clone()),
+        auth::filters::totp_enroll(conn.clone()),
+        auth::filters::totp_verify(conn.clone()),
+        auth::filters::new_reset_token(conn.clone()),
+        auth::filters::reset_password(conn.clone()),
+        auth::filters::rotate_key(conn.clone()),
         invites::filters::get_all_invites(conn.clone()),
         invites::filters::generate_invite(conn.clone()),
         invites::filters::delete_token(conn.clone()),
@@ -91,28 +169,63 @@ pub async fn warp_core(
         user::filters::change_password(conn.clone()),
         user::filters::delete(conn.clone()),
         user::filters::change_username(conn.clone()),
+        user::filters::set_disabled(conn.clone()),
         user::filters::upload_avatar(conn.clone()),
+        user::filters::get_avatar(conn.clone()),
+        user::filters::get_favorites(conn.clone()),
+        user::filters::get_history(conn.clone()),
+        user::filters::get_activity(conn.clone()),
         /* general routes */
         routes::general::filters::search(conn.clone()),
+        routes::general::filters::search_global(conn.clone()),
         routes::general::filters::get_directory_structure(conn.clone()),
         /* library routes */
         routes::library::filters::library_get(conn.clone()),
         routes::library::filters::library_post(conn.clone(), event_tx.clone()),
         routes::library::filters::library_delete(conn.clone(), event_tx.clone()),
+        routes::library::filters::library_patch(conn.clone()),
+        routes::library::filters::library_rename(conn.clone()),
+        routes::library::filters::library_empty(conn.clone()),
         routes::library::filters::library_get_self(conn.clone()),
         routes::library::filters::get_all_of_library(conn.clone()),
+        routes::library::filters::get_all_of_library_stream(conn.clone()),
+        routes::library::filters::get_page_of_library(conn.clone()),
+        routes::library::filters::get_unwatched_of_library(conn.clone()),
+        routes::library::filters::get_library_timeline(conn.clone()),
+        routes::library::filters::get_random_of_library(conn.clone()),
+        routes::library::filters::get_tagged_of_library(conn.clone()),
+        routes::library::filters::sync_library(conn.clone()),
+        routes::library::filters::get_scan_issues(conn.clone()),
+        routes::library::filters::get_scan_status(conn.clone()),
+        routes::library::filters::get_scan_schedule(conn.clone()),
+        routes::library::filters::set_scan_schedule(conn.clone()),
+        routes::library::filters::clear_scan_schedule(conn.clone()),
         routes::library::filters::get_all_unmatched_media(conn.clone()),
+        routes::library::filters::get_library_access(conn.clone()),
+        routes::library::filters::set_library_access(conn.clone()),
+        routes::library::filters::set_library_restricted(conn.clone()),
         /* dashboard routes */
         routes::dashboard::filters::dashboard(conn.clone(), rt.clone()),
         routes::dashboard::filters::banners(conn.clone()),
         /* media routes */
         routes::media::filters::get_media_by_id(conn.clone()),
         routes::media::filters::get_media_files(conn.clone()),
+        routes::media::filters::get_stream_info(conn.clone()),
         routes::media::filters::update_media_by_id(conn.clone()),
         routes::media::filters::delete_media_by_id(conn.clone()),
         routes::media::filters::tmdb_search(conn.clone()),
         routes::media::filters::map_progress(conn.clone()),
         routes::media::filters::get_mediafile_tree(conn.clone()),
+        routes::media::filters::refresh_stale(conn.clone()),
+        routes::media::filters::add_favorite(conn.clone()),
+        routes::media::filters::remove_favorite(conn.clone()),
+        routes::media::filters::get_tags(conn.clone()),
+        routes::media::filters::add_tag(conn.clone()),
+        routes::media::filters::remove_tag(conn.clone()),
+        routes::media::filters::set_rating(conn.clone()),
+        routes::media::filters::set_hidden(conn.clone()),
+        routes::media::filters::get_user_rating(conn.clone()),
+        routes::media::filters::set_user_rating(conn.clone()),
         routes::rematch_media::filters::rematch_media_by_id(conn.clone(), event_tx.clone()),
         /* tv routes */
         routes::tv::filters::get_tv_seasons(conn.clone()),
@@ -121,6 +234,7 @@ pub async fn warp_core(
         routes::tv::filters::get_season_episodes(conn.clone()),
         routes::tv::filters::patch_episode_by_id(conn.clone()),
         routes::tv::filters::delete_episode_by_id(conn.clone()),
+        routes::tv::filters::renumber_episodes(conn.clone()),
         /* mediafile routes */
         routes::mediafile::filters::get_mediafile_info(conn.clone()),
         routes::mediafile::filters::rematch_mediafile(conn.clone()),
@@ -170,6 +284,7 @@ pub async fn warp_core(
             .recover(routes::global_filters::handle_rejection),
         /* static routes */
         routes::statik::filters::dist_static(),
+        routes::statik::filters::get_identicon(),
         routes::statik::filters::get_image(conn.clone()),
         routes::statik::filters::react_routes(),
     ]
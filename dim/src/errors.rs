@@ -16,8 +16,11 @@ pub trait ErrorStatusCode {
 }
 
 // FIXME: A lot of these errors need to fucking go man.
+/// Errors returned by the http routes. Every error is sent to the client as a JSON body of the form
+/// `{"error": "INVALID_CREDENTIALS", "message": "Invalid credentials."}` where `error` is a stable
+/// code clients can branch on.
 #[derive(Clone, Display, Debug, Error, Serialize)]
-#[serde(tag = "error")]
+#[serde(tag = "error", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum DimError {
     /// A database error occured: {description}.
     DatabaseError { description: String },
@@ -33,8 +36,12 @@ pub enum DimError {
     NotFoundError,
     /// Authentication is required for this route.
     Unauthenticated,
+    /// No owner account has been created yet, register one first.
+    SetupRequired,
     /// Invalid Media type supplied.
     InvalidMediaType,
+    /// The requested media cannot be played back.
+    NotStreamable,
     /// An error in the streaming module has occured
     #[error(transparent)]
     StreamingError(#[from] StreamingErrors),
@@ -44,14 +51,40 @@ pub enum DimError {
     ScannerError(#[from] ScannerError),
     /// Upload failed.
     UploadFailed,
+    /// No file has been uploaded in the `file` form field.
+    MissingUpload,
+    /// Only a single file can be uploaded at a time.
+    AmbiguousUpload,
+    /// Uploaded file is too large.
+    UploadTooLarge,
+    /// Uploading this file would exceed the upload quota of the user.
+    UploadQuotaExceeded,
+    /// Storage for uploaded files is unavailable.
+    StorageUnavailable,
+    /// Failed to reload the config: {description}.
+    InvalidConfig { description: String },
+    /// The requested asset no longer exists on disk.
+    AssetGone,
     /// Failed to deserialize request body: {description:?}.
     MissingFieldInBody { description: String },
     /// Unsupported file type.
     UnsupportedFile,
     /// Library does not exist.
     LibraryNotFound,
+    /// A library with this name already exists or already indexes this location.
+    LibraryExists,
+    /// Library names cannot be blank.
+    InvalidLibraryName,
+    /// The media type of a library that holds media cannot be changed.
+    LibraryNotEmpty,
     /// Invite token required.
     NoToken,
+    /// The invite token doesnt exist or has already been claimed.
+    InviteInvalid,
+    /// The invite token has expired.
+    InviteExpired,
+    /// The invite token has already been claimed by another account.
+    InviteAlreadyClaimed,
     /// Invalid credentials.
     InvalidCredentials,
     /// Requested username is not available.
@@ -63,6 +96,36 @@ pub enum DimError {
     MediafileRouteError(#[from] mediafile::Error),
     /// User does not exist
     UserNotFound,
+    /// This account has been disabled.
+    AccountDisabled,
+    /// Invalid two-factor authentication code.
+    InvalidTotpCode,
+    /// Two-factor authentication is already enabled.
+    TotpAlreadyEnabled,
+    /// Two-factor authentication has not been enrolled.
+    TotpNotEnrolled,
+    /// Unknown role: {role}.
+    InvalidRole { role: String },
+    /// Invalid timestamp: {value}.
+    InvalidTimestamp { value: String },
+    /// Tags cannot be empty.
+    InvalidTag,
+    /// Invalid rating {value}, ratings must be between 0 and 100.
+    InvalidRating { value: i64 },
+    /// Invalid scan interval {value}, libraries can be rescanned at most every 5 minutes.
+    InvalidScanInterval { value: i64 },
+    /// Cannot remove the last owner of this server.
+    LastOwner,
+    /// Season {season} already has a episode {episode}.
+    DuplicateEpisode { season: i64, episode: i64 },
+    /// Episode {id} is renumbered more than once.
+    DuplicateRenumber { id: i64 },
+    /// Password must be at least {min_len} characters long.
+    WeakPassword { min_len: usize },
+    /// Invalid password hashing iterations {rounds}, must be between {min} and {max}.
+    InvalidHashRounds { rounds: u32, min: u32, max: u32 },
+    /// The password reset token is invalid, expired or has already been used.
+    InvalidResetToken,
     /// Couldn't find the tmdb id provided.
     TmdbIdSearchError(crate::scanners::tmdb::TmdbError),
 }
@@ -77,8 +140,36 @@ impl From<sqlx::Error> for DimError {
 
 impl From<DatabaseError> for DimError {
     fn from(e: DatabaseError) -> Self {
-        Self::DatabaseError {
-            description: format!("{:?}", e),
+        match e {
+            DatabaseError::NotFound => Self::NotFoundError,
+            DatabaseError::LibraryExists => Self::LibraryExists,
+            DatabaseError::InvalidLibraryName => Self::InvalidLibraryName,
+            DatabaseError::LibraryNotEmpty => Self::LibraryNotEmpty,
+            DatabaseError::InvalidMediaType(_) | DatabaseError::MediaTypeMismatch { .. } => {
+                Self::InvalidMediaType
+            }
+            DatabaseError::InvalidRole(role) => Self::InvalidRole { role },
+            DatabaseError::InvalidTimestamp(value) => Self::InvalidTimestamp { value },
+            DatabaseError::InvalidTag(_) => Self::InvalidTag,
+            DatabaseError::InvalidRating(value) => Self::InvalidRating { value },
+            DatabaseError::InvalidScanInterval(value) => Self::InvalidScanInterval { value },
+            DatabaseError::LastOwner => Self::LastOwner,
+            DatabaseError::PermissionDenied => Self::Unauthorized,
+            DatabaseError::DuplicateEpisode { season, episode } => {
+                Self::DuplicateEpisode { season, episode }
+            }
+            DatabaseError::DuplicateRenumber { id } => Self::DuplicateRenumber { id },
+            DatabaseError::WeakPassword { min_len } => Self::WeakPassword { min_len },
+            DatabaseError::InvalidHashRounds { rounds, min, max } => {
+                Self::InvalidHashRounds { rounds, min, max }
+            }
+            DatabaseError::InviteRequired => Self::NoToken,
+            DatabaseError::InviteInvalid => Self::InviteInvalid,
+            DatabaseError::InviteExpired => Self::InviteExpired,
+            DatabaseError::InviteAlreadyClaimed => Self::InviteAlreadyClaimed,
+            e => Self::DatabaseError {
+                description: format!("{:?}", e),
+            },
         }
     }
 }
@@ -101,6 +192,7 @@ impl warp::reject::Reject for DimError {}
 impl warp::Reply for DimError {
     fn into_response(self) -> warp::reply::Response {
         let status = match self {
+            Self::ScannerError(ScannerError::AlreadyScanning) => StatusCode::CONFLICT,
             Self::LibraryNotFound
             | Self::NoneError
             | Self::NotFoundError
@@ -111,23 +203,51 @@ impl warp::Reply for DimError {
             | Self::IOError
             | Self::InternalServerError
             | Self::ScannerError(_)
-            | Self::UploadFailed => StatusCode::INTERNAL_SERVER_ERROR,
+            | Self::UploadFailed
+            | Self::InvalidConfig { .. } => StatusCode::INTERNAL_SERVER_ERROR,
             Self::Unauthenticated
             | Self::Unauthorized
             | Self::InvalidCredentials
             | Self::CookieError(_)
             | Self::NoToken
-            | Self::UserNotFound => StatusCode::UNAUTHORIZED,
-            Self::UsernameNotAvailable => StatusCode::BAD_REQUEST,
-            Self::UnsupportedFile | Self::InvalidMediaType | Self::MissingFieldInBody { .. } => {
-                StatusCode::NOT_ACCEPTABLE
-            }
+            | Self::UserNotFound
+            | Self::AccountDisabled
+            | Self::InvalidTotpCode => StatusCode::UNAUTHORIZED,
+            Self::UsernameNotAvailable
+            | Self::InviteInvalid
+            | Self::InviteExpired
+            | Self::InviteAlreadyClaimed
+            | Self::LibraryExists
+            | Self::InvalidLibraryName
+            | Self::LibraryNotEmpty
+            | Self::InvalidRole { .. }
+            | Self::InvalidTimestamp { .. }
+            | Self::InvalidTag
+            | Self::InvalidRating { .. }
+            | Self::InvalidScanInterval { .. }
+            | Self::MissingUpload
+            | Self::AmbiguousUpload
+            | Self::LastOwner
+            | Self::DuplicateEpisode { .. }
+            | Self::DuplicateRenumber { .. }
+            | Self::WeakPassword { .. }
+            | Self::InvalidHashRounds { .. }
+            | Self::InvalidResetToken
+            | Self::TotpAlreadyEnabled
+            | Self::TotpNotEnrolled => StatusCode::BAD_REQUEST,
+            Self::UnsupportedFile
+            | Self::InvalidMediaType
+            | Self::NotStreamable
+            | Self::MissingFieldInBody { .. } => StatusCode::NOT_ACCEPTABLE,
+            Self::UploadTooLarge | Self::UploadQuotaExceeded => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::StorageUnavailable | Self::SetupRequired => StatusCode::SERVICE_UNAVAILABLE,
+            Self::AssetGone => StatusCode::GONE,
             Self::MediafileRouteError(ref e) => e.status_code(),
         };
 
         let resp = json!({
             "error": json!(&self)["error"],
-            "messsage": self.to_string(),
+            "message": self.to_string(),
         });
 
         warp::http::Response::builder()
@@ -139,7 +259,7 @@ impl warp::Reply for DimError {
 }
 
 #[derive(Clone, Display, Debug, Error, Serialize)]
-#[serde(tag = "error")]
+#[serde(tag = "error", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum StreamingErrors {
     /// A database error occured: {0}
     DatabaseError(String),
@@ -191,7 +311,7 @@ impl warp::Reply for StreamingErrors {
 
         let resp = json!({
             "error": json!(&self)["error"],
-            "messsage": self.to_string(),
+            "message": self.to_string(),
         });
 
         warp::http::Response::builder()
@@ -53,6 +53,7 @@ pub mod websocket;
 
 pub use routes::settings::get_global_settings;
 pub use routes::settings::init_global_settings;
+pub use routes::settings::reload_global_settings;
 pub use routes::settings::set_global_settings;
 pub use routes::settings::GlobalSettings;
 
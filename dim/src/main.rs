@@ -36,29 +36,57 @@ fn main() {
 
     let global_settings = dim::get_global_settings();
 
-    // never panics because we set a default value to metadata_dir
-    let _ = create_dir_all(global_settings.metadata_dir.clone());
-
     // set our jwt secret key
-    let settings_clone = global_settings.clone();
-    let secret_key = global_settings.secret_key.unwrap_or_else(move || {
-        let secret_key = database::generate_key();
+    if global_settings.secret_key.is_none() {
         dim::set_global_settings(GlobalSettings {
-            secret_key: Some(secret_key),
-            ..settings_clone
+            secret_key: Some(database::generate_key()),
+            ..global_settings.clone()
         })
         .expect("Failed to save JWT secret_key.");
-        secret_key
-    });
+    }
+
+    database::user::set_hash_rounds(global_settings.password_hash_iterations)
+        .expect("Invalid password_hash_iterations.");
 
-    database::set_key(secret_key);
+    let key_settings = dim::get_global_settings();
+    // NOTE: `secret_key` is never rotated, which is what we want for encrypting stored secrets.
+    database::set_storage_key(key_settings.secret_key.expect("secret_key must be set"));
+    database::set_keys(
+        dim::routes::settings::signing_keys(&key_settings),
+        key_settings.key_grace_period,
+    );
 
     core::METADATA_PATH
         .set(global_settings.metadata_dir.clone())
         .expect("Failed to set METADATA_PATH");
 
+    let uploads_dir = global_settings
+        .uploads_dir
+        .clone()
+        .unwrap_or_else(|| global_settings.metadata_dir.clone());
+
+    core::UPLOADS_PATH
+        .set(uploads_dir.clone())
+        .expect("Failed to set UPLOADS_PATH");
+
     setup_logging(global_settings.verbose);
 
+    // Assets get written to the metadata dir while serving requests, so we'd rather fail here than
+    // deep inside of a handler.
+    if let Err(e) = dim::utils::ensure_writable_dir(&global_settings.metadata_dir) {
+        error!(
+            reason = ?e,
+            path = ?global_settings.metadata_dir,
+            "Metadata directory is not writable."
+        );
+        std::process::exit(1);
+    }
+
+    if let Err(e) = dim::utils::ensure_writable_dir(&uploads_dir) {
+        error!(reason = ?e, path = ?uploads_dir, "Uploads directory is not writable.");
+        std::process::exit(1);
+    }
+
     {
         let failed = streaming::ffcheck()
             .into_iter()
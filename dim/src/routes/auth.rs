@@ -1,11 +1,21 @@
 //! This module contains all docs and APIs related to authentication and user creation.
 //!
 //! # Request Authentication and Authorization
-//! Most API endpoints require a valid JWT authentication token. If no such token is supplied, the
+//! Most API endpoints require a valid authentication token. If no such token is supplied, the
 //! API will return [`Unauthenticated`]. Authentication tokens can be obtained by logging in with
 //! the [`login`] method. Authentication tokens must be passed to the server through a
 //! `Authroization` header.
 //!
+//! Tokens are opaque to clients and other services. They are not JWTs and carry no claims, only
+//! the id of the user sealed with the secret key of the server, so they cant be verified by a API
+//! gateway in front of Dim. As the account is looked up on every request, changes to its roles or
+//! disabling it take effect right away for tokens that have already been issued.
+//!
+//! If `cookie_auth` is enabled in the settings, [`login`] additionally sets the token as a
+//! `HttpOnly; Secure; SameSite=Lax` cookie named `token`. Browser clients can then rely on that
+//! cookie instead of keeping the token in storage that scripts can read, [`logout`] clears it
+//! again. The `Authorization` header takes precedence over the cookie.
+//!
 //! ## Example of an authenticated call
 //! ```text
 //! curl -X POST http://127.0.0.1:8000/api/v1/auth/whoami -H "Content-type: application/json" -H
@@ -16,19 +26,81 @@
 //! By default tokens expire after exactly two weeks, once the tokens expire the client must renew
 //! them. At the moment renewing the token is only possible by logging in again.
 //!
+//! # First run
+//! Until the first account has been created with [`register`], there is nobody who could be
+//! authenticated. During that time authenticated endpoints return [`SetupRequired`] instead, so
+//! clients can send the user to the setup screen.
+//!
+//! # Key rotation
+//! Tokens carry the id of the key they were signed with. Owners can introduce a new signing key
+//! with [`rotate_key`], tokens signed with older keys keep working until the grace period runs
+//! out.
+//!
 //! [`Unauthenticated`]: crate::errors::DimError::Unauthenticated
 //! [`login`]: fn@login
+//! [`logout`]: fn@logout
+//! [`rotate_key`]: fn@rotate_key
+//! [`register`]: fn@register
+//! [`SetupRequired`]: crate::errors::DimError::SetupRequired
 use crate::core::DbConnection;
 use crate::errors;
+use crate::routes::settings::get_global_settings;
+use crate::routes::settings::set_global_settings;
+use crate::routes::settings::signing_keys;
 
+use database::asset::Asset;
+use database::audit::AuditLog;
+use database::totp;
+use database::unix_now;
+use database::user::check_password;
+use database::user::needs_rehash;
 use database::user::verify;
+use database::user::verify_dummy;
 use database::user::InsertableUser;
 use database::user::Login;
+use database::user::Permission;
 use database::user::User;
+use database::DatabaseError;
 
+use http::header::HeaderValue;
+use http::header::SET_COOKIE;
+use http::StatusCode;
 use serde_json::json;
 
 use warp::reply;
+use warp::Reply;
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+use tracing::warn;
+
+/// Issuer shown by authenticator apps next to the account name.
+const TOTP_ISSUER: &str = "Dim";
+/// Number of failed logins for the same username after which an entry is written to the audit log.
+const FAILED_LOGIN_BURST: u32 = 5;
+/// Window in seconds in which failed logins are counted towards a burst.
+const FAILED_LOGIN_WINDOW: u64 = 300;
+/// Max number of usernames failed logins are tracked for. Usernames are supplied by the client, so
+/// without a cap the map could be grown without bound.
+const FAILED_LOGIN_MAX_TRACKED: usize = 4096;
+/// Maximum number of accounts `/api/v1/auth/users` returns at once.
+const MAX_USERS_PAGE: i64 = 200;
+/// Maximum number of entries `/api/v1/auth/audit` returns at once.
+const MAX_AUDIT_PAGE: i64 = 200;
+
+/// Name of the cookie the token is stored in when `cookie_auth` is enabled.
+pub(crate) const TOKEN_COOKIE: &str = "token";
+/// How long browsers keep the token cookie. Tokens themselves dont expire, they stay valid until the
+/// key they were signed with is rotated out, so this only bounds how long a browser stays logged in.
+const TOKEN_COOKIE_MAX_AGE: u64 = 14 * 24 * 60 * 60;
+
+/// Tracks failed logins per username as `(window start, count)`.
+static FAILED_LOGINS: Lazy<Mutex<HashMap<String, (u64, u32)>>> = Lazy::new(Default::default);
 
 pub mod filters {
     use crate::core::DbConnection;
@@ -38,6 +110,11 @@ pub mod filters {
 
     use database::user::Login;
 
+    use std::net::SocketAddr;
+
+    use crate::routes::settings::get_global_settings;
+
+    use super::super::global_filters::with_auth;
     use super::super::global_filters::with_db;
 
     pub fn login(
@@ -46,12 +123,40 @@ pub mod filters {
         warp::path!("api" / "v1" / "auth" / "login")
             .and(warp::post())
             .and(warp::body::json::<Login>())
+            .and(warp::addr::remote())
+            .and(warp::header::optional::<String>("x-forwarded-for"))
             .and(with_db(conn))
-            .and_then(|new_login: Login, conn: DbConnection| async move {
-                super::login(new_login, conn)
-                    .await
-                    .map_err(|e| reject::custom(e))
-            })
+            .and_then(
+                |new_login: Login,
+                 remote: Option<SocketAddr>,
+                 forwarded_for: Option<String>,
+                 conn: DbConnection| async move {
+                    let ip = super::client_ip(
+                        remote,
+                        forwarded_for.as_deref(),
+                        &get_global_settings().trusted_proxies,
+                    );
+
+                    super::login(new_login, ip, conn)
+                        .await
+                        .map_err(|e| reject::custom(e))
+                },
+            )
+    }
+
+    pub fn logout() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "auth" / "logout")
+            .and(warp::post())
+            .and_then(|| async move { super::logout().await.map_err(reject::custom) })
+    }
+
+    pub fn verify_token(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "auth" / "verify")
+            .and(warp::get())
+            .and(with_auth(conn))
+            .and_then(|user| async move { super::verify_token(user).await.map_err(reject::custom) })
     }
 
     pub fn admin_exists(
@@ -67,6 +172,186 @@ pub mod filters {
             })
     }
 
+    pub fn get_all_users(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(serde::Deserialize)]
+        struct Args {
+            #[serde(default = "default_limit")]
+            limit: i64,
+            #[serde(default)]
+            offset: i64,
+        }
+
+        fn default_limit() -> i64 {
+            50
+        }
+
+        warp::path!("api" / "v1" / "auth" / "users")
+            .and(warp::get())
+            .and(warp::query::<Args>())
+            .and(with_auth(conn.clone()))
+            .and(with_db(conn))
+            .and_then(
+                |Args { limit, offset }: Args, user, conn: DbConnection| async move {
+                    super::get_all_users(conn, user, limit, offset)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn get_audit_log(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(serde::Deserialize)]
+        struct Args {
+            #[serde(default = "default_limit")]
+            limit: i64,
+            #[serde(default)]
+            offset: i64,
+        }
+
+        fn default_limit() -> i64 {
+            50
+        }
+
+        warp::path!("api" / "v1" / "auth" / "audit")
+            .and(warp::get())
+            .and(warp::query::<Args>())
+            .and(with_auth(conn.clone()))
+            .and(with_db(conn))
+            .and_then(
+                |Args { limit, offset }: Args, user, conn: DbConnection| async move {
+                    super::get_audit_log(conn, user, limit, offset)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn set_roles(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(serde::Deserialize)]
+        struct Params {
+            roles: Vec<String>,
+        }
+
+        warp::path!("api" / "v1" / "auth" / "users" / String / "roles")
+            .and(warp::patch())
+            .and(warp::body::json::<Params>())
+            .and(with_auth(conn.clone()))
+            .and(with_db(conn))
+            .and_then(
+                |username: String, Params { roles }: Params, user, conn: DbConnection| async move {
+                    super::set_roles(conn, user, username, roles)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn delete_user(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "auth" / "users" / String)
+            .and(warp::delete())
+            .and(with_auth(conn.clone()))
+            .and(with_db(conn))
+            .and_then(|username: String, user, conn: DbConnection| async move {
+                super::delete_user(conn, user, username)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
+
+    pub fn totp_enroll(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "auth" / "2fa" / "enroll")
+            .and(warp::post())
+            .and(with_auth(conn.clone()))
+            .and(with_db(conn))
+            .and_then(|user, conn: DbConnection| async move {
+                super::totp_enroll(conn, user).await.map_err(reject::custom)
+            })
+    }
+
+    pub fn totp_verify(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(serde::Deserialize)]
+        struct Params {
+            code: String,
+        }
+
+        warp::path!("api" / "v1" / "auth" / "2fa" / "verify")
+            .and(warp::post())
+            .and(warp::body::json::<Params>())
+            .and(with_auth(conn.clone()))
+            .and(with_db(conn))
+            .and_then(
+                |Params { code }: Params, user, conn: DbConnection| async move {
+                    super::totp_verify(conn, user, code)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn new_reset_token(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "auth" / "users" / String / "reset_token")
+            .and(warp::post())
+            .and(with_auth(conn.clone()))
+            .and(with_db(conn))
+            .and_then(|username: String, user, conn: DbConnection| async move {
+                super::new_reset_token(conn, user, username)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
+
+    pub fn rotate_key(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "auth" / "rotate_key")
+            .and(warp::post())
+            .and(with_auth(conn.clone()))
+            .and(with_db(conn))
+            .and_then(|user, conn: DbConnection| async move {
+                super::rotate_key(conn, user).await.map_err(reject::custom)
+            })
+    }
+
+    pub fn reset_password(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(serde::Deserialize)]
+        struct Params {
+            token: String,
+            new_password: String,
+        }
+
+        warp::path!("api" / "v1" / "auth" / "reset")
+            .and(warp::post())
+            .and(warp::body::json::<Params>())
+            .and(with_db(conn))
+            .and_then(
+                |Params {
+                     token,
+                     new_password,
+                 }: Params,
+                 conn: DbConnection| async move {
+                    super::reset_password(conn, token, new_password)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
     pub fn register(
         conn: DbConnection,
     ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
@@ -95,40 +380,568 @@ pub mod filters {
 /// '{"username": "testuser", "password": "testpassword"}'
 /// ```
 ///
+/// If the user has enabled two-factor authentication, the body must additionally contain a
+/// `totp_code` field with the current 6 digit code. Every code can only be used once.
+///
+/// Successful logins are recorded along with the client address. Behind one of the configured
+/// `trusted_proxies` the address is taken from the `X-Forwarded-For` header.
+///
 /// # Response
 /// If authentication is successful, this method will return status `200 0K` as well as a
-/// authentication token.
+/// authentication token. If `cookie_auth` is enabled the token is also set as a cookie.
 /// ```
 /// {
 ///   "token": "...."
 /// }
 /// ```
 ///
+/// If the user has enabled two-factor authentication but no code has been supplied, this method
+/// returns `200 OK` without a token. The client should ask for a code and retry the request with
+/// `totp_code` set.
+/// ```
+/// {
+///   "2fa_required": true
+/// }
+/// ```
+///
 /// # Errors
 /// * [`InvalidCredentials`] - The provided username or password is incorrect.
+/// * [`AccountDisabled`] - The account has been disabled by an owner.
+/// * [`InvalidTotpCode`] - The provided two-factor code is incorrect or has already been used.
 ///
 /// [`InvalidCredentials`]: crate::errors::DimError::InvalidCredentials
+/// [`AccountDisabled`]: crate::errors::DimError::AccountDisabled
+/// [`InvalidTotpCode`]: crate::errors::DimError::InvalidTotpCode
 /// [`Login`]: database::user::Login
 pub async fn login(
     new_login: Login,
+    ip: Option<IpAddr>,
     conn: DbConnection,
 ) -> Result<impl warp::Reply, errors::DimError> {
+    let username = new_login.username.clone();
+    let result = try_login(new_login, ip, &conn).await;
+
+    if let Err(errors::DimError::InvalidCredentials | errors::DimError::InvalidTotpCode) = result {
+        if track_failed_login(&username) {
+            let detail = format!(
+                "{} failed attempts within {}s",
+                FAILED_LOGIN_BURST, FAILED_LOGIN_WINDOW
+            );
+            if let Err(e) = record_failed_login_burst(&conn, &username, &detail).await {
+                warn!(reason = ?e, %username, "Failed to record failed login burst.");
+            }
+        }
+    }
+
+    let token = match result? {
+        Some(token) => token,
+        None => return Ok(reply::json(&json!({ "2fa_required": true })).into_response()),
+    };
+
+    let mut response = reply::json(&json!({ "token": token })).into_response();
+    if get_global_settings().cookie_auth {
+        set_cookie(&mut response, token_cookie(&token, TOKEN_COOKIE_MAX_AGE));
+    }
+
+    Ok(response)
+}
+
+/// # POST `/api/v1/auth/logout`
+/// Method clears the token cookie set by [`login`] when `cookie_auth` is enabled. Tokens passed
+/// through the `Authorization` header are not affected, clients using those should simply drop
+/// the token.
+///
+/// # Authentication
+/// This method does not require any authentication tokens.
+///
+/// ## Example
+/// ```text
+/// curl -X POST http://127.0.0.1:8000/api/v1/auth/logout
+/// ```
+///
+/// # Response
+/// This method will return `200 OK` and instruct the browser to drop the cookie.
+///
+/// [`login`]: fn@login
+pub async fn logout() -> Result<impl warp::Reply, errors::DimError> {
+    let mut response = StatusCode::OK.into_response();
+    set_cookie(&mut response, token_cookie("", 0));
+    Ok(response)
+}
+
+/// # GET `/api/v1/auth/verify`
+/// Method checks whether the authentication token is still valid. Unlike
+/// [`whoami`](crate::routes::user::whoami) this doesnt compute any profile data, the only query
+/// made is the account lookup every authenticated route does, which also rejects tokens of
+/// deleted or disabled accounts. This makes it cheap enough for clients to poll.
+///
+/// # Authentication
+/// This method requires a authentication token.
+///
+/// ## Example
+/// ```text
+/// curl -X GET http://127.0.0.1:8000/api/v1/auth/verify -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// If the token is valid this method returns `200 OK` with the account the token belongs to.
+/// ```
+/// {
+///   "id": 1,
+///   "username": "admin",
+///   "roles": ["owner"]
+/// }
+/// ```
+///
+/// # Errors
+/// * [`Unauthenticated`] - The request doesnt contain a token.
+/// * [`CookieError`] - The token is malformed, has been tampered with or its key was retired.
+/// * [`UserNotFound`] - The account the token was issued for has been deleted.
+/// * [`AccountDisabled`] - The account the token was issued for has been disabled.
+///
+/// [`Unauthenticated`]: crate::errors::DimError::Unauthenticated
+/// [`CookieError`]: crate::errors::DimError::CookieError
+/// [`UserNotFound`]: crate::errors::DimError::UserNotFound
+/// [`AccountDisabled`]: crate::errors::DimError::AccountDisabled
+pub async fn verify_token(user: User) -> Result<impl warp::Reply, errors::DimError> {
+    Ok(reply::json(&json!({
+        "id": user.id,
+        "username": user.username,
+        "roles": user.roles(),
+    })))
+}
+
+/// Returns the value of a `Set-Cookie` header that stores `token` for `max_age` seconds.
+pub(crate) fn token_cookie(token: &str, max_age: u64) -> String {
+    format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; Secure; SameSite=Lax",
+        TOKEN_COOKIE, token, max_age
+    )
+}
+
+fn set_cookie(response: &mut warp::reply::Response, cookie: String) {
+    if let Ok(value) = HeaderValue::from_str(&cookie) {
+        response.headers_mut().append(SET_COOKIE, value);
+    }
+}
+
+/// Returns the token of the user if the login is valid, or `None` if a two-factor code is still
+/// required.
+async fn try_login(
+    new_login: Login,
+    ip: Option<IpAddr>,
+    conn: &DbConnection,
+) -> Result<Option<String>, errors::DimError> {
     let mut tx = conn.read().begin().await?;
-    let user = User::get(&mut tx, &new_login.username)
-        .await
-        .map_err(|_| errors::DimError::InvalidCredentials)?;
+    let user = match User::get(&mut tx, &new_login.username).await {
+        Ok(user) => user,
+        Err(_) => {
+            // NOTE: we still hash the password so that unknown usernames dont fail any faster.
+            verify_dummy(new_login.password);
+            return Err(errors::DimError::InvalidCredentials);
+        }
+    };
     let pass = user.get_pass(&mut tx).await?;
-    if verify(user.username, pass, new_login.password) {
-        let token = database::user::Login::create_cookie(user.id);
+    if verify(
+        user.username.clone(),
+        pass.clone(),
+        new_login.password.clone(),
+    ) {
+        if user.is_disabled {
+            return Err(errors::DimError::AccountDisabled);
+        }
+
+        let totp_counter = match user.get_totp_secret(&mut tx).await? {
+            Some(secret) => match new_login.totp_code {
+                None => return Ok(None),
+                Some(code) => Some(
+                    totp::verify(&secret, &code, unix_now())
+                        .ok_or(errors::DimError::InvalidTotpCode)?,
+                ),
+            },
+            None => None,
+        };
+
+        drop(tx);
+
+        if let Some(counter) = totp_counter {
+            use_totp_counter(conn, &user, counter).await?;
+        }
 
-        return Ok(reply::json(&json!({
-            "token": token,
-        })));
+        // NOTE: Passwords hashed with an outdated scheme get upgraded now that we know the
+        // plaintext. Like recording the login this is best-effort.
+        let rehash = Some(new_login.password).filter(|_| needs_rehash(&pass));
+
+        // NOTE: Recording the login is best-effort, we dont want to lock people out because of it.
+        let ip = ip.map(|x| x.to_string());
+        if let Err(e) = record_login(conn, &user, ip.as_deref(), rehash).await {
+            warn!(reason = ?e, username = %user.username, "Failed to record login.");
+        }
+
+        return Ok(Some(database::user::Login::create_cookie(user.id)));
     }
 
     Err(errors::DimError::InvalidCredentials)
 }
 
+/// Marks the TOTP code with the counter `counter` as used, rejecting codes that have been used
+/// before.
+async fn use_totp_counter(
+    conn: &DbConnection,
+    user: &User,
+    counter: u64,
+) -> Result<(), errors::DimError> {
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    if !user.use_totp_counter(&mut tx, counter).await? {
+        return Err(errors::DimError::InvalidTotpCode);
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+async fn record_login(
+    conn: &DbConnection,
+    user: &User,
+    ip: Option<&str>,
+    rehash: Option<String>,
+) -> Result<(), errors::DimError> {
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    user.record_login(&mut tx, unix_now() as i64, ip).await?;
+    if let Some(password) = rehash {
+        user.set_password(&mut tx, password).await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Returns the address of the client that connected from `remote`. As any client can set
+/// `X-Forwarded-For` it is only honored if `remote` is one of the `trusted` proxies, in which case
+/// the client is the last hop that isnt a trusted proxy itself.
+pub(crate) fn client_ip(
+    remote: Option<SocketAddr>,
+    forwarded_for: Option<&str>,
+    trusted: &[IpAddr],
+) -> Option<IpAddr> {
+    let remote = remote?.ip();
+    if !trusted.contains(&remote) {
+        return Some(remote);
+    }
+
+    for hop in forwarded_for.unwrap_or_default().rsplit(',') {
+        match hop.trim().parse::<IpAddr>() {
+            Ok(ip) if trusted.contains(&ip) => continue,
+            Ok(ip) => return Some(ip),
+            // hops left of one we cant make sense of cant be trusted either.
+            Err(_) => break,
+        }
+    }
+
+    Some(remote)
+}
+
+/// Returns `true` once the number of failed logins for `username` within
+/// [`FAILED_LOGIN_WINDOW`] reaches [`FAILED_LOGIN_BURST`]. At most [`FAILED_LOGIN_MAX_TRACKED`]
+/// usernames are tracked at a time.
+fn track_failed_login(username: &str) -> bool {
+    let now = unix_now();
+    let mut lock = FAILED_LOGINS.lock().unwrap();
+    lock.retain(|_, (start, _)| now.saturating_sub(*start) < FAILED_LOGIN_WINDOW);
+
+    // make room by evicting the entry whose window started the longest time ago.
+    if lock.len() >= FAILED_LOGIN_MAX_TRACKED && !lock.contains_key(username) {
+        let oldest = lock
+            .iter()
+            .min_by_key(|(_, (start, _))| *start)
+            .map(|(k, _)| k.clone());
+
+        if let Some(oldest) = oldest {
+            lock.remove(&oldest);
+        }
+    }
+
+    let (_, count) = lock.entry(username.to_string()).or_insert((now, 0));
+    *count += 1;
+    *count == FAILED_LOGIN_BURST
+}
+
+async fn record_failed_login_burst(
+    conn: &DbConnection,
+    username: &str,
+    detail: &str,
+) -> Result<(), errors::DimError> {
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    AuditLog::record(
+        &mut tx,
+        None,
+        "login_failed_burst",
+        Some(username),
+        Some(detail),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// # POST `/api/v1/auth/2fa/enroll`
+/// Method starts enrolling the current user into two-factor authentication by generating a new
+/// TOTP secret. Two-factor authentication is not enforced until the secret has been confirmed with
+/// [`totp_verify`]. Enrolling again before confirming replaces the secret.
+///
+/// # Request
+/// ## Example
+/// ```text
+/// curl -X POST http://127.0.0.1:8000/api/v1/auth/2fa/enroll -H "Authorization: ...."
+/// ```
+///
+/// # Response
+/// This method returns the base32 encoded secret as well as a `otpauth://` uri which can be
+/// displayed as a QR code for authenticator apps.
+/// ```
+/// {
+///   "secret": "JBSWY3DPEHPK3PXP...",
+///   "uri": "otpauth://totp/Dim:testuser?secret=JBSWY3DPEHPK3PXP...&issuer=Dim&..."
+/// }
+/// ```
+///
+/// # Errors
+/// * [`TotpAlreadyEnabled`] - Two-factor authentication is already enabled for this user.
+///
+/// [`TotpAlreadyEnabled`]: crate::errors::DimError::TotpAlreadyEnabled
+pub async fn totp_enroll(
+    conn: DbConnection,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let secret = totp::generate_secret();
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    if user.set_pending_totp_secret(&mut tx, &secret).await? < 1 {
+        return Err(errors::DimError::TotpAlreadyEnabled);
+    }
+    tx.commit().await?;
+
+    Ok(reply::json(&json!({
+        "uri": totp::provisioning_uri(&secret, &user.username, TOTP_ISSUER),
+        "secret": secret,
+    })))
+}
+
+/// # POST `/api/v1/auth/2fa/verify`
+/// Method confirms the secret generated by [`totp_enroll`] and enables two-factor authentication
+/// for the current user. From then on [`login`] requires a TOTP code.
+///
+/// # Request
+/// This method accepts a JSON body with the following schema:
+/// ```
+/// {
+///   "code": String
+/// }
+/// ```
+///
+/// ## Example
+/// ```text
+/// curl -X POST http://127.0.0.1:8000/api/v1/auth/2fa/verify -H "Content-type: application/json" -H "Authorization: ...." -d '{"code": "123456"}'
+/// ```
+///
+/// # Response
+/// If the code is valid, this method returns `200 OK`.
+///
+/// # Errors
+/// * [`TotpNotEnrolled`] - The user hasnt started enrolling or is already enrolled.
+/// * [`InvalidTotpCode`] - The provided code is incorrect.
+///
+/// [`TotpNotEnrolled`]: crate::errors::DimError::TotpNotEnrolled
+/// [`InvalidTotpCode`]: crate::errors::DimError::InvalidTotpCode
+pub async fn totp_verify(
+    conn: DbConnection,
+    user: User,
+    code: String,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    let secret = user
+        .get_pending_totp_secret(&mut tx)
+        .await?
+        .ok_or(errors::DimError::TotpNotEnrolled)?;
+
+    let counter =
+        totp::verify(&secret, &code, unix_now()).ok_or(errors::DimError::InvalidTotpCode)?;
+
+    user.enable_totp(&mut tx, counter).await?;
+    tx.commit().await?;
+
+    Ok(StatusCode::OK)
+}
+
+/// # POST `/api/v1/auth/rotate_key`
+/// Method generates a new key with which authentication tokens are signed from now on. Tokens
+/// signed with the previous key stay valid for `key_grace_period` seconds (7 days by default) so
+/// that logged in clients have time to log in again, after which they are rejected.
+///
+/// # Authorization
+/// This route requires a valid authentication token to be supplied. The token must have `owner`
+/// permissions.
+///
+/// # Request
+/// ## Example
+/// ```text
+/// curl -X POST http://127.0.0.1:8000/api/v1/auth/rotate_key -H "Authorization: ...."
+/// ```
+///
+/// # Response
+/// This method returns the id of the new key.
+/// ```
+/// {
+///   "active_key": 1
+/// }
+/// ```
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+pub async fn rotate_key(
+    conn: DbConnection,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageSettings)?;
+
+    let now = unix_now();
+
+    let mut settings = get_global_settings();
+    let mut keys = signing_keys(&settings);
+    let id = database::rotate_keys(&mut keys, settings.key_grace_period, now);
+    let grace_period = settings.key_grace_period;
+
+    // persist the new key before handing out tokens signed with it.
+    settings.signing_keys = keys.clone();
+    set_global_settings(settings).map_err(|e| {
+        warn!(reason = ?e, "Failed to persist rotated signing key.");
+        errors::DimError::InternalServerError
+    })?;
+    database::set_keys(keys, grace_period);
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    AuditLog::record(
+        &mut tx,
+        Some(&user.username),
+        "signing_key_rotated",
+        None,
+        Some(&format!("key {}", id)),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(reply::json(&json!({ "active_key": id })))
+}
+
+/// # POST `/api/v1/auth/users/<username>/reset_token`
+/// Method mints a single-use password reset token for the supplied user. The token can be handed
+/// to the user out of band and redeemed with [`reset_password`]. Tokens expire after 24 hours.
+///
+/// # Authorization
+/// This method requires the `owner` role.
+///
+/// # Request
+/// ## Example
+/// ```text
+/// curl -X POST http://127.0.0.1:8000/api/v1/auth/users/testuser/reset_token -H "Authorization: ...."
+/// ```
+///
+/// # Response
+/// ```
+/// {
+///   "token": "72390330-b8af-4413-8305-5f8cae1c8f88"
+/// }
+/// ```
+///
+/// # Errors
+/// * [`Unauthorized`] - The current user is not an owner.
+/// * [`UserNotFound`] - No account with the supplied username exists.
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+/// [`UserNotFound`]: crate::errors::DimError::UserNotFound
+pub async fn new_reset_token(
+    conn: DbConnection,
+    user: User,
+    username: String,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageUsers)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    let target = User::get(&mut tx, &username)
+        .await
+        .map_err(|_| errors::DimError::UserNotFound)?;
+    let token = target.new_reset_token(&mut tx).await?;
+    AuditLog::record(
+        &mut tx,
+        Some(&user.username),
+        "password_reset_token_created",
+        Some(&target.username),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(reply::json(&json!({ "token": token })))
+}
+
+/// # POST `/api/v1/auth/reset`
+/// Method sets a new password for the user a reset token was minted for, without requiring the
+/// old password. Each token can only be used once.
+///
+/// # Request
+/// This method accepts a JSON body with the following schema:
+/// ```
+/// {
+///   "token": String,
+///   "new_password": String
+/// }
+/// ```
+///
+/// ## Example
+/// ```text
+/// curl -X POST http://127.0.0.1:8000/api/v1/auth/reset -H "Content-type: application/json" -d
+/// '{"token": "72390330-b8af-4413-8305-5f8cae1c8f88", "new_password": "newpassword"}'
+/// ```
+///
+/// # Response
+/// If the password has been changed, this method returns `200 OK`.
+///
+/// # Errors
+/// * [`WeakPassword`] - The new password doesnt satisfy the password policy.
+/// * [`InvalidResetToken`] - The token is unknown, expired or has already been used.
+///
+/// [`WeakPassword`]: crate::errors::DimError::WeakPassword
+/// [`InvalidResetToken`]: crate::errors::DimError::InvalidResetToken
+pub async fn reset_password(
+    conn: DbConnection,
+    token: String,
+    new_password: String,
+) -> Result<impl warp::Reply, errors::DimError> {
+    // NOTE: check the policy first so that a rejected password doesnt burn the token.
+    check_password(&new_password)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    let user = User::consume_reset_token(&mut tx, &token)
+        .await?
+        .ok_or(errors::DimError::InvalidResetToken)?;
+    user.set_password(&mut tx, new_password).await?;
+    AuditLog::record(&mut tx, None, "password_reset", Some(&user.username), None).await?;
+    tx.commit().await?;
+
+    Ok(StatusCode::OK)
+}
+
 pub async fn admin_exists(conn: DbConnection) -> Result<impl warp::Reply, errors::DimError> {
     let mut tx = conn.read().begin().await?;
     Ok(reply::json(&json!({
@@ -136,6 +949,245 @@ pub async fn admin_exists(conn: DbConnection) -> Result<impl warp::Reply, errors
     })))
 }
 
+/// # GET `/api/v1/auth/users`
+/// Method will return a page of all registered accounts.
+///
+/// # Authorization
+/// This route requires a valid authentication token to be supplied. The token must have `owner`
+/// permissions.
+///
+/// # Query params
+/// * `limit` - max number of accounts to return, defaults to 50, at most 200.
+/// * `offset` - number of accounts to skip, defaults to 0.
+///
+/// # Request
+/// ## Example
+/// ```text
+/// curl -X GET "http://127.0.0.1:8000/api/v1/auth/users?limit=10&offset=0" -H "Authorization: ...."
+/// ```
+///
+/// # Response
+/// ```
+/// [
+///   {
+///     "id": 1,
+///     "username": "admin",
+///     "roles": ["owner"],
+///     "is_disabled": false,
+///     "created": 1638708402,
+///     "last_login": 1638709102,
+///     "last_login_ip": "192.168.1.10"
+///   },
+///   ...
+/// ]
+/// ```
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+pub async fn get_all_users(
+    conn: DbConnection,
+    user: User,
+    limit: i64,
+    offset: i64,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageUsers)?;
+
+    let mut tx = conn.read().begin().await?;
+    Ok(reply::json(
+        &User::get_page(&mut tx, limit.clamp(1, MAX_USERS_PAGE), offset).await?,
+    ))
+}
+
+/// # GET `/api/v1/auth/audit`
+/// Method will return a page of the audit log, newest entries first. The audit log records
+/// sensitive actions such as invite management, role and password changes, account deletion and
+/// bursts of failed logins. Entries can't be modified or removed.
+///
+/// # Authorization
+/// This route requires a valid authentication token to be supplied. The token must have `owner`
+/// permissions.
+///
+/// # Query params
+/// * `limit` - max number of entries to return, defaults to 50 and is capped at 200.
+/// * `offset` - number of entries to skip, defaults to 0.
+///
+/// # Request
+/// ## Example
+/// ```text
+/// curl -X GET "http://127.0.0.1:8000/api/v1/auth/audit?limit=10&offset=0" -H "Authorization: ...."
+/// ```
+///
+/// # Response
+/// ```
+/// [
+///   {
+///     "id": 2,
+///     "ts": 1638708402,
+///     "actor": "admin",
+///     "action": "roles_changed",
+///     "target": "testuser",
+///     "detail": "owner,user"
+///   },
+///   ...
+/// ]
+/// ```
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+pub async fn get_audit_log(
+    conn: DbConnection,
+    user: User,
+    limit: i64,
+    offset: i64,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageUsers)?;
+
+    let mut tx = conn.read().begin().await?;
+    Ok(reply::json(
+        &AuditLog::get_page(&mut tx, limit.clamp(1, MAX_AUDIT_PAGE), offset).await?,
+    ))
+}
+
+/// # PATCH `/api/v1/auth/users/<username>/roles`
+/// Method replaces the roles of an account. Known roles are `owner` and `user`.
+///
+/// Tokens dont carry roles, the roles of an account are looked up on every request. The new roles
+/// thus apply to the next request the account makes with its existing tokens, there is no need to
+/// log in again or revoke tokens after a demotion.
+///
+/// # Authorization
+/// This route requires a valid authentication token to be supplied. The token must have `owner`
+/// permissions.
+///
+/// # Request
+/// This method accepts a JSON body with the following schema:
+/// ```
+/// {
+///   "roles": [String]
+/// }
+/// ```
+///
+/// ## Example
+/// ```text
+/// curl -X PATCH http://127.0.0.1:8000/api/v1/auth/users/testuser/roles -H "Content-type: application/json" -H "Authorization: ...." -d '{"roles": ["owner"]}'
+/// ```
+///
+/// # Response
+/// If the roles have been updated this method will return `200 OK`.
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+/// * [`UserNotFound`] - No account with the supplied username exists.
+/// * [`InvalidRole`] - One of the supplied roles is unknown.
+/// * [`LastOwner`] - The request would remove the last owner of this server.
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+/// [`UserNotFound`]: crate::errors::DimError::UserNotFound
+/// [`InvalidRole`]: crate::errors::DimError::InvalidRole
+/// [`LastOwner`]: crate::errors::DimError::LastOwner
+pub async fn set_roles(
+    conn: DbConnection,
+    user: User,
+    username: String,
+    roles: Vec<String>,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageUsers)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    let detail = roles.join(",");
+    if User::set_roles(&mut tx, &username, database::user::Roles(roles)).await? < 1 {
+        return Err(errors::DimError::UserNotFound);
+    }
+    AuditLog::record(
+        &mut tx,
+        Some(&user.username),
+        "roles_changed",
+        Some(&username),
+        Some(&detail),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(StatusCode::OK)
+}
+
+/// # DELETE `/api/v1/auth/users/<username>`
+/// Method deletes an account along with its progress, favorites, ratings, library access entries
+/// and avatar, all in a single transaction.
+///
+/// # Authorization
+/// This route requires a valid authentication token to be supplied. The token must have `owner`
+/// permissions.
+///
+/// ## Example
+/// ```text
+/// curl -X DELETE http://127.0.0.1:8000/api/v1/auth/users/testuser -H "Authorization: ...."
+/// ```
+///
+/// # Response
+/// If the account has been deleted this method returns how many related rows were removed.
+/// ```
+/// {
+///   "progress": 14,
+///   "favorites": 3,
+///   "ratings": 1,
+///   "library_access": 0,
+///   "assets": 1
+/// }
+/// ```
+///
+/// # SAFETY and caveats
+/// Tokens are stateless, so tokens issued to the deleted account arent revoked. They are still
+/// rejected by every authenticated route as the account they belong to no longer exists.
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+/// * [`UserNotFound`] - No account with the supplied username exists.
+/// * [`LastOwner`] - The account is the last owner of this server.
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+/// [`UserNotFound`]: crate::errors::DimError::UserNotFound
+/// [`LastOwner`]: crate::errors::DimError::LastOwner
+pub async fn delete_user(
+    conn: DbConnection,
+    user: User,
+    username: String,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageUsers)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+
+    let target = User::get(&mut tx, &username)
+        .await
+        .map_err(|_| errors::DimError::UserNotFound)?;
+    let avatar = Asset::get_of_user(&mut tx, target.id).await.ok();
+
+    let summary = User::delete_account(&mut tx, &username).await?;
+    AuditLog::record(
+        &mut tx,
+        Some(&user.username),
+        "account_deleted",
+        Some(&username),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    if let Some(path) = avatar.and_then(|x| crate::core::uploaded_file_path(&x.local_path)) {
+        if let Err(e) = tokio::fs::remove_file(&path).await {
+            warn!(reason = ?e, path = ?path, "Failed to remove avatar of deleted account.");
+        }
+    }
+
+    Ok(reply::json(&summary))
+}
+
 /// # POST `/api/v1/auth/register`
 /// Method will create a new user and return it a authentication token if a user has been
 /// successfuly created.
@@ -146,7 +1198,8 @@ pub async fn admin_exists(conn: DbConnection) -> Result<impl warp::Reply, errors
 /// will not require an invite token.
 ///
 /// If there is a user in the database, this request will require an invite token and the user will
-/// be given only `user` permissions.
+/// be given the roles in the `default_roles` setting. Invites expire after `invite_ttl` seconds if
+/// that setting is set.
 ///
 /// ## Example
 /// ```text
@@ -157,48 +1210,63 @@ pub async fn admin_exists(conn: DbConnection) -> Result<impl warp::Reply, errors
 ///
 /// # Response
 /// If a user is successfully created, this method will return status `200 0K` as well as the
-/// create user's username.
+/// created user's id, username and roles. The first user to register will be given the `owner`
+/// role, everyone else gets the roles in the `default_roles` setting, `user` by default.
 /// ```
 /// {
-///   "username": "...."
+///   "id": 1,
+///   "username": "....",
+///   "roles": ["owner"]
 /// }
 /// ```
 ///
 /// # Errors
-/// * [`NoToken`] - Either the request doesnt contain an invite token, or the invite token is
-/// invalid.
+/// * [`NoToken`] - The request doesnt contain an invite token.
+/// * [`InviteInvalid`] - The invite token doesnt exist.
+/// * [`InviteExpired`] - The invite token is older than the `invite_ttl` setting.
+/// * [`InviteAlreadyClaimed`] - The invite token has already been redeemed by another account.
+/// * [`WeakPassword`] - The password is too short.
+/// * [`UsernameNotAvailable`] - The requested username has already been claimed by another user.
+/// * [`InvalidRole`] - The `default_roles` setting contains an unknown role.
 ///
 /// [`NoToken`]: crate::errors::DimError::NoToken
+/// [`InviteInvalid`]: crate::errors::DimError::InviteInvalid
+/// [`InviteExpired`]: crate::errors::DimError::InviteExpired
+/// [`InviteAlreadyClaimed`]: crate::errors::DimError::InviteAlreadyClaimed
+/// [`WeakPassword`]: crate::errors::DimError::WeakPassword
+/// [`UsernameNotAvailable`]: crate::errors::DimError::UsernameNotAvailable
+/// [`InvalidRole`]: crate::errors::DimError::InvalidRole
 /// [`Login`]: database::user::Login
 pub async fn register(
     new_user: Login,
     conn: DbConnection,
 ) -> Result<impl warp::Reply, errors::DimError> {
-    // FIXME: Return INTERNAL SERVER ERROR maybe with a traceback?
     let mut lock = conn.writer().lock_owned().await;
     let mut tx = database::write_tx(&mut lock).await?;
-    // NOTE: I doubt this method can faily all the time, we should map server error here too.
     let users_empty = User::get_all(&mut tx).await?.is_empty();
 
-    if !users_empty
-        && (new_user.invite_token.is_none() || !new_user.invite_token_valid(&mut tx).await?)
-    {
-        return Err(errors::DimError::NoToken);
-    }
+    check_password(&new_user.password)?;
 
-    let roles = database::user::Roles(if !users_empty {
-        vec!["user".to_string()]
+    let (roles, claimed_invite) = if users_empty {
+        (
+            database::user::Roles(vec!["owner".to_string()]),
+            Login::new_invite(&mut tx).await?,
+        )
     } else {
-        vec!["owner".to_string()]
-    });
+        let ttl = get_global_settings().invite_ttl.map(|x| x as i64);
+        new_user.check_invite(&mut tx, ttl).await?;
 
-    let claimed_invite = if users_empty {
-        // NOTE: Double check what we are returning here.
-        Login::new_invite(&mut tx).await?
-    } else {
-        new_user.invite_token.ok_or(errors::DimError::NoToken)?
+        (
+            default_roles()?,
+            new_user
+                .invite_token
+                .clone()
+                .ok_or(errors::DimError::NoToken)?,
+        )
     };
 
+    Login::claim_invite(&mut tx, &claimed_invite).await?;
+
     let res = InsertableUser {
         username: new_user.username.clone(),
         password: new_user.password.clone(),
@@ -207,10 +1275,33 @@ pub async fn register(
         prefs: Default::default(),
     }
     .insert(&mut tx)
-    .await?;
+    .await
+    .map_err(|e| match e {
+        // the invite has been claimed in this transaction, so only the username can collide.
+        DatabaseError::UniqueViolation(_) => errors::DimError::UsernameNotAvailable,
+        e => e.into(),
+    })?;
 
-    // FIXME: Return internal server error.
     tx.commit().await?;
 
-    Ok(reply::json(&json!({ "username": res.username })))
+    Ok(reply::json(&json!({
+        "id": res.id,
+        "username": res.username,
+        "roles": res.roles,
+    })))
+}
+
+/// Returns the roles given to newly created accounts, as configured by the `default_roles`
+/// setting.
+fn default_roles() -> Result<database::user::Roles, errors::DimError> {
+    let mut roles = get_global_settings().default_roles;
+
+    if roles.is_empty() {
+        roles.push("user".into());
+    }
+
+    let roles = database::user::Roles(roles);
+    roles.validate()?;
+
+    Ok(roles)
 }
@@ -1,7 +1,8 @@
-use crate::core::DbConnection;
 use crate::errors;
 use crate::json;
+use crate::routes::library::denied_libraries;
 
+use database::asset::resolve_url;
 use database::episode::Episode;
 use database::genre::*;
 use database::library::MediaType;
@@ -15,14 +16,12 @@ use serde_json::Value;
 use warp::reply;
 
 pub mod filters {
-    use database::DbConnection;
-
     use database::user::User;
+    use database::DbConnection;
     use warp::reject;
     use warp::Filter;
 
-    use crate::routes::global_filters::with_auth;
-
+    use super::super::global_filters::with_auth_tx;
     use super::super::global_filters::with_state;
 
     use tokio::runtime::Handle as TokioHandle;
@@ -33,12 +32,11 @@ pub mod filters {
     ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
         warp::path!("api" / "v1" / "dashboard")
             .and(warp::get())
-            .and(with_auth(conn.clone()))
-            .and(with_state::<DbConnection>(conn))
+            .and(with_auth_tx(conn))
             .and(with_state::<TokioHandle>(rt))
             .and_then(
-                |user: User, conn: DbConnection, rt: TokioHandle| async move {
-                    super::dashboard(conn, user, rt)
+                |user: User, tx: database::Transaction<'static>, rt: TokioHandle| async move {
+                    super::dashboard(tx, user, rt)
                         .await
                         .map_err(|e| reject::custom(e))
                 },
@@ -50,25 +48,26 @@ pub mod filters {
     ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
         warp::path!("api" / "v1" / "dashboard" / "banner")
             .and(warp::get())
-            .and(with_auth(conn.clone()))
-            .and(with_state::<DbConnection>(conn))
-            .and_then(|user: User, conn: DbConnection| async move {
-                super::banners(conn, user)
-                    .await
-                    .map_err(|e| reject::custom(e))
-            })
+            .and(with_auth_tx(conn))
+            .and_then(
+                |user: User, tx: database::Transaction<'static>| async move {
+                    super::banners(tx, user)
+                        .await
+                        .map_err(|e| reject::custom(e))
+                },
+            )
     }
 }
 
 pub async fn dashboard(
-    conn: DbConnection,
+    mut tx: database::Transaction<'static>,
     user: User,
     _rt: tokio::runtime::Handle,
 ) -> Result<impl warp::Reply, errors::DimError> {
-    let mut tx = conn.read().begin().await?;
+    let denied = denied_libraries(&mut tx, &user).await?;
 
     let mut top_rated = Vec::new();
-    for media in Media::get_top_rated(&mut tx, 10).await? {
+    for media in Media::get_top_rated(&mut tx, 10, &denied).await? {
         let item = match sqlx::query!(
             "SELECT _tblmedia.name, assets.local_path FROM _tblmedia LEFT JOIN assets ON assets.id = _tblmedia.poster
             WHERE _tblmedia.id = ?",
@@ -80,13 +79,13 @@ pub async fn dashboard(
 
         top_rated.push(json!({
             "id": media,
-            "poster_path": item.local_path,
+            "poster_path": item.local_path.as_deref().and_then(resolve_url),
             "name": item.name
         }));
     }
 
     let mut recently_added = Vec::new();
-    for media in Media::get_recently_added(&mut tx, 10).await? {
+    for media in Media::get_recently_added(&mut tx, 10, &denied).await? {
         let item = match sqlx::query!(
             "SELECT _tblmedia.name, assets.local_path FROM _tblmedia LEFT JOIN assets ON assets.id = _tblmedia.poster
             WHERE _tblmedia.id = ?",
@@ -98,13 +97,13 @@ pub async fn dashboard(
 
         recently_added.push(json!({
             "id": media,
-            "poster_path": item.local_path,
+            "poster_path": item.local_path.as_deref().and_then(resolve_url),
             "name": item.name
         }));
     }
 
     let mut continue_watching = Vec::new();
-    for media in Progress::get_continue_watching(&mut tx, user.id, 10).await? {
+    for media in Progress::get_continue_watching(&mut tx, user.id, 10, &denied).await? {
         let item = match sqlx::query!(
             "SELECT _tblmedia.name, assets.local_path FROM _tblmedia LEFT JOIN assets ON assets.id = _tblmedia.poster
             WHERE _tblmedia.id = ?",
@@ -116,7 +115,7 @@ pub async fn dashboard(
 
         continue_watching.push(json!({
             "id": media,
-            "poster_path": item.local_path,
+            "poster_path": item.local_path.as_deref().and_then(resolve_url),
             "name": item.name
         }));
     }
@@ -136,10 +135,13 @@ pub async fn dashboard(
     })))
 }
 
-pub async fn banners(conn: DbConnection, user: User) -> Result<impl warp::Reply, errors::DimError> {
-    let mut tx = conn.read().begin().await?;
+pub async fn banners(
+    mut tx: database::Transaction<'static>,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let denied = denied_libraries(&mut tx, &user).await?;
     let mut banners = Vec::new();
-    for media in Media::get_random_with(&mut tx, 10).await? {
+    for media in Media::get_random_with(&mut tx, 10, &denied).await? {
         if let Ok(x) = match media.media_type {
             MediaType::Tv => banner_for_show(&mut tx, &user, &media).await,
             MediaType::Movie => banner_for_movie(&mut tx, &user, &media).await,
@@ -181,7 +183,7 @@ async fn banner_for_movie(
         "title": media.name,
         "year": media.year,
         "synopsis": media.description,
-        "backdrop": media.backdrop_path,
+        "backdrop": media.backdrop_path.as_deref().and_then(resolve_url),
         "duration": media_duration,
         "genres": genres,
         "delta": progress,
@@ -248,7 +250,7 @@ async fn banner_for_show(
         "title": media.name,
         "year": media.year,
         "synopsis": media.description,
-        "backdrop": media.backdrop_path,
+        "backdrop": media.backdrop_path.as_deref().and_then(resolve_url),
         "duration": duration,
         "genres": genres,
         "delta": progress,
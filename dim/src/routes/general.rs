@@ -1,10 +1,12 @@
 use crate::core::DbConnection;
 use crate::errors;
+use crate::routes::library::denied_libraries;
 
 use database::user::User;
 use serde::Serialize;
 
 use database::genre::*;
+use database::media::Media;
 
 use tokio::task::spawn_blocking;
 
@@ -78,6 +80,34 @@ pub mod filters {
                 },
             )
     }
+
+    pub fn search_global(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct SearchArgs {
+            query: String,
+            #[serde(default = "default_limit")]
+            limit: i64,
+        }
+
+        fn default_limit() -> i64 {
+            30
+        }
+
+        warp::path!("api" / "v1" / "search" / "global")
+            .and(warp::get())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and(warp::query::query::<SearchArgs>())
+            .and_then(
+                |auth: User, conn: DbConnection, SearchArgs { query, limit }: SearchArgs| async move {
+                    super::search_global(conn, query, limit, auth)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
 }
 
 pub fn enumerate_directory<T: AsRef<std::path::Path>>(path: T) -> io::Result<Vec<String>> {
@@ -140,9 +170,10 @@ pub async fn search(
     _library_id: Option<i32>,
     genre: Option<String>,
     _quick: Option<bool>,
-    _user: User,
+    user: User,
 ) -> Result<warp::reply::Json, errors::DimError> {
     let mut tx = conn.read().begin().await?;
+    let denied = denied_libraries(&mut tx, &user).await?;
     if let Some(query_string) = query {
         let query_string = query_string
             .split(' ')
@@ -151,42 +182,85 @@ pub async fn search(
             .as_slice()
             .join(" ");
 
-        return search_by_name(&mut tx, &query_string, 15).await;
+        return search_by_name(&mut tx, &query_string, 15, &denied).await;
     }
 
     if let Some(x) = genre {
         let genre_id = Genre::get_by_name(&mut tx, x).await?.id;
-        return search_by_genre(&mut tx, genre_id).await;
+        return search_by_genre(&mut tx, genre_id, &denied).await;
     }
 
     if let Some(x) = year {
-        return search_by_release_year(&mut tx, x as i64).await;
+        return search_by_release_year(&mut tx, x as i64, &denied).await;
     }
 
     Err(errors::DimError::NotFoundError)
 }
 
+/// Method mapped to `GET /api/v1/search/global` searches movies, tv shows and episodes of all
+/// libraries at once. Media of restricted libraries the user hasnt been granted access to are left
+/// out.
+///
+/// # Arguments
+/// * `conn` - database connection
+/// * `query` - search query
+/// * `limit` - max number of results to return, defaults to 30
+/// * `user` - Auth middleware
+///
+/// # Return Schema
+/// ```text
+/// [
+///     {
+///         "id": int,
+///         "library_id": int,
+///         "name": string,
+///         "media_type": string | enum,
+///         "poster_path": string | uri_path,
+///         "show_name": string | null,
+///     },
+///     ...
+/// ]
+/// ```
+pub async fn search_global(
+    conn: DbConnection,
+    query: String,
+    limit: i64,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut tx = conn.read().begin().await?;
+    let denied = denied_libraries(&mut tx, &user).await?;
+    let results = Media::search_global(&mut tx, &query, limit, &denied).await?;
+
+    Ok(reply::json(&results))
+}
+
 async fn search_by_name(
     conn: &mut database::Transaction<'_>,
     query: &str,
     limit: i64,
+    denied: &[i64],
 ) -> Result<warp::reply::Json, errors::DimError> {
     #[derive(Serialize)]
     struct Record {
         id: i64,
         library_id: i64,
         name: String,
+        #[serde(serialize_with = "database::asset::serialize_url")]
         poster_path: Option<String>,
     }
 
+    let denied = database::json_ids(denied);
+
     let data = sqlx::query_as!(
         Record,
         r#"SELECT _tblmedia.id, library_id, name, assets.local_path as poster_path FROM _tblmedia
            LEFT JOIN assets on _tblmedia.poster = assets.id
-           WHERE NOT media_type = "episode"
+           WHERE NOT media_type = "episode" AND NOT is_hidden
            AND UPPER(name) LIKE ?
+           AND library_id NOT IN (SELECT value FROM json_each(?))
            LIMIT ?"#,
         query,
+        denied,
         limit
     )
     .fetch_all(conn)
@@ -199,25 +273,31 @@ async fn search_by_name(
 async fn search_by_genre(
     conn: &mut database::Transaction<'_>,
     genre_id: i64,
+    denied: &[i64],
 ) -> Result<warp::reply::Json, errors::DimError> {
     #[derive(Serialize)]
     struct Record {
         id: i64,
         library_id: i64,
         name: String,
+        #[serde(serialize_with = "database::asset::serialize_url")]
         poster_path: Option<String>,
     }
 
+    let denied = database::json_ids(denied);
+
     let data = sqlx::query_as!(
         Record,
         r#"SELECT _tblmedia.id, library_id, name, assets.local_path as poster_path
                 FROM _tblmedia
                 LEFT JOIN assets on _tblmedia.poster = assets.id
                 INNER JOIN genre_media ON genre_media.media_id = _tblmedia.id
-                WHERE NOT media_type = "episode"
+                WHERE NOT media_type = "episode" AND NOT is_hidden
                 AND genre_media.genre_id = ?
+                AND library_id NOT IN (SELECT value FROM json_each(?))
                 "#,
         genre_id,
+        denied,
     )
     .fetch_all(conn)
     .await
@@ -229,24 +309,30 @@ async fn search_by_genre(
 async fn search_by_release_year(
     conn: &mut database::Transaction<'_>,
     year: i64,
+    denied: &[i64],
 ) -> Result<warp::reply::Json, errors::DimError> {
     #[derive(Serialize)]
     struct Record {
         id: i64,
         library_id: i64,
         name: String,
+        #[serde(serialize_with = "database::asset::serialize_url")]
         poster_path: Option<String>,
     }
 
+    let denied = database::json_ids(denied);
+
     let data = sqlx::query_as!(
         Record,
         r#"SELECT _tblmedia.id, library_id, name, assets.local_path as poster_path
                 FROM _tblmedia
             LEFT JOIN assets on _tblmedia.poster = assets.id
-                WHERE NOT media_type = "episode"
+                WHERE NOT media_type = "episode" AND NOT is_hidden
                 AND year = ?
+                AND library_id NOT IN (SELECT value FROM json_each(?))
                 "#,
         year,
+        denied,
     )
     .fetch_all(conn)
     .await
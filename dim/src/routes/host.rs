@@ -2,11 +2,20 @@
 use crate::core::DbConnection;
 use crate::errors;
 use crate::json;
+use crate::routes::settings::get_global_settings;
+use crate::routes::settings::reload_global_settings;
+use database::user::Permission;
 use database::user::User;
+use database::user::MIN_PASSWORD_LEN;
+use http::StatusCode;
+use std::time::Instant;
+use tracing::info;
 use warp::reply;
 
 /// # GET `/api/v1/host/admin_exists`
-/// Method will hint to the client whether an admin has already been created on this server.
+/// Method will hint to the client whether an admin has already been created on this server,
+/// along with what the setup wizard needs to render the right registration form. Once the owner
+/// has registered, every further account needs an invite.
 ///
 /// # Authentication
 /// This method does not require any authentication tokens and is fully public.
@@ -19,22 +28,226 @@ use warp::reply;
 /// # Response
 /// ```
 /// {
-///   "exists": bool
+///   "exists": bool,
+///   "invite_required": bool,
+///   "min_password_length": usize
 /// }
 /// ```
 pub async fn admin_exists(conn: DbConnection) -> Result<impl warp::Reply, errors::DimError> {
     let mut tx = conn.read().begin().await?;
+    let exists = User::any_exist(&mut tx).await?;
+
     Ok(reply::json(&json!({
-        "exists": !User::get_all(&mut tx).await?.is_empty()
+        "exists": exists,
+        "invite_required": exists,
+        "min_password_length": MIN_PASSWORD_LEN,
     })))
 }
 
+/// # GET `/api/v1/host/health`
+/// Method returns the state of the database connection pool alongside the latency of a trivial
+/// database round-trip. This is useful when diagnosing slow responses caused by pool exhaustion.
+///
+/// # Authorization
+/// This route requires a valid authentication token to be supplied. The token must have `owner`
+/// permissions.
+///
+/// ## Example
+/// ```text
+/// curl -X GET http://127.0.0.1:8000/api/v1/host/health -H "Authorization: ...."
+/// ```
+///
+/// # Response
+/// ```
+/// {
+///   "pool": {
+///     "idle": u32,
+///     "active": u32,
+///     "max_connections": u32,
+///     "writer_busy": bool
+///   },
+///   "latency_ms": f64
+/// }
+/// ```
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+pub async fn health(conn: DbConnection, user: User) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ViewHostInfo)?;
+
+    let pool = conn.pool_status();
+
+    let now = Instant::now();
+    sqlx::query("SELECT 1").execute(conn.read_ref()).await?;
+    let latency_ms = now.elapsed().as_secs_f64() * 1000.0;
+
+    Ok(reply::json(&json!({
+        "pool": pool,
+        "latency_ms": latency_ms,
+    })))
+}
+
+/// # GET `/api/v1/host/live`
+/// Method is a liveness probe, it always succeeds as long as the server is able to answer
+/// requests.
+///
+/// # Authentication
+/// This method does not require any authentication tokens and is fully public.
+///
+/// ## Example
+/// ```text
+/// curl -X GET http://127.0.0.1:8000/api/v1/host/live
+/// ```
+///
+/// # Response
+/// This method will return a 200 with an empty body.
+pub async fn live() -> Result<impl warp::Reply, errors::DimError> {
+    Ok(StatusCode::OK)
+}
+
+/// # GET `/api/v1/host/ready`
+/// Method is a readiness probe, it only succeeds once the database can be queried, the migrations
+/// have been applied and the metadata directory is writable.
+///
+/// # Authentication
+/// This method does not require any authentication tokens and is fully public.
+///
+/// ## Example
+/// ```text
+/// curl -X GET http://127.0.0.1:8000/api/v1/host/ready
+/// ```
+///
+/// # Response
+/// This method will return a 200 if the server is ready and a 503 otherwise, alongside the state
+/// of every check.
+/// ```
+/// {
+///   "ready": bool,
+///   "database": bool,
+///   "migrations": bool,
+///   "metadata_writable": bool
+/// }
+/// ```
+pub async fn ready(conn: DbConnection) -> Result<impl warp::Reply, errors::DimError> {
+    let database = sqlx::query("SELECT 1")
+        .execute(conn.read_ref())
+        .await
+        .is_ok();
+    let migrations = database::migrations_done();
+    let metadata_writable = crate::core::METADATA_PATH
+        .get()
+        .map(|path| crate::utils::ensure_writable_dir(path).is_ok())
+        .unwrap_or(false);
+
+    let ready = database && migrations && metadata_writable;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    Ok(reply::with_status(
+        reply::json(&json!({
+            "ready": ready,
+            "database": database,
+            "migrations": migrations,
+            "metadata_writable": metadata_writable,
+        })),
+        status,
+    ))
+}
+
+/// # GET `/api/v1/host/info`
+/// Method returns the version of this server, the database backend it has been built with and the
+/// optional features that are enabled, so that clients can check compatibility and adapt to the
+/// server before logging in.
+///
+/// # Authentication
+/// This method does not require any authentication tokens and is fully public.
+///
+/// ## Example
+/// ```text
+/// curl -X GET http://127.0.0.1:8000/api/v1/host/info
+/// ```
+///
+/// # Response
+/// ```
+/// {
+///   "version": "0.3.0-rc6",
+///   "database": "sqlite",
+///   "features": ["embed_ui", "hwaccel", "cookie_auth"]
+/// }
+/// ```
+pub async fn info() -> Result<impl warp::Reply, errors::DimError> {
+    let database = if cfg!(feature = "postgres") {
+        "postgres"
+    } else {
+        "sqlite"
+    };
+
+    let settings = get_global_settings();
+    let features = [
+        ("embed_ui", cfg!(feature = "embed_ui")),
+        ("vaapi", cfg!(feature = "vaapi")),
+        ("hwaccel", settings.enable_hwaccel),
+        ("cookie_auth", settings.cookie_auth),
+        ("public_avatars", settings.public_avatars),
+    ]
+    .iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(feature, _)| *feature)
+    .collect::<Vec<_>>();
+
+    Ok(reply::json(&json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "database": database,
+        "features": features,
+    })))
+}
+
+/// # POST `/api/v1/host/reload_config`
+/// Method re-reads the config file and applies the new settings without restarting the server.
+/// Settings that are only read on boot, like `port` or `enable_ssl`, still require a restart.
+///
+/// # Authorization
+/// This route requires a valid authentication token to be supplied. The token must have `owner`
+/// permissions.
+///
+/// ## Example
+/// ```text
+/// curl -X POST http://127.0.0.1:8000/api/v1/host/reload_config -H "Authorization: ...."
+/// ```
+///
+/// # Response
+/// If the config has been reloaded, this method returns the new global settings.
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+/// * [`InvalidConfig`] - The config file couldnt be read or parsed, the current settings are kept.
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+/// [`InvalidConfig`]: crate::errors::DimError::InvalidConfig
+pub async fn reload_config(user: User) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageSettings)?;
+
+    let settings = reload_global_settings().map_err(|e| errors::DimError::InvalidConfig {
+        description: e.to_string(),
+    })?;
+
+    info!("Reloaded global settings.");
+
+    Ok(reply::json(&settings))
+}
+
 #[doc(hidden)]
 pub(crate) mod filters {
     use crate::core::DbConnection;
     use warp::reject;
     use warp::Filter;
 
+    use super::super::global_filters::with_auth;
     use super::super::global_filters::with_state;
 
     pub fn admin_exists(
@@ -49,4 +262,50 @@ pub(crate) mod filters {
                     .map_err(|e| reject::custom(e))
             })
     }
+
+    pub fn health(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "host" / "health")
+            .and(warp::get())
+            .and(with_auth(conn.clone()))
+            .and(with_state(conn))
+            .and_then(|user, conn: DbConnection| async move {
+                super::health(conn, user).await.map_err(reject::custom)
+            })
+    }
+
+    pub fn live() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "host" / "live")
+            .and(warp::get())
+            .and_then(|| async move { super::live().await.map_err(reject::custom) })
+    }
+
+    pub fn info() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "host" / "info")
+            .and(warp::get())
+            .and_then(|| async move { super::info().await.map_err(reject::custom) })
+    }
+
+    pub fn ready(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "host" / "ready")
+            .and(warp::get())
+            .and(with_state(conn))
+            .and_then(|conn: DbConnection| async move {
+                super::ready(conn).await.map_err(reject::custom)
+            })
+    }
+
+    pub fn reload_config(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "host" / "reload_config")
+            .and(warp::post())
+            .and(with_auth(conn))
+            .and_then(
+                |user| async move { super::reload_config(user).await.map_err(reject::custom) },
+            )
+    }
 }
@@ -8,7 +8,9 @@ use crate::core::DbConnection;
 use crate::errors;
 use crate::json;
 
+use database::audit::AuditLog;
 use database::user::Login;
+use database::user::Permission;
 use database::user::User;
 
 use http::StatusCode;
@@ -63,45 +65,44 @@ pub async fn get_all_invites(
     conn: DbConnection,
     user: User,
 ) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageInvites)?;
+
     let mut tx = conn.read().begin().await?;
-    if user.has_role("owner") {
-        #[derive(serde::Serialize)]
-        struct Row {
-            id: String,
-            created: i64,
-            claimed_by: Option<String>,
-        }
-
-        // FIXME: LEFT JOINs cause sqlx::query! to panic, thus we must get tokens in two queries.
-        // TODO: Move these into database.
-        // TODO: We silently drop db errors here, we should probably change this.
-        let mut row = sqlx::query_as!(
+
+    #[derive(serde::Serialize)]
+    struct Row {
+        id: String,
+        created: i64,
+        claimed_by: Option<String>,
+    }
+
+    // FIXME: LEFT JOINs cause sqlx::query! to panic, thus we must get tokens in two queries.
+    // TODO: Move these into database.
+    // TODO: We silently drop db errors here, we should probably change this.
+    let mut row = sqlx::query_as!(
+        Row,
+        r#"SELECT invites.id, invites.date_added as created, NULL as "claimed_by: _"
+            FROM invites
+            WHERE invites.id NOT IN (SELECT users.claimed_invite FROM users)
+            ORDER BY created ASC"#
+    )
+    .fetch_all(&mut tx)
+    .await
+    .unwrap_or_default();
+
+    row.append(
+        &mut sqlx::query_as!(
             Row,
-            r#"SELECT invites.id, invites.date_added as created, NULL as "claimed_by: _"
-                FROM invites
-                WHERE invites.id NOT IN (SELECT users.claimed_invite FROM users)
-                ORDER BY created ASC"#
+            r#"SELECT invites.id, invites.date_added as created, users.username as "claimed_by: Option<String>"
+        FROM  invites
+        INNER JOIN users ON users.claimed_invite = invites.id"#
         )
         .fetch_all(&mut tx)
         .await
-        .unwrap_or_default();
-
-        row.append(
-            &mut sqlx::query_as!(
-                Row,
-                r#"SELECT invites.id, invites.date_added as created, users.username as "claimed_by: Option<String>"
-            FROM  invites
-            INNER JOIN users ON users.claimed_invite = invites.id"#
-            )
-            .fetch_all(&mut tx)
-            .await
-            .unwrap_or_default(),
-        );
-
-        return Ok(reply::json(&row));
-    }
+        .unwrap_or_default(),
+    );
 
-    Err(errors::DimError::Unauthorized)
+    Ok(reply::json(&row))
 }
 
 /// # POST `/api/v1/auth/new_invite`
@@ -140,14 +141,20 @@ pub async fn generate_invite(
     conn: DbConnection,
     user: User,
 ) -> Result<impl warp::Reply, errors::DimError> {
-    if !user.has_role("owner") {
-        return Err(errors::DimError::Unauthorized);
-    }
+    user.require(Permission::ManageInvites)?;
 
     let mut lock = conn.writer().lock_owned().await;
     let mut tx = database::write_tx(&mut lock).await?;
 
     let token = Login::new_invite(&mut tx).await?;
+    AuditLog::record(
+        &mut tx,
+        Some(&user.username),
+        "invite_created",
+        Some(&token),
+        None,
+    )
+    .await?;
 
     tx.commit().await?;
 
@@ -180,13 +187,20 @@ pub async fn delete_invite(
     user: User,
     token: String,
 ) -> Result<impl warp::Reply, errors::DimError> {
-    if !user.has_role("owner") {
-        return Err(errors::DimError::Unauthorized);
-    }
+    user.require(Permission::ManageInvites)?;
 
     let mut lock = conn.writer().lock_owned().await;
     let mut tx = database::write_tx(&mut lock).await?;
-    Login::delete_token(&mut tx, token).await?;
+    if Login::delete_token(&mut tx, token.clone()).await? > 0 {
+        AuditLog::record(
+            &mut tx,
+            Some(&user.username),
+            "invite_deleted",
+            Some(&token),
+            None,
+        )
+        .await?;
+    }
     tx.commit().await?;
 
     Ok(StatusCode::OK)
@@ -5,23 +5,38 @@ use crate::scanners;
 use crate::scanners::scanner_daemon::FsWatcher;
 use crate::tree;
 
+use database::audit::AuditLog;
 use database::compact_mediafile::CompactMediafile;
 use database::library::InsertableLibrary;
 use database::library::Library;
+use database::library::LibraryStats;
+use database::library::MediaType;
+use database::library::UpdateLibrary;
+use database::media::AddedGranularity;
 use database::media::Media;
+use database::media::PageCursor;
 use database::mediafile::MediaFile;
 
+use database::user::Permission;
 use database::user::User;
+use database::DatabaseError;
 use events::Message;
 use events::PushEventType;
 
 use std::collections::HashMap;
 
+use warp::http::header::HeaderValue;
+use warp::http::header::CONTENT_TYPE;
+use warp::http::Response;
 use warp::http::StatusCode;
+use warp::hyper::Body;
 use warp::reply;
 
+use futures::StreamExt;
+
 use serde::Deserialize;
 use serde::Serialize;
+use serde_json::json;
 
 use tracing::error;
 use tracing::info;
@@ -30,6 +45,12 @@ use tracing::instrument;
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
 
+/// Maximum number of media `/api/v1/library/<id>/random` picks at once.
+const MAX_RANDOM: i64 = 100;
+
+/// Maximum number of media `/api/v1/library/<id>/media/page` returns at once.
+const MAX_PAGE: i64 = 200;
+
 pub mod filters {
     use warp::reject;
     use warp::Filter;
@@ -98,6 +119,59 @@ pub mod filters {
             )
     }
 
+    pub fn library_patch(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "library" / i64)
+            .and(warp::patch())
+            .and(warp::body::json::<UpdateLibrary>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64, data: UpdateLibrary, user: User, conn: DbConnection| async move {
+                    super::library_patch(conn, id, data, user)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn library_rename(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct Body {
+            name: String,
+        }
+
+        warp::path!("api" / "v1" / "library" / i64 / "name")
+            .and(warp::put())
+            .and(warp::body::json::<Body>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64, Body { name }: Body, user: User, conn: DbConnection| async move {
+                    super::library_rename(conn, id, name, user)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn library_empty(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "library" / i64 / "media")
+            .and(warp::delete())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(|id: i64, user: User, conn: DbConnection| async move {
+                super::library_empty(conn, id, user)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
+
     pub fn library_get_self(
         conn: DbConnection,
     ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
@@ -115,231 +189,1589 @@ pub mod filters {
     pub fn get_all_of_library(
         conn: DbConnection,
     ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct Args {
+            #[serde(default)]
+            include_hidden: bool,
+        }
+
         warp::path!("api" / "v1" / "library" / i64 / "media")
+            .and(warp::get())
+            .and(warp::query::<Args>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64, Args { include_hidden }: Args, user: User, conn: DbConnection| async move {
+                    super::get_all_library(conn, id, include_hidden, user)
+                        .await
+                        .map_err(|e| reject::custom(e))
+                },
+            )
+    }
+
+    pub fn get_all_of_library_stream(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "library" / i64 / "media" / "stream")
             .and(warp::get())
             .and(with_auth(conn.clone()))
             .and(with_state::<DbConnection>(conn))
             .and_then(|id: i64, user: User, conn: DbConnection| async move {
-                super::get_all_library(conn, id, user)
+                super::get_all_library_stream(conn, id, user)
                     .await
-                    .map_err(|e| reject::custom(e))
+                    .map_err(reject::custom)
             })
     }
 
-    pub fn get_all_unmatched_media(
+    pub fn get_page_of_library(
         conn: DbConnection,
     ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
         #[derive(Deserialize)]
         struct Args {
-            search: Option<String>,
+            after_added: Option<String>,
+            after_id: Option<i64>,
+            #[serde(default = "default_limit")]
+            limit: i64,
         }
 
-        warp::path!("api" / "v1" / "library" / i64 / "unmatched")
+        fn default_limit() -> i64 {
+            50
+        }
+
+        warp::path!("api" / "v1" / "library" / i64 / "media" / "page")
             .and(warp::get())
+            .and(warp::query::<Args>())
             .and(with_auth(conn.clone()))
             .and(with_state::<DbConnection>(conn))
-            .and(warp::filters::query::query::<Args>())
             .and_then(
-                |id: i64, user: User, conn: DbConnection, Args { search }: Args| async move {
-                    super::get_all_unmatched_media(conn, id, user, search)
+                |id: i64,
+                 Args {
+                     after_added,
+                     after_id,
+                     limit,
+                 }: Args,
+                 user: User,
+                 conn: DbConnection| async move {
+                    let after = after_id.map(|id| PageCursor {
+                        added: after_added.unwrap_or_default(),
+                        id,
+                    });
+
+                    super::get_page_of_library(conn, id, user, after, limit)
                         .await
-                        .map_err(|e| reject::custom(e))
+                        .map_err(reject::custom)
                 },
             )
     }
-}
 
-/// Method maps to `GET /api/v1/library` and returns a list of all libraries in te database.
-/// This method can only be accessed by authenticated users.
-///
-/// # Arguments
-/// * `conn` - database connection
-/// * `_log` - logger
-/// * `_user` - Authentication middleware
-pub async fn library_get(
-    conn: DbConnection,
-    _user: User,
-) -> Result<impl warp::Reply, errors::DimError> {
-    let mut tx = conn.read().begin().await?;
-    Ok(reply::json(&{
-        let mut x = Library::get_all(&mut tx).await;
-        x.sort_by(|a, b| a.name.cmp(&b.name));
-        x
-    }))
-}
+    pub fn get_unwatched_of_library(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct Args {
+            #[serde(default = "default_limit")]
+            limit: i64,
+        }
 
-/// Method maps to `POST /api/v1/library`, it adds a new library to the database, starts a new
-/// scanner for it, then dispatches a event to all clients notifying them that a new library has
-/// been created. This method can only be accessed by authenticated users. Method returns 200 OK
-///
-/// # Arguments
-/// * `conn` - database connection
-/// * `new_library` - new library information posted by client
-/// * `log` - logger
-/// * `_user` - Auth middleware
-pub async fn library_post(
-    conn: DbConnection,
-    new_library: InsertableLibrary,
-    event_tx: EventTx,
-    _user: User,
-) -> Result<impl warp::Reply, errors::DimError> {
-    let mut lock = conn.writer().lock_owned().await;
-    let mut tx = database::write_tx(&mut lock).await?;
-    let id = new_library.insert(&mut tx).await?;
-    tx.commit().await?;
-    drop(lock);
+        fn default_limit() -> i64 {
+            50
+        }
 
-    let tx_clone = event_tx.clone();
+        warp::path!("api" / "v1" / "library" / i64 / "unwatched")
+            .and(warp::get())
+            .and(warp::query::<Args>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64, Args { limit }: Args, user: User, conn: DbConnection| async move {
+                    super::get_unwatched_of_library(conn, id, user, limit)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
 
-    let fs_watcher = FsWatcher::new(conn.clone(), id, new_library.media_type, tx_clone.clone());
-    tokio::spawn(async move { fs_watcher.start_daemon().await });
-    tokio::spawn(scanners::start(conn, id, tx_clone));
+    pub fn get_library_timeline(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct Args {
+            #[serde(default)]
+            granularity: AddedGranularity,
+        }
 
-    let event = Message {
-        id,
-        event_type: PushEventType::EventNewLibrary,
-    };
+        warp::path!("api" / "v1" / "library" / i64 / "timeline")
+            .and(warp::get())
+            .and(warp::query::<Args>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64, Args { granularity }: Args, user: User, conn: DbConnection| async move {
+                    super::get_library_timeline(conn, id, user, granularity)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
 
-    let _ = event_tx.send(serde_json::to_string(&event).unwrap());
+    pub fn get_random_of_library(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct Args {
+            #[serde(default = "default_count")]
+            count: i64,
+            media_type: Option<MediaType>,
+        }
 
-    Ok(StatusCode::CREATED)
-}
+        fn default_count() -> i64 {
+            1
+        }
 
-/// Method mapped to `DELETE /api/v1/library/<id>` is used to delete a library from the database.
-/// It deletes the database based on the parameter `id`, then dispatches a event notifying all
-/// clients that the database with this id has been removed. Method can only be accessed by
-/// authenticated users.
-///
-/// # Arguments:
-/// * `conn` - database connection
-/// * `id` - id of the library we want to delete
-/// * `event_tx` - channel over which to dispatch events
-/// * `_user` - Auth middleware
-// NOTE: Should we only allow the owner to add/remove libraries?
-#[instrument(err, skip(conn, event_tx, _user), fields(auth.user = _user.username.as_str()))]
-pub async fn library_delete(
-    id: i64,
-    _user: User,
-    conn: DbConnection,
-    event_tx: EventTx,
-) -> Result<impl warp::Reply, errors::DimError> {
-    // First we mark the library as scheduled for deletion which will make the library and all its
-    // content hidden. This is necessary because huge libraries take a long time to delete.
-    {
-        let mut lock = conn.writer().lock_owned().await;
-        let mut tx = database::write_tx(&mut lock).await?;
-        if Library::mark_hidden(&mut tx, id).await? < 1 {
-            return Err(errors::DimError::LibraryNotFound);
+        warp::path!("api" / "v1" / "library" / i64 / "random")
+            .and(warp::get())
+            .and(warp::query::<Args>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64,
+                 Args { count, media_type }: Args,
+                 user: User,
+                 conn: DbConnection| async move {
+                    super::get_random_of_library(conn, id, user, count, media_type)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn get_tagged_of_library(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct Args {
+            tag: String,
         }
-        tx.commit().await?;
+
+        warp::path!("api" / "v1" / "library" / i64 / "tagged")
+            .and(warp::get())
+            .and(warp::query::<Args>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64, Args { tag }: Args, user: User, conn: DbConnection| async move {
+                    super::get_tagged_of_library(conn, id, user, tag)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
     }
 
-    let delete_lib_fut = async move {
-        let inner = async {
-            let mut lock = conn.writer().lock_owned().await;
-            let mut tx = database::write_tx(&mut lock).await?;
+    pub fn sync_library(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct Args {
+            #[serde(default)]
+            since: i64,
+        }
 
-            Library::delete(&mut tx, id).await?;
-            Media::delete_by_lib_id(&mut tx, id).await?;
-            MediaFile::delete_by_lib_id(&mut tx, id).await?;
+        warp::path!("api" / "v1" / "library" / i64 / "sync")
+            .and(warp::get())
+            .and(warp::query::<Args>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64, Args { since }: Args, user: User, conn: DbConnection| async move {
+                    super::sync_library(conn, id, user, since)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
 
-            tx.commit().await?;
+    pub fn get_scan_status(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "library" / i64 / "scan_status")
+            .and(warp::get())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(|id: i64, user: User, conn: DbConnection| async move {
+                super::get_scan_status(conn, id, user)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
 
-            Ok::<_, database::error::DatabaseError>(())
-        };
+    pub fn get_scan_schedule(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "library" / i64 / "scan_schedule")
+            .and(warp::get())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(|id: i64, user: User, conn: DbConnection| async move {
+                super::get_scan_schedule(conn, id, user)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
 
-        if let Err(e) = inner.await {
-            error!(reason = ?e, "Failed to delete library and its content.");
-        } else {
-            info!("Deleted library");
+    pub fn set_scan_schedule(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct Body {
+            interval: i64,
         }
-    };
 
-    let event = Message {
-        id,
-        event_type: PushEventType::EventRemoveLibrary,
-    };
+        warp::path!("api" / "v1" / "library" / i64 / "scan_schedule")
+            .and(warp::put())
+            .and(warp::body::json::<Body>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64, Body { interval }: Body, user: User, conn: DbConnection| async move {
+                    super::set_scan_schedule(conn, id, interval, user)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
 
-    let _ = event_tx.send(serde_json::to_string(&event).unwrap());
+    pub fn clear_scan_schedule(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "library" / i64 / "scan_schedule")
+            .and(warp::delete())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(|id: i64, user: User, conn: DbConnection| async move {
+                super::clear_scan_schedule(conn, id, user)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
+
+    pub fn get_scan_issues(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "library" / i64 / "issues")
+            .and(warp::get())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(|id: i64, user: User, conn: DbConnection| async move {
+                super::get_scan_issues(conn, id, user)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
+
+    pub fn get_library_access(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "library" / i64 / "access")
+            .and(warp::get())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(|id: i64, user: User, conn: DbConnection| async move {
+                super::get_library_access(conn, id, user)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
+
+    pub fn set_library_access(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct Params {
+            username: String,
+        }
+
+        let grant = warp::post().map(|| true);
+        let revoke = warp::delete().map(|| false);
+
+        warp::path!("api" / "v1" / "library" / i64 / "access")
+            .and(grant.or(revoke).unify())
+            .and(warp::body::json::<Params>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64,
+                 grant: bool,
+                 Params { username }: Params,
+                 user: User,
+                 conn: DbConnection| async move {
+                    super::set_library_access(conn, id, user, username, grant)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn set_library_restricted(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct Params {
+            restricted: bool,
+        }
+
+        warp::path!("api" / "v1" / "library" / i64 / "access")
+            .and(warp::patch())
+            .and(warp::body::json::<Params>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64, Params { restricted }: Params, user: User, conn: DbConnection| async move {
+                    super::set_library_restricted(conn, id, user, restricted)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn get_all_unmatched_media(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct Args {
+            search: Option<String>,
+        }
+
+        warp::path!("api" / "v1" / "library" / i64 / "unmatched")
+            .and(warp::get())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and(warp::filters::query::query::<Args>())
+            .and_then(
+                |id: i64, user: User, conn: DbConnection, Args { search }: Args| async move {
+                    super::get_all_unmatched_media(conn, id, user, search)
+                        .await
+                        .map_err(|e| reject::custom(e))
+                },
+            )
+    }
+}
+
+/// Method maps to `GET /api/v1/library` and returns a list of all libraries in te database.
+/// This method can only be accessed by authenticated users.
+///
+/// Restricted libraries the user hasnt been granted access to are left out. Every library comes
+/// with a `media_count` field holding how many movies or tv shows it holds, hidden media aside.
+///
+/// # Arguments
+/// * `conn` - database connection
+/// * `user` - Authentication middleware
+pub async fn library_get(
+    conn: DbConnection,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    #[derive(Serialize)]
+    struct Record {
+        #[serde(flatten)]
+        library: Library,
+        media_count: i64,
+    }
+
+    let mut tx = conn.read().begin().await?;
+    let mut libraries = Library::get_all_with_counts(&mut tx).await?;
+
+    if !user.can(Permission::AccessAllLibraries) {
+        let allowed = Library::get_all_for_user(&mut tx, user.id)
+            .await
+            .into_iter()
+            .map(|x| x.id)
+            .collect::<Vec<_>>();
+
+        libraries.retain(|(library, _)| allowed.contains(&library.id));
+    }
+
+    libraries.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+
+    Ok(reply::json(
+        &libraries
+            .into_iter()
+            .map(|(library, media_count)| Record {
+                library,
+                media_count,
+            })
+            .collect::<Vec<_>>(),
+    ))
+}
+
+/// Method maps to `POST /api/v1/library`, it adds a new library to the database, starts a new
+/// scanner for it, then dispatches a event to all clients notifying them that a new library has
+/// been created. This method can only be accessed by authenticated users. Method returns 200 OK
+///
+/// # Arguments
+/// * `conn` - database connection
+/// * `new_library` - new library information posted by client
+/// * `log` - logger
+/// * `_user` - Auth middleware
+///
+/// # Errors
+/// * [`LibraryExists`] - The name is already used or one of the supplied locations is already
+/// indexed by another library.
+/// * [`InvalidLibraryName`] - The name is blank.
+///
+/// [`LibraryExists`]: crate::errors::DimError::LibraryExists
+/// [`InvalidLibraryName`]: crate::errors::DimError::InvalidLibraryName
+pub async fn library_post(
+    conn: DbConnection,
+    new_library: InsertableLibrary,
+    event_tx: EventTx,
+    _user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    let id = new_library.insert(&mut tx).await?;
+    tx.commit().await?;
+    drop(lock);
+
+    let tx_clone = event_tx.clone();
+
+    let fs_watcher = FsWatcher::new(conn.clone(), id, new_library.media_type, tx_clone.clone());
+    tokio::spawn(async move { fs_watcher.start_daemon().await });
+    tokio::spawn(scanners::start(conn, id, tx_clone));
+
+    let event = Message {
+        id,
+        event_type: PushEventType::EventNewLibrary,
+    };
+
+    let _ = event_tx.send(serde_json::to_string(&event).unwrap());
+
+    Ok(StatusCode::CREATED)
+}
+
+/// Method mapped to `DELETE /api/v1/library/<id>` is used to delete a library from the database.
+/// It deletes the database based on the parameter `id`, then dispatches a event notifying all
+/// clients that the database with this id has been removed. Method can only be accessed by
+/// authenticated users.
+///
+/// # Arguments:
+/// * `conn` - database connection
+/// * `id` - id of the library we want to delete
+/// * `event_tx` - channel over which to dispatch events
+/// * `_user` - Auth middleware
+// NOTE: Should we only allow the owner to add/remove libraries?
+#[instrument(err, skip(conn, event_tx, _user), fields(auth.user = _user.username.as_str()))]
+pub async fn library_delete(
+    id: i64,
+    _user: User,
+    conn: DbConnection,
+    event_tx: EventTx,
+) -> Result<impl warp::Reply, errors::DimError> {
+    // First we mark the library as scheduled for deletion which will make the library and all its
+    // content hidden. This is necessary because huge libraries take a long time to delete.
+    {
+        let mut lock = conn.writer().lock_owned().await;
+        let mut tx = database::write_tx(&mut lock).await?;
+        if Library::mark_hidden(&mut tx, id).await? < 1 {
+            return Err(errors::DimError::LibraryNotFound);
+        }
+        tx.commit().await?;
+    }
+
+    let delete_lib_fut = async move {
+        let inner = async {
+            let mut lock = conn.writer().lock_owned().await;
+            let mut tx = database::write_tx(&mut lock).await?;
+
+            let summary = Library::purge(&mut tx, id).await?;
+            Library::delete(&mut tx, id).await?;
+
+            tx.commit().await?;
+
+            Ok::<_, database::error::DatabaseError>(summary)
+        };
+
+        match inner.await {
+            Ok(summary) => info!(?summary, "Deleted library"),
+            Err(e) => error!(reason = ?e, "Failed to delete library and its content."),
+        }
+    };
+
+    let event = Message {
+        id,
+        event_type: PushEventType::EventRemoveLibrary,
+    };
+
+    let _ = event_tx.send(serde_json::to_string(&event).unwrap());
 
     tokio::spawn(delete_lib_fut);
 
     Ok(StatusCode::NO_CONTENT)
 }
 
-/// Method mapped to `GET /api/v1/library/<id>` returns info about the library with the supplied
-/// id. Method can only be accessed by authenticated users.
+/// Method mapped to `PATCH /api/v1/library/<id>` updates the name, the media type, the
+/// `is_private` flag or the description of a library. Sending an empty description removes it.
+/// The media type can only be changed while the library holds no media, as switching the type of
+/// a populated library would leave media that doesnt match it.
+/// This method requires the `owner` role.
 ///
 /// # Arguments
 /// * `conn` - database connection
-/// * `id` - id of the library we want info of
-/// * `_user` - Auth middleware
+/// * `id` - id of the library we want to update
+/// * `data` - fields to update
+/// * `user` - Auth middleware
+///
+/// # Errors
+/// * [`LibraryNotEmpty`] - The media type of a library that holds media was changed.
+/// * [`InvalidLibraryName`] - The new name is blank.
+/// * [`LibraryExists`] - Another library already uses the new name.
+///
+/// [`LibraryNotEmpty`]: crate::errors::DimError::LibraryNotEmpty
+/// [`InvalidLibraryName`]: crate::errors::DimError::InvalidLibraryName
+/// [`LibraryExists`]: crate::errors::DimError::LibraryExists
+pub async fn library_patch(
+    conn: DbConnection,
+    id: i64,
+    data: UpdateLibrary,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    data.update(&mut tx, id).await.map_err(|e| match e {
+        DatabaseError::NotFound => errors::DimError::LibraryNotFound,
+        e => e.into(),
+    })?;
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// # PUT `/api/v1/library/<id>/name`
+/// Method renames a library. Leading and trailing whitespace is stripped from the new name, and
+/// no two libraries can share a name.
+///
+/// # Authorization
+/// This method requires the user to have the `owner` role.
+///
+/// # Request
+/// ```
+/// {
+///   "name": "Movies"
+/// }
+/// ```
+///
+/// # Example
+/// ```text
+/// curl -X PUT http://127.0.0.1:8000/api/v1/library/1/name -H "Content-type: application/json" -H "Authorization: ..." -d '{"name": "Movies"}'
+/// ```
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+/// * [`LibraryNotFound`] - The library does not exist.
+/// * [`InvalidLibraryName`] - The new name is blank.
+/// * [`LibraryExists`] - Another library already uses this name.
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+/// [`LibraryNotFound`]: crate::errors::DimError::LibraryNotFound
+/// [`InvalidLibraryName`]: crate::errors::DimError::InvalidLibraryName
+/// [`LibraryExists`]: crate::errors::DimError::LibraryExists
+pub async fn library_rename(
+    conn: DbConnection,
+    id: i64,
+    name: String,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    Library::rename(&mut tx, id, &name)
+        .await
+        .map_err(|e| match e {
+            DatabaseError::NotFound => errors::DimError::LibraryNotFound,
+            e => e.into(),
+        })?;
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// # DELETE `/api/v1/library/<id>/media`
+/// Method removes all media of a library, along with their files, progress, favorites, tags and
+/// ratings, but keeps the library itself. This is useful ahead of a full rescan after the layout
+/// on disk changed drastically. Everything is removed in a single transaction.
+///
+/// # Authorization
+/// This method requires the `owner` role.
+///
+/// # Example
+/// ```text
+/// curl -X DELETE http://127.0.0.1:8000/api/v1/library/1/media -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// ```text
+/// {
+///   "mediafiles": 120,
+///   "progress": 14,
+///   "movies": 118,
+///   "tv_shows": 0,
+///   "seasons": 0,
+///   "episodes": 0,
+///   "media": 118
+/// }
+/// ```
+///
+/// # Errors
+/// * [`LibraryNotFound`] - The library does not exist.
+///
+/// [`LibraryNotFound`]: crate::errors::DimError::LibraryNotFound
+pub async fn library_empty(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    Library::get_one(&mut tx, id)
+        .await
+        .map_err(|_| errors::DimError::LibraryNotFound)?;
+
+    let summary = Library::empty(&mut tx, id).await?;
+    tx.commit().await?;
+
+    info!(library_id = id, ?summary, "Emptied library");
+
+    Ok(reply::json(&summary))
+}
+
+/// # GET `/api/v1/library/<id>`
+/// Method returns info about the library with the supplied id along with how many media and
+/// files it holds, ie for a library settings page. The locations the library indexes are left out
+/// unless the user has the `owner` role.
+///
+/// # Authorization
+/// This method requires the user to be logged in and to have access to the library.
+///
+/// # Example
+/// ```text
+/// curl -X GET http://127.0.0.1:8000/api/v1/library/1 -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// ```text
+/// {
+///   "id": 1,
+///   "name": "Movies",
+///   "locations": ["/media/movies"],
+///   "media_type": "movie",
+///   "is_private": false,
+///   "description": null,
+///   "stats": {
+///     "media": 118,
+///     "episodes": 0,
+///     "mediafiles": 120,
+///     "unmatched": 2
+///   }
+/// }
+/// ```
+///
+/// # Errors
+/// * [`NotFoundError`] - The library does not exist or the user cant access it.
+///
+/// [`NotFoundError`]: crate::errors::DimError::NotFoundError
 pub async fn get_self(
     conn: DbConnection,
     id: i64,
-    _user: User,
+    user: User,
 ) -> Result<impl warp::Reply, errors::DimError> {
     let mut tx = conn.read().begin().await?;
-    Ok(reply::json(&Library::get_one(&mut tx, id).await?))
+    check_library_access(&mut tx, &user, id).await?;
+
+    let mut library = Library::get_one(&mut tx, id).await?;
+    if !user.can(Permission::ManageLibraries) {
+        library.locations.clear();
+    }
+
+    #[derive(Serialize)]
+    struct Record {
+        #[serde(flatten)]
+        library: Library,
+        stats: LibraryStats,
+    }
+
+    Ok(reply::json(&Record {
+        stats: Library::get_stats(&mut tx, id).await?,
+        library,
+    }))
 }
 
 /// Method mapped to `GET /api/v1/library/<id>/media` returns all the movies/tv shows that belong
 /// to the library with the id supplied. Method can only be accessed by authenticated users.
+/// Hidden media are left out unless `?include_hidden=true` is passed, which requires the `owner`
+/// role.
 ///
 /// # Arguments
 /// * `conn` - database connection
 /// * `id` - id of the library we want media of
-/// * `_user` - Auth middleware
+/// * `include_hidden` - whether to include hidden media
+/// * `user` - Auth middleware
 pub async fn get_all_library(
     conn: DbConnection,
     id: i64,
-    _user: User,
+    include_hidden: bool,
+    user: User,
 ) -> Result<impl warp::Reply, errors::DimError> {
+    if include_hidden {
+        user.require(Permission::ManageLibraries)?;
+    }
+
     let mut result = HashMap::new();
     let mut tx = conn.read().begin().await?;
+    check_library_access(&mut tx, &user, id).await?;
     let lib = Library::get_one(&mut tx, id).await?;
 
     #[derive(Serialize)]
     struct Record {
         id: i64,
         name: String,
+        #[serde(serialize_with = "database::asset::serialize_url")]
         poster_path: Option<String>,
     }
 
-    let mut data = sqlx::query_as!(
+    let data = sqlx::query_as!(
         Record,
         r#"SELECT _tblmedia.id, name, assets.local_path as poster_path FROM _tblmedia
         LEFT JOIN assets ON _tblmedia.poster = assets.id
-        WHERE library_id = ? AND NOT media_type = "episode""#,
-        id
+        WHERE library_id = ? AND NOT media_type = "episode" AND (? OR NOT is_hidden)
+        ORDER BY _tblmedia.sort_title"#,
+        id,
+        include_hidden
     )
     .fetch_all(&mut tx)
     .await
     .map_err(|_| errors::DimError::NotFoundError)?;
 
-    data.sort_by(|a, b| a.name.cmp(&b.name));
-
     result.insert(lib.name, data);
 
     Ok(reply::json(&result))
 }
 
+/// # GET `/api/v1/library/<id>/media/page`
+/// Method returns a page of the movies and tv shows of a library, oldest first, for infinite
+/// scrolling. Pages are keyed on the `added` timestamp and id of the last media of the previous
+/// page, so media added in the meantime doesnt shift the pages.
+///
+/// # Authorization
+/// This method requires the user to be logged in and to have access to the library.
+///
+/// # Query params
+/// * `after_added` - `added` of the cursor returned with the previous page, optional.
+/// * `after_id` - `id` of the cursor returned with the previous page, omit for the first page.
+/// * `limit` - max number of medias to return, defaults to 50 and is capped at 200.
+///
+/// # Example
+/// ```text
+/// curl -X GET "http://127.0.0.1:8000/api/v1/library/1/media/page?after_added=2022-06-13T12:34:56Z&after_id=42" -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// ```text
+/// {
+///   "media": [{"id":43,"library_id":1,"name":"Metropolis","media_type":"movie",...}, ...],
+///   "next": {"added":"2022-06-14T08:00:00Z","id":92}
+/// }
+/// ```
+/// `next` is `null` on the last page.
+pub async fn get_page_of_library(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+    after: Option<PageCursor>,
+    limit: i64,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut tx = conn.read().begin().await?;
+    check_library_access(&mut tx, &user, id).await?;
+
+    Ok(reply::json(
+        &Media::get_page_after(&mut tx, id, after.as_ref(), limit.clamp(1, MAX_PAGE)).await?,
+    ))
+}
+
+/// # GET `/api/v1/library/<id>/unwatched`
+/// Method returns the movies and tv shows of a library the user hasnt finished watching yet, most
+/// recently added first. Tv shows are included as long as at least one of their episodes is
+/// unwatched. This backs the "unwatched only" filter of the library view.
+///
+/// # Authorization
+/// This method requires the user to be logged in and to have access to the library.
+///
+/// # Query params
+/// * `limit` - max number of medias to return, defaults to 50.
+///
+/// # Example
+/// ```text
+/// curl -X GET "http://127.0.0.1:8000/api/v1/library/1/unwatched?limit=10" -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// ```text
+/// [
+///   {"id":2,"library_id":1,"name":"Metropolis","media_type":"movie",...},
+///   ...
+/// ]
+/// ```
+pub async fn get_unwatched_of_library(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+    limit: i64,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut tx = conn.read().begin().await?;
+    check_library_access(&mut tx, &user, id).await?;
+
+    Ok(reply::json(
+        &Media::get_unwatched(&mut tx, id, user.id, limit).await?,
+    ))
+}
+
+/// # GET `/api/v1/library/<id>/timeline`
+/// Method returns how many movies and tv shows have been added to a library per month or day,
+/// newest first. Days or months in which nothing was added are left out.
+///
+/// # Authorization
+/// This method requires the user to be logged in and to have access to the library.
+///
+/// # Query params
+/// * `granularity` - either `day` or `month`, defaults to `month`.
+///
+/// # Example
+/// ```text
+/// curl -X GET "http://127.0.0.1:8000/api/v1/library/1/timeline?granularity=day" -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// ```text
+/// [
+///   {"date": "2022-06-13", "count": 4},
+///   {"date": "2022-06-01", "count": 12},
+///   ...
+/// ]
+/// ```
+pub async fn get_library_timeline(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+    granularity: AddedGranularity,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut tx = conn.read().begin().await?;
+    check_library_access(&mut tx, &user, id).await?;
+
+    Ok(reply::json(
+        &Media::get_added_grouped(&mut tx, id, granularity).await?,
+    ))
+}
+
+/// # GET `/api/v1/library/<id>/random`
+/// Method returns random movies and tv shows of a library, for features like shuffle or "surprise
+/// me".
+///
+/// # Authorization
+/// This method requires the user to be logged in and to have access to the library.
+///
+/// # Query params
+/// * `count` - how many media to pick at most, defaults to 1 and is capped at 100.
+/// * `media_type` - only pick `movie` or `tv` media, optional.
+///
+/// # Example
+/// ```text
+/// curl -X GET "http://127.0.0.1:8000/api/v1/library/1/random?count=5" -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// ```text
+/// [
+///   {"id":2,"library_id":1,"name":"Metropolis","media_type":"movie",...},
+///   ...
+/// ]
+/// ```
+pub async fn get_random_of_library(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+    count: i64,
+    media_type: Option<MediaType>,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut tx = conn.read().begin().await?;
+    check_library_access(&mut tx, &user, id).await?;
+
+    Ok(reply::json(
+        &Media::get_random(&mut tx, id, count.clamp(0, MAX_RANDOM), media_type).await?,
+    ))
+}
+
+/// # GET `/api/v1/library/<id>/tagged`
+/// Method returns the movies and tv shows of a library that carry a tag, sorted by name.
+///
+/// # Authorization
+/// This method requires the user to be logged in and to have access to the library.
+///
+/// # Query params
+/// * `tag` - name of the tag, matched case-insensitively.
+///
+/// # Example
+/// ```text
+/// curl -X GET "http://127.0.0.1:8000/api/v1/library/1/tagged?tag=4k" -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// ```text
+/// [
+///   {"id":2,"library_id":1,"name":"Metropolis","media_type":"movie",...},
+///   ...
+/// ]
+/// ```
+///
+/// # Errors
+/// * [`InvalidTag`] - The tag is empty.
+///
+/// [`InvalidTag`]: crate::errors::DimError::InvalidTag
+pub async fn get_tagged_of_library(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+    tag: String,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut tx = conn.read().begin().await?;
+    check_library_access(&mut tx, &user, id).await?;
+
+    Ok(reply::json(&Media::get_by_tag(&mut tx, id, &tag).await?))
+}
+
+/// # GET `/api/v1/library/<id>/sync`
+/// Method returns what changed in a library since the last sync, so that clients dont have to
+/// fetch the whole library again. `changed` holds the movies and tv shows that have been added or
+/// whose metadata changed, `deleted` the ids of movies and tv shows that have been removed.
+///
+/// Clients should store the returned `cursor` and pass it as `since` on their next sync. Media
+/// changed in the same second as the cursor may be returned twice.
+///
+/// # Authorization
+/// This method requires the user to be logged in and to have access to the library.
+///
+/// # Query params
+/// * `since` - cursor returned by the previous sync, omit it to fetch everything.
+///
+/// # Example
+/// ```text
+/// curl -X GET "http://127.0.0.1:8000/api/v1/library/1/sync?since=1655121600" -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// ```text
+/// {
+///   "cursor": 1655125200,
+///   "changed": [{"id":2,"library_id":1,"name":"Metropolis","media_type":"movie",...}],
+///   "deleted": [5, 8]
+/// }
+/// ```
+pub async fn sync_library(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+    since: i64,
+) -> Result<impl warp::Reply, errors::DimError> {
+    // NOTE: the cursor is taken before querying so changes made while we query show up next time.
+    let cursor = database::unix_now() as i64;
+
+    let mut tx = conn.read().begin().await?;
+    check_library_access(&mut tx, &user, id).await?;
+    Library::get_one(&mut tx, id)
+        .await
+        .map_err(|_| errors::DimError::LibraryNotFound)?;
+
+    Ok(reply::json(&json!({
+        "cursor": cursor,
+        "changed": Media::get_changed_since(&mut tx, id, since).await?,
+        "deleted": Media::get_deleted_since(&mut tx, id, since).await?,
+    })))
+}
+
+/// # GET `/api/v1/library/<id>/media/stream`
+/// Method streams all the movies and tv shows that belong to a library as newline delimited JSON,
+/// one media object per line, ordered by name. Unlike [`get_all_library`] the result is never
+/// fully materialized, which makes this the better choice for very large libraries.
+///
+/// # Authorization
+/// This method requires the user to be logged in.
+///
+/// # Example
+/// ```text
+/// curl -X GET http://127.0.0.1:8000/api/v1/library/1/media/stream -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// The response has the content type `application/x-ndjson`:
+/// ```text
+/// {"id":1,"library_id":1,"name":"Blade Runner","media_type":"movie",...}
+/// {"id":2,"library_id":1,"name":"Metropolis","media_type":"movie",...}
+/// ```
+///
+/// If the database fails midway through, the response body is aborted.
+pub async fn get_all_library_stream(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut tx = conn.read().begin().await?;
+    check_library_access(&mut tx, &user, id).await?;
+    // make sure the library exists before we commit to a response.
+    let _ = Library::get_one(&mut tx, id).await?;
+
+    let (mut sender, body) = Body::channel();
+
+    tokio::spawn(async move {
+        let mut rows = sqlx::query_as!(
+            Media,
+            r#"SELECT id, library_id, name, description, rating, year, added, poster_path as "poster_path?", backdrop_path as "backdrop_path?", duration, tmdb_id as "tmdb_id: i32", imdb_id, added_by, media_type as "media_type: _"
+            FROM media WHERE library_id = ? AND NOT media_type = "episode" AND NOT is_hidden
+            ORDER BY sort_title"#,
+            id
+        )
+        .fetch(&mut tx);
+
+        while let Some(row) = rows.next().await {
+            let media = match row {
+                Ok(x) => x,
+                Err(e) => {
+                    error!(reason = ?e, library_id = id, "Failed to stream library media.");
+                    sender.abort();
+                    return;
+                }
+            };
+
+            let mut line = match serde_json::to_vec(&media) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!(reason = ?e, media_id = media.id, "Failed to serialize media.");
+                    continue;
+                }
+            };
+            line.push(b'\n');
+
+            // the client has gone away.
+            if sender.send_data(line.into()).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    let mut response = Response::new(body);
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/x-ndjson"),
+    );
+
+    Ok(response)
+}
+
+/// # GET `/api/v1/library/<id>/scan_status`
+/// Method returns the state of the last scan of a library. Scans that were interrupted by a
+/// restart are reported as `failed`.
+///
+/// # Authorization
+/// This method requires the user to be logged in and to have access to the library.
+///
+/// # Example
+/// ```text
+/// curl -X GET http://127.0.0.1:8000/api/v1/library/1/scan_status -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// ```
+/// {
+///   "library_id": 1,
+///   "status": "idle" | "scanning" | "failed",
+///   "started_at": 1655208000 | null,
+///   "finished_at": 1655208120 | null,
+///   "files_processed": 154
+/// }
+/// ```
+///
+/// # Errors
+/// * [`NotFoundError`] - The library doesnt exist or the user cant access it.
+///
+/// [`NotFoundError`]: crate::errors::DimError::NotFoundError
+pub async fn get_scan_status(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut tx = conn.read().begin().await?;
+    check_library_access(&mut tx, &user, id).await?;
+    let _ = Library::get_one(&mut tx, id).await?;
+
+    Ok(reply::json(&Library::scan_status(&mut tx, id).await?))
+}
+
+/// # GET `/api/v1/library/<id>/scan_schedule`
+/// Method returns how often a library is rescanned automatically and when the next scheduled scan
+/// runs. Libraries without a schedule return `null`.
+///
+/// # Authorization
+/// This method requires the user to have the `owner` role.
+///
+/// # Example
+/// ```text
+/// curl -X GET http://127.0.0.1:8000/api/v1/library/1/scan_schedule -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// ```
+/// {
+///   "library_id": 1,
+///   "interval": 86400,
+///   "next_run": 1655294400
+/// }
+/// ```
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+/// * [`LibraryNotFound`] - The library does not exist.
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+/// [`LibraryNotFound`]: crate::errors::DimError::LibraryNotFound
+pub async fn get_scan_schedule(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    let mut tx = conn.read().begin().await?;
+    Library::get_one(&mut tx, id)
+        .await
+        .map_err(|_| errors::DimError::LibraryNotFound)?;
+
+    Ok(reply::json(&Library::get_scan_schedule(&mut tx, id).await?))
+}
+
+/// # PUT `/api/v1/library/<id>/scan_schedule`
+/// Method makes a library get rescanned every `interval` seconds, replacing any previous schedule.
+/// The first scheduled scan runs one interval from now. Schedules are kept across restarts, and a
+/// scheduled scan is skipped if the library is still being scanned.
+///
+/// # Authorization
+/// This method requires the user to have the `owner` role.
+///
+/// # Request
+/// ```
+/// {
+///   "interval": 86400
+/// }
+/// ```
+///
+/// # Example
+/// ```text
+/// curl -X PUT http://127.0.0.1:8000/api/v1/library/1/scan_schedule -H "Content-type: application/json" -H "Authorization: ..." -d '{"interval": 86400}'
+/// ```
+///
+/// # Response
+/// The new schedule, same as [`get_scan_schedule`].
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+/// * [`LibraryNotFound`] - The library does not exist.
+/// * [`InvalidScanInterval`] - The interval is shorter than 5 minutes.
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+/// [`LibraryNotFound`]: crate::errors::DimError::LibraryNotFound
+/// [`InvalidScanInterval`]: crate::errors::DimError::InvalidScanInterval
+pub async fn set_scan_schedule(
+    conn: DbConnection,
+    id: i64,
+    interval: i64,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    Library::get_one(&mut tx, id)
+        .await
+        .map_err(|_| errors::DimError::LibraryNotFound)?;
+
+    let schedule = Library::set_scan_schedule(&mut tx, id, interval).await?;
+    tx.commit().await?;
+
+    Ok(reply::json(&schedule))
+}
+
+/// # DELETE `/api/v1/library/<id>/scan_schedule`
+/// Method stops the automatic rescans of a library.
+///
+/// # Authorization
+/// This method requires the user to have the `owner` role.
+///
+/// # Example
+/// ```text
+/// curl -X DELETE http://127.0.0.1:8000/api/v1/library/1/scan_schedule -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// If the schedule has been removed this method returns `204 No Content`.
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+/// * [`NotFoundError`] - The library has no scan schedule.
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+/// [`NotFoundError`]: crate::errors::DimError::NotFoundError
+pub async fn clear_scan_schedule(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    if Library::clear_scan_schedule(&mut tx, id).await? < 1 {
+        return Err(errors::DimError::NotFoundError);
+    }
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// # GET `/api/v1/library/<id>/issues`
+/// Method returns a report of the problems the scanner ran into for a library. `unmatched`
+/// contains the files which couldnt be matched to any media and `orphaned` contains the media
+/// which no longer have any files backing them.
+///
+/// # Authorization
+/// This method requires the user to have the `owner` role.
+///
+/// # Example
+/// ```text
+/// curl -X GET http://127.0.0.1:8000/api/v1/library/1/issues -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// ```
+/// {
+///   "unmatched": [
+///     { "id": 12, "target_file": "/media/movies/some.file.mkv", "raw_name": "some file" }
+///   ],
+///   "orphaned": [
+///     { "id": 3, "name": "Blade Runner", "media_type": "movie" }
+///   ]
+/// }
+/// ```
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+pub async fn get_scan_issues(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    let mut tx = conn.read().begin().await?;
+    let _ = Library::get_one(&mut tx, id).await?;
+
+    #[derive(Serialize)]
+    struct Unmatched {
+        id: i64,
+        target_file: String,
+        raw_name: String,
+    }
+
+    #[derive(Serialize)]
+    struct Orphaned {
+        id: i64,
+        name: String,
+        media_type: MediaType,
+    }
+
+    let unmatched = MediaFile::get_unmatched(&mut tx, id)
+        .await?
+        .into_iter()
+        .map(|x| Unmatched {
+            id: x.id,
+            target_file: x.target_file,
+            raw_name: x.raw_name,
+        })
+        .collect::<Vec<_>>();
+
+    let orphaned = Media::get_orphaned(&mut tx, id)
+        .await?
+        .into_iter()
+        .map(|x| Orphaned {
+            id: x.id,
+            name: x.name,
+            media_type: x.media_type,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(reply::json(&json!({
+        "unmatched": unmatched,
+        "orphaned": orphaned,
+    })))
+}
+
+/// # GET `/api/v1/library/<id>/access`
+/// Method returns whether a library is restricted and the users that have been granted access to
+/// it. Libraries that arent restricted can be browsed by every user.
+///
+/// # Authorization
+/// This method requires the user to have the `owner` role.
+///
+/// # Example
+/// ```text
+/// curl -X GET http://127.0.0.1:8000/api/v1/library/1/access -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// ```
+/// {
+///   "restricted": true,
+///   "users": ["kid"]
+/// }
+/// ```
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+pub async fn get_library_access(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    let mut tx = conn.read().begin().await?;
+    let _ = Library::get_one(&mut tx, id).await?;
+
+    let mut users = Vec::new();
+    for uid in Library::get_access(&mut tx, id).await? {
+        users.push(User::get_by_id(&mut tx, uid).await?.username);
+    }
+    users.sort();
+
+    Ok(reply::json(&json!({
+        "restricted": Library::is_restricted(&mut tx, id).await?,
+        "users": users,
+    })))
+}
+
+/// # POST/DELETE `/api/v1/library/<id>/access`
+/// Method grants (`POST`) or revokes (`DELETE`) the access of a user to a library. Granting access
+/// restricts the library, so it is hidden from everyone else except owners. Revoking the last
+/// grant keeps the library restricted, see [`set_library_restricted`] to open it up again.
+///
+/// # Authorization
+/// This method requires the user to have the `owner` role.
+///
+/// # Request
+/// ```
+/// {
+///   "username": String
+/// }
+/// ```
+///
+/// ## Example
+/// ```text
+/// curl -X POST http://127.0.0.1:8000/api/v1/library/1/access -H "Content-type: application/json" -H "Authorization: ..." -d '{"username": "kid"}'
+/// ```
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+pub async fn set_library_access(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+    username: String,
+    grant: bool,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    let _ = Library::get_one(&mut tx, id).await?;
+    let target = User::get(&mut tx, &username)
+        .await
+        .map_err(|_| errors::DimError::UserNotFound)?;
+
+    let (rows, action) = if grant {
+        (
+            Library::grant_access(&mut tx, id, target.id).await?,
+            "library_access_granted",
+        )
+    } else {
+        (
+            Library::revoke_access(&mut tx, id, target.id).await?,
+            "library_access_revoked",
+        )
+    };
+
+    if rows > 0 {
+        AuditLog::record(
+            &mut tx,
+            Some(&user.username),
+            action,
+            Some(&target.username),
+            Some(&format!("library {}", id)),
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(StatusCode::OK)
+}
+
+/// # PATCH `/api/v1/library/<id>/access`
+/// Method restricts a library or opens it up to every user again. Restricted libraries can only be
+/// browsed by owners and the users that have been granted access, see [`set_library_access`].
+///
+/// # Authorization
+/// This method requires the user to have the `owner` role.
+///
+/// # Request
+/// ```
+/// {
+///   "restricted": bool
+/// }
+/// ```
+///
+/// ## Example
+/// ```text
+/// curl -X PATCH http://127.0.0.1:8000/api/v1/library/1/access -H "Content-type: application/json" -H "Authorization: ..." -d '{"restricted": false}'
+/// ```
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+pub async fn set_library_restricted(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+    restricted: bool,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    let _ = Library::get_one(&mut tx, id).await?;
+
+    if Library::is_restricted(&mut tx, id).await? != restricted {
+        Library::set_restricted(&mut tx, id, restricted).await?;
+        AuditLog::record(
+            &mut tx,
+            Some(&user.username),
+            if restricted {
+                "library_restricted"
+            } else {
+                "library_unrestricted"
+            },
+            None,
+            Some(&format!("library {}", id)),
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(StatusCode::OK)
+}
+
+/// Method checks whether `user` may browse the library with the supplied id. Users with the
+/// [`AccessAllLibraries`](Permission::AccessAllLibraries) permission can browse every library.
+pub(crate) async fn can_access_library(
+    tx: &mut database::Transaction<'_>,
+    user: &User,
+    library_id: i64,
+) -> Result<bool, database::DatabaseError> {
+    if user.can(Permission::AccessAllLibraries) {
+        return Ok(true);
+    }
+
+    Library::can_access(&mut *tx, library_id, user.id).await
+}
+
+/// Same as [`can_access_library`] except it fails with [`NotFoundError`] so that restricted
+/// libraries are indistinguishable from libraries that dont exist.
+///
+/// [`NotFoundError`]: crate::errors::DimError::NotFoundError
+pub(crate) async fn check_library_access(
+    tx: &mut database::Transaction<'_>,
+    user: &User,
+    library_id: i64,
+) -> Result<(), errors::DimError> {
+    if !can_access_library(&mut *tx, user, library_id).await? {
+        return Err(errors::DimError::NotFoundError);
+    }
+
+    Ok(())
+}
+
+/// Method checks whether `user` may browse the library the media with the supplied id belongs
+/// to. Fails with [`NotFoundError`] if the media doesnt exist or is restricted.
+///
+/// [`NotFoundError`]: crate::errors::DimError::NotFoundError
+pub(crate) async fn check_media_access(
+    tx: &mut database::Transaction<'_>,
+    user: &User,
+    media_id: i64,
+) -> Result<(), errors::DimError> {
+    let library_id = Media::get(&mut *tx, media_id)
+        .await
+        .map_err(|_| errors::DimError::NotFoundError)?
+        .library_id;
+
+    check_library_access(&mut *tx, user, library_id).await
+}
+
+/// Method returns the ids of the libraries `user` isnt allowed to browse, used to filter results
+/// that span several libraries.
+pub(crate) async fn denied_libraries(
+    tx: &mut database::Transaction<'_>,
+    user: &User,
+) -> Result<Vec<i64>, errors::DimError> {
+    if user.can(Permission::AccessAllLibraries) {
+        return Ok(vec![]);
+    }
+
+    Ok(Library::get_denied_ids(&mut *tx, user.id).await?)
+}
+
 /// Method mapped to `GET` /api/v1/library/<id>/unmatched` returns a list of all unmatched medias
 /// to be displayed in the library pages.
 ///
 /// # Arguments
 /// * `conn` - database connection
 /// * `id` - id of the library
-/// * `_user` - auth middleware
+/// * `user` - auth middleware
 /// * `search` - query to fuzzy match against
 // NOTE: construct_standard on a mediafile will yield buggy deltas
 pub async fn get_all_unmatched_media(
     conn: DbConnection,
     id: i64,
-    _user: User,
+    user: User,
     search: Option<String>,
 ) -> Result<impl warp::Reply, errors::DimError> {
     let mut tx = conn.read().begin().await?;
+    check_library_access(&mut tx, &user, id).await?;
 
     let mut files = CompactMediafile::unmatched_for_library(&mut tx, id)
         .await
@@ -1,25 +1,42 @@
 use crate::core::DbConnection;
 use crate::errors;
+use crate::get_global_settings;
 use crate::json;
+use crate::routes::library::check_media_access;
 use crate::scanners::ApiMedia;
 use crate::tree;
+use crate::utils::etag_for;
+use crate::utils::etag_matches;
 
+use database::user::Permission;
 use database::user::User;
 
+use database::asset::resolve_url;
+use database::audio_track::AudioTrack;
 use database::compact_mediafile::CompactMediafile;
 use database::episode::Episode;
+use database::favorite::Favorite;
 use database::genre::Genre;
 use database::library::MediaType;
 use database::media::Media;
 use database::media::UpdateMedia;
 use database::mediafile::MediaFile;
 use database::progress::Progress;
+use database::rating::Rating;
+use database::rating::UserRating;
+use database::subtitle::Subtitle;
+use database::tag::Tag;
+use database::DatabaseError;
 
 use warp::http::status::StatusCode;
 use warp::reply;
 
 use std::collections::HashMap;
+use std::path::Path;
 
+use tracing::warn;
+
+use serde::Deserialize;
 use serde::Serialize;
 
 pub mod filters {
@@ -42,11 +59,14 @@ pub mod filters {
             .and(warp::get())
             .and(with_state::<DbConnection>(conn.clone()))
             .and(with_auth(conn))
-            .and_then(|id: i64, conn: DbConnection, user: User| async move {
-                super::get_media_by_id(conn, id, user)
-                    .await
-                    .map_err(|e| reject::custom(e))
-            })
+            .and(warp::header::optional::<String>("if-none-match"))
+            .and_then(
+                |id: i64, conn: DbConnection, user: User, if_none_match: Option<String>| async move {
+                    super::get_media_by_id(conn, id, user, if_none_match)
+                        .await
+                        .map_err(|e| reject::custom(e))
+                },
+            )
     }
 
     pub fn get_media_files(
@@ -56,13 +76,27 @@ pub mod filters {
             .and(warp::get())
             .and(with_state::<DbConnection>(conn.clone()))
             .and(with_auth(conn))
-            .and_then(|id: i64, conn: DbConnection, _user: User| async move {
-                super::get_media_files(conn, id)
+            .and_then(|id: i64, conn: DbConnection, user: User| async move {
+                super::get_media_files(conn, id, user)
                     .await
                     .map_err(|e| reject::custom(e))
             })
     }
 
+    pub fn get_stream_info(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "media" / i64 / "stream_info")
+            .and(warp::get())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(|id: i64, auth: User, conn: DbConnection| async move {
+                super::get_stream_info(conn, id, auth)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
+
     pub fn get_mediafile_tree(
         conn: DbConnection,
     ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
@@ -70,8 +104,8 @@ pub mod filters {
             .and(warp::get())
             .and(with_state::<DbConnection>(conn.clone()))
             .and(with_auth(conn))
-            .and_then(|id, conn, _user| async move {
-                super::get_mediafile_tree(conn, id)
+            .and_then(|id, conn, user| async move {
+                super::get_mediafile_tree(conn, id, user)
                     .await
                     .map_err(reject::custom)
             })
@@ -140,6 +174,7 @@ pub mod filters {
         #[derive(Deserialize)]
         struct RouteArgs {
             offset: i64,
+            duration: Option<i64>,
         }
 
         warp::path!("api" / "v1" / "media" / i64 / "progress")
@@ -147,21 +182,216 @@ pub mod filters {
             .and(warp::query::query::<RouteArgs>())
             .and(with_state::<DbConnection>(conn.clone()))
             .and(with_auth(conn))
-            .and_then(|id: i64, RouteArgs { offset }: RouteArgs, conn: DbConnection, auth: User| async move {
-                super::map_progress(conn, id, offset, auth)
+            .and_then(
+                |id: i64,
+                 RouteArgs { offset, duration }: RouteArgs,
+                 conn: DbConnection,
+                 auth: User| async move {
+                    super::map_progress(conn, id, offset, duration, auth)
+                        .await
+                        .map_err(|e| reject::custom(e))
+                },
+            )
+    }
+
+    pub fn add_favorite(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "media" / i64 / "favorite")
+            .and(warp::post())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(|id: i64, auth: User, conn: DbConnection| async move {
+                super::add_favorite(conn, id, auth)
                     .await
-                    .map_err(|e| reject::custom(e))
+                    .map_err(reject::custom)
+            })
+    }
+
+    pub fn remove_favorite(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "media" / i64 / "favorite")
+            .and(warp::delete())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(|id: i64, auth: User, conn: DbConnection| async move {
+                super::remove_favorite(conn, id, auth)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
+
+    pub fn get_tags(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "media" / i64 / "tags")
+            .and(warp::get())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(|id: i64, auth: User, conn: DbConnection| async move {
+                super::get_tags(conn, id, auth)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
+
+    pub fn add_tag(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "media" / i64 / "tags")
+            .and(warp::post())
+            .and(warp::body::json::<super::TagBody>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64, body: super::TagBody, auth: User, conn: DbConnection| async move {
+                    super::add_tag(conn, id, body.tag, auth)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn remove_tag(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "media" / i64 / "tags")
+            .and(warp::delete())
+            .and(warp::body::json::<super::TagBody>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64, body: super::TagBody, auth: User, conn: DbConnection| async move {
+                    super::remove_tag(conn, id, body.tag, auth)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn set_rating(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "media" / i64 / "ratings")
+            .and(warp::post())
+            .and(warp::body::json::<super::RatingBody>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64, body: super::RatingBody, auth: User, conn: DbConnection| async move {
+                    super::set_rating(conn, id, body, auth)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn set_hidden(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct Body {
+            hidden: bool,
+        }
+
+        warp::path!("api" / "v1" / "media" / i64 / "hidden")
+            .and(warp::put())
+            .and(warp::body::json::<Body>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64, Body { hidden }: Body, auth: User, conn: DbConnection| async move {
+                    super::set_hidden(conn, id, hidden, auth)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn get_user_rating(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "media" / i64 / "rating")
+            .and(warp::get())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(|id: i64, auth: User, conn: DbConnection| async move {
+                super::get_user_rating(conn, id, auth)
+                    .await
+                    .map_err(reject::custom)
             })
     }
+
+    pub fn set_user_rating(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "media" / i64 / "rating")
+            .and(warp::put())
+            .and(warp::body::json::<super::UserRatingBody>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |id: i64, body: super::UserRatingBody, auth: User, conn: DbConnection| async move {
+                    super::set_user_rating(conn, id, body, auth)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn refresh_stale(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct RouteArgs {
+            library_id: i64,
+            #[serde(default)]
+            force: bool,
+            #[serde(default = "default_max_age")]
+            max_age: i64,
+        }
+
+        fn default_max_age() -> i64 {
+            super::DEFAULT_REFRESH_MAX_AGE
+        }
+
+        warp::path!("api" / "v1" / "media" / "refresh")
+            .and(warp::post())
+            .and(warp::body::json::<RouteArgs>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |RouteArgs {
+                     library_id,
+                     force,
+                     max_age,
+                 }: RouteArgs,
+                 auth: User,
+                 conn: DbConnection| async move {
+                    super::refresh_stale(conn, auth, library_id, force, max_age)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
 }
 
+/// Media whose metadata is older than this many seconds is refreshed by [`refresh_stale`] by
+/// default.
+pub const DEFAULT_REFRESH_MAX_AGE: i64 = 30 * 24 * 60 * 60;
+
 /// Method mapped to `GET /api/v1/media/<id>` returns info about a media based on the id queried.
 /// This method can only be accessed by authenticated users.
 ///
+/// The response carries a `ETag` derived from its body. Clients can send it back in a
+/// `If-None-Match` header, in which case `304 Not Modified` is returned while nothing changed.
+///
 /// # Arguments
 /// * `conn` - database connection
 /// * `id` - id of the media we want to query info of
-/// * `_user` - Auth middleware
+/// * `user` - Auth middleware
+/// * `if_none_match` - value of the `If-None-Match` header
 ///
 /// # Return Schema
 /// ```text
@@ -176,7 +406,28 @@ pub mod filters {
 ///     "poster_path": string | uri_path,
 ///     "backdrop_path": string | uri_path,
 ///     "media_type": string | enum,
+///     "added_by": string | null,
+///     "is_hidden": bool,
 ///     "genres": [string],
+///     "ratings": [{"source": string, "score": int}],
+///     "audio_tracks": [{
+///         "id": int,
+///         "mediafile_id": int,
+///         "stream_index": int,
+///         "language": string | null,
+///         "codec": string,
+///         "channels": int | null,
+///         "is_default": bool,
+///     }],
+///     "subtitles": [{
+///         "id": int,
+///         "mediafile_id": int,
+///         "language": string | null,
+///         "codec": string,
+///         "external_path": string | null,
+///         "stream_index": int | null,
+///         "forced": bool,
+///     }],
 ///     "duration": int,
 ///     "duration_pretty": string,
 /// }
@@ -188,8 +439,10 @@ pub async fn get_media_by_id(
     conn: DbConnection,
     id: i64,
     user: User,
+    if_none_match: Option<String>,
 ) -> Result<impl warp::Reply, errors::DimError> {
     let mut tx = conn.read().begin().await?;
+    check_media_access(&mut tx, &user, id).await?;
     let media = Media::get(&mut tx, id).await?;
 
     let media_id = match media.media_type {
@@ -215,6 +468,11 @@ pub async fn get_media_by_id(
         .map(|x| x.name)
         .collect::<Vec<String>>();
 
+    let ratings = Rating::get_all(&mut tx, id).await?;
+    let is_hidden = Media::is_hidden(&mut tx, id).await?;
+    let audio_tracks = AudioTrack::get_for_media(&mut tx, media_id).await?;
+    let subtitles = Subtitle::get_for_media(&mut tx, media_id).await?;
+
     let progress = match media.media_type {
         MediaType::Episode | MediaType::Movie => Progress::get_for_media_user(&mut tx, user.id, id)
             .await
@@ -343,7 +601,7 @@ pub async fn get_media_by_id(
     };
 
     // FIXME: Remove the duration tag once the UI transitioned to using duration_pretty
-    Ok(reply::json(&json!({
+    let body = serde_json::to_vec(&json!({
         "id": media.id,
         "library_id": media.library_id,
         "name": media.name,
@@ -351,23 +609,98 @@ pub async fn get_media_by_id(
         "rating": media.rating,
         "year": media.year,
         "added": media.added,
-        "poster_path": media.poster_path,
-        "backdrop_path": media.backdrop_path,
+        "poster_path": media.poster_path.as_deref().and_then(resolve_url),
+        "backdrop_path": media.backdrop_path.as_deref().and_then(resolve_url),
         "media_type": media.media_type,
+        "added_by": media.added_by,
+        "is_hidden": is_hidden,
         "genres": genres,
+        "ratings": ratings,
+        "audio_tracks": audio_tracks,
+        "subtitles": subtitles,
         "duration": duration,
         "tags": quality_tags,
         ..?next_episode_id,
         ..?season_episode_tag,
         ..?progress
-    })))
+    }))
+    .map_err(|_| errors::DimError::InternalServerError)?;
+
+    // the body contains the progress of the user, so the tag changes whenever the user watches
+    // some more, not just when the metadata changes.
+    let etag = etag_for(&body);
+    let status = if etag_matches(if_none_match.as_deref(), &etag) {
+        StatusCode::NOT_MODIFIED
+    } else {
+        StatusCode::OK
+    };
+
+    let body = if status == StatusCode::NOT_MODIFIED {
+        Vec::new()
+    } else {
+        body
+    };
+
+    warp::http::Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .header("ETag", etag)
+        .header("Cache-Control", "private, no-cache")
+        .body(body)
+        .map_err(|_| errors::DimError::InternalServerError)
+}
+
+/// Method mapped to `GET /api/v1/media/<id>/stream_info` returns the container, codecs and
+/// resolution of the files of a movie or episode as probed when they were scanned. Clients can use
+/// this to decide between direct play and transcoding before requesting a manifest.
+///
+/// # Arguments
+/// * `conn` - database connection
+/// * `id` - id of the media
+/// * `user` - Auth middleware
+///
+/// # Return Schema
+/// ```text
+/// [
+///     {
+///         "mediafile_id": int,
+///         "container": string | null,
+///         "video_codec": string | null,
+///         "profile": string | null,
+///         "resolution": string | null,
+///         "audio_codec": string | null,
+///         "channels": int | null,
+///         "duration": int | null,
+///     }
+/// ]
+/// ```
+///
+/// # Errors
+/// * [`NotStreamable`] - Returned if the media is a tv show, which cant be played back by itself.
+///
+/// [`NotStreamable`]: crate::errors::DimError::NotStreamable
+pub async fn get_stream_info(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut tx = conn.read().begin().await?;
+    check_media_access(&mut tx, &user, id).await?;
+
+    if !Media::is_streamable(&mut tx, id).await? {
+        return Err(errors::DimError::NotStreamable);
+    }
+
+    Ok(reply::json(&MediaFile::get_stream_info(&mut tx, id).await?))
 }
 
 pub async fn get_media_files(
     conn: DbConnection,
     id: i64,
+    user: User,
 ) -> Result<impl warp::Reply, errors::DimError> {
     let mut tx = conn.read().begin().await?;
+    check_media_access(&mut tx, &user, id).await?;
     let media_type = Media::media_mediatype(&mut tx, id).await?;
 
     let mediafiles = match media_type {
@@ -386,8 +719,10 @@ pub async fn get_media_files(
 pub async fn get_mediafile_tree(
     conn: DbConnection,
     id: i64,
+    user: User,
 ) -> Result<impl warp::Reply, errors::DimError> {
     let mut tx = conn.read().begin().await?;
+    check_media_access(&mut tx, &user, id).await?;
     let media_type = Media::media_mediatype(&mut tx, id).await?;
 
     let mut mediafiles = match media_type {
@@ -482,10 +817,27 @@ pub async fn delete_media_by_id(
     id: i64,
     _user: User,
 ) -> Result<impl warp::Reply, errors::DimError> {
+    let metadata_path = crate::core::METADATA_PATH
+        .get()
+        .ok_or(errors::DimError::StorageUnavailable)?;
+
     let mut lock = conn.writer().lock_owned().await;
     let mut tx = database::write_tx(&mut lock).await?;
-    Media::delete(&mut tx, id).await?;
+    let orphans = Media::delete_with_assets(&mut tx, id).await?;
     tx.commit().await?;
+
+    // assets are stored as `images/<file>` but the fetcher caches them straight into the
+    // metadata directory.
+    for asset in orphans {
+        let file = asset.strip_prefix("images/").unwrap_or(&asset);
+        let path = Path::new(metadata_path).join(file);
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!(reason = ?e, path = ?path, "Failed to remove asset of deleted media."),
+        }
+    }
+
     Ok(StatusCode::OK)
 }
 
@@ -531,15 +883,351 @@ pub async fn tmdb_search(
 ///
 /// # Query params
 /// * `offset` - offset in seconds
+/// * `duration` - optional duration of the media in seconds, the offset is clamped to it.
+///
+/// # Errors
+/// * [`NotStreamable`] - Returned if the media is a tv show, which cant be played back by itself.
+///
+/// [`NotStreamable`]: crate::errors::DimError::NotStreamable
 pub async fn map_progress(
     conn: DbConnection,
     id: i64,
     offset: i64,
+    duration: Option<i64>,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    check_media_access(&mut tx, &user, id).await?;
+
+    if !Media::is_streamable(&mut tx, id).await? {
+        return Err(errors::DimError::NotStreamable);
+    }
+
+    Progress::upsert(&mut tx, user.id, id, offset, duration).await?;
+    tx.commit().await?;
+    Ok(StatusCode::OK)
+}
+
+/// Method mapped to `POST /api/v1/media/<id>/favorite` adds a media to the favorites of the
+/// logged in user. Adding a media which already is a favorite does nothing.
+///
+/// # Arguments
+/// * `conn` - database connection
+/// * `id` - id of the media to bookmark
+/// * `user` - Auth middleware
+pub async fn add_favorite(
+    conn: DbConnection,
+    id: i64,
     user: User,
 ) -> Result<impl warp::Reply, errors::DimError> {
     let mut lock = conn.writer().lock_owned().await;
     let mut tx = database::write_tx(&mut lock).await?;
-    Progress::set(&mut tx, offset, user.id, id).await?;
+    check_media_access(&mut tx, &user, id).await?;
+    Favorite::add(&mut tx, user.id, id).await?;
     tx.commit().await?;
     Ok(StatusCode::OK)
 }
+
+/// Method mapped to `DELETE /api/v1/media/<id>/favorite` removes a media from the favorites of
+/// the logged in user.
+///
+/// # Arguments
+/// * `conn` - database connection
+/// * `id` - id of the media to remove
+/// * `user` - Auth middleware
+pub async fn remove_favorite(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    Favorite::remove(&mut tx, user.id, id).await?;
+    tx.commit().await?;
+    Ok(StatusCode::OK)
+}
+
+/// Body of the routes adding or removing a tag.
+#[derive(Deserialize)]
+pub struct TagBody {
+    pub tag: String,
+}
+
+/// Method mapped to `GET /api/v1/media/<id>/tags` returns the tags of a media, sorted by name.
+///
+/// # Arguments
+/// * `conn` - database connection
+/// * `id` - id of the media
+/// * `user` - Auth middleware
+pub async fn get_tags(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut tx = conn.read().begin().await?;
+    check_media_access(&mut tx, &user, id).await?;
+    Ok(reply::json(&Tag::get_by_media(&mut tx, id).await?))
+}
+
+/// Method mapped to `POST /api/v1/media/<id>/tags` tags a media. The body must be a json object
+/// with a `tag` field. Tags are case-insensitive and adding a tag twice does nothing. This method
+/// requires the `owner` role.
+///
+/// # Arguments
+/// * `conn` - database connection
+/// * `id` - id of the media to tag
+/// * `tag` - name of the tag
+/// * `user` - Auth middleware
+pub async fn add_tag(
+    conn: DbConnection,
+    id: i64,
+    tag: String,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    {
+        let mut tx = conn.read().begin().await?;
+        let _ = Media::get(&mut tx, id).await?;
+    }
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    Tag::add(&mut tx, id, &tag).await?;
+    tx.commit().await?;
+    Ok(StatusCode::OK)
+}
+
+/// Method mapped to `DELETE /api/v1/media/<id>/tags` removes a tag from a media. The body must be
+/// a json object with a `tag` field. This method requires the `owner` role.
+///
+/// # Arguments
+/// * `conn` - database connection
+/// * `id` - id of the media
+/// * `tag` - name of the tag
+/// * `user` - Auth middleware
+pub async fn remove_tag(
+    conn: DbConnection,
+    id: i64,
+    tag: String,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    Tag::remove(&mut tx, id, &tag).await?;
+    tx.commit().await?;
+    Ok(StatusCode::OK)
+}
+
+/// Body of the route setting the score of a media from one source.
+#[derive(Deserialize)]
+pub struct RatingBody {
+    pub source: String,
+    pub score: i64,
+}
+
+/// Method mapped to `POST /api/v1/media/<id>/ratings` sets the score a media got from a source, ie
+/// imdb. Scores must be normalized to the range 0 to 100. If `source` is the primary rating source
+/// the `rating` of the media is updated too. This method requires the `owner` role.
+///
+/// # Arguments
+/// * `conn` - database connection
+/// * `id` - id of the media
+/// * `body` - source and score
+/// * `user` - Auth middleware
+pub async fn set_rating(
+    conn: DbConnection,
+    id: i64,
+    body: RatingBody,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    {
+        let mut tx = conn.read().begin().await?;
+        let _ = Media::get(&mut tx, id).await?;
+    }
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    Rating::set(&mut tx, id, &body.source, body.score).await?;
+    Rating::sync_primary(&mut tx, id, &get_global_settings().primary_rating_source).await?;
+    tx.commit().await?;
+    Ok(StatusCode::OK)
+}
+
+/// # PUT `/api/v1/media/<id>/hidden`
+/// Method hides or unhides a movie or tv show. Hidden media are left out of library listings,
+/// search and the dashboard, but are otherwise kept intact and can still be fetched by id. This
+/// is meant for curation, ie hiding a bonus disc, and unlike deleting a media keeps its files,
+/// progress and metadata around.
+///
+/// # Authorization
+/// This method requires the user to have the `owner` role.
+///
+/// # Request
+/// ```
+/// {
+///   "hidden": true
+/// }
+/// ```
+///
+/// # Example
+/// ```text
+/// curl -X PUT http://127.0.0.1:8000/api/v1/media/1/hidden -H "Content-type: application/json" -H "Authorization: ..." -d '{"hidden": true}'
+/// ```
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+/// * [`NotFoundError`] - The media does not exist.
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+/// [`NotFoundError`]: crate::errors::DimError::NotFoundError
+pub async fn set_hidden(
+    conn: DbConnection,
+    id: i64,
+    hidden: bool,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    if Media::set_hidden(&mut tx, id, hidden).await? == 0 {
+        return Err(errors::DimError::NotFoundError);
+    }
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Body of the route setting the personal rating of a user.
+#[derive(Deserialize)]
+pub struct UserRatingBody {
+    pub score: i64,
+    #[serde(default)]
+    pub review: Option<String>,
+}
+
+/// Method mapped to `GET /api/v1/media/<id>/rating` returns the personal rating the current user
+/// gave a media, alongside the average score of all users. Both are `null` if nobody rated the
+/// media yet.
+///
+/// # Arguments
+/// * `conn` - database connection
+/// * `id` - id of the media
+/// * `user` - Auth middleware
+///
+/// # Return Schema
+/// ```text
+/// {
+///     "rating": {
+///         "media_id": int,
+///         "user_id": int,
+///         "score": int,
+///         "review": string | null,
+///         "updated_at": int,
+///     } | null,
+///     "average": float | null,
+/// }
+/// ```
+pub async fn get_user_rating(
+    conn: DbConnection,
+    id: i64,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut tx = conn.read().begin().await?;
+    check_media_access(&mut tx, &user, id).await?;
+
+    let rating = match UserRating::get(&mut tx, user.id, id).await {
+        Ok(x) => Some(x),
+        Err(DatabaseError::NotFound) => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(reply::json(&json!({
+        "rating": rating,
+        "average": UserRating::average_for_media(&mut tx, id).await?,
+    })))
+}
+
+/// Method mapped to `PUT /api/v1/media/<id>/rating` sets the personal rating of the current user
+/// for a media. The body must be a json object with a `score` between 0 and 100 and an optional
+/// `review`. Rating a media again replaces the previous rating.
+///
+/// # Arguments
+/// * `conn` - database connection
+/// * `id` - id of the media
+/// * `body` - score and review
+/// * `user` - Auth middleware
+pub async fn set_user_rating(
+    conn: DbConnection,
+    id: i64,
+    body: UserRatingBody,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    check_media_access(&mut tx, &user, id).await?;
+    UserRating::set(&mut tx, user.id, id, body.score, body.review).await?;
+    tx.commit().await?;
+    Ok(StatusCode::OK)
+}
+
+/// Method mapped to `POST /api/v1/media/refresh` queues a metadata refresh for all movies and tv
+/// shows in a library whose metadata hasnt been updated in `max_age` seconds. The refresh happens
+/// in the background, this method returns the number of queued media immediately. Only media that
+/// have been matched to a external id can be refreshed. This method requires the `owner` role.
+///
+/// # Arguments
+/// * `conn` - database connection
+/// * `user` - Auth middleware
+/// * `library_id` - id of the library to refresh
+/// * `force` - refresh all media regardless of when they have last been updated
+/// * `max_age` - age after which metadata counts as stale, see [`DEFAULT_REFRESH_MAX_AGE`]
+///
+/// # Return Schema
+/// ```text
+/// {
+///     "queued": int,
+/// }
+/// ```
+pub async fn refresh_stale(
+    conn: DbConnection,
+    user: User,
+    library_id: i64,
+    force: bool,
+    max_age: i64,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    let cutoff = if force {
+        None
+    } else {
+        let now = database::unix_now() as i64;
+        Some(now - max_age)
+    };
+
+    let mut tx = conn.read().begin().await?;
+    let stale = Media::get_stale(&mut tx, library_id, cutoff).await?;
+    drop(tx);
+
+    let queued = stale.len();
+    tokio::spawn(async move {
+        let matcher = crate::scanners::get_matcher_unchecked();
+
+        for media in stale {
+            if let Err(e) = matcher
+                .refresh_media(media.id, media.external_id, media.media_type)
+                .await
+            {
+                warn!(reason = ?e, media_id = media.id, "Failed to refresh media.");
+            }
+        }
+    });
+
+    Ok(reply::json(&json!({ "queued": queued })))
+}
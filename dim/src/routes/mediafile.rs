@@ -1,6 +1,7 @@
 use crate::core::DbConnection;
 use crate::errors;
 use crate::errors::ErrorStatusCode;
+use crate::routes::library::check_library_access;
 use crate::scanners::tmdb::Tmdb;
 
 use futures::future;
@@ -81,14 +82,14 @@ pub mod filters {
             .and(with_state::<DbConnection>(conn))
             .and(warp::body::json::<RouteArgs>())
             .and_then(
-                |_auth: User,
+                |auth: User,
                  conn: DbConnection,
                  RouteArgs {
                      tmdb_id,
                      media_type,
                      mediafiles,
                  }: RouteArgs| async move {
-                    super::rematch_mediafile(conn, mediafiles, tmdb_id, media_type)
+                    super::rematch_mediafile(conn, auth, mediafiles, tmdb_id, media_type)
                         .await
                         .map_err(reject::custom)
                 },
@@ -103,12 +104,13 @@ pub mod filters {
 pub async fn get_mediafile_info(
     conn: DbConnection,
     id: i64,
-    _user: User,
+    user: User,
 ) -> Result<impl warp::Reply, errors::DimError> {
     let mut tx = conn.read().begin().await?;
     let mediafile = MediaFile::get_one(&mut tx, id)
         .await
         .map_err(|_| errors::DimError::NotFoundError)?;
+    check_library_access(&mut tx, &user, mediafile.library_id).await?;
 
     Ok(reply::json(&json!({
         "id": mediafile.id,
@@ -127,10 +129,12 @@ pub async fn get_mediafile_info(
 /// * `event_tx` - websocket channel over which we dispatch a event notifying other clients of the
 /// new metadata
 ///
+/// * `user` - the user doing the rematch, recorded as the one who added the new media
 /// * `mediafiles` - ids of the orphan mediafiles we want to rematch
 /// * `tmdb_id` - the tmdb id of the proper metadata we want to fetch for the media
 pub async fn rematch_mediafile(
     conn: DbConnection,
+    user: User,
     mediafiles: Vec<i64>,
     tmdb_id: i32,
     media_type: String,
@@ -167,14 +171,18 @@ pub async fn rematch_mediafile(
             MediaType::Movie => {
                 futures.push(
                     matcher
-                        .match_movie_to_result(mediafile, result.clone().into())
+                        .match_movie_to_result(
+                            mediafile,
+                            result.clone().into(),
+                            user.username.clone(),
+                        )
                         .boxed(),
                 );
             }
             MediaType::Tv => {
                 futures.push(
                     matcher
-                        .match_tv_to_result(mediafile, result.clone().into())
+                        .match_tv_to_result(mediafile, result.clone().into(), user.username.clone())
                         .boxed(),
                 );
             }
@@ -35,6 +35,7 @@ pub mod user;
 pub mod global_filters {
     use crate::errors;
     use crate::errors::DimError;
+    use crate::routes::settings::get_global_settings;
     use database::user::User;
     use database::DbConnection;
     use http::header::AUTHORIZATION;
@@ -58,29 +59,97 @@ pub mod global_filters {
         warp::any().map(move || state.clone())
     }
 
+    /// Filter opens a read transaction that lives for the rest of the request. Handlers that
+    /// call several helpers should take this instead of a [`DbConnection`] so that all their
+    /// reads see the same snapshot.
+    pub fn with_read_tx(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = (database::Transaction<'static>,), Error = Rejection> + Clone {
+        warp::any()
+            .map(move || conn.clone())
+            .and_then(|c: DbConnection| async move { begin_read(&c).await.map_err(reject::custom) })
+    }
+
+    /// Filter extracts the authentication token of a request. The token is taken from the
+    /// `Authorization` header, or from the token cookie if `cookie_auth` is enabled.
+    fn auth_token() -> impl Filter<Extract = (Option<String>,), Error = Rejection> + Clone {
+        warp::header::optional::<String>(AUTHORIZATION.as_str())
+            .and(warp::cookie::optional::<String>(super::auth::TOKEN_COOKIE))
+            .map(|header: Option<String>, cookie: Option<String>| {
+                header.or(cookie.filter(|_| get_global_settings().cookie_auth))
+            })
+    }
+
     pub fn with_auth(
         conn: DbConnection,
     ) -> impl Filter<Extract = (User,), Error = Rejection> + Clone {
-        warp::header(AUTHORIZATION.as_str())
+        auth_token()
             .and(warp::any().map(move || conn.clone()))
-            .and_then(|x, c: DbConnection| async move {
-                let mut tx = match c.read().begin().await {
-                    Ok(tx) => tx,
-                    Err(_) => {
-                        return Err(reject::custom(DimError::DatabaseError {
-                            description: String::from("Failed to start transaction"),
-                        }))
+            .and_then(|x: Option<String>, c: DbConnection| async move {
+                let mut tx = begin_read(&c).await.map_err(reject::custom)?;
+                authenticate(&mut tx, x).await.map_err(reject::custom)
+            })
+    }
+
+    /// Same as [`with_auth`] except that the transaction used to look up the user is handed
+    /// over to the handler instead of being dropped.
+    pub fn with_auth_tx(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = (User, database::Transaction<'static>), Error = Rejection> + Clone
+    {
+        auth_token()
+            .and(with_read_tx(conn))
+            .and_then(
+                |x: Option<String>, mut tx: database::Transaction<'static>| async move {
+                    match authenticate(&mut tx, x).await {
+                        Ok(user) => Ok((user, tx)),
+                        Err(e) => Err(reject::custom(e)),
                     }
-                };
-                let id = database::user::Login::verify_cookie(x)
-                    .map_err(|e| reject::custom(DimError::CookieError(e)))?;
+                },
+            )
+            .untuple_one()
+    }
 
-                User::get_by_id(&mut tx, id)
-                    .await
-                    .map_err(|_| reject::custom(DimError::UserNotFound))
+    async fn begin_read(conn: &DbConnection) -> Result<database::Transaction<'static>, DimError> {
+        conn.read()
+            .begin()
+            .await
+            .map_err(|_| DimError::DatabaseError {
+                description: String::from("Failed to start transaction"),
             })
     }
 
+    async fn authenticate(
+        tx: &mut database::Transaction<'_>,
+        token: Option<String>,
+    ) -> Result<User, DimError> {
+        // NOTE: until the owner has registered no token can possibly be valid, we only
+        // check for that once authentication has failed to keep the happy path cheap.
+        let user = match token.map(database::user::Login::verify_cookie) {
+            Some(Ok(id)) => User::get_by_id(&mut *tx, id)
+                .await
+                .map_err(|_| DimError::UserNotFound),
+            Some(Err(e)) => Err(DimError::CookieError(e)),
+            None => Err(DimError::Unauthenticated),
+        };
+
+        let user = match user {
+            Ok(user) => user,
+            Err(e) => {
+                return Err(match User::any_exist(&mut *tx).await {
+                    Ok(false) => DimError::SetupRequired,
+                    _ => e,
+                })
+            }
+        };
+
+        if user.is_disabled {
+            return Err(DimError::AccountDisabled);
+        }
+
+        Ok(user)
+    }
+
     pub async fn handle_rejection(
         err: warp::reject::Rejection,
     ) -> Result<impl warp::Reply, warp::reject::Rejection> {
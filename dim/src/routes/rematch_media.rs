@@ -10,6 +10,7 @@ use crate::scanners::tv_show::TvShowMatcher;
 use database::library::MediaType;
 use database::media::Media;
 use database::mediafile::MediaFile;
+use database::user::User;
 
 use http::status::StatusCode;
 
@@ -50,8 +51,8 @@ pub mod filters {
                  }: RouteArgs,
                  conn: DbConnection,
                  event_tx: EventTx,
-                 _: User| async move {
-                    super::rematch_media(conn, event_tx, id, external_id, media_type)
+                 user: User| async move {
+                    super::rematch_media(conn, event_tx, user, id, external_id, media_type)
                         .await
                         .map_err(|e| reject::custom(e))
                 },
@@ -62,11 +63,19 @@ pub mod filters {
 pub async fn rematch_media(
     conn: DbConnection,
     event_tx: EventTx,
+    user: User,
     id: i64,
     external_id: i32,
     media_type: String,
 ) -> Result<impl warp::Reply, DimError> {
-    // first fetch the data from tmdb
+    // first look the media up, there is no point in taking the writer for a media that doesnt
+    // exist.
+    let target = {
+        let mut tx = conn.read().begin().await?;
+        Media::get(&mut tx, id).await?
+    };
+
+    // then fetch the data from tmdb
     let target_type = match media_type.to_lowercase().as_ref() {
         "movie" => ExternalMediaType::Movie,
         "tv" => ExternalMediaType::Tv,
@@ -106,7 +115,8 @@ pub async fn rematch_media(
     let mut lock = conn.writer().lock_owned().await;
     let mut tx = database::write_tx(&mut lock).await?;
 
-    let target = Media::get(&mut tx, id).await?;
+    // the media is recreated under the same id, so it keeps who added it in the first place.
+    let added_by = target.added_by.clone().unwrap_or(user.username);
 
     use database::episode::Episode;
 
@@ -132,6 +142,7 @@ pub async fn rematch_media(
                 let matcher = MovieMatcher {
                     conn: &conn,
                     event_tx: &event_tx,
+                    added_by: &added_by,
                 };
 
                 matcher
@@ -142,6 +153,7 @@ pub async fn rematch_media(
                 let matcher = TvShowMatcher {
                     conn: &conn,
                     event_tx: &event_tx,
+                    added_by: &added_by,
                 };
 
                 patch_tv_metadata(&mut orphan, &mut tx).await?;
@@ -2,9 +2,11 @@ use crate::core::DbConnection;
 use crate::errors;
 use crate::utils::ffpath;
 
+use database::user::Permission;
 use database::user::UpdateableUser;
 use database::user::User;
 use database::user::UserSettings;
+use database::SigningKey;
 
 use serde::Deserialize;
 use serde::Serialize;
@@ -14,6 +16,7 @@ use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::Read;
 use std::io::Write;
+use std::net::IpAddr;
 use std::sync::Mutex;
 
 use once_cell::sync::Lazy;
@@ -35,8 +38,93 @@ pub struct GlobalSettings {
     pub disable_auth: bool,
 
     pub verbose: bool,
+    /// Never serialized into http responses, see [`PersistedSettings`] for how it is saved.
+    #[serde(skip_serializing)]
     pub secret_key: Option<[u8; 32]>,
     pub enable_hwaccel: bool,
+    /// Whether avatars can be fetched without an authentication token.
+    #[serde(default)]
+    pub public_avatars: bool,
+    /// How many seconds tokens signed with a retired key are still accepted after a key rotation.
+    #[serde(default = "default_key_grace_period")]
+    pub key_grace_period: u64,
+    /// Whether logging in also sets the token as a `HttpOnly` cookie, which is then accepted in
+    /// place of the `Authorization` header. The cookie is marked `Secure`, so browsers only send
+    /// it over https or to localhost.
+    #[serde(default)]
+    pub cookie_auth: bool,
+    /// Rating source whose score is shown as the rating of a media, ie "tmdb" or "imdb". Media
+    /// without a score from this source keep their tmdb rating.
+    #[serde(default = "default_primary_rating_source")]
+    pub primary_rating_source: String,
+    /// Roles given to every account created after the owner's, ie `["user"]`. Unknown roles are
+    /// rejected when the account is created, an empty list falls back to `user`.
+    #[serde(default = "default_roles")]
+    pub default_roles: Vec<String>,
+    /// How many seconds invite tokens can be claimed for after they have been created. Invites
+    /// never expire if this is unset.
+    #[serde(default)]
+    pub invite_ttl: Option<u64>,
+    /// Directory files uploaded by users, ie avatars, are written to. Uploads go into
+    /// `metadata_dir` if this is unset.
+    #[serde(default)]
+    pub uploads_dir: Option<String>,
+    /// How many bytes each user can upload in total. Users can upload as much as they want if
+    /// this is unset.
+    #[serde(default)]
+    pub upload_quota: Option<u64>,
+    /// How many argon2 iterations new passwords are hashed with, between 1 and 10. Existing
+    /// passwords keep the parameters they were hashed with until they are changed.
+    // NOTE: this used to be `password_hash_rounds`, which counted pbkdf2 rounds. Those values make
+    // no sense as argon2 iterations, so the old key is ignored.
+    #[serde(default = "default_password_hash_iterations")]
+    pub password_hash_iterations: u32,
+    /// Addresses of reverse proxies dim runs behind. `X-Forwarded-For` is only trusted on
+    /// requests coming from one of these, otherwise the address of the connection is used.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+    /// Keys tokens are signed with, managed through key rotation. While this is empty
+    /// `secret_key` is the only key. Like `secret_key` this is never serialized into http
+    /// responses.
+    #[serde(default, skip_serializing)]
+    pub signing_keys: Vec<SigningKey>,
+}
+
+/// Layout of the settings file. [`GlobalSettings`] leaves out the key material when it is
+/// serialized so that it can be returned over http as is, this puts the keys back in for the file.
+#[derive(Serialize)]
+struct PersistedSettings<'a> {
+    #[serde(flatten)]
+    settings: &'a GlobalSettings,
+    secret_key: &'a Option<[u8; 32]>,
+    // NOTE: this must stay the last field as toml requires tables to come after plain values.
+    signing_keys: &'a Vec<SigningKey>,
+}
+
+impl<'a> From<&'a GlobalSettings> for PersistedSettings<'a> {
+    fn from(settings: &'a GlobalSettings) -> Self {
+        Self {
+            settings,
+            secret_key: &settings.secret_key,
+            signing_keys: &settings.signing_keys,
+        }
+    }
+}
+
+fn default_key_grace_period() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_primary_rating_source() -> String {
+    "tmdb".into()
+}
+
+fn default_roles() -> Vec<String> {
+    vec!["user".into()]
+}
+
+fn default_password_hash_iterations() -> u32 {
+    database::user::DEFAULT_HASH_ROUNDS
 }
 
 impl Default for GlobalSettings {
@@ -61,10 +149,39 @@ impl Default for GlobalSettings {
             verbose: false,
             secret_key: None,
             enable_hwaccel: true,
+            public_avatars: false,
+            key_grace_period: default_key_grace_period(),
+            cookie_auth: false,
+            primary_rating_source: default_primary_rating_source(),
+            default_roles: default_roles(),
+            invite_ttl: None,
+            uploads_dir: None,
+            upload_quota: None,
+            password_hash_iterations: default_password_hash_iterations(),
+            trusted_proxies: vec![],
+            signing_keys: vec![],
         }
     }
 }
 
+/// Returns the keys tokens are signed with. Installs that never rotated their key only have
+/// `secret_key`, which acts as the key with id `0`.
+pub fn signing_keys(settings: &GlobalSettings) -> Vec<SigningKey> {
+    if !settings.signing_keys.is_empty() {
+        return settings.signing_keys.clone();
+    }
+
+    settings
+        .secret_key
+        .map(|key| SigningKey {
+            id: 0,
+            key,
+            retired_at: None,
+        })
+        .into_iter()
+        .collect()
+}
+
 static GLOBAL_SETTINGS: Lazy<Mutex<GlobalSettings>> = Lazy::new(|| Default::default());
 static SETTINGS_PATH: OnceCell<String> = OnceCell::new();
 
@@ -95,6 +212,34 @@ pub fn init_global_settings(path: Option<String>) -> Result<(), Box<dyn Error>>
     Ok(())
 }
 
+/// Re-reads the settings file and swaps the in-memory settings, handlers pick up the new values
+/// the next time they call [`get_global_settings`]. If the file cant be parsed the current
+/// settings are kept. Signing keys are only ever changed through key rotation, so they are carried
+/// over from the current settings, a changed `key_grace_period` is applied to them though.
+/// Settings that are only read on boot, like `port`, still require a restart.
+pub fn reload_global_settings() -> Result<GlobalSettings, Box<dyn Error>> {
+    let path = SETTINGS_PATH
+        .get()
+        .cloned()
+        .unwrap_or(ffpath("config/config.toml"));
+
+    let content = std::fs::read_to_string(path)?;
+    let mut settings: GlobalSettings = toml::from_str(&content)?;
+    database::user::set_hash_rounds(settings.password_hash_iterations)?;
+
+    {
+        let mut lock = GLOBAL_SETTINGS.lock().unwrap();
+        settings.secret_key = lock.secret_key;
+        settings.signing_keys = lock.signing_keys.clone();
+        *lock = settings.clone();
+    }
+
+    // the keys themselves are unchanged, but `key_grace_period` might not be.
+    database::set_keys(signing_keys(&settings), settings.key_grace_period);
+
+    Ok(settings)
+}
+
 pub fn set_global_settings(settings: GlobalSettings) -> Result<(), Box<dyn Error>> {
     let path = SETTINGS_PATH
         .get()
@@ -108,7 +253,11 @@ pub fn set_global_settings(settings: GlobalSettings) -> Result<(), Box<dyn Error
 
     let settings = get_global_settings();
     File::create(path)?
-        .write(toml::to_string_pretty(&settings).unwrap().as_ref())
+        .write(
+            toml::to_string_pretty(&PersistedSettings::from(&settings))
+                .unwrap()
+                .as_ref(),
+        )
         .unwrap();
 
     Ok(())
@@ -213,20 +362,25 @@ pub async fn post_user_settings(
     Ok(reply::json(&new_settings))
 }
 
-// TODO: Hide secret key.
-pub async fn http_get_global_settings(_user: User) -> Result<impl warp::Reply, errors::DimError> {
+/// Returns the global settings. The keys tokens are signed with are left out.
+pub async fn http_get_global_settings(user: User) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageSettings)?;
+
     Ok(reply::json(&get_global_settings()))
 }
 
-// TODO: Disallow setting secret key over http.
 pub async fn http_set_global_settings(
     user: User,
-    new_settings: GlobalSettings,
+    mut new_settings: GlobalSettings,
 ) -> Result<impl warp::Reply, errors::DimError> {
-    if user.has_role("owner") {
-        set_global_settings(new_settings).unwrap();
-        return Ok(reply::json(&get_global_settings()));
-    }
-
-    Err(errors::DimError::Unauthorized)
+    user.require(Permission::ManageSettings)?;
+
+    // signing keys are only ever changed through key rotation, and are never sent to clients, so
+    // whatever a client sends for them is ignored.
+    let current = get_global_settings();
+    new_settings.secret_key = current.secret_key;
+    new_settings.signing_keys = current.signing_keys;
+    database::user::set_hash_rounds(new_settings.password_hash_iterations)?;
+    set_global_settings(new_settings).unwrap();
+    Ok(reply::json(&get_global_settings()))
 }
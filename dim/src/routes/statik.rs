@@ -50,6 +50,13 @@ pub mod filters {
             )
     }
 
+    pub fn get_identicon(
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("images" / "identicon" / String)
+            .and(warp::get())
+            .map(|username: String| super::get_identicon(username))
+    }
+
     pub fn dist_static() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone
     {
         warp::path("static").and(warp::path::full()).and_then(
@@ -130,6 +137,22 @@ pub async fn react_routes() -> Result<impl warp::Reply, warp::Rejection> {
     }
 }
 
+/// # GET `/images/identicon/<username>`
+/// Method renders a deterministic identicon for the supplied username. This is used as the
+/// avatar of users that havent uploaded a picture.
+///
+/// # Response
+/// This method returns a `image/svg+xml` image which only depends on the username.
+pub fn get_identicon(username: String) -> impl warp::Reply {
+    let username = percent_encoding::percent_decode_str(&username).decode_utf8_lossy();
+
+    warp::http::Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "image/svg+xml")
+        .header("Cache-Control", "public, max-age=86400")
+        .body(crate::utils::identicon_svg(&username))
+}
+
 pub async fn get_image(
     path: path::Tail,
     _resize_w: Option<u32>,
@@ -140,6 +163,13 @@ pub async fn get_image(
     let mut file_path = PathBuf::from(&meta_path);
     file_path.push(path.as_str());
 
+    // avatars live in the uploads directory if it has been configured.
+    if !file_path.exists() {
+        if let Some(uploaded) = crate::core::uploaded_file_path(path.as_str()) {
+            file_path = uploaded;
+        }
+    }
+
     let mut url_path = PathBuf::from("images/");
     url_path.push(path.as_str());
 
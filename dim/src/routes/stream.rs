@@ -1,6 +1,7 @@
 use crate::core::DbConnection;
 use crate::core::StateManager;
 use crate::errors;
+use crate::routes::library::can_access_library;
 use crate::stream_tracking::ContentType;
 use crate::stream_tracking::StreamTracking;
 use crate::stream_tracking::VirtualManifest;
@@ -299,7 +300,6 @@ pub async fn return_virtual_manifest(
     }
 
     let mut tx = conn.read().begin().await?;
-    let user_prefs = auth.prefs;
 
     let gid = uuid::Uuid::new_v4();
 
@@ -307,6 +307,17 @@ pub async fn return_virtual_manifest(
         .await
         .map_err(|e| errors::StreamingErrors::NoMediaFileFound(e.to_string()))?;
 
+    if !can_access_library(&mut tx, &auth, media.library_id)
+        .await
+        .map_err(|e| errors::StreamingErrors::DatabaseError(format!("{:?}", e)))?
+    {
+        return Err(errors::StreamingErrors::NoMediaFileFound(
+            "mediafile not found".into(),
+        ));
+    }
+
+    let user_prefs = auth.prefs;
+
     let target_file = media.target_file.clone();
 
     // FIXME: When `fs::try_exists` gets stabilized we should use that as it will allow us to
@@ -1,11 +1,14 @@
 use crate::core::DbConnection;
 use crate::errors;
+use crate::routes::library::check_media_access;
 
+use database::user::Permission;
 use database::user::User;
 
 use database::episode::{Episode, UpdateEpisode};
 use database::season::{Season, UpdateSeason};
 
+use serde::Deserialize;
 use warp::http::status::StatusCode;
 use warp::reply;
 
@@ -111,6 +114,23 @@ pub mod filters {
             )
     }
 
+    pub fn renumber_episodes(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+        warp::path!("api" / "v1" / "episode" / "renumber")
+            .and(warp::post())
+            .and(warp::body::json::<Vec<super::Renumber>>())
+            .and(with_auth(conn.clone()))
+            .and(with_state::<DbConnection>(conn))
+            .and_then(
+                |renumbers: Vec<super::Renumber>, auth: User, conn: DbConnection| async move {
+                    super::renumber_episodes(conn, renumbers, auth)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
     pub fn delete_episode_by_id(
         conn: DbConnection,
     ) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
@@ -134,9 +154,10 @@ pub mod filters {
 pub async fn get_tv_seasons(
     conn: DbConnection,
     id: i64,
-    _user: User,
+    user: User,
 ) -> Result<impl warp::Reply, errors::DimError> {
     let mut tx = conn.read().begin().await?;
+    check_media_access(&mut tx, &user, id).await?;
     Ok(reply::json(&Season::get_all(&mut tx, id).await?))
 }
 
@@ -149,10 +170,12 @@ pub async fn get_tv_seasons(
 pub async fn get_season_by_id(
     conn: DbConnection,
     id: i64,
-    _user: User,
+    user: User,
 ) -> Result<impl warp::Reply, errors::DimError> {
     let mut tx = conn.read().begin().await?;
-    Ok(reply::json(&Season::get_by_id(&mut tx, id).await?))
+    let season = Season::get_by_id(&mut tx, id).await?;
+    check_media_access(&mut tx, &user, season.tvshowid).await?;
+    Ok(reply::json(&season))
 }
 
 /// Method mapped to `PATCH /api/v1/tv/<id>/season/<season_num>` allows you to patch in info about
@@ -197,29 +220,37 @@ pub async fn delete_season_by_id(
 }
 
 /// Method mapped to `GET /api/v1/episode/<id>` returns information
-/// about a episode for a season.
+/// about a episode for a season. The thumbnail of a episode is its still, or its backdrop for
+/// episodes scanned before stills were stored separately.
 ///
 /// # Arguments
 /// * `id` - id of the episode.
 pub async fn get_season_episodes(
     conn: DbConnection,
     season_id: i64,
-    _user: User,
+    user: User,
 ) -> Result<impl warp::Reply, errors::DimError> {
     let mut tx = conn.read().begin().await?;
+    let season = Season::get_by_id(&mut tx, season_id)
+        .await
+        .map_err(|_| errors::DimError::NotFoundError)?;
+    check_media_access(&mut tx, &user, season.tvshowid).await?;
+
     #[derive(serde::Serialize)]
     pub struct Record {
         pub id: i64,
         pub name: String,
+        #[serde(serialize_with = "database::asset::serialize_url")]
         pub thumbnail_url: Option<String>,
         pub episode: i64,
     }
 
     let result = sqlx::query_as!(Record,
-        r#"SELECT episode.id as "id!", _tblmedia.name, assets.local_path as thumbnail_url, episode.episode_ as "episode!"
+        r#"SELECT episode.id as "id!", _tblmedia.name, COALESCE(still.local_path, backdrop.local_path) as "thumbnail_url?: String", episode.episode_ as "episode!"
         FROM episode
         INNER JOIN _tblmedia on _tblmedia.id = episode.id
-        LEFT JOIN assets ON assets.id = _tblmedia.backdrop
+        LEFT JOIN assets still ON still.id = episode.still
+        LEFT JOIN assets backdrop ON backdrop.id = _tblmedia.backdrop
         WHERE episode.seasonid = ?"#,
         season_id
     ).fetch_all(&mut tx).await?;
@@ -266,3 +297,62 @@ pub async fn delete_episode_by_id(
     tx.commit().await?;
     Ok(StatusCode::OK)
 }
+
+/// New position of a episode, see [`renumber_episodes`].
+#[derive(Deserialize)]
+pub struct Renumber {
+    /// id of the episode to move.
+    pub id: i64,
+    /// season number the episode should be part of.
+    pub season: i64,
+    /// new episode number.
+    pub episode: i64,
+}
+
+/// # POST `/api/v1/episode/renumber`
+/// Method moves episodes to different seasons or episode numbers, which fixes episodes that got
+/// mislabeled because of misnamed files without having to rescan. All episodes are moved at once,
+/// so episodes can swap places. Seasons that dont exist yet are created.
+///
+/// # Authorization
+/// This method requires the user to have the `owner` role.
+///
+/// # Request
+/// ```text
+/// curl -X POST http://127.0.0.1:8000/api/v1/episode/renumber -H "Content-type: application/json" -H
+/// "Authorization: ..." -d '[{"id": 12, "season": 1, "episode": 2}, {"id": 13, "season": 1, "episode": 1}]'
+/// ```
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+/// * [`DuplicateEpisode`] - Two episodes would end up with the same season and episode number,
+///   nothing is changed.
+/// * [`DuplicateRenumber`] - The same episode id is part of the request more than once.
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+/// [`DuplicateEpisode`]: crate::errors::DimError::DuplicateEpisode
+/// [`DuplicateRenumber`]: crate::errors::DimError::DuplicateRenumber
+pub async fn renumber_episodes(
+    conn: DbConnection,
+    renumbers: Vec<Renumber>,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageLibraries)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+
+    for Renumber { id, .. } in renumbers.iter() {
+        check_media_access(&mut tx, &user, *id).await?;
+    }
+
+    let renumbers = renumbers
+        .into_iter()
+        .map(|x| (x.id, x.season, x.episode))
+        .collect::<Vec<_>>();
+
+    Episode::renumber_many(&mut tx, &renumbers).await?;
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,12 +1,22 @@
 //! This module contains all docs and APIs related to users and user metadata.
 use crate::core::DbConnection;
 use crate::errors;
+use crate::routes::library::denied_libraries;
+use crate::routes::settings::get_global_settings;
+use bytes::Buf;
 use bytes::BufMut;
 
+use database::asset::resolve_url;
 use database::asset::Asset;
 use database::asset::InsertableAsset;
+use database::audit::AuditLog;
+use database::favorite::Favorite;
 use database::progress::Progress;
+use database::user::check_password;
+use database::user::Permission;
 use database::user::User;
+use database::user::UserID;
+use database::DatabaseError;
 
 use serde_json::json;
 
@@ -15,8 +25,13 @@ use warp::reply;
 use http::StatusCode;
 
 use futures::TryStreamExt;
+use percent_encoding::utf8_percent_encode;
+use percent_encoding::NON_ALPHANUMERIC;
 use uuid::Uuid;
 
+use tracing::error;
+use tracing::warn;
+
 /// # GET `/api/v1/user`
 /// Method returns metadata about the currently logged in user.
 ///
@@ -32,11 +47,24 @@ use uuid::Uuid;
 /// ```
 ///
 /// # Response
-/// This method will return a JSON payload with the following schema:
+/// This method will return a JSON payload with the following schema. If the user hasnt uploaded
+/// an avatar, `picture` points to a generated identicon instead.
+///
+/// `watchTime` holds the total time spent watching in seconds alongside a human readable form.
+/// `spentWatching` is the same value truncated to whole hours; it is kept for older clients and
+/// will be removed in a future release. `inProgress` and `completed` count the media the user has
+/// started but not finished and the media they got past 90% of.
 /// ```
 /// {
-///   "picture": Option<String>,
+///   "picture": String,
+///   "avatar": Option<String>,
+///   "watchTime": {
+///     "seconds": i64,
+///     "formatted": String
+///   },
 ///   "spentWatching": i64,
+///   "inProgress": i64,
+///   "completed": i64,
 ///   "username": String,
 ///   "roles": [String]
 /// }
@@ -46,19 +74,47 @@ use uuid::Uuid;
 /// ```
 /// {
 ///   "picture": "/images/avatar.jpg",
+///   "avatar": "/api/v1/user/avatar/1",
+///   "watchTime": {
+///     "seconds": 45240,
+///     "formatted": "12hr 34m"
+///   },
 ///   "spentWatching": 12,
+///   "inProgress": 3,
+///   "completed": 41,
 ///   "username": "admin",
 ///   "roles": ["owner"],
 /// }
 /// ```
-pub async fn whoami(user: User, conn: DbConnection) -> Result<impl warp::Reply, errors::DimError> {
-    let mut tx = conn.read().begin().await?;
+///
+/// # Errors
+/// * [`UserNotFound`] - The account the token was issued for has been deleted. The account is
+///   looked up in the same transaction the profile is read from, so a user deleted mid-request
+///   never yields a partial profile.
+///
+/// [`UserNotFound`]: crate::errors::DimError::UserNotFound
+pub async fn whoami(
+    user: User,
+    mut tx: database::Transaction<'static>,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let asset = Asset::get_of_user(&mut tx, user.id).await.ok();
+    let stats = Progress::get_user_stats(&mut tx, user.id)
+        .await
+        .unwrap_or_default();
+    let spent_watching = stats.watch_seconds;
 
     Ok(reply::json(&json!({
-        "picture": Asset::get_of_user(&mut tx, user.id).await.ok().map(|x| format!("/images/{}", x.local_path)),
-        "spentWatching": Progress::get_total_time_spent_watching(&mut tx, user.id)
-            .await
-            .unwrap_or(0) / 3600,
+        "picture": asset.as_ref().and_then(|x| resolve_url(&x.local_path)).unwrap_or_else(|| {
+            format!("/images/identicon/{}", utf8_percent_encode(&user.username, NON_ALPHANUMERIC))
+        }),
+        "avatar": asset.as_ref().map(|x| format!("/api/v1/user/avatar/{}", x.id)),
+        "watchTime": {
+            "seconds": spent_watching,
+            "formatted": crate::utils::secs_to_pretty(spent_watching.max(0) as u64),
+        },
+        "spentWatching": spent_watching / 3600,
+        "inProgress": stats.in_progress,
+        "completed": stats.completed,
         "username": user.username,
         "roles": user.roles()
     })))
@@ -90,8 +146,10 @@ pub async fn whoami(user: User, conn: DbConnection) -> Result<impl warp::Reply,
 /// # Errors
 /// * [`InvalidCredentials`] - The provided `old_password` is incorrect or the authentication token
 /// is invalid.
+/// * [`WeakPassword`] - The new password doesnt satisfy the password policy.
 ///
 /// [`InvalidCredentials`]: crate::errors::DimError::InvalidCredentials
+/// [`WeakPassword`]: crate::errors::DimError::WeakPassword
 pub async fn change_password(
     conn: DbConnection,
     user: User,
@@ -105,7 +163,16 @@ pub async fn change_password(
         .await
         .map_err(|_| errors::DimError::InvalidCredentials)?;
 
+    check_password(&new_password)?;
     user.set_password(&mut tx, new_password).await?;
+    AuditLog::record(
+        &mut tx,
+        Some(&user.username),
+        "password_changed",
+        Some(&user.username),
+        None,
+    )
+    .await?;
 
     tx.commit().await?;
 
@@ -157,6 +224,14 @@ pub async fn delete(
         .map_err(|_| errors::DimError::InvalidCredentials)?;
 
     User::delete(&mut tx, user.id).await?;
+    AuditLog::record(
+        &mut tx,
+        Some(&user.username),
+        "account_deleted",
+        Some(&user.username),
+        None,
+    )
+    .await?;
 
     tx.commit().await?;
 
@@ -198,7 +273,70 @@ pub async fn change_username(
         return Err(errors::DimError::UsernameNotAvailable);
     }
 
-    User::set_username(&mut tx, user.username.clone(), new_username).await?;
+    // the check above is only a fast path, the unique constraint has the final say.
+    User::set_username(&mut tx, user.username.clone(), new_username)
+        .await
+        .map_err(|e| match e {
+            DatabaseError::UniqueViolation(_) => errors::DimError::UsernameNotAvailable,
+            e => e.into(),
+        })?;
+    tx.commit().await?;
+
+    Ok(StatusCode::OK)
+}
+
+/// # PATCH `/api/v1/user/disabled`
+/// Method disables or re-enables an account. Disabled accounts keep their watch history but can
+/// no longer log in or use existing tokens.
+///
+/// # Authorization
+/// This method requires the user to have the `owner` role.
+///
+/// # Request
+/// This method accepts a JSON payload with the following schema:
+/// ```
+/// {
+///   "username": String,
+///   "disabled": bool
+/// }
+/// ```
+///
+/// ## Example
+/// ```text
+/// curl -X PATCH http://127.0.0.1:8000/api/v1/user/disabled -H "Content-type: application/json" -H
+/// "Authorization: ..." -d '{"username": "testUsername", "disabled": true}'
+/// ```
+///
+/// # Response
+/// If the account has been updated this method will simply return `200 OK`.
+///
+/// # Errors
+/// * [`Unauthorized`] - Returned if the authentication token lacks `owner` permissions
+/// * [`UserNotFound`] - No account with the supplied username exists.
+/// * [`LastOwner`] - The account is the last enabled owner.
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+/// [`UserNotFound`]: crate::errors::DimError::UserNotFound
+/// [`LastOwner`]: crate::errors::DimError::LastOwner
+pub async fn set_disabled(
+    conn: DbConnection,
+    user: User,
+    username: String,
+    disabled: bool,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageUsers)?;
+
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    if User::set_disabled(&mut tx, &username, disabled).await? < 1 {
+        return Err(errors::DimError::UserNotFound);
+    }
+    let action = if disabled {
+        "account_disabled"
+    } else {
+        "account_enabled"
+    };
+    AuditLog::record(&mut tx, Some(&user.username), action, Some(&username), None).await?;
     tx.commit().await?;
 
     Ok(StatusCode::OK)
@@ -220,43 +358,80 @@ pub async fn change_username(
 /// If the avatar is successfully uploaded, this route will return `200 OK`.
 ///
 /// # Errors
-/// * [`UploadFailed`] - No file has been uploaded correctly or the `file` form field has not been
-/// * [`UnsupportedFile`] - The file uploaded is not supported.
-/// found.
+/// * [`UploadFailed`] - The multipart body could not be read.
+/// * [`MissingUpload`] - No `file` or `avatar` form field has been found.
+/// * [`AmbiguousUpload`] - More than one `file` or `avatar` form field has been sent.
+/// * [`UnsupportedFile`] - The file uploaded is not a jpeg or png, or its contents dont match its content type.
+/// * [`UploadTooLarge`] - The file uploaded is larger than [`MAX_UPLOAD_SIZE`].
+/// * [`UploadQuotaExceeded`] - The file would take the user over `upload_quota`. The replaced avatar doesnt count towards the quota.
+/// * [`StorageUnavailable`] - The uploaded file could not be written to the uploads directory.
 ///
 /// [`UploadFailed`]: crate::errors::DimError::UploadFailed
+/// [`MissingUpload`]: crate::errors::DimError::MissingUpload
+/// [`AmbiguousUpload`]: crate::errors::DimError::AmbiguousUpload
 /// [`UnsupportedFile`]: crate::errors::DimError::UnsupportedFile
+/// [`UploadTooLarge`]: crate::errors::DimError::UploadTooLarge
+/// [`UploadQuotaExceeded`]: crate::errors::DimError::UploadQuotaExceeded
+/// [`StorageUnavailable`]: crate::errors::DimError::StorageUnavailable
 pub async fn upload_avatar(
     conn: DbConnection,
     user: User,
     form: warp::multipart::FormData,
 ) -> Result<impl warp::Reply, errors::DimError> {
-    let parts: Vec<warp::multipart::Part> = form
+    let mut parts: Vec<warp::multipart::Part> = form
+        .try_filter(|x| futures::future::ready(UPLOAD_FIELDS.contains(&x.name())))
         .try_collect()
         .await
         .map_err(|_e| errors::DimError::UploadFailed)?;
 
+    let part = match (parts.pop(), parts.is_empty()) {
+        (Some(part), true) => part,
+        (Some(_), false) => return Err(errors::DimError::AmbiguousUpload),
+        (None, _) => return Err(errors::DimError::MissingUpload),
+    };
+
     let mut lock = conn.writer().lock_owned().await;
     let mut tx = database::write_tx(&mut lock).await?;
-    let asset = if let Some(p) = parts.into_iter().filter(|x| x.name() == "file").next() {
-        process_part(&mut tx, p).await
-    } else {
-        Err(errors::DimError::UploadFailed)
-    };
 
-    User::set_picture(&mut tx, user.id, asset?.id).await?;
+    // the avatar we replace doesnt count towards the quota anymore.
+    let previous = Asset::get_of_user(&mut tx, user.id).await.ok();
+    if let Some(ref previous) = previous {
+        Asset::forget_upload(&mut tx, previous.id).await?;
+    }
+
+    let asset = process_part(&mut tx, user.id, part).await?;
+    User::set_picture(&mut tx, user.id, asset.id).await?;
+
+    let previous = previous.filter(|x| x.id != asset.id);
+    if let Some(ref previous) = previous {
+        Asset::delete(&mut tx, previous.id).await?;
+    }
+
     tx.commit().await?;
 
+    if let Some(path) = previous.and_then(|x| crate::core::uploaded_file_path(&x.local_path)) {
+        if let Err(e) = tokio::fs::remove_file(&path).await {
+            warn!(reason = ?e, path = ?path, "Failed to remove replaced avatar.");
+        }
+    }
+
     Ok(StatusCode::OK)
 }
 
+/// Maximum size in bytes of a single uploaded file.
+pub const MAX_UPLOAD_SIZE: u64 = 5_000_000;
+
+/// Form fields an uploaded file is accepted under.
+pub const UPLOAD_FIELDS: [&str; 2] = ["file", "avatar"];
+
 #[doc(hidden)]
 pub async fn process_part(
     conn: &mut database::Transaction<'_>,
+    uid: UserID,
     p: warp::multipart::Part,
 ) -> Result<Asset, errors::DimError> {
-    if p.name() != "file" {
-        return Err(errors::DimError::UploadFailed);
+    if !UPLOAD_FIELDS.contains(&p.name()) {
+        return Err(errors::DimError::MissingUpload);
     }
 
     let file_ext = match p.content_type() {
@@ -265,33 +440,299 @@ pub async fn process_part(
         _ => return Err(errors::DimError::UnsupportedFile),
     };
 
+    // NOTE: The form level limit relies on the client declaring a length, so we additionally keep
+    // track of how much we have buffered ourselves.
     let contents = p
         .stream()
-        .try_fold(Vec::new(), |mut vec, data| {
+        .map_err(|_| errors::DimError::UploadFailed)
+        .try_fold(Vec::new(), |mut vec, data| async move {
+            if vec.len() + data.remaining() > MAX_UPLOAD_SIZE as usize {
+                return Err(errors::DimError::UploadTooLarge);
+            }
+
             vec.put(data);
-            async move { Ok(vec) }
+            Ok(vec)
         })
-        .await
-        .map_err(|_| errors::DimError::UploadFailed)?;
+        .await?;
+
+    // the content type is chosen by the client, so we only trust it if the file really is in
+    // that format and name the file after what we detected.
+    let file_ext = match crate::utils::sniff_image_ext(&contents) {
+        Some(detected) if detected == file_ext => detected,
+        _ => return Err(errors::DimError::UnsupportedFile),
+    };
+
+    let size = contents.len() as i64;
+    if let Some(quota) = get_global_settings().upload_quota {
+        if (Asset::uploaded_bytes(&mut *conn, uid).await? + size) as u64 > quota {
+            return Err(errors::DimError::UploadQuotaExceeded);
+        }
+    }
+
+    let uploads_path = crate::core::UPLOADS_PATH
+        .get()
+        .ok_or(errors::DimError::StorageUnavailable)?;
 
     let local_file = format!("{}.{}", Uuid::new_v4().to_string(), file_ext);
-    let local_path = format!(
-        "{}/{}",
-        crate::core::METADATA_PATH.get().unwrap(),
-        &local_file
-    );
+    let local_path = format!("{}/{}", uploads_path, &local_file);
 
-    tokio::fs::write(&local_path, contents)
+    // NOTE: a partially written file must never become the avatar, so we dont write to
+    // `local_path` directly.
+    crate::utils::write_atomic(&local_path, contents)
         .await
-        .map_err(|_| errors::DimError::UploadFailed)?;
+        .map_err(|e| {
+            error!(reason = ?e, path = ?local_path, "Failed to write uploaded file.");
+            errors::DimError::StorageUnavailable
+        })?;
 
-    Ok(InsertableAsset {
+    let asset = InsertableAsset {
         local_path: local_file,
         file_ext: file_ext.into(),
         ..Default::default()
     }
-    .insert(conn)
-    .await?)
+    .insert(&mut *conn)
+    .await?;
+
+    Asset::record_upload(&mut *conn, asset.id, uid, size).await?;
+
+    Ok(asset)
+}
+
+/// # GET `/api/v1/user/avatar/<id>`
+/// Method returns the image of the avatar with the supplied asset id. The response carries an
+/// `ETag` based on the asset id so that clients can revalidate with `If-None-Match` instead of
+/// downloading the avatar again.
+///
+/// # Authorization
+/// This method requires a valid authentication token unless `public_avatars` is enabled in the
+/// server settings.
+///
+/// ## Example
+/// ```text
+/// curl -X GET http://127.0.0.1:8000/api/v1/user/avatar/1 -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// This method returns the raw image with a `Content-Type` matching the uploaded file, or
+/// `304 Not Modified` if the `If-None-Match` header matches the current `ETag`.
+///
+/// # Errors
+/// * [`NotFoundError`] - No avatar with the supplied id exists.
+/// * [`AssetGone`] - The avatar exists but its file has been removed from the uploads directory.
+///
+/// [`NotFoundError`]: crate::errors::DimError::NotFoundError
+/// [`AssetGone`]: crate::errors::DimError::AssetGone
+pub async fn get_avatar(
+    conn: DbConnection,
+    id: i64,
+    if_none_match: Option<String>,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut tx = conn.read().begin().await?;
+    let asset = Asset::get_avatar(&mut tx, id)
+        .await?
+        .ok_or(errors::DimError::NotFoundError)?;
+    drop(tx);
+
+    let etag = format!("\"avatar-{}\"", asset.id);
+    let cache_control = if get_global_settings().public_avatars {
+        "public, max-age=3600"
+    } else {
+        "private, max-age=3600"
+    };
+
+    if crate::utils::etag_matches(if_none_match.as_deref(), &etag) {
+        return warp::http::Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header("ETag", etag)
+            .header("Cache-Control", cache_control)
+            .body(Vec::new())
+            .map_err(|_| errors::DimError::InternalServerError);
+    }
+
+    let path =
+        crate::core::uploaded_file_path(&asset.local_path).ok_or(errors::DimError::AssetGone)?;
+    let data = tokio::fs::read(path)
+        .await
+        .map_err(|_| errors::DimError::AssetGone)?;
+
+    let content_type = match asset.file_ext.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        _ => "application/octet-stream",
+    };
+
+    warp::http::Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", content_type)
+        .header("ETag", etag)
+        .header("Cache-Control", cache_control)
+        .body(data)
+        .map_err(|_| errors::DimError::InternalServerError)
+}
+
+/// # GET `/api/v1/user/favorites`
+/// Method returns the media the logged in user has added to their favorites, most recently added
+/// first.
+///
+/// # Authorization
+/// This method requires a valid authentication token.
+///
+/// ## Example
+/// ```text
+/// curl -X GET http://127.0.0.1:8000/api/v1/user/favorites -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// This method returns a JSON list of media objects.
+/// ```
+/// [
+///   {
+///     "id": 1,
+///     "library_id": 1,
+///     "name": "Blade Runner 2049",
+///     "description": "...",
+///     "rating": 7,
+///     "year": 2017,
+///     "added": "2022-06-11",
+///     "poster_path": "/images/...",
+///     "backdrop_path": "/images/...",
+///     "duration": 9840,
+///     "media_type": "movie"
+///   }
+/// ]
+/// ```
+pub async fn get_favorites(
+    conn: DbConnection,
+    user: User,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut tx = conn.read().begin().await?;
+    let denied = denied_libraries(&mut tx, &user).await?;
+    let mut favorites = Favorite::get_for_user(&mut tx, user.id).await?;
+    // access to a library might have been revoked after the media was added.
+    favorites.retain(|x| !denied.contains(&x.library_id));
+
+    Ok(reply::json(&favorites))
+}
+
+/// Maximum number of entries `/api/v1/user/history` returns at once.
+const MAX_HISTORY_PAGE: i64 = 200;
+
+/// # GET `/api/v1/user/history`
+/// Method returns everything the logged in user has watched, most recently watched first. Unlike
+/// the continue watching banner this includes media the user has finished.
+///
+/// # Authorization
+/// This method requires a valid authentication token.
+///
+/// # Query params
+/// * `limit` - max number of entries to return, defaults to 50 and is capped at 200.
+/// * `offset` - number of entries to skip, defaults to 0.
+///
+/// ## Example
+/// ```text
+/// curl -X GET "http://127.0.0.1:8000/api/v1/user/history?limit=10&offset=0" -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// This method returns a JSON list of media objects along with the playback position in seconds,
+/// the unix timestamp of when they were last watched and whether the user got past 90% of them.
+/// ```
+/// [
+///   {
+///     "id": 1,
+///     "library_id": 1,
+///     "name": "Blade Runner 2049",
+///     "description": "...",
+///     "rating": 7,
+///     "year": 2017,
+///     "added": "2022-06-11",
+///     "poster_path": "/images/...",
+///     "backdrop_path": "/images/...",
+///     "duration": 9840,
+///     "media_type": "movie",
+///     "position": 9500,
+///     "watched_at": 1655942400,
+///     "completed": true
+///   }
+/// ]
+/// ```
+pub async fn get_history(
+    conn: DbConnection,
+    user: User,
+    limit: i64,
+    offset: i64,
+) -> Result<impl warp::Reply, errors::DimError> {
+    let mut tx = conn.read().begin().await?;
+    let denied = denied_libraries(&mut tx, &user).await?;
+    // access to a library might have been revoked after the media was watched.
+    let history = Progress::get_history(
+        &mut tx,
+        user.id,
+        limit.clamp(1, MAX_HISTORY_PAGE),
+        offset,
+        &denied,
+    )
+    .await?;
+
+    Ok(reply::json(&history))
+}
+
+/// Maximum number of entries `/api/v1/user/activity` returns at once.
+const MAX_ACTIVITY_PAGE: i64 = 200;
+
+/// # GET `/api/v1/user/activity`
+/// Method returns what all users of the server have watched recently, most recently watched
+/// first. Users who set `hide_activity` in their preferences are left out.
+///
+/// # Authorization
+/// This method requires a valid authentication token with `owner` permissions.
+///
+/// # Query params
+/// * `limit` - max number of entries to return, defaults to 20 and is capped at 200.
+///
+/// ## Example
+/// ```text
+/// curl -X GET "http://127.0.0.1:8000/api/v1/user/activity?limit=10" -H "Authorization: ..."
+/// ```
+///
+/// # Response
+/// This method returns a JSON list of media objects along with the name of the user who watched
+/// them and the unix timestamp of when they were last watched.
+/// ```
+/// [
+///   {
+///     "username": "admin",
+///     "id": 1,
+///     "library_id": 1,
+///     "name": "Blade Runner 2049",
+///     "description": "...",
+///     "rating": 7,
+///     "year": 2017,
+///     "added": "2022-06-11",
+///     "poster_path": "/images/...",
+///     "backdrop_path": "/images/...",
+///     "duration": 9840,
+///     "media_type": "movie",
+///     "watched_at": 1655942400
+///   }
+/// ]
+/// ```
+///
+/// # Errors
+/// * [`Unauthorized`] - The user is not a owner.
+///
+/// [`Unauthorized`]: crate::errors::DimError::Unauthorized
+pub async fn get_activity(
+    conn: DbConnection,
+    user: User,
+    limit: i64,
+) -> Result<impl warp::Reply, errors::DimError> {
+    user.require(Permission::ManageUsers)?;
+
+    let mut tx = conn.read().begin().await?;
+    Ok(reply::json(
+        &Progress::get_recent_activity(&mut tx, limit.clamp(1, MAX_ACTIVITY_PAGE)).await?,
+    ))
 }
 
 #[doc(hidden)]
@@ -304,7 +745,10 @@ pub(crate) mod filters {
     use warp::reject;
     use warp::Filter;
 
+    use crate::routes::settings::get_global_settings;
+
     use super::super::global_filters::with_auth;
+    use super::super::global_filters::with_auth_tx;
     use super::super::global_filters::with_state;
 
     pub fn whoami(
@@ -315,13 +759,12 @@ pub(crate) mod filters {
 
         path.unify()
             .and(warp::get())
-            .and(with_auth(conn.clone()))
-            .and(with_state(conn))
-            .and_then(|auth: User, conn: DbConnection| async move {
-                super::whoami(auth, conn)
-                    .await
-                    .map_err(|e| reject::custom(e))
-            })
+            .and(with_auth_tx(conn))
+            .and_then(
+                |auth: User, tx: database::Transaction<'static>| async move {
+                    super::whoami(auth, tx).await.map_err(reject::custom)
+                },
+            )
     }
 
     pub fn change_password(
@@ -395,13 +838,131 @@ pub(crate) mod filters {
             })
     }
 
+    pub fn set_disabled(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        pub struct Params {
+            username: String,
+            disabled: bool,
+        }
+
+        warp::path!("api" / "v1" / "user" / "disabled")
+            .and(warp::patch())
+            .and(with_auth(conn.clone()))
+            .and(warp::body::json::<Params>())
+            .and(with_state(conn))
+            .and_then(
+                |user, Params { username, disabled }: Params, conn| async move {
+                    super::set_disabled(conn, user, username, disabled)
+                        .await
+                        .map_err(reject::custom)
+                },
+            )
+    }
+
+    pub fn get_avatar(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        // NOTE: when avatars are public we skip authentication entirely, otherwise we require a
+        // valid token just like every other route.
+        let auth = warp::any()
+            .and_then(|| async {
+                if get_global_settings().public_avatars {
+                    Ok(())
+                } else {
+                    Err(reject::not_found())
+                }
+            })
+            .or(with_auth(conn.clone()).map(|_: User| ()))
+            .unify()
+            .untuple_one();
+
+        warp::path!("api" / "v1" / "user" / "avatar" / i64)
+            .and(warp::get())
+            .and(auth)
+            .and(warp::header::optional::<String>("if-none-match"))
+            .and(with_state(conn))
+            .and_then(|id, if_none_match, conn| async move {
+                super::get_avatar(conn, id, if_none_match)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
+
+    pub fn get_favorites(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("api" / "v1" / "user" / "favorites")
+            .and(warp::get())
+            .and(with_auth(conn.clone()))
+            .and(with_state(conn))
+            .and_then(|user, conn| async move {
+                super::get_favorites(conn, user)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
+
+    pub fn get_history(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct Args {
+            #[serde(default = "default_limit")]
+            limit: i64,
+            #[serde(default)]
+            offset: i64,
+        }
+
+        fn default_limit() -> i64 {
+            50
+        }
+
+        warp::path!("api" / "v1" / "user" / "history")
+            .and(warp::get())
+            .and(warp::query::<Args>())
+            .and(with_auth(conn.clone()))
+            .and(with_state(conn))
+            .and_then(|Args { limit, offset }: Args, user, conn| async move {
+                super::get_history(conn, user, limit, offset)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
+
+    pub fn get_activity(
+        conn: DbConnection,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct Args {
+            #[serde(default = "default_limit")]
+            limit: i64,
+        }
+
+        fn default_limit() -> i64 {
+            20
+        }
+
+        warp::path!("api" / "v1" / "user" / "activity")
+            .and(warp::get())
+            .and(warp::query::<Args>())
+            .and(with_auth(conn.clone()))
+            .and(with_state(conn))
+            .and_then(|Args { limit }: Args, user, conn| async move {
+                super::get_activity(conn, user, limit)
+                    .await
+                    .map_err(reject::custom)
+            })
+    }
+
     pub fn upload_avatar(
         conn: DbConnection,
     ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
         warp::path!("api" / "v1" / "user" / "avatar")
             .and(warp::post())
             .and(with_auth(conn.clone()))
-            .and(warp::multipart::form().max_length(5_000_000))
+            .and(warp::multipart::form().max_length(super::MAX_UPLOAD_SIZE))
             .and(with_state(conn))
             .and_then(|user, form, conn| async move {
                 super::upload_avatar(conn, user, form)
@@ -11,13 +11,20 @@ use tracing::instrument;
 use tracing::warn;
 use tracing::Instrument;
 
+use database::asset::InsertableAsset;
+use database::audio_track::InsertableAudioTrack;
+use database::genre::Genre;
 use database::library::MediaType;
+use database::media::InsertableMedia;
+use database::media::Media;
 use database::mediafile::InsertableMediaFile;
 use database::mediafile::MediaFile;
 use database::mediafile::UpdateMediaFile;
+use database::subtitle::InsertableSubtitle;
 use database::DbConnection;
 
 use crate::core::EventTx;
+use crate::fetcher::insert_into_queue;
 use crate::scanners::movie::MovieMatcher;
 use crate::scanners::tmdb::Tmdb;
 use crate::scanners::tv_show::TvShowMatcher;
@@ -52,12 +59,15 @@ pub enum ScannerError {
     UnknownError,
     /// Database error: {0}
     DatabaseError(String),
+    /// The library is already being scanned
+    AlreadyScanning,
 }
 
 impl From<database::DatabaseError> for ScannerError {
     fn from(e: database::DatabaseError) -> Self {
         match e {
             database::DatabaseError::DatabaseError(e) => Self::DatabaseError(e.to_string()),
+            e => Self::DatabaseError(e.to_string()),
         }
     }
 }
@@ -182,7 +192,10 @@ impl MetadataExtractor {
             audio: ffprobe_data
                 .get_primary_codec("audio")
                 .map(ToOwned::to_owned),
-            original_resolution: Default::default(),
+            original_resolution: ffprobe_data
+                .get_width()
+                .zip(ffprobe_data.get_height())
+                .map(|(w, h)| format!("{}x{}", w, h)),
             duration: ffprobe_data.get_duration().map(|x| x as i64),
             corrupt: ffprobe_data.is_corrupt(),
             channels: ffprobe_data.get_primary_channels(),
@@ -195,6 +208,53 @@ impl MetadataExtractor {
                 .map(ToString::to_string),
         };
 
+        // NOTE: `mediafile_id` is filled in once the mediafile has been inserted.
+        let audio_tracks = ffprobe_data
+            .find_by_type("audio")
+            .into_iter()
+            .map(|stream| InsertableAudioTrack {
+                stream_index: stream.index,
+                language: stream
+                    .get_language()
+                    .as_deref()
+                    .and_then(crate::utils::lang_from_iso639)
+                    .map(ToString::to_string),
+                codec: stream.codec_name.clone(),
+                channels: stream.channels,
+                is_default: matches!(&stream.disposition, Some(x) if x.default == 1),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let embedded_subtitles = ffprobe_data
+            .find_by_type("subtitle")
+            .into_iter()
+            .map(|stream| InsertableSubtitle {
+                language: stream
+                    .get_language()
+                    .as_deref()
+                    .and_then(crate::utils::lang_from_iso639)
+                    .map(ToString::to_string),
+                codec: stream.codec_name.clone(),
+                stream_index: Some(stream.index),
+                forced: matches!(&stream.disposition, Some(x) if x.forced == 1),
+                ..Default::default()
+            });
+
+        let sidecar_subtitles = crate::utils::find_sidecar_subtitles(&file)
+            .into_iter()
+            .map(|x| InsertableSubtitle {
+                language: x.language,
+                codec: x.codec.to_string(),
+                external_path: Some(x.path.to_string_lossy().to_string()),
+                forced: x.forced,
+                ..Default::default()
+            });
+
+        let subtitles = embedded_subtitles
+            .chain(sidecar_subtitles)
+            .collect::<Vec<_>>();
+
         let mediafile = {
             let mut lock = self.conn.writer().lock_owned().await;
             let mut tx = database::write_tx(&mut lock)
@@ -212,6 +272,26 @@ impl MetadataExtractor {
 
             assert!(file_id == mediafile.id);
 
+            for track in audio_tracks {
+                InsertableAudioTrack {
+                    mediafile_id: file_id,
+                    ..track
+                }
+                .insert(&mut tx)
+                .instrument(debug_span!("audio_track_insert"))
+                .await?;
+            }
+
+            for subtitle in subtitles {
+                InsertableSubtitle {
+                    mediafile_id: file_id,
+                    ..subtitle
+                }
+                .insert(&mut tx)
+                .instrument(debug_span!("subtitle_insert"))
+                .await?;
+            }
+
             tx.commit()
                 .instrument(debug_span!("TxCommit"))
                 .await
@@ -267,7 +347,8 @@ impl MetadataMatcher {
             }
         };
 
-        self.match_movie_to_result(media, result).await
+        self.match_movie_to_result(media, result, Media::ADDED_BY_SCANNER.into())
+            .await
     }
 
     #[handler]
@@ -275,10 +356,12 @@ impl MetadataMatcher {
         &mut self,
         media: MediaFile,
         result: ApiMedia,
+        added_by: String,
     ) -> Result<(), ScannerError> {
         let matcher = MovieMatcher {
             conn: &self.conn,
             event_tx: &self.event_tx,
+            added_by: &added_by,
         };
 
         matcher.match_to_result(result, &media).await;
@@ -360,7 +443,8 @@ impl MetadataMatcher {
             }
         };
 
-        self.match_tv_to_result(media, result).await
+        self.match_tv_to_result(media, result, Media::ADDED_BY_SCANNER.into())
+            .await
     }
 
     #[handler]
@@ -368,6 +452,7 @@ impl MetadataMatcher {
         &mut self,
         media: MediaFile,
         result: ApiMedia,
+        added_by: String,
     ) -> Result<(), ScannerError> {
         // FIXME: Our handler macro cant handle `mut` keyword yet.
         let mut media = media;
@@ -410,11 +495,120 @@ impl MetadataMatcher {
         let matcher = TvShowMatcher {
             conn: &self.conn,
             event_tx: &self.event_tx,
+            added_by: &added_by,
         };
 
         matcher.match_to_result(result, &media).await;
         Ok(())
     }
+
+    /// Re-fetches the metadata of a movie or tv show from its external id and updates it in
+    /// place.
+    #[handler]
+    #[instrument(skip(self))]
+    pub async fn refresh_media(
+        &mut self,
+        media_id: i64,
+        external_id: String,
+        media_type: MediaType,
+    ) -> Result<(), ScannerError> {
+        let tmdb_id = external_id
+            .strip_prefix("tmdb:")
+            .and_then(|x| x.parse::<i32>().ok())
+            .ok_or(ScannerError::UnknownError)?;
+
+        let media = {
+            let mut tx = self
+                .conn
+                .read()
+                .begin()
+                .await
+                .map_err(|e| ScannerError::DatabaseError(format!("{:?}", e)))?;
+            Media::get(&mut tx, media_id).await?
+        };
+
+        let tmdb = match media_type {
+            MediaType::Movie => &mut self.movie_tmdb,
+            MediaType::Tv => &mut self.tv_tmdb,
+            _ => return Err(ScannerError::UnknownError),
+        };
+
+        let result: ApiMedia = match tmdb.search_by_id(tmdb_id).await {
+            Ok(x) => x.into(),
+            Err(e) => {
+                error!(reason = ?e, "Could not refresh media from tmdb");
+                return Err(ScannerError::UnknownError);
+            }
+        };
+
+        let mut lock = self.conn.writer().lock_owned().await;
+        let mut tx = database::write_tx(&mut lock)
+            .await
+            .map_err(|e| ScannerError::DatabaseError(format!("{:?}", e)))?;
+
+        let poster = insert_remote_asset(&mut tx, &result.poster_path, &result.poster_file).await;
+        let backdrop =
+            insert_remote_asset(&mut tx, &result.backdrop_path, &result.backdrop_file).await;
+
+        InsertableMedia {
+            library_id: media.library_id,
+            name: result.title.clone(),
+            description: result.overview.clone(),
+            rating: result.rating.map(|x| x as i64),
+            year: result.year().map(|x| x as i64),
+            added: media.added.unwrap_or_default(),
+            poster,
+            backdrop,
+            duration: media.duration,
+            external_id: Some(external_id),
+            tmdb_id: Some(tmdb_id),
+            imdb_id: None,
+            added_by: media.added_by,
+            media_type,
+        }
+        .upsert_by_external_id(&mut tx)
+        .await?;
+
+        super::store_rating(&mut tx, media_id, result.rating).await;
+
+        for name in result.genres.iter() {
+            if let Err(e) = Genre::attach(&mut tx, media_id, name).await {
+                warn!(media_id = media_id, genre = %name, reason = ?e, "Failed to attach genre.");
+            }
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| ScannerError::DatabaseError(format!("{:?}", e)))?;
+
+        Ok(())
+    }
+}
+
+/// Queues a remote image for download and inserts it as a asset, returning the id of the asset.
+async fn insert_remote_asset(
+    tx: &mut database::Transaction<'_>,
+    remote_url: &Option<String>,
+    file: &Option<String>,
+) -> Option<i64> {
+    let remote_url = remote_url.clone()?;
+    insert_into_queue(remote_url.clone(), 3).await;
+
+    let asset = InsertableAsset {
+        remote_url: Some(remote_url),
+        local_path: super::format_path(file.clone()),
+        file_ext: "jpg".into(),
+    }
+    .insert(&mut *tx)
+    .await;
+
+    match asset {
+        Ok(x) => Some(x.id),
+        Err(e) => {
+            warn!(reason = ?e, "Failed to insert asset into db");
+            None
+        }
+    }
 }
 
 #[instrument(skip(media, tx))]
@@ -1,6 +1,7 @@
 pub mod base;
 pub mod movie;
 pub mod scanner_daemon;
+pub mod scheduler;
 pub mod tmdb;
 pub mod tv_show;
 
@@ -9,20 +10,25 @@ use chrono::NaiveDate;
 
 use database::library::Library;
 use database::library::MediaType;
+use database::rating::Rating;
 
 use tracing::info;
 use tracing::instrument;
+use tracing::warn;
 
 use crate::core::DbConnection;
 use crate::core::EventTx;
 use crate::json;
 use crate::utils::secs_to_pretty;
 
+use once_cell::sync::Lazy;
 use once_cell::sync::OnceCell;
 use walkdir::WalkDir;
 
+use std::collections::HashSet;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Mutex;
 use std::time::Instant;
 
 use serde::Deserialize;
@@ -172,10 +178,18 @@ where
     )
     .unwrap();
 
+    record_scan_state(library_id, None).await;
+
     let extractor = get_extractor(&tx);
     let matcher = get_matcher(&tx);
 
-    let files = get_subfiles(paths).await?;
+    let files = match get_subfiles(paths).await {
+        Ok(files) => files,
+        Err(e) => {
+            record_scan_state(library_id, Some((0, true))).await;
+            return Err(e);
+        }
+    };
 
     let total_files = files.len();
 
@@ -209,6 +223,8 @@ where
 
     futures::future::join_all(futures).await;
 
+    record_scan_state(library_id, Some((total_files, false))).await;
+
     info!(
         library_id = library_id,
         files = total_files,
@@ -228,11 +244,99 @@ where
     Ok(())
 }
 
+/// Function persists the scanner state of a library. `finished` is `None` when a scan starts and
+/// holds the number of processed files and whether the scan failed once it is over. The state is
+/// purely informational, thus errors are only logged.
+async fn record_scan_state(library_id: i64, finished: Option<(usize, bool)>) {
+    let conn = match database::try_get_conn() {
+        Some(conn) => conn,
+        None => return,
+    };
+
+    let mut lock = conn.writer().lock_owned().await;
+    let result = async {
+        let mut tx = database::write_tx(&mut lock).await?;
+        match finished {
+            None => Library::scan_started(&mut tx, library_id).await?,
+            Some((files, failed)) => {
+                Library::scan_finished(&mut tx, library_id, files as i64, failed).await?
+            }
+        }
+        tx.commit().await?;
+        Ok::<_, database::DatabaseError>(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        warn!(library_id = library_id, reason = ?e, "Failed to record scan state.");
+    }
+}
+
+/// Function stores the tmdb score of a matched media and syncs `Media.rating` with the primary
+/// rating source from the settings. Ratings are not essential to a match, thus errors are only
+/// logged.
+pub(crate) async fn store_rating(
+    tx: &mut database::Transaction<'_>,
+    media_id: i64,
+    rating: Option<f64>,
+) {
+    let result = async {
+        if let Some(rating) = rating {
+            let score = (rating * 10.0).round().clamp(0.0, 100.0) as i64;
+            Rating::set(&mut *tx, media_id, Rating::TMDB, score).await?;
+        }
+
+        let primary = crate::get_global_settings().primary_rating_source;
+        Rating::sync_primary(&mut *tx, media_id, &primary).await?;
+        Ok::<_, database::DatabaseError>(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        warn!(media_id = media_id, reason = ?e, "Failed to store rating.");
+    }
+}
+
+/// Ids of the libraries that are currently being scanned by [`start`].
+static RUNNING_SCANS: Lazy<Mutex<HashSet<i64>>> = Lazy::new(Default::default);
+
+/// Guard marking a library as being scanned until it is dropped.
+struct RunningScan(i64);
+
+impl RunningScan {
+    /// Marks `id` as being scanned, returns `None` if a scan of the library is already running.
+    fn acquire(id: i64) -> Option<Self> {
+        RUNNING_SCANS.lock().unwrap().insert(id).then(|| Self(id))
+    }
+}
+
+impl Drop for RunningScan {
+    fn drop(&mut self) {
+        RUNNING_SCANS.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Function scans all locations of a library. Full scans of the same library never overlap, if the
+/// library is already being scanned [`ScannerError::AlreadyScanning`] is returned. Scans of single
+/// directories started by the scanner daemon through [`start_custom`] are not affected.
+///
+/// [`ScannerError::AlreadyScanning`]: self::base::ScannerError::AlreadyScanning
 pub async fn start(
     conn: DbConnection,
     id: i64,
     tx: EventTx,
 ) -> Result<(), self::base::ScannerError> {
+    let _running = match RunningScan::acquire(id) {
+        Some(x) => x,
+        None => {
+            info!(
+                library_id = id,
+                "Library is already being scanned, skipping."
+            );
+            return Err(self::base::ScannerError::AlreadyScanning);
+        }
+    };
+
     let mut tx_ = conn
         .read()
         .begin()
@@ -1,11 +1,11 @@
 use database::asset::InsertableAsset;
-use database::genre::InsertableGenre;
-use database::genre::InsertableGenreMedia;
+use database::genre::Genre;
 use database::movie::InsertableMovie;
 use database::DbConnection;
 
 use database::library::MediaType;
 use database::media::InsertableMedia;
+use database::media::Media;
 use database::mediafile::MediaFile;
 use database::mediafile::UpdateMediaFile;
 
@@ -27,6 +27,9 @@ use crate::fetcher::insert_into_queue;
 pub struct MovieMatcher<'a> {
     pub conn: &'a DbConnection,
     pub event_tx: &'a EventTx,
+    /// Recorded as [`Media::added_by`](database::media::Media::added_by) of the media this
+    /// matcher inserts.
+    pub added_by: &'a str,
 }
 
 impl<'a> MovieMatcher<'a> {
@@ -149,6 +152,11 @@ impl<'a> MovieMatcher<'a> {
 
             poster,
             backdrop,
+            duration: orphan.duration,
+            external_id: Some(format!("tmdb:{}", result.id)),
+            tmdb_id: Some(result.id as i32),
+            imdb_id: None,
+            added_by: Some(self.added_by.to_string()),
             media_type: MediaType::Movie,
         };
 
@@ -174,16 +182,21 @@ impl<'a> MovieMatcher<'a> {
         let media_id = if let Some(id) = reuse_media_id {
             media.insert_with_id(&mut *tx, id).await?
         } else {
-            media.insert(&mut *tx).await?
+            // the movie might have been matched before under a different name, ie a localized
+            // title, so we look it up by its tmdb id first.
+            match Media::get_by_tmdb_id(&mut *tx, result.id as i32).await? {
+                Some(x) if x.library_id == orphan.library_id => x.id,
+                _ => media.insert(&mut *tx).await?,
+            }
         };
+        super::store_rating(&mut *tx, media_id, result.rating).await;
+
         // the reason we ignore the result here is that in some cases this can fail. Specifically when there are multiple mediafiles for a movie.
         let _ = InsertableMovie::insert(&mut *tx, media_id).await;
 
         for name in result.genres {
-            let genre = InsertableGenre { name };
-
-            if let Ok(x) = genre.insert(&mut *tx).await {
-                let _ = InsertableGenreMedia::insert_pair(x, media_id, &mut *tx).await;
+            if let Err(e) = Genre::attach(&mut *tx, media_id, &name).await {
+                warn!(media_id = media_id, genre = %name, reason = ?e, "Failed to attach genre.");
             }
         }
 
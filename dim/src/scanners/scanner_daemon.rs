@@ -78,6 +78,21 @@ impl FsWatcher {
         Ok(())
     }
 
+    /// Returns the current media type of the library. The type of an empty library can be changed
+    /// while the daemon is running, so we look it up again instead of trusting the type the daemon
+    /// was started with.
+    async fn media_type(&self) -> MediaType {
+        let mut tx = match self.conn.read().begin().await {
+            Ok(x) => x,
+            Err(_) => return self.media_type,
+        };
+
+        Library::get_one(&mut tx, self.library_id)
+            .await
+            .map(|x| x.media_type)
+            .unwrap_or(self.media_type)
+    }
+
     async fn handle_create(&self, path: PathBuf) {
         debug!("Received handle_create event type: {:?}", path);
 
@@ -89,12 +104,13 @@ impl FsWatcher {
         {
             let extractor = super::get_extractor(&self.tx);
             let matcher = super::get_matcher(&self.tx);
+            let media_type = self.media_type().await;
 
             if let Ok(mfile) = extractor
-                .mount_file(path.clone(), self.library_id, self.media_type)
+                .mount_file(path.clone(), self.library_id, media_type)
                 .await
             {
-                match self.media_type {
+                match media_type {
                     MediaType::Movie => {
                         let _ = matcher.match_movie(mfile).await;
                     }
@@ -110,7 +126,7 @@ impl FsWatcher {
                     self.library_id,
                     self.tx.clone(),
                     IntoIterator::into_iter([x]),
-                    self.media_type,
+                    self.media_type().await,
                 )
                 .await;
             }
@@ -131,6 +147,13 @@ impl FsWatcher {
             }
         };
 
+        // Most events are for files we dont track, so we only grab the writer once we know there
+        // is something to remove.
+        let media_file = match self.get_mediafile_by_file(path).await {
+            Some(x) => x,
+            None => return,
+        };
+
         let mut lock = self.conn.writer().lock_owned().await;
         let mut tx = match database::write_tx(&mut lock).await {
             Ok(x) => x,
@@ -140,30 +163,28 @@ impl FsWatcher {
             }
         };
 
-        if let Ok(media_file) = MediaFile::get_by_file(&mut tx, path).await {
-            let media = Media::get_of_mediafile(&mut tx, media_file.id).await;
+        let media = Media::get_of_mediafile(&mut tx, media_file.id).await;
 
-            if let Err(e) = MediaFile::delete(&mut tx, media_file.id).await {
-                error!(reason = ?e, "Failed to remove mediafile");
-                return;
-            }
+        if let Err(e) = MediaFile::delete(&mut tx, media_file.id).await {
+            error!(reason = ?e, "Failed to remove mediafile");
+            return;
+        }
 
-            // if we have a media with no mediafiles we want to purge it as it is a ghost media
-            // entry.
-            if let Ok(media) = media {
-                if let Ok(media_files) = MediaFile::get_of_media(&mut tx, media.id).await {
-                    if media_files.is_empty() {
-                        if let Err(e) = Media::delete(&mut tx, media.id).await {
-                            error!(reason = ?e, "Failed to delete ghost media");
-                            return;
-                        }
+        // if we have a media with no mediafiles we want to purge it as it is a ghost media
+        // entry.
+        if let Ok(media) = media {
+            if let Ok(media_files) = MediaFile::get_of_media(&mut tx, media.id).await {
+                if media_files.is_empty() {
+                    if let Err(e) = Media::delete(&mut tx, media.id).await {
+                        error!(reason = ?e, "Failed to delete ghost media");
+                        return;
                     }
                 }
             }
+        }
 
-            if let Err(e) = tx.commit().await {
-                error!(reason = ?e, "Failed to commit transaction.");
-            }
+        if let Err(e) = tx.commit().await {
+            error!(reason = ?e, "Failed to commit transaction.");
         }
     }
 
@@ -196,6 +217,11 @@ impl FsWatcher {
             }
         };
 
+        let media_file = match self.get_mediafile_by_file(from).await {
+            Some(x) => x,
+            None => return,
+        };
+
         let mut lock = self.conn.writer().lock_owned().await;
         let mut tx = match database::write_tx(&mut lock).await {
             Ok(x) => x,
@@ -205,26 +231,38 @@ impl FsWatcher {
             }
         };
 
-        if let Ok(media_file) = MediaFile::get_by_file(&mut tx, from).await {
-            let update_query = UpdateMediaFile {
-                target_file: Some(to.into()),
-                ..Default::default()
-            };
+        let update_query = UpdateMediaFile {
+            target_file: Some(to.into()),
+            ..Default::default()
+        };
 
-            if let Err(_e) = update_query.update(&mut tx, media_file.id).await {
-                error!(
-                    from = ?from,
-                    to = ?to,
-                    mediafile_id = media_file.id,
-                    "Failed to update target file",
-                );
-            }
+        if let Err(_e) = update_query.update(&mut tx, media_file.id).await {
+            error!(
+                from = ?from,
+                to = ?to,
+                mediafile_id = media_file.id,
+                "Failed to update target file",
+            );
+        }
 
-            if let Err(e) = tx.commit().await {
-                error!(reason = ?e, "Failed to commit transaction.");
-            }
+        if let Err(e) = tx.commit().await {
+            error!(reason = ?e, "Failed to commit transaction.");
         }
     }
+
+    /// Looks up the mediafile for `path` on a read transaction so that we dont contend for the
+    /// writer on events for files we dont know about.
+    async fn get_mediafile_by_file(&self, path: &str) -> Option<MediaFile> {
+        let mut tx = match self.conn.read().begin().await {
+            Ok(x) => x,
+            Err(e) => {
+                error!(reason = ?e, "Failed to open a transaction.");
+                return None;
+            }
+        };
+
+        MediaFile::get_by_file(&mut tx, path).await.ok()
+    }
 }
 
 // FIXME(val): This code is pretty cursed. We should replace this with native async when notify==5.0.0
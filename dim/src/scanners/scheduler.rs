@@ -0,0 +1,56 @@
+//! Periodic rescans of libraries. Schedules are stored in the database, see
+//! [`Library::set_scan_schedule`], so they survive restarts.
+use crate::core::DbConnection;
+use crate::core::EventTx;
+
+use database::library::Library;
+use database::library::ScanSchedule;
+
+use std::time::Duration;
+
+use tracing::error;
+use tracing::info;
+use tracing::instrument;
+
+/// How often the scheduler checks for due scans.
+const TICK: Duration = Duration::from_secs(60);
+
+/// Function starts the scans of libraries whose schedule is due, forever. Libraries that are
+/// still being scanned when their next run comes up are skipped until the run after, see
+/// [`start`](super::start).
+#[instrument(skip_all)]
+pub async fn run_scheduler(conn: DbConnection, tx: EventTx) {
+    let mut ticker = tokio::time::interval(TICK);
+
+    loop {
+        ticker.tick().await;
+
+        let due = match take_due_scans(&conn).await {
+            Ok(x) => x,
+            Err(e) => {
+                error!(reason = ?e, "Failed to fetch scheduled library scans.");
+                continue;
+            }
+        };
+
+        for schedule in due {
+            info!(
+                library_id = schedule.library_id,
+                next_run = schedule.next_run,
+                "Starting scheduled library scan."
+            );
+
+            tokio::spawn(super::start(conn.clone(), schedule.library_id, tx.clone()));
+        }
+    }
+}
+
+async fn take_due_scans(conn: &DbConnection) -> Result<Vec<ScanSchedule>, database::DatabaseError> {
+    let mut lock = conn.writer().lock_owned().await;
+    let mut tx = database::write_tx(&mut lock).await?;
+    let now = database::unix_now() as i64;
+    let due = Library::take_due_scans(&mut tx, now).await?;
+    tx.commit().await?;
+
+    Ok(due)
+}
@@ -1,6 +1,5 @@
 use database::asset::InsertableAsset;
-use database::genre::InsertableGenre;
-use database::genre::InsertableGenreMedia;
+use database::genre::Genre;
 use database::DbConnection;
 
 use database::episode::InsertableEpisode;
@@ -33,6 +32,9 @@ use crate::fetcher::insert_into_queue;
 pub struct TvShowMatcher<'a> {
     pub conn: &'a DbConnection,
     pub event_tx: &'a EventTx,
+    /// Recorded as [`Media::added_by`](database::media::Media::added_by) of the media this
+    /// matcher inserts.
+    pub added_by: &'a str,
 }
 
 impl<'a> TvShowMatcher<'a> {
@@ -153,6 +155,11 @@ impl<'a> TvShowMatcher<'a> {
             added: Utc::now().to_string(),
             poster,
             backdrop,
+            duration: None,
+            external_id: Some(format!("tmdb:{}", result.id)),
+            tmdb_id: Some(result.id as i32),
+            imdb_id: None,
+            added_by: Some(self.added_by.to_string()),
             media_type: MediaType::Tv,
         };
 
@@ -179,16 +186,16 @@ impl<'a> TvShowMatcher<'a> {
         let media_id = if let Some(id) = reuse_media_id {
             media.insert_with_id(&mut *tx, id).await?
         } else {
-            media.insert(&mut *tx).await?
+            media.get_or_create_show(&mut *tx).await?
         };
 
+        super::store_rating(&mut *tx, media_id, result.rating).await;
+
         let _ = TVShow::insert(&mut *tx, media_id).await;
 
         for name in result.genres {
-            let genre = InsertableGenre { name };
-
-            if let Ok(x) = genre.insert(&mut *tx).await {
-                let _ = InsertableGenreMedia::insert_pair(x, media_id, &mut *tx).await;
+            if let Err(e) = Genre::attach(&mut *tx, media_id, &name).await {
+                warn!(media_id = media_id, genre = %name, reason = ?e, "Failed to attach genre.");
             }
         }
 
@@ -301,6 +308,7 @@ impl<'a> TvShowMatcher<'a> {
         let episode = InsertableEpisode {
             episode: orphan.episode.unwrap_or(0),
             seasonid,
+            still: backdrop,
             media: InsertableMedia {
                 library_id: orphan.library_id,
                 name: search_ep
@@ -313,7 +321,10 @@ impl<'a> TvShowMatcher<'a> {
                     .as_ref()
                     .map(|x| x.overview.clone())
                     .unwrap_or_default(),
+                // NOTE: kept for clients that still read the still from the backdrop.
                 backdrop,
+                duration: orphan.duration,
+                added_by: Some(self.added_by.to_string()),
                 ..Default::default()
             },
         };
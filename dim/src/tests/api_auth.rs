@@ -1,5 +1,114 @@
 use crate::routes::auth::*;
+use warp::http::header::SET_COOKIE;
 use warp::test::request;
+use warp::Reply;
 
 #[test]
 fn _test() {}
+
+/// Splits a `Set-Cookie` value into its `name=value` pair and its attributes.
+fn parse_cookie(cookie: &str) -> ((&str, &str), Vec<&str>) {
+    let mut parts = cookie.split("; ");
+    let pair = parts.next().and_then(|x| x.split_once('=')).unwrap();
+    (pair, parts.collect())
+}
+
+#[test]
+fn test_token_cookie() {
+    let cookie = token_cookie("abc", 60);
+    let (pair, attributes) = parse_cookie(&cookie);
+    assert_eq!(pair, ("token", "abc"));
+    assert!(attributes.contains(&"HttpOnly"));
+    assert!(attributes.contains(&"Secure"));
+    assert!(attributes.contains(&"SameSite=Lax"));
+    assert!(attributes.contains(&"Max-Age=60"));
+
+    // logging out must clear the very same cookie, otherwise browsers keep the old one around.
+    let response = futures::executor::block_on(logout())
+        .unwrap()
+        .into_response();
+    let cleared = response.headers()[SET_COOKIE].to_str().unwrap();
+    let (pair, cleared_attributes) = parse_cookie(cleared);
+    assert_eq!(pair, ("token", ""));
+    assert!(cleared_attributes.contains(&"Max-Age=0"));
+
+    let without_age = |x: &[&str]| {
+        x.iter()
+            .filter(|x| !x.starts_with("Max-Age="))
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(without_age(&attributes), without_age(&cleared_attributes));
+}
+
+#[test]
+fn test_client_ip() {
+    let proxy: std::net::IpAddr = "10.0.0.1".parse().unwrap();
+    let remote = |x: &str| Some(std::net::SocketAddr::new(x.parse().unwrap(), 1234));
+
+    // without trusted proxies the header is ignored.
+    assert_eq!(
+        client_ip(remote("1.2.3.4"), Some("5.6.7.8"), &[]),
+        Some("1.2.3.4".parse().unwrap())
+    );
+    assert_eq!(
+        client_ip(remote("1.2.3.4"), Some("5.6.7.8"), &[proxy]),
+        Some("1.2.3.4".parse().unwrap())
+    );
+
+    // behind a trusted proxy the client is the last hop that isnt a proxy, anything before it
+    // could have been set by the client.
+    assert_eq!(
+        client_ip(
+            remote("10.0.0.1"),
+            Some("6.6.6.6, 5.6.7.8, 10.0.0.1"),
+            &[proxy]
+        ),
+        Some("5.6.7.8".parse().unwrap())
+    );
+    assert_eq!(
+        client_ip(remote("10.0.0.1"), Some("6.6.6.6, garbage"), &[proxy]),
+        Some(proxy)
+    );
+    assert_eq!(client_ip(remote("10.0.0.1"), None, &[proxy]), Some(proxy));
+    assert_eq!(client_ip(None, Some("5.6.7.8"), &[proxy]), None);
+}
+
+#[test]
+fn test_register_invite_twice() {
+    use crate::errors::DimError;
+    use database::user::Login;
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    rt.block_on(async {
+        let conn = database::get_conn_memory().await.unwrap();
+        let login = |username: &str, invite_token: Option<String>| Login {
+            username: username.into(),
+            password: "password".into(),
+            invite_token,
+            ..Default::default()
+        };
+
+        // the first account doesnt need an invite.
+        register(login("owner", None), conn.clone()).await.unwrap();
+
+        let invite = {
+            let mut lock = conn.writer().lock_owned().await;
+            let mut tx = database::write_tx(&mut lock).await.unwrap();
+            let invite = Login::new_invite(&mut tx).await.unwrap();
+            tx.commit().await.unwrap();
+            invite
+        };
+
+        register(login("first", Some(invite.clone())), conn.clone())
+            .await
+            .unwrap();
+
+        let result = register(login("second", Some(invite)), conn.clone()).await;
+        assert!(matches!(result, Err(DimError::InviteAlreadyClaimed)));
+    });
+}
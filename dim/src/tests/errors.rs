@@ -0,0 +1,31 @@
+use crate::errors::DimError;
+use crate::errors::StreamingErrors;
+
+use serde_json::Value;
+use warp::http::StatusCode;
+use warp::Reply;
+
+fn into_body(reply: impl Reply) -> (StatusCode, Value) {
+    let resp = reply.into_response();
+    let status = resp.status();
+    let body = futures::executor::block_on(warp::hyper::body::to_bytes(resp.into_body())).unwrap();
+
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[test]
+fn test_error_body() {
+    let (status, body) = into_body(DimError::InvalidCredentials);
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"], "INVALID_CREDENTIALS");
+    assert_eq!(body["message"], "Invalid credentials.");
+
+    let (_, body) = into_body(DimError::DatabaseError {
+        description: "oops".into(),
+    });
+    assert_eq!(body["error"], "DATABASE_ERROR");
+
+    let (status, body) = into_body(StreamingErrors::FileDoesNotExist);
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"], "FILE_DOES_NOT_EXIST");
+}
@@ -1,2 +1,4 @@
 // NOTE: Might want to add a v1 module.
 pub mod api_auth;
+pub mod errors;
+pub mod utils;
@@ -0,0 +1,85 @@
+use crate::utils::etag_for;
+use crate::utils::etag_matches;
+use crate::utils::parse_sidecar_subtitle;
+use crate::utils::sniff_image_ext;
+use crate::utils::write_atomic;
+
+#[test]
+fn test_etag_matches() {
+    let etag = etag_for(b"{\"id\":1}");
+    assert_eq!(etag, etag_for(b"{\"id\":1}"));
+    assert_ne!(etag, etag_for(b"{\"id\":2}"));
+
+    assert!(!etag_matches(None, &etag));
+    assert!(etag_matches(Some(&etag), &etag));
+    assert!(etag_matches(Some(&format!("W/{}", etag)), &etag));
+    assert!(etag_matches(Some(&format!("\"other\", {}", etag)), &etag));
+    assert!(etag_matches(Some("*"), &etag));
+    assert!(!etag_matches(Some("\"other\""), &etag));
+}
+
+#[test]
+fn test_sniff_image_ext() {
+    assert_eq!(
+        sniff_image_ext(&[0xff, 0xd8, 0xff, 0xe0, 0x00]),
+        Some("jpg")
+    );
+    assert_eq!(
+        sniff_image_ext(b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR"),
+        Some("png")
+    );
+    assert_eq!(
+        sniff_image_ext(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"),
+        None
+    );
+    assert_eq!(sniff_image_ext(b"<html></html>"), None);
+    assert_eq!(sniff_image_ext(&[]), None);
+}
+
+#[test]
+fn test_parse_sidecar_subtitle() {
+    use std::path::Path;
+
+    let sub =
+        parse_sidecar_subtitle("Movie (2020)", Path::new("/m/Movie (2020).en.forced.srt")).unwrap();
+    assert_eq!(sub.codec, "subrip");
+    assert_eq!(sub.language.as_deref(), Some("English"));
+    assert!(sub.forced);
+
+    let sub = parse_sidecar_subtitle("Movie (2020)", Path::new("/m/Movie (2020).VTT")).unwrap();
+    assert_eq!(sub.codec, "webvtt");
+    assert_eq!(sub.language, None);
+    assert!(!sub.forced);
+
+    let sub = parse_sidecar_subtitle("Movie", Path::new("/m/Movie.ger.srt")).unwrap();
+    assert_eq!(sub.language.as_deref(), Some("German"));
+
+    assert!(parse_sidecar_subtitle("Movie", Path::new("/m/Movie.nfo")).is_none());
+    assert!(parse_sidecar_subtitle("Movie", Path::new("/m/Movie 2.srt")).is_none());
+    assert!(parse_sidecar_subtitle("Movie", Path::new("/m/Other.en.srt")).is_none());
+}
+
+#[test]
+fn test_write_atomic() {
+    let dir = std::env::temp_dir().join(format!("dim-write-atomic-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("avatar.png");
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    rt.block_on(write_atomic(&path, b"first")).unwrap();
+    rt.block_on(write_atomic(&path, b"second")).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"second");
+    // the temporary file has been renamed into place.
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    // writing into a directory that doesnt exist fails without leaving anything behind.
+    let missing = dir.join("missing").join("avatar.png");
+    assert!(rt.block_on(write_atomic(&missing, b"data")).is_err());
+    assert!(!missing.exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -519,3 +519,192 @@ pub fn lang_from_iso639(tag: &str) -> Option<&'static str> {
         .find(|x| x.v2b() == tag)
         .map(|x| x.name())
 }
+
+/// Subtitle file found next to a video file, see [`find_sidecar_subtitles`].
+#[derive(Debug, PartialEq)]
+pub struct SidecarSubtitle {
+    pub path: std::path::PathBuf,
+    /// Codec in the naming ffprobe uses, ie `subrip`.
+    pub codec: &'static str,
+    pub language: Option<String>,
+    pub forced: bool,
+}
+
+/// Function checks whether `path` is a subtitle file belonging to a video named `video_stem`,
+/// that is a `.srt` or `.vtt` file named like the video with optional language and `forced` tags
+/// in between, ie `Movie.en.forced.srt` for `Movie.mkv`.
+pub fn parse_sidecar_subtitle(video_stem: &str, path: &std::path::Path) -> Option<SidecarSubtitle> {
+    let codec = match path.extension()?.to_str()?.to_lowercase().as_str() {
+        "srt" => "subrip",
+        "vtt" => "webvtt",
+        _ => return None,
+    };
+
+    let stem = path.file_stem()?.to_str()?;
+    let tags = match stem.strip_prefix(video_stem)? {
+        "" => "",
+        x => x.strip_prefix('.')?,
+    };
+
+    let mut language = None;
+    let mut forced = false;
+
+    for tag in tags.split('.').map(str::to_lowercase) {
+        if tag == "forced" {
+            forced = true;
+        } else if language.is_none() {
+            language = dia_i18n::iso_639::LANG_CODES
+                .iter()
+                .find(|x| x.v1() == tag || x.v2b() == tag)
+                .map(|x| x.name().to_string());
+        }
+    }
+
+    Some(SidecarSubtitle {
+        path: path.to_path_buf(),
+        codec,
+        language,
+        forced,
+    })
+}
+
+/// Function returns the sidecar subtitle files next to `video`, see [`parse_sidecar_subtitle`].
+pub fn find_sidecar_subtitles(video: &std::path::Path) -> Vec<SidecarSubtitle> {
+    let (dir, video_stem) = match (video.parent(), video.file_stem().and_then(|x| x.to_str())) {
+        (Some(dir), Some(stem)) => (dir, stem),
+        _ => return vec![],
+    };
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(x) => x,
+        Err(_) => return vec![],
+    };
+
+    let mut subtitles = entries
+        .filter_map(Result::ok)
+        .filter_map(|x| parse_sidecar_subtitle(video_stem, &x.path()))
+        .collect::<Vec<_>>();
+
+    subtitles.sort_by(|a, b| a.path.cmp(&b.path));
+    subtitles
+}
+
+/// Makes sure that `path` is a directory we can write to, creating it if it doesnt exist yet.
+pub fn ensure_writable_dir(path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    let path = path.as_ref();
+    std::fs::create_dir_all(path)?;
+
+    // permissions on the directory dont tell the whole story (ie read-only mounts), so we actually
+    // try to write something.
+    let probe = path.join(".dim-write-test");
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(&probe)
+}
+
+/// Writes `contents` to `path` such that `path` either holds all of `contents` or is left
+/// untouched, even if we crash halfway through. The data is written to a temporary file next to
+/// `path` first, which is flushed to disk and then renamed into place. The temporary file is
+/// removed again if anything fails.
+pub async fn write_atomic(
+    path: impl AsRef<std::path::Path>,
+    contents: impl AsRef<[u8]>,
+) -> std::io::Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let path = path.as_ref();
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".part");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let result = async {
+        let mut file = tokio::fs::File::create(&tmp_path).await?;
+        file.write_all(contents.as_ref()).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&tmp_path, path).await
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+    }
+
+    result
+}
+
+/// FNV-1a, used where we need a hash that is stable across releases which rules out
+/// `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325u64, |acc, b| {
+        (acc ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Returns a quoted `ETag` for a response body. The same body always yields the same tag.
+pub fn etag_for(body: &[u8]) -> String {
+    format!("\"{:016x}\"", fnv1a(body))
+}
+
+/// Checks whether the value of a `If-None-Match` header matches `etag`. Handles lists of tags,
+/// weak tags and the `*` wildcard.
+pub fn etag_matches(if_none_match: Option<&str>, etag: &str) -> bool {
+    let header = match if_none_match {
+        Some(x) => x,
+        None => return false,
+    };
+
+    header.split(',').map(str::trim).any(|x| {
+        x == "*" || x.strip_prefix("W/").unwrap_or(x) == etag.strip_prefix("W/").unwrap_or(etag)
+    })
+}
+
+/// Renders a deterministic 5x5 identicon for `seed` as a SVG image. The same seed always yields
+/// the same image.
+pub fn identicon_svg(seed: &str) -> String {
+    let hash = fnv1a(seed.as_bytes());
+
+    let hue = (hash >> 40) % 360;
+    let mut cells = String::new();
+
+    // only the left three columns are derived from the hash, the right two mirror them.
+    for col in 0..3 {
+        for row in 0..5 {
+            if hash >> (col * 5 + row) & 1 == 0 {
+                continue;
+            }
+
+            let mut columns = vec![col];
+            if col != 2 {
+                columns.push(4 - col);
+            }
+
+            for x in columns {
+                cells.push_str(&format!(
+                    r#"<rect x="{}" y="{}" width="50" height="50"/>"#,
+                    x * 50 + 25,
+                    row * 50 + 25
+                ));
+            }
+        }
+    }
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="300" height="300" viewBox="0 0 300 300"><rect width="300" height="300" fill="#f0f0f0"/><g fill="hsl({}, 55%, 55%)">{}</g></svg>"##,
+        hue, cells
+    )
+}
+
+/// Returns the file extension of the image format `contents` is actually encoded in, based on
+/// its magic bytes, or `None` if it isnt a jpeg or png. Used to make sure uploads are what their
+/// content type claims.
+pub fn sniff_image_ext(contents: &[u8]) -> Option<&'static str> {
+    const JPEG: &[u8] = &[0xff, 0xd8, 0xff];
+    const PNG: &[u8] = &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+    if contents.starts_with(JPEG) {
+        Some("jpg")
+    } else if contents.starts_with(PNG) {
+        Some("png")
+    } else {
+        None
+    }
+}
@@ -159,9 +159,11 @@ pub fn event_socket(
                                 if let Ok(token_data) = database::user::Login::verify_cookie(token)
                                 {
                                     if let Ok(mut tx) = conn.read().begin().await {
-                                        if let Ok(u) =
+                                        if let Some(u) =
                                             database::user::User::get_by_id(&mut tx, token_data)
                                                 .await
+                                                .ok()
+                                                .filter(|u| !u.is_disabled)
                                         {
                                             let _ = i_tx.send(CtrlEvent::Track {
                                                 addr,